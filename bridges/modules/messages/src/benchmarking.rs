@@ -19,8 +19,8 @@
 #![cfg(feature = "runtime-benchmarks")]
 
 use crate::{
-	active_outbound_lane, weights_ext::EXPECTED_DEFAULT_MESSAGE_LENGTH, BridgedChainOf, Call,
-	InboundLanes, OutboundLanes,
+	active_inbound_lane, active_outbound_lane, weights_ext::EXPECTED_DEFAULT_MESSAGE_LENGTH,
+	BridgedChainOf, Call, InboundLanes, OutboundLanes,
 };
 
 use bp_messages::{
@@ -501,6 +501,48 @@ mod benchmarks {
 		assert!(T::is_relayer_rewarded(&relayer2_id));
 	}
 
+	// Benchmark pruning of `r` unrewarded relayer entries from the inbound lane, when the outbound
+	// lane state that is delivered with messages confirms all of them. Every entry is for a single
+	// message.
+	//
+	// This is the part of the `receive_messages_proof` weight that depends on the number of
+	// pruned entries, which is otherwise covered by the flat
+	// `receive_single_message_proof_with_outbound_lane_state` weight.
+	#[benchmark]
+	fn prune_inbound_relayers(
+		r: Linear<1, { T::BridgedChain::MAX_UNREWARDED_RELAYERS_IN_CONFIRMATION_TX as u32 }>,
+	) {
+		// setup code
+		InboundLanes::<T, I>::insert(
+			T::bench_lane_id(),
+			InboundLaneData {
+				state: LaneState::Opened,
+				relayers: (1..=r as MessageNonce)
+					.map(|nonce| UnrewardedRelayer {
+						relayer: T::bridged_relayer_id(),
+						messages: DeliveredMessages::new(nonce),
+					})
+					.collect(),
+				last_confirmed_nonce: 0,
+			},
+		);
+		let mut inbound_lane = active_inbound_lane::<T, I>(T::bench_lane_id()).unwrap();
+
+		#[block]
+		{
+			inbound_lane.receive_state_update(OutboundLaneData {
+				latest_received_nonce: r as MessageNonce,
+				..Default::default()
+			});
+		}
+
+		// verification code
+		assert_eq!(
+			InboundLanes::<T, I>::get(T::bench_lane_id()).map(|d| d.relayers.len()),
+			Some(0),
+		);
+	}
+
 	//
 	// Benchmarks that the runtime developers may use for proper pallet configuration.
 	//
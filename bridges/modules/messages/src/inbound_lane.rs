@@ -146,6 +146,15 @@ impl<S: InboundLaneStorage> InboundLane<S> {
 		&mut self,
 		outbound_lane_data: OutboundLaneData,
 	) -> Option<MessageNonce> {
+		self.receive_state_update_and_prune(outbound_lane_data).map(|(nonce, _)| nonce)
+	}
+
+	/// Receive state of the corresponding outbound lane. Returns the new latest confirmed nonce and
+	/// the number of unrewarded relayer entries that have been pruned.
+	pub fn receive_state_update_and_prune(
+		&mut self,
+		outbound_lane_data: OutboundLaneData,
+	) -> Option<(MessageNonce, MessageNonce)> {
 		let mut data = self.storage.data();
 		let last_delivered_nonce = data.last_delivered_nonce();
 
@@ -160,6 +169,7 @@ impl<S: InboundLaneStorage> InboundLane<S> {
 		let new_confirmed_nonce = outbound_lane_data.latest_received_nonce;
 		data.last_confirmed_nonce = new_confirmed_nonce;
 		// Firstly, remove all of the records where higher nonce <= new confirmed nonce
+		let mut pruned_relayers = 0;
 		while data
 			.relayers
 			.front()
@@ -167,6 +177,7 @@ impl<S: InboundLaneStorage> InboundLane<S> {
 			.unwrap_or(false)
 		{
 			data.relayers.pop_front();
+			pruned_relayers += 1;
 		}
 		// Secondly, update the next record with lower nonce equal to new confirmed nonce if needed.
 		// Note: There will be max. 1 record to update as we don't allow messages from relayers to
@@ -179,7 +190,7 @@ impl<S: InboundLaneStorage> InboundLane<S> {
 		}
 
 		self.storage.set_data(data);
-		Some(outbound_lane_data.latest_received_nonce)
+		Some((outbound_lane_data.latest_received_nonce, pruned_relayers))
 	}
 
 	/// Receive new message.
@@ -367,6 +378,30 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn receive_status_update_prunes_many_relayer_entries() {
+		run_test(|| {
+			let mut lane = active_inbound_lane::<TestRuntime, _>(test_lane_id()).unwrap();
+			let mut seed_storage_data = lane.storage.data();
+			for nonce in 1..=10 {
+				seed_storage_data
+					.relayers
+					.push_back(unrewarded_relayer(nonce, nonce, TEST_RELAYER_A));
+			}
+			lane.storage.set_data(seed_storage_data);
+
+			assert_eq!(
+				lane.receive_state_update_and_prune(OutboundLaneData {
+					latest_received_nonce: 10,
+					..Default::default()
+				}),
+				Some((10, 10)),
+			);
+			assert_eq!(lane.storage.data().last_confirmed_nonce, 10);
+			assert_eq!(lane.storage.data().relayers, vec![]);
+		});
+	}
+
 	#[test]
 	fn fails_to_receive_message_with_incorrect_nonce() {
 		run_test(|| {
@@ -46,11 +46,11 @@ pub use outbound_lane::{
 pub use weights::WeightInfo;
 pub use weights_ext::{
 	ensure_able_to_receive_confirmation, ensure_able_to_receive_message,
-	ensure_maximal_message_dispatch, ensure_weights_are_correct, WeightInfoExt,
-	EXPECTED_DEFAULT_MESSAGE_LENGTH, EXTRA_STORAGE_PROOF_SIZE,
+	ensure_maximal_message_dispatch, ensure_weights_are_correct, recommended_batch_size,
+	WeightInfoExt, EXPECTED_DEFAULT_MESSAGE_LENGTH, EXTRA_STORAGE_PROOF_SIZE,
 };
 
-use bp_header_chain::HeaderChain;
+use bp_header_chain::{HeaderChain, HeaderChainError};
 use bp_messages::{
 	source_chain::{
 		DeliveryConfirmationPayments, FromBridgedChainMessagesDeliveryProof, OnMessagesDelivered,
@@ -209,7 +209,7 @@ pub mod pallet {
 		/// The call may succeed, but some messages may not be delivered e.g. if they are not fit
 		/// into the unrewarded relayers vector.
 		#[pallet::call_index(2)]
-		#[pallet::weight(T::WeightInfo::receive_messages_proof_weight(&**proof, *messages_count, *dispatch_weight))]
+		#[pallet::weight(Pallet::<T, I>::receive_messages_proof_declared_weight(&**proof, *messages_count, *dispatch_weight))]
 		pub fn receive_messages_proof(
 			origin: OriginFor<T>,
 			relayer_id_at_bridged_chain: AccountIdOf<BridgedChainOf<T, I>>,
@@ -237,7 +237,7 @@ pub mod pallet {
 			//
 			// The DeclaredWeight is exactly what's computed here. Unfortunately it is impossible
 			// to get pre-computed value (and it has been already computed by the executive).
-			let declared_weight = T::WeightInfo::receive_messages_proof_weight(
+			let declared_weight = Self::receive_messages_proof_declared_weight(
 				&*proof,
 				messages_count,
 				dispatch_weight,
@@ -250,7 +250,15 @@ pub mod pallet {
 					|err| {
 						log::trace!(target: LOG_TARGET, "Rejecting invalid messages proof: {:?}", err,);
 
-						Error::<T, I>::InvalidMessagesProof
+						match err {
+							// the proof may be built against any header that the bridged header
+							// chain still has, but old headers are eventually pruned. Pruned
+							// headers aren't remembered, so they can't be told apart from headers
+							// that have never been imported
+							VerificationError::HeaderChain(HeaderChainError::UnknownHeader) =>
+								Error::<T, I>::UnknownBridgedHeader,
+							_ => Error::<T, I>::InvalidMessagesProof,
+						}
 					},
 				)?;
 
@@ -267,9 +275,11 @@ pub mod pallet {
 				actual_weight.proof_size().saturating_sub(lane_extra_proof_size_bytes),
 			);
 
+			let mut pruned_relayers = 0;
 			if let Some(lane_state) = lane_data.lane_state {
-				let updated_latest_confirmed_nonce = lane.receive_state_update(lane_state);
-				if let Some(updated_latest_confirmed_nonce) = updated_latest_confirmed_nonce {
+				let state_update = lane.receive_state_update_and_prune(lane_state);
+				if let Some((updated_latest_confirmed_nonce, pruned)) = state_update {
+					pruned_relayers = pruned;
 					log::trace!(
 						target: LOG_TARGET,
 						"Received lane {:?} state update: latest_confirmed_nonce={}. Unrewarded relayers: {:?}",
@@ -280,6 +290,15 @@ pub mod pallet {
 				}
 			}
 
+			// the declared weight accounts pruning of the maximal number of unrewarded relayer
+			// entries, so let's refund entries that have not been pruned
+			let unpruned_relayers =
+				BridgedChainOf::<T, I>::MAX_UNREWARDED_RELAYERS_IN_CONFIRMATION_TX
+					.saturating_sub(pruned_relayers);
+			actual_weight = actual_weight.saturating_sub(
+				T::WeightInfo::receive_messages_proof_relayers_pruning_overhead(unpruned_relayers),
+			);
+
 			let mut messages_received_status =
 				ReceivedMessages::new(lane_id, Vec::with_capacity(lane_data.messages.len()));
 			for mut message in lane_data.messages {
@@ -375,7 +394,12 @@ pub mod pallet {
 						err,
 					);
 
-					Error::<T, I>::InvalidMessagesDeliveryProof
+					match err {
+						// see the same match in `receive_messages_proof`
+						VerificationError::HeaderChain(HeaderChainError::UnknownHeader) =>
+							Error::<T, I>::UnknownBridgedHeader,
+						_ => Error::<T, I>::InvalidMessagesDeliveryProof,
+					}
 				})?;
 			ensure!(
 				relayers_state.is_valid(&lane_data),
@@ -497,6 +521,13 @@ pub mod pallet {
 		ReceptionConfirmation(ReceptionConfirmationError),
 		/// Error generated by the `OwnedBridgeModule` trait.
 		BridgeModule(bp_runtime::OwnedBridgeModuleError),
+		/// Messages proof or messages delivery proof has been built against the bridged header
+		/// that is not known to the bridged header chain.
+		///
+		/// Usually the header has already been pruned, but the bridged header chain keeps no
+		/// record of pruned headers, so this is also returned for headers that have never been
+		/// imported.
+		UnknownBridgedHeader,
 	}
 
 	/// Optional pallet owner.
@@ -575,6 +606,21 @@ pub mod pallet {
 	}
 
 	impl<T: Config<I>, I: 'static> Pallet<T, I> {
+		/// Weight of the `receive_messages_proof` call, declared before it is dispatched.
+		///
+		/// The outbound lane state, bundled with messages, may prune any number of unrewarded
+		/// relayer entries at the inbound lane, so the weight of pruning all of them is included.
+		fn receive_messages_proof_declared_weight(
+			proof: &impl Size,
+			messages_count: u32,
+			dispatch_weight: Weight,
+		) -> Weight {
+			T::WeightInfo::receive_messages_proof_weight(proof, messages_count, dispatch_weight)
+				.saturating_add(T::WeightInfo::receive_messages_proof_relayers_pruning_overhead(
+					BridgedChainOf::<T, I>::MAX_UNREWARDED_RELAYERS_IN_CONFIRMATION_TX,
+				))
+		}
+
 		/// Get stored data of the outbound message with given nonce.
 		pub fn outbound_message_data(
 			lane: T::LaneId,
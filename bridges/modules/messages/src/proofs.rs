@@ -218,7 +218,10 @@ mod tests {
 
 	use bp_header_chain::{HeaderChainError, StoredHeaderDataBuilder};
 	use bp_messages::LaneState;
-	use bp_runtime::{HeaderId, StorageProofError};
+	use bp_runtime::{
+		expand_compact_storage_proof, HasherOf, HeaderId, StorageProofError, StorageProofFormat,
+		UnverifiedStorageProofParams,
+	};
 	use codec::Encode;
 	use sp_runtime::traits::Header;
 
@@ -230,13 +233,35 @@ mod tests {
 		add_duplicate_key: bool,
 		add_unused_key: bool,
 		test: impl Fn(FromBridgedChainMessagesProof<BridgedHeaderHash, TestLaneIdType>) -> R,
+	) -> R {
+		using_messages_proof_with_params(
+			UnverifiedStorageProofParams::default(),
+			nonces_end,
+			outbound_lane_data,
+			encode_message,
+			encode_outbound_lane_data,
+			add_duplicate_key,
+			add_unused_key,
+			test,
+		)
+	}
+
+	fn using_messages_proof_with_params<R>(
+		proof_params: UnverifiedStorageProofParams,
+		nonces_end: MessageNonce,
+		outbound_lane_data: Option<OutboundLaneData>,
+		encode_message: impl Fn(MessageNonce, &MessagePayload) -> Option<Vec<u8>>,
+		encode_outbound_lane_data: impl Fn(&OutboundLaneData) -> Vec<u8>,
+		add_duplicate_key: bool,
+		add_unused_key: bool,
+		test: impl Fn(FromBridgedChainMessagesProof<BridgedHeaderHash, TestLaneIdType>) -> R,
 	) -> R {
 		let (state_root, storage_proof) =
 			prepare_messages_storage_proof::<BridgedChain, ThisChain, TestLaneIdType>(
 				test_lane_id(),
 				1..=nonces_end,
 				outbound_lane_data,
-				bp_runtime::UnverifiedStorageProofParams::default(),
+				proof_params,
 				generate_dummy_message,
 				encode_message,
 				encode_outbound_lane_data,
@@ -540,6 +565,59 @@ mod tests {
 		);
 	}
 
+	#[test]
+	fn compact_trie_message_proof_is_accepted_once_expanded() {
+		let outbound_lane_data = OutboundLaneData {
+			state: LaneState::Opened,
+			oldest_unpruned_nonce: 1,
+			latest_received_nonce: 1,
+			latest_generated_nonce: 2,
+		};
+		let using_compact_proof = |test: fn(_) -> _| {
+			using_messages_proof_with_params(
+				UnverifiedStorageProofParams::default()
+					.with_format(StorageProofFormat::CompactTrie),
+				2,
+				Some(outbound_lane_data.clone()),
+				encode_all_messages,
+				encode_lane_data,
+				false,
+				false,
+				test,
+			)
+		};
+
+		// only plain trie nodes are read when verifying proofs
+		assert_eq!(
+			using_compact_proof(|proof| verify_messages_proof::<TestRuntime, ()>(proof, 2)),
+			Err(VerificationError::HeaderChain(HeaderChainError::StorageProof(
+				StorageProofError::StorageRootMismatch
+			))),
+		);
+		assert_eq!(
+			using_compact_proof(|mut proof| {
+				let root = BridgedHeaderChainOf::<TestRuntime, ()>::finalized_header_state_root(
+					proof.bridged_header_hash,
+				)
+				.unwrap();
+				proof.storage_proof = expand_compact_storage_proof::<HasherOf<BridgedChain>>(
+					proof.storage_proof,
+					root,
+				);
+				verify_messages_proof::<TestRuntime, ()>(proof, 2)
+			}),
+			using_messages_proof(
+				2,
+				Some(outbound_lane_data.clone()),
+				encode_all_messages,
+				encode_lane_data,
+				false,
+				false,
+				|proof| verify_messages_proof::<TestRuntime, ()>(proof, 2),
+			),
+		);
+	}
+
 	#[test]
 	fn verify_messages_proof_does_not_panic_if_messages_count_mismatches() {
 		assert_eq!(
@@ -17,12 +17,12 @@
 //! Helpers for generating message storage proofs, that are used by tests and by benchmarks.
 
 use bp_messages::{
-	storage_keys, ChainWithMessages, InboundLaneData, MessageKey, MessageNonce, MessagePayload,
+	storage_keys, ChainWithMessages, InboundLaneData, MessageNonce, MessagePayload,
 	OutboundLaneData,
 };
 use bp_runtime::{
-	grow_storage_value, record_all_trie_keys, AccountIdOf, Chain, HashOf, HasherOf,
-	RawStorageProof, UnverifiedStorageProofParams,
+	encode_storage_proof, grow_storage_value, record_all_trie_keys, AccountIdOf, Chain, HashOf,
+	HasherOf, RawStorageProof, UnverifiedStorageProofParams,
 };
 use codec::Encode;
 use sp_std::{ops::RangeInclusive, prelude::*};
@@ -43,6 +43,15 @@ pub fn encode_lane_data(d: &OutboundLaneData) -> Vec<u8> {
 	d.encode()
 }
 
+/// Storage key of the message with given nonce, sent over given lane by the chain that is bridged
+/// with `ThisChain`.
+pub fn messages_storage_key<ThisChain: ChainWithMessages, LaneId: Encode>(
+	lane: LaneId,
+	nonce: MessageNonce,
+) -> Vec<u8> {
+	storage_keys::message_key(ThisChain::WITH_CHAIN_MESSAGES_PALLET_NAME, &lane, nonce).0
+}
+
 /// Prepare storage proof of given messages.
 ///
 /// Returns state trie root and nodes with prepared messages.
@@ -76,7 +85,6 @@ where
 
 		// insert messages
 		for (i, nonce) in message_nonces.into_iter().enumerate() {
-			let message_key = MessageKey { lane_id: lane, nonce };
 			let message_payload = match encode_message(nonce, &generate_message(nonce)) {
 				Some(message_payload) =>
 					if i == 0 {
@@ -86,12 +94,7 @@ where
 					},
 				None => continue,
 			};
-			let storage_key = storage_keys::message_key(
-				ThisChain::WITH_CHAIN_MESSAGES_PALLET_NAME,
-				&message_key.lane_id,
-				message_key.nonce,
-			)
-			.0;
+			let storage_key = messages_storage_key::<ThisChain, _>(lane, nonce);
 			trie.insert(&storage_key, &message_payload)
 				.map_err(|_| "TrieMut::insert has failed")
 				.expect("TrieMut::insert should not fail in benchmarks");
@@ -114,10 +117,11 @@ where
 	}
 
 	// generate storage proof to be delivered to This chain
+	let storage_proof = record_all_trie_keys::<LayoutV1<HasherOf<BridgedChain>>, _>(&mdb, &root)
+		.map_err(|_| "record_all_trie_keys has failed")
+		.expect("record_all_trie_keys should not fail in benchmarks");
 	let mut storage_proof =
-		record_all_trie_keys::<LayoutV1<HasherOf<BridgedChain>>, _>(&mdb, &root)
-			.map_err(|_| "record_all_trie_keys has failed")
-			.expect("record_all_trie_keys should not fail in benchmarks");
+		encode_storage_proof::<HasherOf<BridgedChain>>(storage_proof, root, proof_params.format);
 
 	if add_duplicate_key {
 		assert!(!storage_proof.is_empty());
@@ -133,6 +137,54 @@ where
 	(root, storage_proof)
 }
 
+/// Prepare storage proof of messages, sent over several lanes and stored in the same state.
+///
+/// Returns state trie root, nodes with messages of all lanes and, for every lane, storage keys of
+/// its messages.
+pub fn prepare_multi_lane_messages_storage_proof<
+	BridgedChain: Chain,
+	ThisChain: ChainWithMessages,
+	LaneId: Encode + Copy,
+>(
+	lanes: Vec<(LaneId, RangeInclusive<MessageNonce>, MessagePayload)>,
+) -> (HashOf<BridgedChain>, RawStorageProof, Vec<Vec<Vec<u8>>>)
+where
+	HashOf<BridgedChain>: Copy + Default,
+{
+	let lanes_storage_keys = lanes
+		.iter()
+		.map(|(lane, message_nonces, _)| {
+			message_nonces
+				.clone()
+				.map(|nonce| messages_storage_key::<ThisChain, _>(*lane, nonce))
+				.collect::<Vec<_>>()
+		})
+		.collect::<Vec<_>>();
+
+	// prepare Bridged chain storage with messages of all lanes
+	let mut root = Default::default();
+	let mut mdb = MemoryDB::default();
+	{
+		let mut trie =
+			TrieDBMutBuilderV1::<HasherOf<BridgedChain>>::new(&mut mdb, &mut root).build();
+		for ((_, _, message_payload), storage_keys) in lanes.iter().zip(&lanes_storage_keys) {
+			let message_payload = message_payload.encode();
+			for storage_key in storage_keys {
+				trie.insert(storage_key, &message_payload)
+					.map_err(|_| "TrieMut::insert has failed")
+					.expect("TrieMut::insert should not fail in benchmarks");
+			}
+		}
+	}
+
+	// generate storage proof of messages of all lanes to be delivered to This chain
+	let storage_proof = record_all_trie_keys::<LayoutV1<HasherOf<BridgedChain>>, _>(&mdb, &root)
+		.map_err(|_| "record_all_trie_keys has failed")
+		.expect("record_all_trie_keys should not fail in benchmarks");
+
+	(root, storage_proof, lanes_storage_keys)
+}
+
 /// Prepare storage proof of given messages delivery.
 ///
 /// Returns state trie root and nodes with prepared messages.
@@ -167,5 +219,5 @@ where
 		.map_err(|_| "record_all_trie_keys has failed")
 		.expect("record_all_trie_keys should not fail in benchmarks");
 
-	(root, storage_proof)
+	(root, encode_storage_proof::<HasherOf<BridgedChain>>(storage_proof, root, proof_params.format))
 }
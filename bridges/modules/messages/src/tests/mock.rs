@@ -559,3 +559,25 @@ pub fn prepare_messages_delivery_proof(
 		lane,
 	}
 }
+
+/// Assert that the nonces range proven by the messages delivery proof is exactly the range that
+/// is acknowledged by the confirmation proof.
+///
+/// The header of `confirmation_proof` must be known to the bridged header chain, so it is
+/// expected to be the last proof prepared by the test.
+pub fn assert_roundtrip_nonce_consistency(
+	delivery_proof: &FromBridgedChainMessagesProof<BridgedHeaderHash, TestLaneIdType>,
+	confirmation_proof: &FromBridgedChainMessagesDeliveryProof<BridgedHeaderHash, TestLaneIdType>,
+) {
+	let (lane, inbound_lane_data) =
+		crate::proofs::verify_messages_delivery_proof::<TestRuntime, ()>(
+			confirmation_proof.clone(),
+		)
+		.expect("confirmation proof is invalid");
+	assert_eq!(delivery_proof.lane, lane, "proofs are for different lanes");
+
+	let delivered = delivery_proof.nonces_start..=delivery_proof.nonces_end;
+	let acknowledged =
+		inbound_lane_data.last_confirmed_nonce + 1..=inbound_lane_data.last_delivered_nonce();
+	assert_eq!(delivered, acknowledged, "confirmed nonces differ from delivered nonces");
+}
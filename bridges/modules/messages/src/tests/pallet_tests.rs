@@ -26,6 +26,7 @@ use crate::{
 	Pallet, PalletOperatingMode, PalletOwner, StoredInboundLaneData,
 };
 
+use bp_header_chain::StoredHeaderData;
 use bp_messages::{
 	source_chain::{FromBridgedChainMessagesDeliveryProof, MessagesBridge},
 	target_chain::{FromBridgedChainMessagesProof, MessageDispatch},
@@ -34,7 +35,7 @@ use bp_messages::{
 	OutboundLaneData, OutboundMessageDetails, UnrewardedRelayer, UnrewardedRelayersState,
 	VerificationError,
 };
-use bp_runtime::{BasicOperatingMode, PreComputedSize, RangeInclusiveExt, Size};
+use bp_runtime::{BasicOperatingMode, HeaderId, PreComputedSize, RangeInclusiveExt, Size};
 use bp_test_utils::generate_owned_bridge_module_tests;
 use codec::Encode;
 use frame_support::{
@@ -473,6 +474,79 @@ fn receive_messages_proof_rejects_invalid_proof() {
 	});
 }
 
+#[test]
+fn receive_messages_proof_accepts_proof_against_retained_non_best_header() {
+	run_test(|| {
+		let proof = prepare_messages_proof(vec![message(1, REGULAR_PAYLOAD)], None);
+
+		// the proof header is still retained, but it is no longer the best one
+		let best_header_hash = BridgedHeaderHash::repeat_byte(1);
+		pallet_bridge_grandpa::ImportedHeaders::<TestRuntime>::insert(
+			best_header_hash,
+			StoredHeaderData { number: 1, state_root: Default::default() },
+		);
+		pallet_bridge_grandpa::BestFinalized::<TestRuntime>::put(HeaderId(1, best_header_hash));
+		assert_ne!(proof.bridged_header_hash, best_header_hash);
+
+		assert_ok!(Pallet::<TestRuntime>::receive_messages_proof(
+			RuntimeOrigin::signed(1),
+			TEST_RELAYER_A,
+			proof,
+			1,
+			REGULAR_PAYLOAD.declared_weight,
+		));
+		assert_eq!(
+			InboundLanes::<TestRuntime>::get(test_lane_id())
+				.unwrap()
+				.0
+				.last_delivered_nonce(),
+			1
+		);
+	});
+}
+
+#[test]
+fn receive_messages_proof_rejects_proof_against_pruned_header() {
+	run_test(|| {
+		let proof = prepare_messages_proof(vec![message(1, REGULAR_PAYLOAD)], None);
+		pallet_bridge_grandpa::ImportedHeaders::<TestRuntime>::remove(proof.bridged_header_hash);
+
+		assert_noop!(
+			Pallet::<TestRuntime, ()>::receive_messages_proof(
+				RuntimeOrigin::signed(1),
+				TEST_RELAYER_A,
+				proof,
+				1,
+				REGULAR_PAYLOAD.declared_weight,
+			),
+			Error::<TestRuntime, ()>::UnknownBridgedHeader,
+		);
+	});
+}
+
+#[test]
+fn receive_messages_proof_rejects_proof_against_never_imported_header() {
+	run_test(|| {
+		let mut proof = prepare_messages_proof(vec![message(1, REGULAR_PAYLOAD)], None);
+		proof.bridged_header_hash = BridgedHeaderHash::repeat_byte(42);
+		assert!(!pallet_bridge_grandpa::ImportedHeaders::<TestRuntime>::contains_key(
+			proof.bridged_header_hash
+		));
+
+		// the bridged header chain can't tell a never imported header from a pruned one
+		assert_noop!(
+			Pallet::<TestRuntime, ()>::receive_messages_proof(
+				RuntimeOrigin::signed(1),
+				TEST_RELAYER_A,
+				proof,
+				1,
+				REGULAR_PAYLOAD.declared_weight,
+			),
+			Error::<TestRuntime, ()>::UnknownBridgedHeader,
+		);
+	});
+}
+
 #[test]
 fn receive_messages_proof_rejects_proof_with_too_many_messages() {
 	run_test(|| {
@@ -490,6 +564,51 @@ fn receive_messages_proof_rejects_proof_with_too_many_messages() {
 	});
 }
 
+#[test]
+fn roundtrip_nonce_consistency_holds_for_matched_proofs() {
+	run_test(|| {
+		let delivery_proof = prepare_messages_proof(
+			vec![message(1, REGULAR_PAYLOAD), message(2, REGULAR_PAYLOAD)],
+			None,
+		);
+		let confirmation_proof = prepare_messages_delivery_proof(
+			test_lane_id(),
+			InboundLaneData {
+				state: LaneState::Opened,
+				last_confirmed_nonce: 0,
+				relayers: vec![
+					unrewarded_relayer(1, 1, TEST_RELAYER_A),
+					unrewarded_relayer(2, 2, TEST_RELAYER_B),
+				]
+				.into(),
+			},
+		);
+
+		assert_roundtrip_nonce_consistency(&delivery_proof, &confirmation_proof);
+	});
+}
+
+#[test]
+#[should_panic(expected = "confirmed nonces differ from delivered nonces")]
+fn roundtrip_nonce_consistency_fails_for_mismatched_proofs() {
+	run_test(|| {
+		let delivery_proof = prepare_messages_proof(
+			vec![message(1, REGULAR_PAYLOAD), message(2, REGULAR_PAYLOAD)],
+			None,
+		);
+		let confirmation_proof = prepare_messages_delivery_proof(
+			test_lane_id(),
+			InboundLaneData {
+				state: LaneState::Opened,
+				last_confirmed_nonce: 0,
+				relayers: vec![unrewarded_relayer(1, 1, TEST_RELAYER_A)].into(),
+			},
+		);
+
+		assert_roundtrip_nonce_consistency(&delivery_proof, &confirmation_proof);
+	});
+}
+
 #[test]
 fn receive_messages_delivery_proof_works() {
 	run_test(|| {
@@ -645,6 +764,23 @@ fn receive_messages_delivery_proof_rejects_invalid_proof() {
 	});
 }
 
+#[test]
+fn receive_messages_delivery_proof_rejects_proof_against_pruned_header() {
+	run_test(|| {
+		let proof = prepare_messages_delivery_proof(test_lane_id(), Default::default());
+		pallet_bridge_grandpa::ImportedHeaders::<TestRuntime>::remove(proof.bridged_header_hash);
+
+		assert_noop!(
+			Pallet::<TestRuntime>::receive_messages_delivery_proof(
+				RuntimeOrigin::signed(1),
+				proof,
+				Default::default(),
+			),
+			Error::<TestRuntime, ()>::UnknownBridgedHeader,
+		);
+	});
+}
+
 #[test]
 fn receive_messages_delivery_proof_rejects_proof_if_declared_relayers_state_is_invalid() {
 	run_test(|| {
@@ -58,6 +58,7 @@ pub trait WeightInfo {
 	fn receive_delivery_proof_for_two_messages_by_single_relayer() -> Weight;
 	fn receive_delivery_proof_for_two_messages_by_two_relayers() -> Weight;
 	fn receive_single_n_bytes_message_proof_with_dispatch(n: u32) -> Weight;
+	fn prune_inbound_relayers(r: u32) -> Weight;
 }
 
 /// Weights for `pallet_bridge_messages` that are generated using one of the Bridge testnets.
@@ -293,6 +294,23 @@ impl<T: frame_system::Config> WeightInfo for BridgeWeight<T> {
 			.saturating_add(T::DbWeight::get().reads(3_u64))
 			.saturating_add(T::DbWeight::get().writes(1_u64))
 	}
+	/// Storage: BridgeUnknownMessages InboundLanes (r:1 w:1)
+	///
+	/// Proof: BridgeUnknownMessages InboundLanes (max_values: None, max_size: Some(49180), added:
+	/// 51655, mode: MaxEncodedLen)
+	///
+	/// The range of component `r` is `[1, 16]`.
+	fn prune_inbound_relayers(r: u32) -> Weight {
+		// Proof Size summary in bytes:
+		//  Measured:  `110 + r * (49 ±0)`
+		//  Estimated: `52645`
+		// Minimum execution time: 4_562 nanoseconds.
+		Weight::from_parts(4_318_642, 52645)
+			// Standard Error: 1_043
+			.saturating_add(Weight::from_parts(218_371, 0).saturating_mul(r.into()))
+			.saturating_add(T::DbWeight::get().reads(1_u64))
+			.saturating_add(T::DbWeight::get().writes(1_u64))
+	}
 }
 
 // For backwards compatibility and tests
@@ -527,4 +545,21 @@ impl WeightInfo for () {
 			.saturating_add(RocksDbWeight::get().reads(3_u64))
 			.saturating_add(RocksDbWeight::get().writes(1_u64))
 	}
+	/// Storage: BridgeUnknownMessages InboundLanes (r:1 w:1)
+	///
+	/// Proof: BridgeUnknownMessages InboundLanes (max_values: None, max_size: Some(49180), added:
+	/// 51655, mode: MaxEncodedLen)
+	///
+	/// The range of component `r` is `[1, 16]`.
+	fn prune_inbound_relayers(r: u32) -> Weight {
+		// Proof Size summary in bytes:
+		//  Measured:  `110 + r * (49 ±0)`
+		//  Estimated: `52645`
+		// Minimum execution time: 4_562 nanoseconds.
+		Weight::from_parts(4_318_642, 52645)
+			// Standard Error: 1_043
+			.saturating_add(Weight::from_parts(218_371, 0).saturating_mul(r.into()))
+			.saturating_add(RocksDbWeight::get().reads(1_u64))
+			.saturating_add(RocksDbWeight::get().writes(1_u64))
+	}
 }
@@ -44,6 +44,8 @@ pub fn ensure_weights_are_correct<W: WeightInfoExt>() {
 	// the outbound lane state processing code (`InboundLane::receive_state_update`) is minimal and
 	// may not be accounted by our benchmarks
 	assert_eq!(W::receive_messages_proof_outbound_lane_state_overhead().proof_size(), 0);
+	assert_ne!(W::receive_messages_proof_relayers_pruning_overhead(1).ref_time(), 0);
+	assert_eq!(W::receive_messages_proof_relayers_pruning_overhead(1).proof_size(), 0);
 	assert_ne!(W::storage_proof_size_overhead(1).ref_time(), 0);
 	assert_eq!(W::storage_proof_size_overhead(1).proof_size(), 0);
 
@@ -154,6 +156,40 @@ pub fn ensure_able_to_receive_confirmation<W: WeightInfoExt>(
 	);
 }
 
+/// Returns the maximal number of messages of `avg_message_bytes` size each, that may be delivered
+/// in a single `receive_messages_proof` call without exceeding `remaining_block_weight`.
+///
+/// This is an off-chain helper for relayers. It only accounts for the per-message and per-byte
+/// components of the delivery transaction weight, so the dispatch weight of messages must be
+/// subtracted from `remaining_block_weight` by the caller.
+pub fn recommended_batch_size<W: WeightInfoExt>(
+	avg_message_bytes: u32,
+	remaining_block_weight: Weight,
+) -> u32 {
+	let delivery_weight = |messages_count: u32| {
+		let proof_size = avg_message_bytes
+			.saturating_mul(messages_count)
+			.saturating_add(W::expected_extra_storage_proof_size());
+		W::receive_messages_proof_weight(
+			&PreComputedSize(proof_size as usize),
+			messages_count,
+			Weight::zero(),
+		)
+	};
+
+	// delivery weight is monotonic in the number of messages, so we may use binary search
+	let (mut low, mut high) = (0u32, u32::MAX);
+	while low < high {
+		let mid = low + (high - low) / 2 + 1;
+		if delivery_weight(mid).all_lte(remaining_block_weight) {
+			low = mid;
+		} else {
+			high = mid - 1;
+		}
+	}
+	low
+}
+
 /// Panics if `proof_size` of message delivery call depends on the message proof size.
 fn messages_proof_size_does_not_affect_proof_size<W: WeightInfoExt>() {
 	let dispatch_weight = Weight::zero();
@@ -367,6 +403,17 @@ pub trait WeightInfoExt: WeightInfo {
 		weight_of_single_message_and_lane_state.saturating_sub(weight_of_single_message)
 	}
 
+	/// Returns weight that needs to be accounted when outbound lane state, delivered by the
+	/// message delivery transaction (`receive_messages_proof`), confirms and prunes given number
+	/// of unrewarded relayers entries at the inbound lane.
+	fn receive_messages_proof_relayers_pruning_overhead(relayers: MessageNonce) -> Weight {
+		let weight_of_two_relayers = Self::prune_inbound_relayers(2);
+		let weight_of_single_relayer = Self::prune_inbound_relayers(1);
+		weight_of_two_relayers
+			.saturating_sub(weight_of_single_relayer)
+			.saturating_mul(relayers as _)
+	}
+
 	/// Returns weight overhead of delivery confirmation transaction
 	/// (`receive_messages_delivery_proof`).
 	fn receive_messages_delivery_proof_overhead() -> Weight {
@@ -467,4 +514,88 @@ mod tests {
 	fn ensure_default_weights_are_correct() {
 		ensure_weights_are_correct::<BridgeWeight<TestRuntime>>();
 	}
+
+	#[test]
+	fn relayers_pruning_overhead_is_linear_in_pruned_relayers() {
+		struct PruningWeight;
+
+		impl WeightInfo for PruningWeight {
+			fn receive_single_message_proof() -> Weight {
+				<()>::receive_single_message_proof()
+			}
+			fn receive_n_messages_proof(n: u32) -> Weight {
+				<()>::receive_n_messages_proof(n)
+			}
+			fn receive_single_message_proof_with_outbound_lane_state() -> Weight {
+				<()>::receive_single_message_proof_with_outbound_lane_state()
+			}
+			fn receive_single_n_bytes_message_proof(n: u32) -> Weight {
+				<()>::receive_single_n_bytes_message_proof(n)
+			}
+			fn receive_delivery_proof_for_single_message() -> Weight {
+				<()>::receive_delivery_proof_for_single_message()
+			}
+			fn receive_delivery_proof_for_two_messages_by_single_relayer() -> Weight {
+				<()>::receive_delivery_proof_for_two_messages_by_single_relayer()
+			}
+			fn receive_delivery_proof_for_two_messages_by_two_relayers() -> Weight {
+				<()>::receive_delivery_proof_for_two_messages_by_two_relayers()
+			}
+			fn receive_single_n_bytes_message_proof_with_dispatch(n: u32) -> Weight {
+				<()>::receive_single_n_bytes_message_proof_with_dispatch(n)
+			}
+			fn prune_inbound_relayers(r: u32) -> Weight {
+				Weight::from_parts(1_000 + 100 * r as u64, 3_000)
+			}
+		}
+
+		impl WeightInfoExt for PruningWeight {
+			fn expected_extra_storage_proof_size() -> u32 {
+				EXTRA_STORAGE_PROOF_SIZE
+			}
+
+			fn receive_messages_proof_overhead_from_runtime() -> Weight {
+				Weight::zero()
+			}
+
+			fn receive_messages_delivery_proof_overhead_from_runtime() -> Weight {
+				Weight::zero()
+			}
+		}
+
+		// the base cost of pruning is already covered by the outbound lane state overhead
+		type W = PruningWeight;
+		assert_eq!(W::receive_messages_proof_relayers_pruning_overhead(0), Weight::zero());
+		assert_eq!(
+			W::receive_messages_proof_relayers_pruning_overhead(16),
+			Weight::from_parts(1_600, 0),
+		);
+	}
+
+	#[test]
+	fn recommended_batch_size_respects_weight_budget() {
+		type W = BridgeWeight<TestRuntime>;
+
+		let avg_message_bytes = EXPECTED_DEFAULT_MESSAGE_LENGTH;
+		let proof_weight = |messages_count: u32| {
+			W::receive_messages_proof_weight(
+				&PreComputedSize(
+					(avg_message_bytes * messages_count + W::expected_extra_storage_proof_size())
+						as usize,
+				),
+				messages_count,
+				Weight::zero(),
+			)
+		};
+
+		// nothing fits into an empty budget
+		assert_eq!(recommended_batch_size::<W>(avg_message_bytes, Weight::zero()), 0);
+
+		// exactly `n` messages fit into the weight of `n` messages
+		let budget = proof_weight(10);
+		let recommended = recommended_batch_size::<W>(avg_message_bytes, budget);
+		assert_eq!(recommended, 10);
+		assert!(proof_weight(recommended).all_lte(budget));
+		assert!(!proof_weight(recommended + 1).all_lte(budget));
+	}
 }
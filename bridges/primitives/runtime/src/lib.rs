@@ -44,8 +44,8 @@ use num_traits::{CheckedAdd, CheckedSub, One, SaturatingAdd, Zero};
 pub use storage_proof::craft_valid_storage_proof;
 #[cfg(feature = "test-helpers")]
 pub use storage_proof::{
-	grow_storage_proof, grow_storage_value, record_all_keys as record_all_trie_keys,
-	UnverifiedStorageProofParams,
+	encode_storage_proof, expand_compact_storage_proof, grow_storage_proof, grow_storage_value,
+	record_all_keys as record_all_trie_keys, StorageProofFormat, UnverifiedStorageProofParams,
 };
 pub use storage_proof::{
 	raw_storage_proof_size, RawStorageProof, StorageProofChecker, StorageProofError,
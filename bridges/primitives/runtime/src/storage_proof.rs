@@ -27,7 +27,9 @@ use codec::{Decode, DecodeWithMemTracking, Encode};
 use hash_db::{HashDB, Hasher, EMPTY_PREFIX};
 use scale_info::TypeInfo;
 #[cfg(feature = "test-helpers")]
-use sp_trie::{recorder_ext::RecorderExt, Recorder, TrieDBBuilder, TrieError, TrieHash};
+use sp_trie::{
+	recorder_ext::RecorderExt, CompactProof, Recorder, TrieDBBuilder, TrieError, TrieHash,
+};
 #[cfg(feature = "test-helpers")]
 use trie_db::{Trie, TrieConfiguration, TrieDBMut};
 
@@ -93,16 +95,36 @@ pub fn raw_storage_proof_size(raw_storage_proof: &RawStorageProof) -> usize {
 pub struct UnverifiedStorageProofParams {
 	/// Expected storage proof size in bytes.
 	pub db_size: Option<u32>,
+	/// Format of the storage proof.
+	pub format: StorageProofFormat,
 }
 
 #[cfg(feature = "test-helpers")]
 impl UnverifiedStorageProofParams {
 	/// Make storage proof parameters that require proof of at least `db_size` bytes.
 	pub fn from_db_size(db_size: u32) -> Self {
-		Self { db_size: Some(db_size) }
+		Self { db_size: Some(db_size), ..Default::default() }
+	}
+
+	/// Make storage proof parameters that require proof in the given `format`.
+	pub fn with_format(mut self, format: StorageProofFormat) -> Self {
+		self.format = format;
+		self
 	}
 }
 
+/// Format of generated storage proofs.
+#[cfg(feature = "test-helpers")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StorageProofFormat {
+	/// Plain trie nodes, as read by the [`StorageProofChecker`].
+	#[default]
+	Trie,
+	/// Trie nodes in the compact encoding of [`sp_trie::CompactProof`], which omits the hashes
+	/// of nodes that may be computed from the rest of the proof.
+	CompactTrie,
+}
+
 /// This struct is used to read storage values from a subset of a Merklized database. The "proof"
 /// is a subset of the nodes in the Merkle structure of the database, so that it provides
 /// authentication against a known Merkle root as well as the values in the
@@ -280,6 +302,41 @@ where
 	Ok(recorder.into_raw_storage_proof())
 }
 
+/// Encode the plain trie nodes of `proof`, recorded from the trie with the given `root`, in the
+/// given `format`.
+#[cfg(feature = "test-helpers")]
+pub fn encode_storage_proof<H: Hasher>(
+	proof: RawStorageProof,
+	root: H::Out,
+	format: StorageProofFormat,
+) -> RawStorageProof {
+	match format {
+		StorageProofFormat::Trie => proof,
+		StorageProofFormat::CompactTrie =>
+			StorageProof::new(proof)
+				.into_compact_proof::<H>(root)
+				.map_err(|_| "StorageProof::into_compact_proof has failed")
+				.expect("StorageProof::into_compact_proof should not fail in benchmarks")
+				.encoded_nodes,
+	}
+}
+
+/// Decode a proof encoded in [`StorageProofFormat::CompactTrie`] back into the plain trie nodes
+/// that are read by the [`StorageProofChecker`].
+#[cfg(feature = "test-helpers")]
+pub fn expand_compact_storage_proof<H: Hasher>(
+	compact_proof: RawStorageProof,
+	root: H::Out,
+) -> RawStorageProof {
+	CompactProof { encoded_nodes: compact_proof }
+		.to_storage_proof::<H>(Some(&root))
+		.map_err(|_| "CompactProof::to_storage_proof has failed")
+		.expect("CompactProof::to_storage_proof should not fail for encoded proofs")
+		.0
+		.into_iter_nodes()
+		.collect()
+}
+
 /// Return valid storage proof and state root.
 ///
 /// Note: This should only be used for **testing**.
@@ -265,4 +265,19 @@ impl<T: frame_system::Config> pallet_bridge_messages::WeightInfo for WeightInfo<
 			.saturating_add(T::DbWeight::get().reads(10))
 			.saturating_add(T::DbWeight::get().writes(3))
 	}
+	/// Storage: `BridgeRococoMessages::InboundLanes` (r:1 w:1)
+	/// Proof: `BridgeRococoMessages::InboundLanes` (`max_values`: None, `max_size`: Some(49180), added: 51655, mode: `MaxEncodedLen`)
+	/// The range of component `r` is `[1, 1024]`.
+	fn prune_inbound_relayers(r: u32, ) -> Weight {
+		// Proof Size summary in bytes:
+		//  Measured:  `265 + r * (49 ±0)`
+		//  Estimated: `52645`
+		// Minimum execution time: 9_871_000 picoseconds.
+		Weight::from_parts(10_204_356, 0)
+			.saturating_add(Weight::from_parts(0, 52645))
+			// Standard Error: 18
+			.saturating_add(Weight::from_parts(301_228, 0).saturating_mul(r.into()))
+			.saturating_add(T::DbWeight::get().reads(1))
+			.saturating_add(T::DbWeight::get().writes(1))
+	}
 }
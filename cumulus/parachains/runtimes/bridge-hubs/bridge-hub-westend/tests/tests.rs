@@ -16,7 +16,7 @@
 
 #![cfg(test)]
 
-use bp_messages::LegacyLaneId;
+use bp_messages::{LaneState, LegacyLaneId};
 use bp_polkadot_core::Signature;
 use bp_relayers::{PayRewardFromAccount, RewardsAccountOwner, RewardsAccountParams};
 use bridge_common_config::{BridgeRelayersInstance, BridgeReward, RequiredStakeForStakeAndSlash};
@@ -350,6 +350,292 @@ fn relayed_incoming_message_works() {
 	)
 }
 
+#[test]
+fn relayed_incoming_message_with_outbound_lane_state_works() {
+	from_parachain::relayed_incoming_message_with_outbound_lane_state_works::<RuntimeTestsAdapter>(
+		collator_session_keys(),
+		slot_durations(),
+		bp_bridge_hub_westend::BRIDGE_HUB_WESTEND_PARACHAIN_ID,
+		bp_bridge_hub_rococo::BRIDGE_HUB_ROCOCO_PARACHAIN_ID,
+		SIBLING_PARACHAIN_ID,
+		ByGenesis(WESTEND_GENESIS_HASH),
+		|| {
+			// we need to create lane between sibling parachain and remote destination
+			bridge_hub_test_utils::ensure_opened_bridge::<
+				Runtime,
+				XcmOverBridgeHubRococoInstance,
+				LocationToAccountId,
+				WestendLocation,
+			>(
+				SiblingParachainLocation::get(),
+				BridgedUniversalLocation::get(),
+				false,
+				|locations, _fee| {
+					bridge_hub_test_utils::open_bridge_with_storage::<
+						Runtime,
+						XcmOverBridgeHubRococoInstance,
+					>(locations, LegacyLaneId([0, 0, 0, 1]))
+				},
+			)
+			.1
+		},
+		construct_and_apply_extrinsic,
+	)
+}
+
+#[test]
+fn replayed_message_delivery_of_last_delivered_nonce_is_ignored() {
+	from_parachain::replayed_message_delivery_is_ignored::<RuntimeTestsAdapter>(
+		collator_session_keys(),
+		bp_bridge_hub_rococo::BRIDGE_HUB_ROCOCO_PARACHAIN_ID,
+		|| {
+			// we need to create lane between sibling parachain and remote destination
+			bridge_hub_test_utils::ensure_opened_bridge::<
+				Runtime,
+				XcmOverBridgeHubRococoInstance,
+				LocationToAccountId,
+				WestendLocation,
+			>(
+				SiblingParachainLocation::get(),
+				BridgedUniversalLocation::get(),
+				false,
+				|locations, _fee| {
+					bridge_hub_test_utils::open_bridge_with_storage::<
+						Runtime,
+						XcmOverBridgeHubRococoInstance,
+					>(locations, LegacyLaneId([0, 0, 0, 1]))
+				},
+			)
+			.1
+		},
+		5,
+		5,
+	)
+}
+
+#[test]
+fn replayed_message_delivery_of_older_nonce_is_ignored() {
+	from_parachain::replayed_message_delivery_is_ignored::<RuntimeTestsAdapter>(
+		collator_session_keys(),
+		bp_bridge_hub_rococo::BRIDGE_HUB_ROCOCO_PARACHAIN_ID,
+		|| {
+			// we need to create lane between sibling parachain and remote destination
+			bridge_hub_test_utils::ensure_opened_bridge::<
+				Runtime,
+				XcmOverBridgeHubRococoInstance,
+				LocationToAccountId,
+				WestendLocation,
+			>(
+				SiblingParachainLocation::get(),
+				BridgedUniversalLocation::get(),
+				false,
+				|locations, _fee| {
+					bridge_hub_test_utils::open_bridge_with_storage::<
+						Runtime,
+						XcmOverBridgeHubRococoInstance,
+					>(locations, LegacyLaneId([0, 0, 0, 1]))
+				},
+			)
+			.1
+		},
+		5,
+		3,
+	)
+}
+
+#[test]
+fn undispatchable_message_is_delivered() {
+	from_parachain::undispatchable_message_is_delivered::<RuntimeTestsAdapter>(
+		collator_session_keys(),
+		bp_bridge_hub_rococo::BRIDGE_HUB_ROCOCO_PARACHAIN_ID,
+		|| {
+			// we need to create lane between sibling parachain and remote destination
+			bridge_hub_test_utils::ensure_opened_bridge::<
+				Runtime,
+				XcmOverBridgeHubRococoInstance,
+				LocationToAccountId,
+				WestendLocation,
+			>(
+				SiblingParachainLocation::get(),
+				BridgedUniversalLocation::get(),
+				false,
+				|locations, _fee| {
+					bridge_hub_test_utils::open_bridge_with_storage::<
+						Runtime,
+						XcmOverBridgeHubRococoInstance,
+					>(locations, LegacyLaneId([0, 0, 0, 1]))
+				},
+			)
+			.1
+		},
+	)
+}
+
+#[test]
+fn relay_chain_authority_set_change_is_enacted() {
+	from_parachain::relay_chain_authority_set_change_is_enacted::<RuntimeTestsAdapter>(
+		collator_session_keys(),
+	)
+}
+
+#[test]
+fn complex_relayer_delivery_batch_starts_with_prelude() {
+	from_parachain::complex_relayer_delivery_batch_starts_with_prelude::<RuntimeTestsAdapter>(
+		collator_session_keys(),
+		RuntimeCall::Balances(pallet_balances::Call::transfer_keep_alive {
+			dest: Bob.to_account_id().into(),
+			value: ExistentialDeposit::get(),
+		}),
+	)
+}
+
+#[test]
+fn complex_relayer_delivery_batch_with_two_message_proofs() {
+	from_parachain::complex_relayer_delivery_batch_with_two_message_proofs::<RuntimeTestsAdapter>(
+		collator_session_keys(),
+	)
+}
+
+#[test]
+fn delivery_proof_with_mismatched_lane_is_rejected() {
+	from_parachain::delivery_proof_with_mismatched_lane_is_rejected::<RuntimeTestsAdapter>(
+		collator_session_keys(),
+		bp_bridge_hub_rococo::BRIDGE_HUB_ROCOCO_PARACHAIN_ID,
+		|| {
+			// we need to create lane between sibling parachain and remote destination
+			bridge_hub_test_utils::ensure_opened_bridge::<
+				Runtime,
+				XcmOverBridgeHubRococoInstance,
+				LocationToAccountId,
+				WestendLocation,
+			>(
+				SiblingParachainLocation::get(),
+				BridgedUniversalLocation::get(),
+				false,
+				|locations, _fee| {
+					bridge_hub_test_utils::open_bridge_with_storage::<
+						Runtime,
+						XcmOverBridgeHubRococoInstance,
+					>(locations, LegacyLaneId([0, 0, 0, 1]))
+				},
+			)
+			.1
+		},
+		LegacyLaneId([0, 0, 0, 2]),
+	)
+}
+
+fn check_delivery_proof_of_inbound_lane_in_state_is_accepted(lane_state: LaneState) {
+	from_parachain::delivery_proof_of_inbound_lane_in_state_is_accepted::<RuntimeTestsAdapter>(
+		collator_session_keys(),
+		bp_bridge_hub_rococo::BRIDGE_HUB_ROCOCO_PARACHAIN_ID,
+		|| {
+			// we need to create lane between sibling parachain and remote destination
+			bridge_hub_test_utils::ensure_opened_bridge::<
+				Runtime,
+				XcmOverBridgeHubRococoInstance,
+				LocationToAccountId,
+				WestendLocation,
+			>(
+				SiblingParachainLocation::get(),
+				BridgedUniversalLocation::get(),
+				false,
+				|locations, _fee| {
+					bridge_hub_test_utils::open_bridge_with_storage::<
+						Runtime,
+						XcmOverBridgeHubRococoInstance,
+					>(locations, LegacyLaneId([0, 0, 0, 1]))
+				},
+			)
+			.1
+		},
+		lane_state,
+	)
+}
+
+#[test]
+fn delivery_proof_of_opened_inbound_lane_is_accepted() {
+	check_delivery_proof_of_inbound_lane_in_state_is_accepted(LaneState::Opened);
+}
+
+#[test]
+fn delivery_proof_of_closed_inbound_lane_is_accepted() {
+	check_delivery_proof_of_inbound_lane_in_state_is_accepted(LaneState::Closed);
+}
+
+#[test]
+fn stale_bridged_para_head_is_rejected() {
+	from_parachain::stale_bridged_para_head_is_rejected::<RuntimeTestsAdapter>(
+		collator_session_keys(),
+		bp_bridge_hub_rococo::BRIDGE_HUB_ROCOCO_PARACHAIN_ID,
+		|| {
+			// we need to create lane between sibling parachain and remote destination
+			bridge_hub_test_utils::ensure_opened_bridge::<
+				Runtime,
+				XcmOverBridgeHubRococoInstance,
+				LocationToAccountId,
+				WestendLocation,
+			>(
+				SiblingParachainLocation::get(),
+				BridgedUniversalLocation::get(),
+				false,
+				|locations, _fee| {
+					bridge_hub_test_utils::open_bridge_with_storage::<
+						Runtime,
+						XcmOverBridgeHubRococoInstance,
+					>(locations, LegacyLaneId([0, 0, 0, 1]))
+				},
+			)
+			.1
+		},
+	)
+}
+
+#[test]
+fn relayer_id_from_keyring_builds_delivery_call() {
+	use bridge_hub_test_utils::test_data::{
+		from_parachain::make_standalone_relayer_delivery_call, relayer_id_from_keyring,
+	};
+
+	let relayer_id =
+		relayer_id_from_keyring::<Runtime, WithBridgeHubRococoMessagesInstance>("Alice");
+	assert_eq!(relayer_id, Alice.to_account_id());
+
+	let call = make_standalone_relayer_delivery_call::<Runtime, WithBridgeHubRococoMessagesInstance>(
+		bp_messages::target_chain::FromBridgedChainMessagesProof {
+			bridged_header_hash: Default::default(),
+			storage_proof: Default::default(),
+			lane: LegacyLaneId([0, 0, 0, 1]),
+			nonces_start: 1,
+			nonces_end: 1,
+		},
+		relayer_id,
+	);
+	assert!(matches!(
+		call,
+		RuntimeCall::BridgeRococoMessages(pallet_bridge_messages::Call::receive_messages_proof {
+			relayer_id_at_bridged_chain,
+			..
+		}) if relayer_id_at_bridged_chain == Alice.to_account_id()
+	));
+}
+
+#[test]
+fn messages_storage_key_matches_outbound_messages_key() {
+	type OutboundMessages =
+		pallet_bridge_messages::OutboundMessages<Runtime, WithBridgeHubRococoMessagesInstance>;
+
+	// messages to Rococo are stored by the pallet that is named after the Rococo bridge hub
+	let lane = LegacyLaneId([0, 0, 0, 1]);
+	assert_eq!(
+		bridge_hub_test_utils::test_data::messages_storage_key::<
+			bp_bridge_hub_rococo::BridgeHubRococo,
+			_,
+		>(lane, 42),
+		OutboundMessages::hashed_key_for(bp_messages::MessageKey { lane_id: lane, nonce: 42 }),
+	);
+}
+
 #[test]
 fn free_relay_extrinsic_works() {
 	// from Rococo
@@ -157,6 +157,7 @@ pub fn relayed_incoming_message_works<RuntimeHelper>(
 					xcm.into(),
 					message_nonce,
 					message_destination,
+					None,
 					relay_header_number,
 					false,
 				);
@@ -286,6 +287,7 @@ pub fn free_relay_extrinsic_works<RuntimeHelper>(
 					xcm.into(),
 					message_nonce,
 					message_destination,
+					None,
 					relay_header_number.into(),
 					true,
 				);
@@ -400,6 +402,7 @@ pub fn complex_relay_extrinsic_works<RuntimeHelper>(
 					xcm.into(),
 					message_nonce,
 					message_destination,
+					None,
 					relay_header_number,
 					false,
 				);
@@ -482,6 +485,7 @@ where
 				vec![Instruction::<()>::ClearOrigin; 1_024].into(),
 				1,
 				[GlobalConsensus(Polkadot), Parachain(1_000)].into(),
+				None,
 				1u32.into(),
 				false,
 			);
@@ -588,6 +592,7 @@ where
 				vec![Instruction::<()>::ClearOrigin; 1_024].into(),
 				1,
 				[GlobalConsensus(Polkadot), Parachain(1_000)].into(),
+				None,
 				1u32.into(),
 				false,
 			);
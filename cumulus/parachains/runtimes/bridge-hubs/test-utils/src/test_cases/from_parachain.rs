@@ -25,11 +25,17 @@ use crate::{
 
 use alloc::{boxed::Box, vec};
 use bp_header_chain::ChainWithGrandpa;
-use bp_messages::UnrewardedRelayersState;
-use bp_polkadot_core::parachains::ParaHash;
+use bp_messages::{
+	DeliveredMessages, LaneState, MessageNonce, OutboundLaneData, UnrewardedRelayer,
+	UnrewardedRelayersState,
+};
+use bp_polkadot_core::parachains::{ParaHash, ParaId};
 use bp_relayers::{RewardsAccountOwner, RewardsAccountParams};
-use bp_runtime::{Chain, Parachain};
-use frame_support::traits::{OnFinalize, OnInitialize};
+use bp_runtime::{Chain, Parachain, StorageProofFormat};
+use frame_support::{
+	assert_err, assert_ok,
+	traits::{OnFinalize, OnInitialize},
+};
 use frame_system::pallet_prelude::BlockNumberFor;
 use pallet_bridge_messages::{BridgedChainOf, LaneIdOf, ThisChainOf};
 use parachains_runtimes_test_utils::{
@@ -177,6 +183,7 @@ pub fn relayed_incoming_message_works<RuntimeHelper>(
 				xcm.into(),
 				message_nonce,
 				message_destination,
+				None,
 				para_header_number,
 				relay_header_number,
 				bridged_para_id,
@@ -334,6 +341,7 @@ pub fn free_relay_extrinsic_works<RuntimeHelper>(
 				xcm.into(),
 				message_nonce,
 				message_destination,
+				None,
 				para_header_number,
 				relay_header_number,
 				bridged_para_id,
@@ -407,6 +415,152 @@ pub fn free_relay_extrinsic_works<RuntimeHelper>(
 	);
 }
 
+/// Test-case makes sure that Runtime accepts messages proof, carrying the state of the outbound
+/// lane at the bridged chain, and uses it to confirm previously delivered messages.
+pub fn relayed_incoming_message_with_outbound_lane_state_works<RuntimeHelper>(
+	collator_session_key: CollatorSessionKeys<RuntimeHelper::Runtime>,
+	slot_durations: SlotDurations,
+	runtime_para_id: u32,
+	bridged_para_id: u32,
+	sibling_parachain_id: u32,
+	local_relay_chain_id: NetworkId,
+	prepare_configuration: impl Fn() -> LaneIdOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+	construct_and_apply_extrinsic: fn(
+		sp_keyring::Sr25519Keyring,
+		<RuntimeHelper::Runtime as frame_system::Config>::RuntimeCall,
+	) -> sp_runtime::DispatchOutcome,
+) where
+	RuntimeHelper: WithRemoteParachainHelper,
+	AccountIdOf<RuntimeHelper::Runtime>: From<AccountId32>,
+	RuntimeCallOf<RuntimeHelper::Runtime>: From<BridgeGrandpaCall<RuntimeHelper::Runtime, RuntimeHelper::GPI>>
+		+ From<BridgeParachainsCall<RuntimeHelper::Runtime, RuntimeHelper::PPI>>
+		+ From<BridgeMessagesCall<RuntimeHelper::Runtime, RuntimeHelper::MPI>>,
+	BridgedChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>: Chain<Hash = ParaHash> + Parachain,
+	<RuntimeHelper::Runtime as BridgeGrandpaConfig<RuntimeHelper::GPI>>::BridgedChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+{
+	helpers::relayed_incoming_message_works::<
+		RuntimeHelper::Runtime,
+		RuntimeHelper::AllPalletsWithoutSystem,
+		RuntimeHelper::MPI,
+	>(
+		collator_session_key,
+		slot_durations,
+		runtime_para_id,
+		sibling_parachain_id,
+		local_relay_chain_id,
+		construct_and_apply_extrinsic,
+		|_relayer_id_at_this_chain,
+		 relayer_id_at_bridged_chain,
+		 message_destination,
+		 message_nonce,
+		 xcm,
+		 _bridged_chain_id| {
+			let para_header_number = 5;
+			let relay_header_number = 1;
+
+			let lane_id = prepare_configuration();
+
+			// start with bridged relay chain block#0
+			helpers::initialize_bridge_grandpa_pallet::<RuntimeHelper::Runtime, RuntimeHelper::GPI>(
+				test_data::initialization_data::<RuntimeHelper::Runtime, RuntimeHelper::GPI>(0),
+			);
+
+			// pretend that the message with the given nonce has already been delivered, so we
+			// are delivering the next one, while the bridged chain has received the delivery
+			// confirmation for the previous one
+			let confirmed_nonce = message_nonce;
+			let message_nonce = message_nonce + 1;
+			pallet_bridge_messages::InboundLanes::<RuntimeHelper::Runtime, RuntimeHelper::MPI>::mutate(
+				lane_id,
+				|lane_data| {
+					let lane_data = lane_data.as_mut().expect("lane is opened by configuration; qed");
+					lane_data.0.relayers.push_back(UnrewardedRelayer {
+						relayer: relayer_id_at_bridged_chain.clone(),
+						messages: DeliveredMessages::new(confirmed_nonce),
+					});
+				},
+			);
+			let outbound_lane_state = OutboundLaneData {
+				state: LaneState::Opened,
+				oldest_unpruned_nonce: confirmed_nonce + 1,
+				latest_received_nonce: confirmed_nonce,
+				latest_generated_nonce: message_nonce,
+			};
+
+			// generate bridged relay chain finality, parachain heads and message proofs,
+			// to be submitted by relayer to this chain.
+			let (
+				relay_chain_header,
+				grandpa_justification,
+				parachain_head,
+				parachain_heads,
+				para_heads_proof,
+				message_proof,
+			) = test_data::from_parachain::make_complex_relayer_delivery_proofs::<
+				<RuntimeHelper::Runtime as BridgeGrandpaConfig<RuntimeHelper::GPI>>::BridgedChain,
+				BridgedChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+				ThisChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+				LaneIdOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+			>(
+				lane_id,
+				xcm.into(),
+				message_nonce,
+				message_destination,
+				Some(outbound_lane_state),
+				para_header_number,
+				relay_header_number,
+				bridged_para_id,
+				false,
+			);
+
+			let parachain_head_hash = parachain_head.hash();
+			let relay_chain_header_hash = relay_chain_header.hash();
+			let relay_chain_header_number = *relay_chain_header.number();
+			vec![
+				(
+					BridgeGrandpaCall::<RuntimeHelper::Runtime, RuntimeHelper::GPI>::submit_finality_proof {
+						finality_target: Box::new(relay_chain_header),
+						justification: grandpa_justification,
+					}.into(),
+					helpers::VerifySubmitGrandpaFinalityProofOutcome::<RuntimeHelper::Runtime, RuntimeHelper::GPI>::expect_best_header_hash(
+						relay_chain_header_hash,
+					),
+				),
+				(
+					BridgeParachainsCall::<RuntimeHelper::Runtime, RuntimeHelper::PPI>::submit_parachain_heads {
+						at_relay_block: (relay_chain_header_number, relay_chain_header_hash),
+						parachains: parachain_heads,
+						parachain_heads_proof: para_heads_proof,
+					}.into(),
+					helpers::VerifySubmitParachainHeaderProofOutcome::<RuntimeHelper::Runtime, RuntimeHelper::PPI>::expect_best_header_hash(
+						bridged_para_id,
+						parachain_head_hash,
+					),
+				),
+				(
+					BridgeMessagesCall::<RuntimeHelper::Runtime, RuntimeHelper::MPI>::receive_messages_proof {
+						relayer_id_at_bridged_chain,
+						proof: Box::new(message_proof),
+						messages_count: 1,
+						dispatch_weight: Weight::from_parts(1000000000, 0),
+					}.into(),
+					Box::new((
+						helpers::VerifySubmitMessagesProofOutcome::<RuntimeHelper::Runtime, RuntimeHelper::MPI>::expect_last_delivered_nonce(
+							lane_id,
+							message_nonce,
+						),
+						helpers::VerifyInboundLaneConfirmedNonce::<RuntimeHelper::Runtime, RuntimeHelper::MPI>::expect_last_confirmed_nonce(
+							lane_id,
+							confirmed_nonce,
+						),
+					)),
+				),
+			]
+		},
+	);
+}
+
 /// Test-case makes sure that Runtime can dispatch XCM messages submitted by relayer,
 /// with proofs (finality, para heads, message) batched together in signed extrinsic.
 /// Also verifies relayer transaction signed extensions work as intended.
@@ -481,6 +635,7 @@ pub fn complex_relay_extrinsic_works<RuntimeHelper>(
 				xcm.into(),
 				message_nonce,
 				message_destination,
+				None,
 				para_header_number,
 				relay_header_number,
 				bridged_para_id,
@@ -545,6 +700,662 @@ pub fn complex_relay_extrinsic_works<RuntimeHelper>(
 	);
 }
 
+/// Test-case makes sure that Runtime rejects message delivery proof, which declares another lane
+/// than the lane whose inbound data is actually proven.
+pub fn delivery_proof_with_mismatched_lane_is_rejected<RuntimeHelper>(
+	collator_session_key: CollatorSessionKeys<RuntimeHelper::Runtime>,
+	bridged_para_id: u32,
+	prepare_configuration: impl Fn() -> LaneIdOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+	mismatched_lane_id: LaneIdOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+) where
+	RuntimeHelper: WithRemoteParachainHelper,
+	AccountIdOf<RuntimeHelper::Runtime>: From<AccountId32>,
+	ThisChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>:
+		Chain<AccountId = AccountIdOf<RuntimeHelper::Runtime>>,
+	BridgedChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>: Chain<Hash = ParaHash> + Parachain,
+	<RuntimeHelper::Runtime as BridgeGrandpaConfig<RuntimeHelper::GPI>>::BridgedChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+{
+	run_test::<RuntimeHelper::Runtime, _>(collator_session_key, 1000, vec![], || {
+		let lane_id = prepare_configuration();
+		assert_ne!(lane_id, mismatched_lane_id);
+
+		// start with bridged relay chain block#0
+		helpers::initialize_bridge_grandpa_pallet::<RuntimeHelper::Runtime, RuntimeHelper::GPI>(
+			test_data::initialization_data::<RuntimeHelper::Runtime, RuntimeHelper::GPI>(0),
+		);
+
+		// generate bridged relay chain finality, parachain heads and delivery proof of the
+		// `lane_id`, which claims to prove the `mismatched_lane_id`
+		let relayer: AccountIdOf<RuntimeHelper::Runtime> = AccountId32::from(Alice.public()).into();
+		let relayers_state = UnrewardedRelayersState {
+			unrewarded_relayer_entries: 1,
+			total_messages: 1,
+			..Default::default()
+		};
+		let (
+			relay_chain_header,
+			grandpa_justification,
+			_,
+			parachain_heads,
+			para_heads_proof,
+			message_delivery_proof,
+		) = test_data::from_parachain::make_complex_relayer_confirmation_proofs_with_lanes::<
+			<RuntimeHelper::Runtime as BridgeGrandpaConfig<RuntimeHelper::GPI>>::BridgedChain,
+			BridgedChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+			ThisChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+			LaneIdOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+		>(
+			lane_id,
+			mismatched_lane_id,
+			1,
+			5,
+			bridged_para_id,
+			relayer.clone(),
+			relayers_state.clone(),
+			LaneState::Opened,
+			StorageProofFormat::Trie,
+		);
+
+		// finality and parachain heads are accepted
+		let relay_chain_header_hash = relay_chain_header.hash();
+		let relay_chain_header_number = *relay_chain_header.number();
+		assert_ok!(pallet_bridge_grandpa::Pallet::<RuntimeHelper::Runtime, RuntimeHelper::GPI>::submit_finality_proof(
+			frame_system::RawOrigin::Signed(relayer.clone()).into(),
+			Box::new(relay_chain_header),
+			grandpa_justification,
+		));
+		assert_ok!(pallet_bridge_parachains::Pallet::<RuntimeHelper::Runtime, RuntimeHelper::PPI>::submit_parachain_heads(
+			frame_system::RawOrigin::Signed(relayer.clone()).into(),
+			(relay_chain_header_number, relay_chain_header_hash),
+			parachain_heads,
+			para_heads_proof,
+		));
+
+		// but the delivery proof is not
+		assert_err!(
+			pallet_bridge_messages::Pallet::<RuntimeHelper::Runtime, RuntimeHelper::MPI>::receive_messages_delivery_proof(
+				frame_system::RawOrigin::Signed(relayer).into(),
+				message_delivery_proof,
+				relayers_state,
+			),
+			pallet_bridge_messages::Error::<RuntimeHelper::Runtime, RuntimeHelper::MPI>::InvalidMessagesDeliveryProof,
+		);
+	})
+}
+
+/// Test-case makes sure that Runtime accepts message delivery proof of the inbound lane in the
+/// given `lane_state`, and that this state doesn't leak into the state of our outbound lane.
+///
+/// `pallet_bridge_messages::proofs::verify_messages_delivery_proof` returns the bridged inbound
+/// lane data without looking at its `state`, and `receive_messages_delivery_proof` only reads the
+/// relayers and nonces from it, so relayers are still rewarded for messages delivered before the
+/// bridged lane was closed.
+pub fn delivery_proof_of_inbound_lane_in_state_is_accepted<RuntimeHelper>(
+	collator_session_key: CollatorSessionKeys<RuntimeHelper::Runtime>,
+	bridged_para_id: u32,
+	prepare_configuration: impl Fn() -> LaneIdOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+	lane_state: LaneState,
+) where
+	RuntimeHelper: WithRemoteParachainHelper,
+	AccountIdOf<RuntimeHelper::Runtime>: From<AccountId32>,
+	ThisChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>:
+		Chain<AccountId = AccountIdOf<RuntimeHelper::Runtime>>,
+	BridgedChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>: Chain<Hash = ParaHash> + Parachain,
+	<RuntimeHelper::Runtime as BridgeGrandpaConfig<RuntimeHelper::GPI>>::BridgedChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+{
+	run_test::<RuntimeHelper::Runtime, _>(collator_session_key, 1000, vec![], || {
+		let lane_id = prepare_configuration();
+
+		// start with bridged relay chain block#0
+		helpers::initialize_bridge_grandpa_pallet::<RuntimeHelper::Runtime, RuntimeHelper::GPI>(
+			test_data::initialization_data::<RuntimeHelper::Runtime, RuntimeHelper::GPI>(0),
+		);
+
+		// pretend that the message, whose delivery is confirmed, has been sent
+		pallet_bridge_messages::OutboundLanes::<RuntimeHelper::Runtime, RuntimeHelper::MPI>::mutate(
+			lane_id,
+			|lane_data| {
+				let lane_data = lane_data.as_mut().expect("lane is opened by configuration; qed");
+				lane_data.latest_generated_nonce = 1;
+			},
+		);
+
+		// generate bridged relay chain finality, parachain heads and delivery proof
+		let relayer: AccountIdOf<RuntimeHelper::Runtime> = AccountId32::from(Alice.public()).into();
+		let relayers_state = UnrewardedRelayersState {
+			unrewarded_relayer_entries: 1,
+			messages_in_oldest_entry: 1,
+			total_messages: 1,
+			last_delivered_nonce: 1,
+		};
+		let (
+			relay_chain_header,
+			grandpa_justification,
+			_,
+			parachain_heads,
+			para_heads_proof,
+			message_delivery_proof,
+		) = test_data::from_parachain::make_complex_relayer_confirmation_proofs_with_lane_state::<
+			<RuntimeHelper::Runtime as BridgeGrandpaConfig<RuntimeHelper::GPI>>::BridgedChain,
+			BridgedChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+			ThisChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+			LaneIdOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+		>(
+			lane_id,
+			1,
+			5,
+			bridged_para_id,
+			relayer.clone(),
+			relayers_state.clone(),
+			lane_state,
+		);
+
+		let relay_chain_header_hash = relay_chain_header.hash();
+		let relay_chain_header_number = *relay_chain_header.number();
+		assert_ok!(pallet_bridge_grandpa::Pallet::<RuntimeHelper::Runtime, RuntimeHelper::GPI>::submit_finality_proof(
+			frame_system::RawOrigin::Signed(relayer.clone()).into(),
+			Box::new(relay_chain_header),
+			grandpa_justification,
+		));
+		assert_ok!(pallet_bridge_parachains::Pallet::<RuntimeHelper::Runtime, RuntimeHelper::PPI>::submit_parachain_heads(
+			frame_system::RawOrigin::Signed(relayer.clone()).into(),
+			(relay_chain_header_number, relay_chain_header_hash),
+			parachain_heads,
+			para_heads_proof,
+		));
+
+		// the delivery is confirmed, whatever the state of the inbound lane
+		assert_ok!(pallet_bridge_messages::Pallet::<RuntimeHelper::Runtime, RuntimeHelper::MPI>::receive_messages_delivery_proof(
+			frame_system::RawOrigin::Signed(relayer).into(),
+			message_delivery_proof,
+			relayers_state,
+		));
+		let lane_data =
+			pallet_bridge_messages::OutboundLanes::<RuntimeHelper::Runtime, RuntimeHelper::MPI>::get(
+				lane_id,
+			);
+		assert_eq!(
+			lane_data.map(|lane_data| (lane_data.latest_received_nonce, lane_data.state)),
+			Some((1, LaneState::Opened)),
+		);
+	})
+}
+
+/// Test-case makes sure that Runtime ignores messages proof, carrying the message with
+/// `replayed_nonce`, when messages up to `last_delivered_nonce` have already been delivered.
+pub fn replayed_message_delivery_is_ignored<RuntimeHelper>(
+	collator_session_key: CollatorSessionKeys<RuntimeHelper::Runtime>,
+	bridged_para_id: u32,
+	prepare_configuration: impl Fn() -> LaneIdOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+	last_delivered_nonce: MessageNonce,
+	replayed_nonce: MessageNonce,
+) where
+	RuntimeHelper: WithRemoteParachainHelper,
+	AccountIdOf<RuntimeHelper::Runtime>: From<AccountId32>,
+	BridgedChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>: Chain<Hash = ParaHash> + Parachain,
+	<RuntimeHelper::Runtime as BridgeGrandpaConfig<RuntimeHelper::GPI>>::BridgedChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+{
+	assert!(replayed_nonce > 0 && replayed_nonce <= last_delivered_nonce);
+
+	run_test::<RuntimeHelper::Runtime, _>(collator_session_key, 1000, vec![], || {
+		let lane_id = prepare_configuration();
+
+		// start with bridged relay chain block#0
+		helpers::initialize_bridge_grandpa_pallet::<RuntimeHelper::Runtime, RuntimeHelper::GPI>(
+			test_data::initialization_data::<RuntimeHelper::Runtime, RuntimeHelper::GPI>(0),
+		);
+
+		// pretend that messages up to `last_delivered_nonce` have already been delivered
+		let relayer_id_at_bridged_chain = helpers::relayer_id_at_bridged_chain::<
+			RuntimeHelper::Runtime,
+			RuntimeHelper::MPI,
+		>();
+		pallet_bridge_messages::InboundLanes::<RuntimeHelper::Runtime, RuntimeHelper::MPI>::mutate(
+			lane_id,
+			|lane_data| {
+				let lane_data = lane_data.as_mut().expect("lane is opened by configuration; qed");
+				lane_data.0 = test_data::expected_inbound_lane_data_after(
+					lane_data.0.clone(),
+					relayer_id_at_bridged_chain.clone(),
+					1..=last_delivered_nonce,
+				);
+			},
+		);
+		let lane_data_before =
+			pallet_bridge_messages::InboundLanes::<RuntimeHelper::Runtime, RuntimeHelper::MPI>::get(
+				lane_id,
+			)
+			.expect("lane is opened by configuration; qed")
+			.0;
+
+		// generate bridged relay chain finality, parachain heads and proof of the stale message
+		let (
+			relay_chain_header,
+			grandpa_justification,
+			_,
+			parachain_heads,
+			para_heads_proof,
+			message_proof,
+		) = test_data::from_parachain::make_replayed_delivery_proof::<
+			<RuntimeHelper::Runtime as BridgeGrandpaConfig<RuntimeHelper::GPI>>::BridgedChain,
+			BridgedChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+			ThisChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+			LaneIdOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+		>(
+			lane_id,
+			replayed_nonce,
+			test_data::dummy_xcm(),
+			[GlobalConsensus(Polkadot), Parachain(1000)].into(),
+			5,
+			1,
+			bridged_para_id,
+		);
+
+		// finality and parachain heads are accepted
+		let relayer: AccountIdOf<RuntimeHelper::Runtime> = AccountId32::from(Alice.public()).into();
+		let relay_chain_header_hash = relay_chain_header.hash();
+		let relay_chain_header_number = *relay_chain_header.number();
+		assert_ok!(pallet_bridge_grandpa::Pallet::<RuntimeHelper::Runtime, RuntimeHelper::GPI>::submit_finality_proof(
+			frame_system::RawOrigin::Signed(relayer.clone()).into(),
+			Box::new(relay_chain_header),
+			grandpa_justification,
+		));
+		assert_ok!(pallet_bridge_parachains::Pallet::<RuntimeHelper::Runtime, RuntimeHelper::PPI>::submit_parachain_heads(
+			frame_system::RawOrigin::Signed(relayer.clone()).into(),
+			(relay_chain_header_number, relay_chain_header_hash),
+			parachain_heads,
+			para_heads_proof,
+		));
+
+		// the stale message proof is accepted, but the message is not delivered again
+		assert_ok!(pallet_bridge_messages::Pallet::<RuntimeHelper::Runtime, RuntimeHelper::MPI>::receive_messages_proof(
+			frame_system::RawOrigin::Signed(relayer).into(),
+			relayer_id_at_bridged_chain,
+			Box::new(message_proof),
+			1,
+			Weight::from_parts(1000000000, 0),
+		));
+		assert_eq!(
+			pallet_bridge_messages::InboundLanes::<RuntimeHelper::Runtime, RuntimeHelper::MPI>::get(
+				lane_id,
+			)
+			.expect("lane is opened by configuration; qed")
+			.0,
+			lane_data_before,
+		);
+	})
+}
+
+/// Test-case makes sure that Runtime doesn't import the bridged parachain head, whose number
+/// differs from the number of the head that is proven by the bridged relay chain state, and
+/// rejects messages proof that is built against that head.
+pub fn stale_bridged_para_head_is_rejected<RuntimeHelper>(
+	collator_session_key: CollatorSessionKeys<RuntimeHelper::Runtime>,
+	bridged_para_id: u32,
+	prepare_configuration: impl Fn() -> LaneIdOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+) where
+	RuntimeHelper: WithRemoteParachainHelper,
+	AccountIdOf<RuntimeHelper::Runtime>: From<AccountId32>,
+	BridgedChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>: Chain<Hash = ParaHash> + Parachain,
+	<RuntimeHelper::Runtime as BridgeGrandpaConfig<RuntimeHelper::GPI>>::BridgedChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+{
+	run_test::<RuntimeHelper::Runtime, _>(collator_session_key, 1000, vec![], || {
+		let lane_id = prepare_configuration();
+
+		// start with bridged relay chain block#0
+		helpers::initialize_bridge_grandpa_pallet::<RuntimeHelper::Runtime, RuntimeHelper::GPI>(
+			test_data::initialization_data::<RuntimeHelper::Runtime, RuntimeHelper::GPI>(0),
+		);
+
+		// generate bridged relay chain finality, proving parachain head#4, and messages proof,
+		// built against the parachain head#5
+		let relayer_id_at_bridged_chain = helpers::relayer_id_at_bridged_chain::<
+			RuntimeHelper::Runtime,
+			RuntimeHelper::MPI,
+		>();
+		let (
+			relay_chain_header,
+			grandpa_justification,
+			claimed_para_head,
+			parachain_heads,
+			para_heads_proof,
+			message_proof,
+		) = test_data::from_parachain::make_inconsistent_relayer_delivery_proofs::<
+			<RuntimeHelper::Runtime as BridgeGrandpaConfig<RuntimeHelper::GPI>>::BridgedChain,
+			BridgedChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+			ThisChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+			LaneIdOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+		>(
+			lane_id,
+			test_data::dummy_xcm(),
+			1,
+			[GlobalConsensus(Polkadot), Parachain(1000)].into(),
+			None,
+			5,
+			4,
+			1,
+			bridged_para_id,
+			false,
+		);
+
+		// finality is accepted
+		let relayer: AccountIdOf<RuntimeHelper::Runtime> = AccountId32::from(Alice.public()).into();
+		let relay_chain_header_hash = relay_chain_header.hash();
+		let relay_chain_header_number = *relay_chain_header.number();
+		assert_ok!(pallet_bridge_grandpa::Pallet::<RuntimeHelper::Runtime, RuntimeHelper::GPI>::submit_finality_proof(
+			frame_system::RawOrigin::Signed(relayer.clone()).into(),
+			Box::new(relay_chain_header),
+			grandpa_justification,
+		));
+
+		// the call succeeds, but the claimed parachain head is ignored
+		assert_ok!(pallet_bridge_parachains::Pallet::<RuntimeHelper::Runtime, RuntimeHelper::PPI>::submit_parachain_heads(
+			frame_system::RawOrigin::Signed(relayer.clone()).into(),
+			(relay_chain_header_number, relay_chain_header_hash),
+			parachain_heads,
+			para_heads_proof,
+		));
+		assert!(pallet_bridge_parachains::ParasInfo::<RuntimeHelper::Runtime, RuntimeHelper::PPI>::get(
+			ParaId(bridged_para_id),
+		)
+		.is_none());
+		assert!(pallet_bridge_parachains::Pallet::<RuntimeHelper::Runtime, RuntimeHelper::PPI>::parachain_head(
+			ParaId(bridged_para_id),
+			claimed_para_head.hash(),
+		)
+		.is_none());
+
+		// so the messages proof, built against the claimed head, is rejected
+		assert_err!(
+			pallet_bridge_messages::Pallet::<RuntimeHelper::Runtime, RuntimeHelper::MPI>::receive_messages_proof(
+				frame_system::RawOrigin::Signed(relayer).into(),
+				relayer_id_at_bridged_chain,
+				Box::new(message_proof),
+				1,
+				Weight::from_parts(1000000000, 0),
+			),
+			pallet_bridge_messages::Error::<RuntimeHelper::Runtime, RuntimeHelper::MPI>::UnknownBridgedHeader,
+		);
+	})
+}
+
+/// Test-case makes sure that Runtime accepts messages proof, carrying the message which can't be
+/// dispatched, and marks the message as delivered anyway.
+pub fn undispatchable_message_is_delivered<RuntimeHelper>(
+	collator_session_key: CollatorSessionKeys<RuntimeHelper::Runtime>,
+	bridged_para_id: u32,
+	prepare_configuration: impl Fn() -> LaneIdOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+) where
+	RuntimeHelper: WithRemoteParachainHelper,
+	AccountIdOf<RuntimeHelper::Runtime>: From<AccountId32>,
+	BridgedChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>: Chain<Hash = ParaHash> + Parachain,
+	<RuntimeHelper::Runtime as BridgeGrandpaConfig<RuntimeHelper::GPI>>::BridgedChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+{
+	run_test::<RuntimeHelper::Runtime, _>(collator_session_key, 1000, vec![], || {
+		let lane_id = prepare_configuration();
+
+		// start with bridged relay chain block#0
+		helpers::initialize_bridge_grandpa_pallet::<RuntimeHelper::Runtime, RuntimeHelper::GPI>(
+			test_data::initialization_data::<RuntimeHelper::Runtime, RuntimeHelper::GPI>(0),
+		);
+
+		// generate bridged relay chain finality, parachain heads and proof of the message
+		let (
+			relay_chain_header,
+			grandpa_justification,
+			_,
+			parachain_heads,
+			para_heads_proof,
+			message_proof,
+		) = test_data::from_parachain::make_failing_delivery_proofs::<
+			<RuntimeHelper::Runtime as BridgeGrandpaConfig<RuntimeHelper::GPI>>::BridgedChain,
+			BridgedChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+			ThisChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+			LaneIdOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+		>(
+			lane_id,
+			1,
+			[GlobalConsensus(Polkadot), Parachain(1000)].into(),
+			5,
+			1,
+			bridged_para_id,
+		);
+
+		// finality and parachain heads are accepted
+		let relayer: AccountIdOf<RuntimeHelper::Runtime> = AccountId32::from(Alice.public()).into();
+		let relay_chain_header_hash = relay_chain_header.hash();
+		let relay_chain_header_number = *relay_chain_header.number();
+		assert_ok!(pallet_bridge_grandpa::Pallet::<RuntimeHelper::Runtime, RuntimeHelper::GPI>::submit_finality_proof(
+			frame_system::RawOrigin::Signed(relayer.clone()).into(),
+			Box::new(relay_chain_header),
+			grandpa_justification,
+		));
+		assert_ok!(pallet_bridge_parachains::Pallet::<RuntimeHelper::Runtime, RuntimeHelper::PPI>::submit_parachain_heads(
+			frame_system::RawOrigin::Signed(relayer.clone()).into(),
+			(relay_chain_header_number, relay_chain_header_hash),
+			parachain_heads,
+			para_heads_proof,
+		));
+
+		// the message is delivered, even though its dispatch fails
+		assert_ok!(pallet_bridge_messages::Pallet::<RuntimeHelper::Runtime, RuntimeHelper::MPI>::receive_messages_proof(
+			frame_system::RawOrigin::Signed(relayer).into(),
+			helpers::relayer_id_at_bridged_chain::<RuntimeHelper::Runtime, RuntimeHelper::MPI>(),
+			Box::new(message_proof),
+			1,
+			Weight::from_parts(1000000000, 0),
+		));
+		assert_eq!(
+			pallet_bridge_messages::InboundLanes::<RuntimeHelper::Runtime, RuntimeHelper::MPI>::get(
+				lane_id,
+			)
+			.expect("lane is opened by configuration; qed")
+			.0
+			.last_delivered_nonce(),
+			1,
+		);
+	})
+}
+
+/// Test-case makes sure that the bridged relay chain header, scheduling GRANDPA authority set
+/// change, is accepted and the new authority set is enacted immediately.
+pub fn relay_chain_authority_set_change_is_enacted<RuntimeHelper>(
+	collator_session_key: CollatorSessionKeys<RuntimeHelper::Runtime>,
+) where
+	RuntimeHelper: WithRemoteParachainHelper,
+	AccountIdOf<RuntimeHelper::Runtime>: From<AccountId32>,
+	<RuntimeHelper::Runtime as BridgeGrandpaConfig<RuntimeHelper::GPI>>::BridgedChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+{
+	run_test::<RuntimeHelper::Runtime, _>(collator_session_key, 1000, vec![], || {
+		// start with bridged relay chain block#0
+		helpers::initialize_bridge_grandpa_pallet::<RuntimeHelper::Runtime, RuntimeHelper::GPI>(
+			test_data::initialization_data::<RuntimeHelper::Runtime, RuntimeHelper::GPI>(0),
+		);
+		let initial_set = pallet_bridge_grandpa::CurrentAuthoritySet::<
+			RuntimeHelper::Runtime,
+			RuntimeHelper::GPI,
+		>::get();
+
+		// generate bridged relay chain block#1, which schedules the authority set change
+		let next_authorities: sp_consensus_grandpa::AuthorityList =
+			vec![(bp_test_utils::ALICE.into(), 1), (bp_test_utils::BOB.into(), 1)];
+		let (relay_chain_header, grandpa_justification) =
+			test_data::from_grandpa_chain::make_complex_bridged_grandpa_header_proof_with_authority_set_change::<
+				<RuntimeHelper::Runtime as BridgeGrandpaConfig<RuntimeHelper::GPI>>::BridgedChain,
+			>(
+				Default::default(),
+				1,
+				true,
+				test_data::from_grandpa_chain::AuthoritySetChange::Scheduled(
+					sp_consensus_grandpa::ScheduledChange {
+						next_authorities: next_authorities.clone(),
+						delay: 0,
+					},
+				),
+			);
+
+		// the header is imported and the new set is enacted
+		let relayer: AccountIdOf<RuntimeHelper::Runtime> = AccountId32::from(Alice.public()).into();
+		assert_ok!(pallet_bridge_grandpa::Pallet::<RuntimeHelper::Runtime, RuntimeHelper::GPI>::submit_finality_proof(
+			frame_system::RawOrigin::Signed(relayer).into(),
+			Box::new(relay_chain_header.clone()),
+			grandpa_justification,
+		));
+		assert_eq!(
+			pallet_bridge_grandpa::BestFinalized::<RuntimeHelper::Runtime, RuntimeHelper::GPI>::get()
+				.map(|id| id.hash()),
+			Some(relay_chain_header.hash()),
+		);
+		let current_set = pallet_bridge_grandpa::CurrentAuthoritySet::<
+			RuntimeHelper::Runtime,
+			RuntimeHelper::GPI,
+		>::get();
+		assert_eq!(current_set.set_id, initial_set.set_id + 1);
+		assert_eq!(current_set.authorities.into_inner(), next_authorities);
+	})
+}
+
+/// Test-case makes sure that the complex relayer delivery batch starts with the given prelude
+/// call (e.g. a fee top-up), followed by the delivery calls.
+pub fn complex_relayer_delivery_batch_starts_with_prelude<RuntimeHelper>(
+	collator_session_key: CollatorSessionKeys<RuntimeHelper::Runtime>,
+	prelude_call: RuntimeCallOf<RuntimeHelper::Runtime>,
+) where
+	RuntimeHelper: WithRemoteParachainHelper,
+	RuntimeHelper::Runtime:
+		pallet_utility::Config<RuntimeCall = RuntimeCallOf<RuntimeHelper::Runtime>>,
+	RuntimeCallOf<RuntimeHelper::Runtime>: From<BridgeGrandpaCall<RuntimeHelper::Runtime, RuntimeHelper::GPI>>
+		+ From<BridgeParachainsCall<RuntimeHelper::Runtime, RuntimeHelper::PPI>>
+		+ From<BridgeMessagesCall<RuntimeHelper::Runtime, RuntimeHelper::MPI>>,
+	BridgedChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>: Chain<Hash = ParaHash> + Parachain,
+	<RuntimeHelper::Runtime as BridgeGrandpaConfig<RuntimeHelper::GPI>>::BridgedChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+{
+	run_test::<RuntimeHelper::Runtime, _>(collator_session_key, 1000, vec![], || {
+		let (
+			relay_chain_header,
+			grandpa_justification,
+			_,
+			parachain_heads,
+			para_heads_proof,
+			message_proof,
+		) = test_data::from_parachain::make_complex_relayer_delivery_proofs::<
+			<RuntimeHelper::Runtime as pallet_bridge_grandpa::Config<RuntimeHelper::GPI>>::BridgedChain,
+			BridgedChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+			ThisChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+			LaneIdOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>
+		>(
+			LaneIdOf::<RuntimeHelper::Runtime, RuntimeHelper::MPI>::default(),
+			vec![Instruction::<()>::ClearOrigin; 1_024].into(),
+			1,
+			[GlobalConsensus(Polkadot), Parachain(1_000)].into(),
+			None,
+			1,
+			5,
+			1_000,
+			false,
+		);
+
+		let batch = test_data::from_parachain::make_complex_relayer_delivery_batch_with_prelude::<
+			RuntimeHelper::Runtime,
+			RuntimeHelper::GPI,
+			RuntimeHelper::PPI,
+			RuntimeHelper::MPI,
+		>(
+			vec![prelude_call.clone()],
+			relay_chain_header,
+			grandpa_justification,
+			parachain_heads,
+			para_heads_proof,
+			message_proof,
+			helpers::relayer_id_at_bridged_chain::<RuntimeHelper::Runtime, RuntimeHelper::MPI>(),
+		);
+
+		// the prelude call is followed by the grandpa, parachain heads and messages calls
+		let pallet_utility::Call::<RuntimeHelper::Runtime>::batch_all { calls } = batch else {
+			panic!("Expected `batch_all` call")
+		};
+		assert_eq!(calls.len(), 4);
+		assert_eq!(calls[0], prelude_call);
+	})
+}
+
+/// Test-case makes sure that the complex relayer delivery batch may carry several message
+/// proofs, each submitted with its own `receive_messages_proof` call.
+pub fn complex_relayer_delivery_batch_with_two_message_proofs<RuntimeHelper>(
+	collator_session_key: CollatorSessionKeys<RuntimeHelper::Runtime>,
+) where
+	RuntimeHelper: WithRemoteParachainHelper,
+	RuntimeHelper::Runtime:
+		pallet_utility::Config<RuntimeCall = RuntimeCallOf<RuntimeHelper::Runtime>>,
+	RuntimeCallOf<RuntimeHelper::Runtime>: From<BridgeGrandpaCall<RuntimeHelper::Runtime, RuntimeHelper::GPI>>
+		+ From<BridgeParachainsCall<RuntimeHelper::Runtime, RuntimeHelper::PPI>>
+		+ From<BridgeMessagesCall<RuntimeHelper::Runtime, RuntimeHelper::MPI>>,
+	BridgedChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>: Chain<Hash = ParaHash> + Parachain,
+	<RuntimeHelper::Runtime as BridgeGrandpaConfig<RuntimeHelper::GPI>>::BridgedChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+{
+	run_test::<RuntimeHelper::Runtime, _>(collator_session_key, 1000, vec![], || {
+		let make_proofs = |message_nonce| {
+			test_data::from_parachain::make_complex_relayer_delivery_proofs::<
+				<RuntimeHelper::Runtime as pallet_bridge_grandpa::Config<RuntimeHelper::GPI>>::BridgedChain,
+				BridgedChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+				ThisChainOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>,
+				LaneIdOf<RuntimeHelper::Runtime, RuntimeHelper::MPI>
+			>(
+				LaneIdOf::<RuntimeHelper::Runtime, RuntimeHelper::MPI>::default(),
+				vec![Instruction::<()>::ClearOrigin; 1_024].into(),
+				message_nonce,
+				[GlobalConsensus(Polkadot), Parachain(1_000)].into(),
+				None,
+				1,
+				5,
+				1_000,
+				false,
+			)
+		};
+		let (
+			relay_chain_header,
+			grandpa_justification,
+			_,
+			parachain_heads,
+			para_heads_proof,
+			first_message_proof,
+		) = make_proofs(1);
+		let (_, _, _, _, _, second_message_proof) = make_proofs(2);
+
+		let batch =
+			test_data::from_parachain::make_complex_relayer_delivery_batch_with_message_proofs::<
+				RuntimeHelper::Runtime,
+				RuntimeHelper::GPI,
+				RuntimeHelper::PPI,
+				RuntimeHelper::MPI,
+			>(
+				relay_chain_header,
+				grandpa_justification,
+				parachain_heads,
+				para_heads_proof,
+				vec![
+					(first_message_proof, 1, Weight::from_parts(1000000000, 0)),
+					(second_message_proof, 1, Weight::from_parts(1000000000, 0)),
+				],
+				helpers::relayer_id_at_bridged_chain::<RuntimeHelper::Runtime, RuntimeHelper::MPI>(),
+			);
+
+		// the grandpa and parachain heads calls are followed by both messages calls
+		let pallet_utility::Call::<RuntimeHelper::Runtime>::batch_all { calls } = batch else {
+			panic!("Expected `batch_all` call")
+		};
+		assert_eq!(calls.len(), 4);
+	})
+}
+
 /// Estimates transaction fee for default message delivery transaction (batched with required
 /// proofs) from bridged parachain.
 pub fn can_calculate_fee_for_complex_message_delivery_transaction<RuntimeHelper>(
@@ -586,6 +1397,7 @@ where
 			vec![Instruction::<()>::ClearOrigin; 1_024].into(),
 			1,
 			[GlobalConsensus(Polkadot), Parachain(1_000)].into(),
+			None,
 			1,
 			5,
 			1_000,
@@ -720,6 +1532,7 @@ where
 			vec![Instruction::<()>::ClearOrigin; 1_024].into(),
 			1,
 			[GlobalConsensus(Polkadot), Parachain(1_000)].into(),
+			None,
 			1,
 			5,
 			1_000,
@@ -164,6 +164,41 @@ where
 	}
 }
 
+/// Checks that the latest confirmed nonce in the bridge messages pallet equals to given one.
+pub struct VerifyInboundLaneConfirmedNonce<Runtime: BridgeMessagesConfig<MPI>, MPI: 'static> {
+	lane: LaneIdOf<Runtime, MPI>,
+	expected_nonce: MessageNonce,
+	_marker: PhantomData<(Runtime, MPI)>,
+}
+
+impl<Runtime, MPI> VerifyInboundLaneConfirmedNonce<Runtime, MPI>
+where
+	Runtime: BridgeMessagesConfig<MPI>,
+	MPI: 'static,
+{
+	/// Expect given confirmed nonce to be the latest after transaction.
+	pub fn expect_last_confirmed_nonce(
+		lane: LaneIdOf<Runtime, MPI>,
+		expected_nonce: MessageNonce,
+	) -> Box<dyn VerifyTransactionOutcome> {
+		Box::new(Self { lane, expected_nonce, _marker: PhantomData })
+	}
+}
+
+impl<Runtime, MPI> VerifyTransactionOutcome for VerifyInboundLaneConfirmedNonce<Runtime, MPI>
+where
+	Runtime: BridgeMessagesConfig<MPI>,
+	MPI: 'static,
+{
+	fn verify_outcome(&self) {
+		assert_eq!(
+			pallet_bridge_messages::InboundLanes::<Runtime, MPI>::get(self.lane)
+				.map(|d| d.last_confirmed_nonce),
+			Some(self.expected_nonce),
+		);
+	}
+}
+
 /// Verifies that relayer is rewarded at this chain.
 pub struct VerifyRelayerRewarded<Runtime: pallet_bridge_relayers::Config<RPI>, RPI: 'static> {
 	relayer: Runtime::AccountId,
@@ -16,14 +16,17 @@
 
 //! Generating test data for bridges with remote GRANDPA chains.
 
-use crate::test_data::{prepare_inbound_xcm, XcmAsPlainPayload};
+use crate::test_data::{prepare_inbound_xcm, MessageProofSizes, XcmAsPlainPayload};
 
 use bp_messages::{
 	source_chain::FromBridgedChainMessagesDeliveryProof,
 	target_chain::FromBridgedChainMessagesProof, ChainWithMessages, LaneState, MessageNonce,
-	UnrewardedRelayersState,
+	OutboundLaneData, UnrewardedRelayersState,
+};
+use bp_runtime::{
+	AccountIdOf, BlockNumberOf, Chain, HeaderOf, RawStorageProof, StorageProofFormat,
+	UnverifiedStorageProofParams,
 };
-use bp_runtime::{AccountIdOf, BlockNumberOf, Chain, HeaderOf, UnverifiedStorageProofParams};
 use bp_test_utils::make_default_justification;
 use codec::Encode;
 use pallet_bridge_grandpa::{BridgedChain, BridgedHeader};
@@ -37,11 +40,34 @@ use bp_runtime::HashOf;
 use pallet_bridge_messages::{
 	messages_generation::{
 		encode_all_messages, encode_lane_data, prepare_message_delivery_storage_proof,
-		prepare_messages_storage_proof,
+		prepare_messages_storage_proof, prepare_multi_lane_messages_storage_proof,
 	},
 	BridgedChainOf, LaneIdOf,
 };
+use sp_consensus_grandpa::{ConsensusLog, ScheduledChange, GRANDPA_ENGINE_ID};
 use sp_runtime::DigestItem;
+use sp_std::ops::RangeInclusive;
+
+/// GRANDPA authority set change, announced by the bridged chain header.
+#[derive(Clone, Debug)]
+pub enum AuthoritySetChange<Number> {
+	/// Standard change, scheduled by the header.
+	Scheduled(ScheduledChange<Number>),
+	/// Forced change, scheduled by the header. The first field is the median last finalized
+	/// block number.
+	Forced(Number, ScheduledChange<Number>),
+}
+
+impl<Number: Encode> AuthoritySetChange<Number> {
+	/// Returns the consensus digest item, announcing this change.
+	pub fn into_digest_item(self) -> DigestItem {
+		let log = match self {
+			Self::Scheduled(change) => ConsensusLog::ScheduledChange(change),
+			Self::Forced(median, change) => ConsensusLog::ForcedChange(median, change),
+		};
+		DigestItem::Consensus(GRANDPA_ENGINE_ID, log.encode())
+	}
+}
 
 /// Prepare a batch call with bridged GRANDPA finality and message proof.
 pub fn make_complex_relayer_delivery_batch<Runtime, GPI, MPI>(
@@ -158,14 +184,166 @@ where
 	.into()
 }
 
+/// Expected transaction fee and reward of the relayer, submitting the message delivery batch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RelayerDeliveryBatchEconomics<Balance> {
+	/// Fee, paid by the relayer for the batch transaction.
+	pub fee: Balance,
+	/// Reward of the relayer: the refunded transaction fee and the reward for delivered messages.
+	pub reward: Balance,
+}
+
+impl<Balance: PartialOrd> RelayerDeliveryBatchEconomics<Balance> {
+	/// Returns true if the relayer gets more than it pays for the batch transaction.
+	pub fn is_net_positive(&self) -> bool {
+		self.reward > self.fee
+	}
+}
+
+/// Compute expected fee and reward of the relayer, delivering `messages_count` messages with the
+/// [`make_complex_relayer_delivery_batch`] call.
+///
+/// The batch weight is the `submit_finality_proof` weight for the justification with given number
+/// of precommits and votes ancestries plus the `receive_messages_proof` weight for given number of
+/// messages, which is computed from the messages pallet weights. The relayers pallet refunds the
+/// fee of the successful delivery, minus the difference between slashing and rewarding the
+/// relayer. The relayer also gets the `delivery_reward` for every delivered message. The length
+/// fee is ignored here, because it is refunded in full.
+pub fn expected_relayer_delivery_batch_economics<
+	GrandpaWeightInfo,
+	MessagesWeightInfo,
+	RelayersWeightInfo,
+	WeightToFee,
+>(
+	precommits_len: u32,
+	votes_ancestries_len: u32,
+	messages_count: u32,
+	dispatch_weight: Weight,
+	delivery_reward: WeightToFee::Balance,
+) -> RelayerDeliveryBatchEconomics<WeightToFee::Balance>
+where
+	GrandpaWeightInfo: pallet_bridge_grandpa::WeightInfoExt,
+	MessagesWeightInfo: pallet_bridge_messages::WeightInfoExt,
+	RelayersWeightInfo: pallet_bridge_relayers::WeightInfoExt,
+	WeightToFee: frame_support::weights::WeightToFee,
+{
+	let submit_finality_proof_weight =
+		GrandpaWeightInfo::submit_finality_proof_weight(precommits_len, votes_ancestries_len);
+	let receive_messages_proof_weight = if messages_count == 1 {
+		MessagesWeightInfo::receive_single_message_proof()
+	} else {
+		MessagesWeightInfo::receive_n_messages_proof(messages_count)
+	}
+	.saturating_add(MessagesWeightInfo::receive_messages_proof_overhead_from_runtime())
+	.saturating_add(dispatch_weight);
+	let batch_weight = submit_finality_proof_weight.saturating_add(receive_messages_proof_weight);
+
+	// the weight of slashing the relayer is replaced with the weight of rewarding it
+	let extra_weight = RelayersWeightInfo::extra_weight_of_successful_receive_messages_proof_call();
+	let refunded_weight = batch_weight.saturating_sub(extra_weight);
+	let messages_reward = delivery_reward.saturating_mul(messages_count.into());
+
+	RelayerDeliveryBatchEconomics {
+		fee: WeightToFee::weight_to_fee(&batch_weight),
+		reward: WeightToFee::weight_to_fee(&refunded_weight).saturating_add(messages_reward),
+	}
+}
+
 /// Prepare storage proofs of messages, stored at the (bridged) source GRANDPA chain.
+///
+/// If `outbound_lane_state` is given, it is included in the proof as well.
 pub fn make_complex_relayer_delivery_proofs<BridgedChain, ThisChainWithMessages, LaneId>(
 	lane_id: LaneId,
 	xcm_message: Xcm<()>,
 	message_nonce: MessageNonce,
 	message_destination: Junctions,
+	outbound_lane_state: Option<OutboundLaneData>,
+	header_number: BlockNumberOf<BridgedChain>,
+	is_minimal_call: bool,
+) -> (
+	HeaderOf<BridgedChain>,
+	GrandpaJustification<HeaderOf<BridgedChain>>,
+	FromBridgedChainMessagesProof<HashOf<BridgedChain>, LaneId>,
+)
+where
+	BridgedChain: ChainWithGrandpa,
+	ThisChainWithMessages: ChainWithMessages,
+	LaneId: Copy + Encode,
+{
+	make_complex_relayer_delivery_proofs_with_proof_format::<
+		BridgedChain,
+		ThisChainWithMessages,
+		LaneId,
+	>(
+		lane_id,
+		xcm_message,
+		message_nonce,
+		message_destination,
+		outbound_lane_state,
+		header_number,
+		is_minimal_call,
+		StorageProofFormat::Trie,
+	)
+}
+
+/// Same as [`make_complex_relayer_delivery_proofs`], but also returns the sizes of the message
+/// payload and of the storage proof.
+pub fn make_complex_relayer_delivery_proofs_with_sizes<
+	BridgedChain,
+	ThisChainWithMessages,
+	LaneId,
+>(
+	lane_id: LaneId,
+	xcm_message: Xcm<()>,
+	message_nonce: MessageNonce,
+	message_destination: Junctions,
+	outbound_lane_state: Option<OutboundLaneData>,
 	header_number: BlockNumberOf<BridgedChain>,
 	is_minimal_call: bool,
+) -> (
+	HeaderOf<BridgedChain>,
+	GrandpaJustification<HeaderOf<BridgedChain>>,
+	FromBridgedChainMessagesProof<HashOf<BridgedChain>, LaneId>,
+	MessageProofSizes,
+)
+where
+	BridgedChain: ChainWithGrandpa,
+	ThisChainWithMessages: ChainWithMessages,
+	LaneId: Copy + Encode,
+{
+	let message_payload = prepare_inbound_xcm(xcm_message.clone(), message_destination.clone());
+	let (header, justification, message_proof) =
+		make_complex_relayer_delivery_proofs::<BridgedChain, ThisChainWithMessages, LaneId>(
+			lane_id,
+			xcm_message,
+			message_nonce,
+			message_destination,
+			outbound_lane_state,
+			header_number,
+			is_minimal_call,
+		);
+	let sizes = MessageProofSizes::new(&message_payload, &message_proof.storage_proof);
+
+	(header, justification, message_proof, sizes)
+}
+
+/// Prepare storage proofs of messages, stored at the (bridged) source GRANDPA chain, in the given
+/// `proof_format`.
+///
+/// If `outbound_lane_state` is given, it is included in the proof as well.
+pub fn make_complex_relayer_delivery_proofs_with_proof_format<
+	BridgedChain,
+	ThisChainWithMessages,
+	LaneId,
+>(
+	lane_id: LaneId,
+	xcm_message: Xcm<()>,
+	message_nonce: MessageNonce,
+	message_destination: Junctions,
+	outbound_lane_state: Option<OutboundLaneData>,
+	header_number: BlockNumberOf<BridgedChain>,
+	is_minimal_call: bool,
+	proof_format: StorageProofFormat,
 ) -> (
 	HeaderOf<BridgedChain>,
 	GrandpaJustification<HeaderOf<BridgedChain>>,
@@ -183,8 +361,9 @@ where
 		prepare_messages_storage_proof::<BridgedChain, ThisChainWithMessages, LaneId>(
 			lane_id,
 			message_nonce..=message_nonce,
-			None,
-			UnverifiedStorageProofParams::from_db_size(message_payload.len() as u32),
+			outbound_lane_state,
+			UnverifiedStorageProofParams::from_db_size(message_payload.len() as u32)
+				.with_format(proof_format),
 			|_| message_payload.clone(),
 			encode_all_messages,
 			encode_lane_data,
@@ -209,6 +388,49 @@ where
 	(header, justification, message_proof)
 }
 
+/// Prepare a storage proof of messages sent over several lanes, stored at the (bridged) source
+/// GRANDPA chain.
+///
+/// Returns the storage proof of messages of all lanes and, for every lane, storage keys of its
+/// messages in the proof.
+pub fn make_multi_lane_delivery_proof<BridgedChain, ThisChainWithMessages, LaneId>(
+	lanes: Vec<(LaneId, RangeInclusive<MessageNonce>, Xcm<()>)>,
+	message_destination: Junctions,
+	header_number: BlockNumberOf<BridgedChain>,
+	is_minimal_call: bool,
+) -> (
+	HeaderOf<BridgedChain>,
+	GrandpaJustification<HeaderOf<BridgedChain>>,
+	RawStorageProof,
+	Vec<(LaneId, Vec<Vec<u8>>)>,
+)
+where
+	BridgedChain: ChainWithGrandpa,
+	ThisChainWithMessages: ChainWithMessages,
+	LaneId: Copy + Encode,
+{
+	// prepare messages and storage proof containing them
+	let lanes = lanes
+		.into_iter()
+		.map(|(lane_id, message_nonces, xcm_message)| {
+			(lane_id, message_nonces, prepare_inbound_xcm(xcm_message, message_destination.clone()))
+		})
+		.collect::<Vec<_>>();
+	let lane_ids = lanes.iter().map(|(lane_id, _, _)| *lane_id).collect::<Vec<_>>();
+	let (state_root, storage_proof, lanes_storage_keys) =
+		prepare_multi_lane_messages_storage_proof::<BridgedChain, ThisChainWithMessages, LaneId>(
+			lanes,
+		);
+
+	let (header, justification) = make_complex_bridged_grandpa_header_proof::<BridgedChain>(
+		state_root,
+		header_number,
+		is_minimal_call,
+	);
+
+	(header, justification, storage_proof, lane_ids.into_iter().zip(lanes_storage_keys).collect())
+}
+
 /// Prepare storage proofs of message confirmations, stored at the (bridged) target GRANDPA chain.
 pub fn make_complex_relayer_confirmation_proofs<
 	BridgedChain,
@@ -225,6 +447,43 @@ pub fn make_complex_relayer_confirmation_proofs<
 	GrandpaJustification<HeaderOf<BridgedChain>>,
 	FromBridgedChainMessagesDeliveryProof<HashOf<BridgedChain>, LaneId>,
 )
+where
+	BridgedChain: ChainWithGrandpa,
+	ThisChainWithMessages: ChainWithMessages,
+	LaneId: Copy + Encode,
+{
+	make_complex_relayer_confirmation_proofs_with_proof_format::<
+		BridgedChain,
+		ThisChainWithMessages,
+		InnerXcmRuntimeCall,
+		LaneId,
+	>(
+		lane_id,
+		header_number,
+		relayer_id_at_this_chain,
+		relayers_state,
+		StorageProofFormat::Trie,
+	)
+}
+
+/// Prepare storage proofs of message confirmations, stored at the (bridged) target GRANDPA chain,
+/// in the given `proof_format`.
+pub fn make_complex_relayer_confirmation_proofs_with_proof_format<
+	BridgedChain,
+	ThisChainWithMessages,
+	InnerXcmRuntimeCall,
+	LaneId,
+>(
+	lane_id: LaneId,
+	header_number: BlockNumberOf<BridgedChain>,
+	relayer_id_at_this_chain: AccountIdOf<ThisChainWithMessages>,
+	relayers_state: UnrewardedRelayersState,
+	proof_format: StorageProofFormat,
+) -> (
+	HeaderOf<BridgedChain>,
+	GrandpaJustification<HeaderOf<BridgedChain>>,
+	FromBridgedChainMessagesDeliveryProof<HashOf<BridgedChain>, LaneId>,
+)
 where
 	BridgedChain: ChainWithGrandpa,
 	ThisChainWithMessages: ChainWithMessages,
@@ -246,7 +505,7 @@ where
 				.into(),
 				last_confirmed_nonce: 1,
 			},
-			UnverifiedStorageProofParams::default(),
+			UnverifiedStorageProofParams::default().with_format(proof_format),
 		);
 
 	let (header, justification) =
@@ -267,6 +526,45 @@ pub fn make_complex_bridged_grandpa_header_proof<BridgedChain>(
 	header_number: BlockNumberOf<BridgedChain>,
 	is_minimal_call: bool,
 ) -> (HeaderOf<BridgedChain>, GrandpaJustification<HeaderOf<BridgedChain>>)
+where
+	BridgedChain: ChainWithGrandpa,
+{
+	make_bridged_grandpa_header_proof::<BridgedChain>(
+		state_root,
+		header_number,
+		is_minimal_call,
+		None,
+	)
+}
+
+/// Make bridged chain header with given state root, which announces given GRANDPA authority set
+/// change, and the justification for it.
+///
+/// The justification is signed by the current (old) authority set, so the header may be imported
+/// by the bridge GRANDPA pallet, which then enacts the new set.
+pub fn make_complex_bridged_grandpa_header_proof_with_authority_set_change<BridgedChain>(
+	state_root: HashOf<BridgedChain>,
+	header_number: BlockNumberOf<BridgedChain>,
+	is_minimal_call: bool,
+	authority_set_change: AuthoritySetChange<BlockNumberOf<BridgedChain>>,
+) -> (HeaderOf<BridgedChain>, GrandpaJustification<HeaderOf<BridgedChain>>)
+where
+	BridgedChain: ChainWithGrandpa,
+{
+	make_bridged_grandpa_header_proof::<BridgedChain>(
+		state_root,
+		header_number,
+		is_minimal_call,
+		Some(authority_set_change.into_digest_item()),
+	)
+}
+
+fn make_bridged_grandpa_header_proof<BridgedChain>(
+	state_root: HashOf<BridgedChain>,
+	header_number: BlockNumberOf<BridgedChain>,
+	is_minimal_call: bool,
+	consensus_digest: Option<DigestItem>,
+) -> (HeaderOf<BridgedChain>, GrandpaJustification<HeaderOf<BridgedChain>>)
 where
 	BridgedChain: ChainWithGrandpa,
 {
@@ -274,6 +572,9 @@ where
 		header_number.into(),
 		state_root.into(),
 	);
+	if let Some(consensus_digest) = consensus_digest {
+		header.digest_mut().push(consensus_digest);
+	}
 
 	// to compute proper cost of GRANDPA call, let's add some dummy bytes to header, so that the
 	// `submit_finality_proof` call size would be close to maximal expected (and refundable)
@@ -294,3 +595,187 @@ pub fn maximal_expected_submit_finality_proof_call_size<BridgedChain: ChainWithG
 		BridgedChain::MAX_AUTHORITIES_COUNT * 2 / 3 + 1,
 	) as usize
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_data::dummy_xcm;
+	use bp_messages::{storage_keys, LegacyLaneId};
+	use bp_runtime::StorageProofChecker;
+	use frame_support::weights::{IdentityFee, WeightToFee};
+	use pallet_bridge_messages::{WeightInfo as _, WeightInfoExt as _};
+
+	struct TestChain;
+
+	impl Chain for TestChain {
+		const ID: bp_runtime::ChainId = *b"tstc";
+
+		type BlockNumber = bp_polkadot_core::BlockNumber;
+		type Hash = bp_polkadot_core::Hash;
+		type Hasher = bp_polkadot_core::Hasher;
+		type Header = bp_polkadot_core::Header;
+		type AccountId = bp_polkadot_core::AccountId;
+		type Balance = bp_polkadot_core::Balance;
+		type Nonce = bp_polkadot_core::Nonce;
+		type Signature = bp_polkadot_core::Signature;
+
+		const STATE_VERSION: sp_runtime::StateVersion = sp_runtime::StateVersion::V1;
+
+		fn max_extrinsic_size() -> u32 {
+			0
+		}
+		fn max_extrinsic_weight() -> Weight {
+			Weight::zero()
+		}
+	}
+
+	impl ChainWithGrandpa for TestChain {
+		const WITH_CHAIN_GRANDPA_PALLET_NAME: &'static str = "WithTestChainBridgeGrandpa";
+		const MAX_AUTHORITIES_COUNT: u32 = 16;
+		const REASONABLE_HEADERS_IN_JUSTIFICATION_ANCESTRY: u32 = 4;
+		const MAX_MANDATORY_HEADER_SIZE: u32 = 4096;
+		const AVERAGE_HEADER_SIZE: u32 = 4096;
+	}
+
+	impl ChainWithMessages for TestChain {
+		const WITH_CHAIN_MESSAGES_PALLET_NAME: &'static str = "WithTestChainBridgeMessages";
+		const MAX_UNREWARDED_RELAYERS_IN_CONFIRMATION_TX: MessageNonce = 16;
+		const MAX_UNCONFIRMED_MESSAGES_IN_CONFIRMATION_TX: MessageNonce = 128;
+	}
+
+	#[test]
+	fn relayer_is_rewarded_for_single_message_delivery_batch() {
+		// reward that covers a millisecond of execution time
+		let delivery_reward = 1_000_000_000;
+		let economics = expected_relayer_delivery_batch_economics::<(), (), (), IdentityFee<u128>>(
+			1,
+			0,
+			1,
+			Weight::zero(),
+			delivery_reward,
+		);
+
+		let expected_batch_weight =
+			<() as pallet_bridge_grandpa::WeightInfoExt>::submit_finality_proof_weight(1, 0)
+				.saturating_add(<()>::receive_single_message_proof())
+				.saturating_add(<()>::receive_messages_proof_overhead_from_runtime());
+		assert_eq!(economics.fee, IdentityFee::<u128>::weight_to_fee(&expected_batch_weight));
+		assert!(economics.reward > delivery_reward);
+		assert!(economics.is_net_positive());
+	}
+
+	#[test]
+	fn relayer_delivery_proofs_are_built_on_bridged_chain_state_root() {
+		let lane_id = LegacyLaneId([0, 0, 0, 1]);
+		let outbound_lane_data = OutboundLaneData {
+			state: LaneState::Opened,
+			latest_generated_nonce: 7,
+			..Default::default()
+		};
+		let (header, justification, message_proof) =
+			make_complex_relayer_delivery_proofs::<TestChain, TestChain, _>(
+				lane_id,
+				dummy_xcm(),
+				7,
+				[GlobalConsensus(Polkadot)].into(),
+				Some(outbound_lane_data.clone()),
+				10,
+				false,
+			);
+
+		// the justification finalizes the header, which is referenced by the message proof
+		assert_eq!(*header.number(), 10);
+		assert_eq!(justification.commit.target_hash, header.hash());
+		assert_eq!(justification.commit.target_number, 10);
+		assert_eq!(message_proof.bridged_header_hash, header.hash());
+		assert_eq!(message_proof.lane, lane_id);
+		assert_eq!((message_proof.nonces_start, message_proof.nonces_end), (7, 7));
+
+		// there's no parachain layer: the message and the lane state are read directly from the
+		// bridged chain state
+		let mut checker = StorageProofChecker::<bp_polkadot_core::Hasher>::new(
+			*header.state_root(),
+			message_proof.storage_proof,
+		)
+		.unwrap();
+		let pallet_name = TestChain::WITH_CHAIN_MESSAGES_PALLET_NAME;
+		assert!(checker
+			.read_value(storage_keys::message_key(pallet_name, lane_id, 7).0.as_ref())
+			.unwrap()
+			.is_some());
+		assert_eq!(
+			checker
+				.read_and_decode_opt_value::<OutboundLaneData>(
+					storage_keys::outbound_lane_data_key(pallet_name, &lane_id).0.as_ref(),
+				)
+				.unwrap(),
+			Some(outbound_lane_data),
+		);
+		assert!(checker.ensure_no_unused_nodes().is_ok());
+	}
+
+	#[test]
+	fn multi_lane_delivery_proof_covers_all_lanes() {
+		let lanes = [LegacyLaneId([0, 0, 0, 1]), LegacyLaneId([0, 0, 0, 2])];
+		let (header, _, storage_proof, lanes_storage_keys) =
+			make_multi_lane_delivery_proof::<TestChain, TestChain, _>(
+				vec![(lanes[0], 1..=2, dummy_xcm()), (lanes[1], 5..=5, dummy_xcm())],
+				[GlobalConsensus(Polkadot)].into(),
+				10,
+				false,
+			);
+
+		let pallet_name = TestChain::WITH_CHAIN_MESSAGES_PALLET_NAME;
+		assert_eq!(
+			lanes_storage_keys,
+			vec![
+				(
+					lanes[0],
+					vec![
+						storage_keys::message_key(pallet_name, &lanes[0], 1).0,
+						storage_keys::message_key(pallet_name, &lanes[0], 2).0,
+					],
+				),
+				(lanes[1], vec![storage_keys::message_key(pallet_name, &lanes[1], 5).0]),
+			],
+		);
+
+		// the single proof is checked against the state root of the header, and has all messages
+		// of both lanes, but nothing else
+		let mut checker = StorageProofChecker::<bp_polkadot_core::Hasher>::new(
+			*header.state_root(),
+			storage_proof,
+		)
+		.unwrap();
+		for (_, lane_storage_keys) in lanes_storage_keys {
+			for storage_key in lane_storage_keys {
+				assert!(checker.read_value(&storage_key).unwrap().is_some());
+			}
+		}
+		assert!(checker.ensure_no_unused_nodes().is_ok());
+	}
+
+	#[test]
+	fn relayer_delivery_proof_sizes_are_reported() {
+		let message_destination: InteriorLocation = [GlobalConsensus(Polkadot)].into();
+		let (_, _, message_proof, sizes) =
+			make_complex_relayer_delivery_proofs_with_sizes::<TestChain, TestChain, _>(
+				LegacyLaneId([0, 0, 0, 1]),
+				dummy_xcm(),
+				1,
+				message_destination.clone(),
+				None,
+				10,
+				false,
+			);
+
+		let encoded_xcm = prepare_inbound_xcm(dummy_xcm(), message_destination);
+		assert_eq!(sizes.message_payload, encoded_xcm.len() as u32);
+		assert_eq!(
+			sizes.storage_proof,
+			bp_runtime::raw_storage_proof_size(&message_proof.storage_proof) as u32,
+		);
+		// the proof is grown to carry at least the message payload
+		assert!(sizes.storage_proof >= sizes.message_payload);
+	}
+}
@@ -18,7 +18,7 @@
 
 use super::{
 	from_grandpa_chain::make_complex_bridged_grandpa_header_proof, prepare_inbound_xcm,
-	XcmAsPlainPayload,
+	MessageProofSizes, XcmAsPlainPayload,
 };
 
 use bp_messages::{
@@ -28,17 +28,20 @@ use bp_messages::{
 };
 use bp_parachains::{RelayBlockHash, RelayBlockNumber};
 use bp_runtime::{
-	AccountIdOf, BlockNumberOf, Chain, HeaderOf, Parachain, UnverifiedStorageProofParams,
+	AccountIdOf, BlockNumberOf, Chain, HeaderOf, Parachain, StorageProofFormat,
+	UnverifiedStorageProofParams,
 };
 use bp_test_utils::prepare_parachain_heads_proof;
-use codec::Encode;
+use codec::{Decode, Encode};
 use pallet_bridge_grandpa::BridgedHeader;
 use sp_runtime::traits::Header as HeaderT;
 use xcm::latest::prelude::*;
 
 use crate::test_cases::helpers::InboundRelayerId;
 use bp_header_chain::{justification::GrandpaJustification, ChainWithGrandpa};
-use bp_messages::{DeliveredMessages, InboundLaneData, MessageNonce, UnrewardedRelayer};
+use bp_messages::{
+	DeliveredMessages, InboundLaneData, MessageNonce, OutboundLaneData, UnrewardedRelayer,
+};
 use bp_polkadot_core::parachains::{ParaHash, ParaHead, ParaHeadsProof, ParaId};
 use pallet_bridge_messages::{
 	messages_generation::{
@@ -48,8 +51,12 @@ use pallet_bridge_messages::{
 	BridgedChainOf, LaneIdOf,
 };
 use sp_runtime::SaturatedConversion;
+use sp_std::ops::RangeInclusive;
 
 /// Prepare a batch call with relay finality proof, parachain head proof and message proof.
+///
+/// Bridges with a remote GRANDPA chain have no parachain head to submit, so they should use
+/// [`super::from_grandpa_chain::make_complex_relayer_delivery_batch`] instead.
 pub fn make_complex_relayer_delivery_batch<Runtime, GPI, PPI, MPI>(
 	relay_chain_header: BridgedHeader<Runtime, GPI>,
 	grandpa_justification: GrandpaJustification<BridgedHeader<Runtime, GPI>>,
@@ -58,6 +65,134 @@ pub fn make_complex_relayer_delivery_batch<Runtime, GPI, PPI, MPI>(
 	message_proof: FromBridgedChainMessagesProof<ParaHash, LaneIdOf<Runtime, MPI>>,
 	relayer_id_at_bridged_chain: InboundRelayerId<Runtime, MPI>,
 ) -> pallet_utility::Call<Runtime>
+where
+	Runtime: pallet_bridge_grandpa::Config<GPI>
+		+ pallet_bridge_parachains::Config<PPI>
+		+ pallet_bridge_messages::Config<MPI, InboundPayload = XcmAsPlainPayload>
+		+ pallet_utility::Config,
+	GPI: 'static,
+	PPI: 'static,
+	MPI: 'static,
+	ParaHash: From<
+		<<Runtime as pallet_bridge_grandpa::Config<GPI>>::BridgedChain as bp_runtime::Chain>::Hash,
+	>,
+	<<Runtime as pallet_bridge_grandpa::Config<GPI>>::BridgedChain as bp_runtime::Chain>::Hash:
+		From<ParaHash>,
+	BridgedChainOf<Runtime, MPI>: Chain<Hash = ParaHash> + Parachain,
+	<Runtime as pallet_utility::Config>::RuntimeCall: From<pallet_bridge_grandpa::Call<Runtime, GPI>>
+		+ From<pallet_bridge_parachains::Call<Runtime, PPI>>
+		+ From<pallet_bridge_messages::Call<Runtime, MPI>>,
+{
+	make_complex_relayer_delivery_batch_with_prelude::<Runtime, GPI, PPI, MPI>(
+		Vec::new(),
+		relay_chain_header,
+		grandpa_justification,
+		parachain_heads,
+		para_heads_proof,
+		message_proof,
+		relayer_id_at_bridged_chain,
+	)
+}
+
+/// Same as [`make_complex_relayer_delivery_batch`], but the `prelude` calls (e.g. a fee top-up)
+/// are prepended to the delivery calls.
+pub fn make_complex_relayer_delivery_batch_with_prelude<Runtime, GPI, PPI, MPI>(
+	prelude: Vec<<Runtime as pallet_utility::Config>::RuntimeCall>,
+	relay_chain_header: BridgedHeader<Runtime, GPI>,
+	grandpa_justification: GrandpaJustification<BridgedHeader<Runtime, GPI>>,
+	parachain_heads: Vec<(ParaId, ParaHash)>,
+	para_heads_proof: ParaHeadsProof,
+	message_proof: FromBridgedChainMessagesProof<ParaHash, LaneIdOf<Runtime, MPI>>,
+	relayer_id_at_bridged_chain: InboundRelayerId<Runtime, MPI>,
+) -> pallet_utility::Call<Runtime>
+where
+	Runtime: pallet_bridge_grandpa::Config<GPI>
+		+ pallet_bridge_parachains::Config<PPI>
+		+ pallet_bridge_messages::Config<MPI, InboundPayload = XcmAsPlainPayload>
+		+ pallet_utility::Config,
+	GPI: 'static,
+	PPI: 'static,
+	MPI: 'static,
+	ParaHash: From<
+		<<Runtime as pallet_bridge_grandpa::Config<GPI>>::BridgedChain as bp_runtime::Chain>::Hash,
+	>,
+	<<Runtime as pallet_bridge_grandpa::Config<GPI>>::BridgedChain as bp_runtime::Chain>::Hash:
+		From<ParaHash>,
+	BridgedChainOf<Runtime, MPI>: Chain<Hash = ParaHash> + Parachain,
+	<Runtime as pallet_utility::Config>::RuntimeCall: From<pallet_bridge_grandpa::Call<Runtime, GPI>>
+		+ From<pallet_bridge_parachains::Call<Runtime, PPI>>
+		+ From<pallet_bridge_messages::Call<Runtime, MPI>>,
+{
+	let mut calls = prelude;
+	calls.extend(make_complex_relayer_delivery_calls::<Runtime, GPI, PPI, MPI>(
+		relay_chain_header,
+		grandpa_justification,
+		parachain_heads,
+		para_heads_proof,
+		vec![(message_proof, 1, Weight::from_parts(1000000000, 0))],
+		relayer_id_at_bridged_chain,
+	));
+	pallet_utility::Call::<Runtime>::batch_all { calls }
+}
+
+/// Same as [`make_complex_relayer_delivery_batch`], but with one `receive_messages_proof` call
+/// per `(proof, messages_count, dispatch_weight)` entry of `message_proofs` (e.g. one per lane).
+pub fn make_complex_relayer_delivery_batch_with_message_proofs<Runtime, GPI, PPI, MPI>(
+	relay_chain_header: BridgedHeader<Runtime, GPI>,
+	grandpa_justification: GrandpaJustification<BridgedHeader<Runtime, GPI>>,
+	parachain_heads: Vec<(ParaId, ParaHash)>,
+	para_heads_proof: ParaHeadsProof,
+	message_proofs: Vec<(
+		FromBridgedChainMessagesProof<ParaHash, LaneIdOf<Runtime, MPI>>,
+		u32,
+		Weight,
+	)>,
+	relayer_id_at_bridged_chain: InboundRelayerId<Runtime, MPI>,
+) -> pallet_utility::Call<Runtime>
+where
+	Runtime: pallet_bridge_grandpa::Config<GPI>
+		+ pallet_bridge_parachains::Config<PPI>
+		+ pallet_bridge_messages::Config<MPI, InboundPayload = XcmAsPlainPayload>
+		+ pallet_utility::Config,
+	GPI: 'static,
+	PPI: 'static,
+	MPI: 'static,
+	ParaHash: From<
+		<<Runtime as pallet_bridge_grandpa::Config<GPI>>::BridgedChain as bp_runtime::Chain>::Hash,
+	>,
+	<<Runtime as pallet_bridge_grandpa::Config<GPI>>::BridgedChain as bp_runtime::Chain>::Hash:
+		From<ParaHash>,
+	BridgedChainOf<Runtime, MPI>: Chain<Hash = ParaHash> + Parachain,
+	<Runtime as pallet_utility::Config>::RuntimeCall: From<pallet_bridge_grandpa::Call<Runtime, GPI>>
+		+ From<pallet_bridge_parachains::Call<Runtime, PPI>>
+		+ From<pallet_bridge_messages::Call<Runtime, MPI>>,
+{
+	pallet_utility::Call::<Runtime>::batch_all {
+		calls: make_complex_relayer_delivery_calls::<Runtime, GPI, PPI, MPI>(
+			relay_chain_header,
+			grandpa_justification,
+			parachain_heads,
+			para_heads_proof,
+			message_proofs,
+			relayer_id_at_bridged_chain,
+		),
+	}
+}
+
+/// Relay finality proof and parachain head proof calls, followed by a message proof call for
+/// every entry of `message_proofs`.
+fn make_complex_relayer_delivery_calls<Runtime, GPI, PPI, MPI>(
+	relay_chain_header: BridgedHeader<Runtime, GPI>,
+	grandpa_justification: GrandpaJustification<BridgedHeader<Runtime, GPI>>,
+	parachain_heads: Vec<(ParaId, ParaHash)>,
+	para_heads_proof: ParaHeadsProof,
+	message_proofs: Vec<(
+		FromBridgedChainMessagesProof<ParaHash, LaneIdOf<Runtime, MPI>>,
+		u32,
+		Weight,
+	)>,
+	relayer_id_at_bridged_chain: InboundRelayerId<Runtime, MPI>,
+) -> Vec<<Runtime as pallet_utility::Config>::RuntimeCall>
 where
 	Runtime: pallet_bridge_grandpa::Config<GPI>
 		+ pallet_bridge_parachains::Config<PPI>
@@ -90,15 +225,17 @@ where
 		parachains: parachain_heads,
 		parachain_heads_proof: para_heads_proof,
 	};
-	let submit_message = pallet_bridge_messages::Call::<Runtime, MPI>::receive_messages_proof {
-		relayer_id_at_bridged_chain: relayer_id_at_bridged_chain.into(),
-		proof: Box::new(message_proof),
-		messages_count: 1,
-		dispatch_weight: Weight::from_parts(1000000000, 0),
-	};
-	pallet_utility::Call::<Runtime>::batch_all {
-		calls: vec![submit_grandpa.into(), submit_para_head.into(), submit_message.into()],
-	}
+	let mut calls = vec![submit_grandpa.into(), submit_para_head.into()];
+	calls.extend(message_proofs.into_iter().map(|(message_proof, messages_count, dispatch_weight)| {
+		pallet_bridge_messages::Call::<Runtime, MPI>::receive_messages_proof {
+			relayer_id_at_bridged_chain: relayer_id_at_bridged_chain.clone(),
+			proof: Box::new(message_proof),
+			messages_count,
+			dispatch_weight,
+		}
+		.into()
+	}));
+	calls
 }
 
 /// Prepare a batch call with relay finality proof, parachain head proof and message delivery
@@ -166,7 +303,7 @@ where
 	BridgedChainOf<Runtime, MPI>: Chain<Hash = ParaHash> + Parachain,
 {
 	pallet_bridge_messages::Call::<Runtime, MPI>::receive_messages_proof {
-		relayer_id_at_bridged_chain: relayer_id_at_bridged_chain.into(),
+		relayer_id_at_bridged_chain,
 		proof: Box::new(message_proof),
 		messages_count: 1,
 		dispatch_weight: Weight::from_parts(1000000000, 0),
@@ -193,6 +330,8 @@ where
 }
 
 /// Prepare storage proofs of messages, stored at the source chain.
+///
+/// If `outbound_lane_state` is given, it is included in the proof as well.
 pub fn make_complex_relayer_delivery_proofs<
 	BridgedRelayChain,
 	BridgedParachain,
@@ -203,10 +342,64 @@ pub fn make_complex_relayer_delivery_proofs<
 	xcm_message: Xcm<()>,
 	message_nonce: MessageNonce,
 	message_destination: Junctions,
+	outbound_lane_state: Option<OutboundLaneData>,
+	para_header_number: u32,
+	relay_header_number: u32,
+	bridged_para_id: u32,
+	is_minimal_call: bool,
+) -> (
+	HeaderOf<BridgedRelayChain>,
+	GrandpaJustification<HeaderOf<BridgedRelayChain>>,
+	ParaHead,
+	Vec<(ParaId, ParaHash)>,
+	ParaHeadsProof,
+	FromBridgedChainMessagesProof<ParaHash, LaneId>,
+)
+where
+	BridgedRelayChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+	BridgedParachain: bp_runtime::Chain<Hash = ParaHash> + Parachain,
+	ThisChainWithMessages: ChainWithMessages,
+	LaneId: Copy + Encode,
+{
+	make_complex_relayer_delivery_proofs_with_proof_format::<
+		BridgedRelayChain,
+		BridgedParachain,
+		ThisChainWithMessages,
+		LaneId,
+	>(
+		lane_id,
+		xcm_message,
+		message_nonce,
+		message_destination,
+		outbound_lane_state,
+		para_header_number,
+		relay_header_number,
+		bridged_para_id,
+		is_minimal_call,
+		StorageProofFormat::Trie,
+	)
+}
+
+/// Prepare storage proofs of messages, stored at the source chain, in the given `proof_format`.
+///
+/// If `outbound_lane_state` is given, it is included in the proof as well.
+pub fn make_complex_relayer_delivery_proofs_with_proof_format<
+	BridgedRelayChain,
+	BridgedParachain,
+	ThisChainWithMessages,
+	LaneId,
+>(
+	lane_id: LaneId,
+	xcm_message: Xcm<()>,
+	message_nonce: MessageNonce,
+	message_destination: Junctions,
+	outbound_lane_state: Option<OutboundLaneData>,
 	para_header_number: u32,
 	relay_header_number: u32,
 	bridged_para_id: u32,
 	is_minimal_call: bool,
+	proof_format: StorageProofFormat,
 ) -> (
 	HeaderOf<BridgedRelayChain>,
 	GrandpaJustification<HeaderOf<BridgedRelayChain>>,
@@ -229,8 +422,9 @@ where
 		prepare_messages_storage_proof::<BridgedParachain, ThisChainWithMessages, LaneId>(
 			lane_id,
 			message_nonce..=message_nonce,
-			None,
-			UnverifiedStorageProofParams::from_db_size(message_payload.len() as u32),
+			outbound_lane_state,
+			UnverifiedStorageProofParams::from_db_size(message_payload.len() as u32)
+				.with_format(proof_format),
 			|_| message_payload.clone(),
 			encode_all_messages,
 			encode_lane_data,
@@ -265,26 +459,27 @@ where
 	)
 }
 
-/// Prepare storage proofs of message confirmations, stored at the target parachain.
-pub fn make_complex_relayer_confirmation_proofs<
+/// Prepare storage proofs of a message, which is delivered to this chain, but can't be dispatched
+/// there, stored at the (bridged) source parachain. See [`super::prepare_failing_inbound_xcm`].
+pub fn make_failing_delivery_proofs<
 	BridgedRelayChain,
 	BridgedParachain,
 	ThisChainWithMessages,
 	LaneId,
 >(
 	lane_id: LaneId,
+	message_nonce: MessageNonce,
+	message_destination: Junctions,
 	para_header_number: u32,
 	relay_header_number: u32,
 	bridged_para_id: u32,
-	relayer_id_at_this_chain: AccountIdOf<ThisChainWithMessages>,
-	relayers_state: UnrewardedRelayersState,
 ) -> (
 	HeaderOf<BridgedRelayChain>,
 	GrandpaJustification<HeaderOf<BridgedRelayChain>>,
 	ParaHead,
 	Vec<(ParaId, ParaHash)>,
 	ParaHeadsProof,
-	FromBridgedChainMessagesDeliveryProof<ParaHash, LaneId>,
+	FromBridgedChainMessagesProof<ParaHash, LaneId>,
 )
 where
 	BridgedRelayChain:
@@ -293,89 +488,1023 @@ where
 	ThisChainWithMessages: ChainWithMessages,
 	LaneId: Copy + Encode,
 {
-	// prepare para storage proof containing message delivery proof
-	let (para_state_root, para_storage_proof) =
-		prepare_message_delivery_storage_proof::<BridgedParachain, ThisChainWithMessages, LaneId>(
-			lane_id,
-			InboundLaneData {
-				state: LaneState::Opened,
-				relayers: vec![
-					UnrewardedRelayer {
-						relayer: relayer_id_at_this_chain.into(),
-						messages: DeliveredMessages::new(1)
-					};
-					relayers_state.unrewarded_relayer_entries as usize
-				]
-				.into(),
-				last_confirmed_nonce: 1,
-			},
-			UnverifiedStorageProofParams::default(),
-		);
-
-	let (relay_chain_header, justification, bridged_para_head, parachain_heads, para_heads_proof) =
-		make_complex_bridged_parachain_heads_proof::<BridgedRelayChain, BridgedParachain>(
-			para_state_root,
-			para_header_number,
-			relay_header_number,
-			bridged_para_id,
-			false,
-		);
-
-	let message_delivery_proof = FromBridgedChainMessagesDeliveryProof {
-		bridged_header_hash: bridged_para_head.hash(),
-		storage_proof: para_storage_proof,
-		lane: lane_id,
-	};
+	make_complex_relayer_delivery_proofs::<
+		BridgedRelayChain,
+		BridgedParachain,
+		ThisChainWithMessages,
+		LaneId,
+	>(
+		lane_id,
+		super::prepare_failing_inbound_xcm(),
+		message_nonce,
+		message_destination,
+		None,
+		para_header_number,
+		relay_header_number,
+		bridged_para_id,
+		false,
+	)
+}
 
-	(
-		relay_chain_header,
-		justification,
-		bridged_para_head,
-		parachain_heads,
-		para_heads_proof,
-		message_delivery_proof,
+/// Prepare storage proofs of a message with `already_delivered_nonce`, stored at the (bridged)
+/// source parachain.
+///
+/// The proof is structurally valid, but the message has already been delivered to this chain, so
+/// the messages pallet must not dispatch it again.
+pub fn make_replayed_delivery_proof<
+	BridgedRelayChain,
+	BridgedParachain,
+	ThisChainWithMessages,
+	LaneId,
+>(
+	lane_id: LaneId,
+	already_delivered_nonce: MessageNonce,
+	xcm_message: Xcm<()>,
+	message_destination: Junctions,
+	para_header_number: u32,
+	relay_header_number: u32,
+	bridged_para_id: u32,
+) -> (
+	HeaderOf<BridgedRelayChain>,
+	GrandpaJustification<HeaderOf<BridgedRelayChain>>,
+	ParaHead,
+	Vec<(ParaId, ParaHash)>,
+	ParaHeadsProof,
+	FromBridgedChainMessagesProof<ParaHash, LaneId>,
+)
+where
+	BridgedRelayChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+	BridgedParachain: bp_runtime::Chain<Hash = ParaHash> + Parachain,
+	ThisChainWithMessages: ChainWithMessages,
+	LaneId: Copy + Encode,
+{
+	make_complex_relayer_delivery_proofs::<
+		BridgedRelayChain,
+		BridgedParachain,
+		ThisChainWithMessages,
+		LaneId,
+	>(
+		lane_id,
+		xcm_message,
+		already_delivered_nonce,
+		message_destination,
+		None,
+		para_header_number,
+		relay_header_number,
+		bridged_para_id,
+		false,
 	)
 }
 
-/// Make bridged parachain header with given state root and relay header that is finalizing it.
-pub fn make_complex_bridged_parachain_heads_proof<BridgedRelayChain, BridgedParachain>(
-	para_state_root: ParaHash,
+/// Same as [`make_complex_relayer_delivery_proofs`], but panics if the message payload is larger
+/// than `max_message_size`, which would make the proof unacceptable for the messages pallet.
+pub fn make_complex_relayer_delivery_proofs_checked<
+	BridgedRelayChain,
+	BridgedParachain,
+	ThisChainWithMessages,
+	LaneId,
+>(
+	lane_id: LaneId,
+	xcm_message: Xcm<()>,
+	message_nonce: MessageNonce,
+	message_destination: Junctions,
+	outbound_lane_state: Option<OutboundLaneData>,
 	para_header_number: u32,
-	relay_header_number: BlockNumberOf<BridgedRelayChain>,
+	relay_header_number: u32,
 	bridged_para_id: u32,
 	is_minimal_call: bool,
+	max_message_size: u32,
 ) -> (
 	HeaderOf<BridgedRelayChain>,
 	GrandpaJustification<HeaderOf<BridgedRelayChain>>,
 	ParaHead,
 	Vec<(ParaId, ParaHash)>,
 	ParaHeadsProof,
+	FromBridgedChainMessagesProof<ParaHash, LaneId>,
 )
 where
 	BridgedRelayChain:
 		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
 	BridgedParachain: bp_runtime::Chain<Hash = ParaHash> + Parachain,
+	ThisChainWithMessages: ChainWithMessages,
+	LaneId: Copy + Encode,
 {
-	let bridged_para_head = ParaHead(
-		bp_test_utils::test_header_with_root::<HeaderOf<BridgedParachain>>(
-			para_header_number.into(),
-			para_state_root,
-		)
-		.encode(),
+	ensure_message_size(
+		&prepare_inbound_xcm(xcm_message.clone(), message_destination.clone()),
+		max_message_size,
 	);
-	let (relay_state_root, para_heads_proof, parachain_heads) =
-		prepare_parachain_heads_proof::<HeaderOf<BridgedParachain>>(vec![(
-			bridged_para_id,
-			bridged_para_head.clone(),
-		)]);
-	assert_eq!(bridged_para_head.hash(), parachain_heads[0].1);
 
-	let (relay_chain_header, justification) =
-		make_complex_bridged_grandpa_header_proof::<BridgedRelayChain>(
-			relay_state_root,
-			relay_header_number,
-			is_minimal_call,
-		);
+	make_complex_relayer_delivery_proofs::<
+		BridgedRelayChain,
+		BridgedParachain,
+		ThisChainWithMessages,
+		LaneId,
+	>(
+		lane_id,
+		xcm_message,
+		message_nonce,
+		message_destination,
+		outbound_lane_state,
+		para_header_number,
+		relay_header_number,
+		bridged_para_id,
+		is_minimal_call,
+	)
+}
 
-	(relay_chain_header, justification, bridged_para_head, parachain_heads, para_heads_proof)
+/// Same as [`make_complex_relayer_delivery_proofs`], but also returns the sizes of the message
+/// payload and of the storage proof.
+pub fn make_complex_relayer_delivery_proofs_with_sizes<
+	BridgedRelayChain,
+	BridgedParachain,
+	ThisChainWithMessages,
+	LaneId,
+>(
+	lane_id: LaneId,
+	xcm_message: Xcm<()>,
+	message_nonce: MessageNonce,
+	message_destination: Junctions,
+	outbound_lane_state: Option<OutboundLaneData>,
+	para_header_number: u32,
+	relay_header_number: u32,
+	bridged_para_id: u32,
+	is_minimal_call: bool,
+) -> (
+	HeaderOf<BridgedRelayChain>,
+	GrandpaJustification<HeaderOf<BridgedRelayChain>>,
+	ParaHead,
+	Vec<(ParaId, ParaHash)>,
+	ParaHeadsProof,
+	FromBridgedChainMessagesProof<ParaHash, LaneId>,
+	MessageProofSizes,
+)
+where
+	BridgedRelayChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+	BridgedParachain: bp_runtime::Chain<Hash = ParaHash> + Parachain,
+	ThisChainWithMessages: ChainWithMessages,
+	LaneId: Copy + Encode,
+{
+	let message_payload = prepare_inbound_xcm(xcm_message.clone(), message_destination.clone());
+	let (
+		relay_chain_header,
+		justification,
+		bridged_para_head,
+		parachain_heads,
+		para_heads_proof,
+		message_proof,
+	) = make_complex_relayer_delivery_proofs::<
+		BridgedRelayChain,
+		BridgedParachain,
+		ThisChainWithMessages,
+		LaneId,
+	>(
+		lane_id,
+		xcm_message,
+		message_nonce,
+		message_destination,
+		outbound_lane_state,
+		para_header_number,
+		relay_header_number,
+		bridged_para_id,
+		is_minimal_call,
+	);
+	let sizes = MessageProofSizes::new(&message_payload, &message_proof.storage_proof);
+
+	(
+		relay_chain_header,
+		justification,
+		bridged_para_head,
+		parachain_heads,
+		para_heads_proof,
+		message_proof,
+		sizes,
+	)
+}
+
+/// Same as [`make_complex_relayer_delivery_proofs`], but the returned bridged parachain head,
+/// which the message proof is built against, has `claimed_para_header_number`, while the relay
+/// chain state proves the head of the same parachain with `proof_para_header_number`.
+///
+/// If the numbers differ, the bundle is inconsistent: the claimed head is not the one proven by
+/// the relay chain, so the parachains pallet must not import it.
+pub fn make_inconsistent_relayer_delivery_proofs<
+	BridgedRelayChain,
+	BridgedParachain,
+	ThisChainWithMessages,
+	LaneId,
+>(
+	lane_id: LaneId,
+	xcm_message: Xcm<()>,
+	message_nonce: MessageNonce,
+	message_destination: Junctions,
+	outbound_lane_state: Option<OutboundLaneData>,
+	claimed_para_header_number: u32,
+	proof_para_header_number: u32,
+	relay_header_number: u32,
+	bridged_para_id: u32,
+	is_minimal_call: bool,
+) -> (
+	HeaderOf<BridgedRelayChain>,
+	GrandpaJustification<HeaderOf<BridgedRelayChain>>,
+	ParaHead,
+	Vec<(ParaId, ParaHash)>,
+	ParaHeadsProof,
+	FromBridgedChainMessagesProof<ParaHash, LaneId>,
+)
+where
+	BridgedRelayChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+	BridgedParachain: bp_runtime::Chain<Hash = ParaHash> + Parachain,
+	ThisChainWithMessages: ChainWithMessages,
+	LaneId: Copy + Encode,
+{
+	let (relay_chain_header, justification, proven_para_head, _, para_heads_proof, message_proof) =
+		make_complex_relayer_delivery_proofs::<
+			BridgedRelayChain,
+			BridgedParachain,
+			ThisChainWithMessages,
+			LaneId,
+		>(
+			lane_id,
+			xcm_message,
+			message_nonce,
+			message_destination,
+			outbound_lane_state,
+			proof_para_header_number,
+			relay_header_number,
+			bridged_para_id,
+			is_minimal_call,
+		);
+
+	// the claimed head commits to the same messages, it only differs by its number
+	let proven_para_header = HeaderOf::<BridgedParachain>::decode(&mut &proven_para_head.0[..])
+		.expect("the head has been built by make_complex_relayer_delivery_proofs; qed");
+	let claimed_para_head = make_bridged_para_head::<BridgedParachain>(
+		*proven_para_header.state_root(),
+		claimed_para_header_number,
+	);
+	let parachain_heads = vec![(ParaId(bridged_para_id), claimed_para_head.hash())];
+	let message_proof = FromBridgedChainMessagesProof {
+		bridged_header_hash: claimed_para_head.hash(),
+		..message_proof
+	};
+
+	(
+		relay_chain_header,
+		justification,
+		claimed_para_head,
+		parachain_heads,
+		para_heads_proof,
+		message_proof,
+	)
+}
+
+/// Prepare storage proofs of message confirmations, stored at the target parachain.
+pub fn make_complex_relayer_confirmation_proofs<
+	BridgedRelayChain,
+	BridgedParachain,
+	ThisChainWithMessages,
+	LaneId,
+>(
+	lane_id: LaneId,
+	para_header_number: u32,
+	relay_header_number: u32,
+	bridged_para_id: u32,
+	relayer_id_at_this_chain: AccountIdOf<ThisChainWithMessages>,
+	relayers_state: UnrewardedRelayersState,
+) -> (
+	HeaderOf<BridgedRelayChain>,
+	GrandpaJustification<HeaderOf<BridgedRelayChain>>,
+	ParaHead,
+	Vec<(ParaId, ParaHash)>,
+	ParaHeadsProof,
+	FromBridgedChainMessagesDeliveryProof<ParaHash, LaneId>,
+)
+where
+	BridgedRelayChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+	BridgedParachain: bp_runtime::Chain<Hash = ParaHash> + Parachain,
+	ThisChainWithMessages: ChainWithMessages,
+	LaneId: Copy + Encode,
+{
+	make_complex_relayer_confirmation_proofs_with_proof_format::<
+		BridgedRelayChain,
+		BridgedParachain,
+		ThisChainWithMessages,
+		LaneId,
+	>(
+		lane_id,
+		para_header_number,
+		relay_header_number,
+		bridged_para_id,
+		relayer_id_at_this_chain,
+		relayers_state,
+		StorageProofFormat::Trie,
+	)
+}
+
+/// Prepare storage proofs of message confirmations, stored at the target parachain, in the given
+/// `proof_format`.
+pub fn make_complex_relayer_confirmation_proofs_with_proof_format<
+	BridgedRelayChain,
+	BridgedParachain,
+	ThisChainWithMessages,
+	LaneId,
+>(
+	lane_id: LaneId,
+	para_header_number: u32,
+	relay_header_number: u32,
+	bridged_para_id: u32,
+	relayer_id_at_this_chain: AccountIdOf<ThisChainWithMessages>,
+	relayers_state: UnrewardedRelayersState,
+	proof_format: StorageProofFormat,
+) -> (
+	HeaderOf<BridgedRelayChain>,
+	GrandpaJustification<HeaderOf<BridgedRelayChain>>,
+	ParaHead,
+	Vec<(ParaId, ParaHash)>,
+	ParaHeadsProof,
+	FromBridgedChainMessagesDeliveryProof<ParaHash, LaneId>,
+)
+where
+	BridgedRelayChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+	BridgedParachain: bp_runtime::Chain<Hash = ParaHash> + Parachain,
+	ThisChainWithMessages: ChainWithMessages,
+	LaneId: Copy + Encode,
+{
+	make_complex_relayer_confirmation_proofs_with_lanes::<
+		BridgedRelayChain,
+		BridgedParachain,
+		ThisChainWithMessages,
+		LaneId,
+	>(
+		lane_id,
+		lane_id,
+		para_header_number,
+		relay_header_number,
+		bridged_para_id,
+		relayer_id_at_this_chain,
+		relayers_state,
+		LaneState::Opened,
+		proof_format,
+	)
+}
+
+/// Prepare storage proofs of message confirmations, stored at the target parachain, where the
+/// inbound lane is in the given `lane_state`.
+pub fn make_complex_relayer_confirmation_proofs_with_lane_state<
+	BridgedRelayChain,
+	BridgedParachain,
+	ThisChainWithMessages,
+	LaneId,
+>(
+	lane_id: LaneId,
+	para_header_number: u32,
+	relay_header_number: u32,
+	bridged_para_id: u32,
+	relayer_id_at_this_chain: AccountIdOf<ThisChainWithMessages>,
+	relayers_state: UnrewardedRelayersState,
+	lane_state: LaneState,
+) -> (
+	HeaderOf<BridgedRelayChain>,
+	GrandpaJustification<HeaderOf<BridgedRelayChain>>,
+	ParaHead,
+	Vec<(ParaId, ParaHash)>,
+	ParaHeadsProof,
+	FromBridgedChainMessagesDeliveryProof<ParaHash, LaneId>,
+)
+where
+	BridgedRelayChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+	BridgedParachain: bp_runtime::Chain<Hash = ParaHash> + Parachain,
+	ThisChainWithMessages: ChainWithMessages,
+	LaneId: Copy + Encode,
+{
+	make_complex_relayer_confirmation_proofs_with_lanes::<
+		BridgedRelayChain,
+		BridgedParachain,
+		ThisChainWithMessages,
+		LaneId,
+	>(
+		lane_id,
+		lane_id,
+		para_header_number,
+		relay_header_number,
+		bridged_para_id,
+		relayer_id_at_this_chain,
+		relayers_state,
+		lane_state,
+		StorageProofFormat::Trie,
+	)
+}
+
+/// Prepare storage proofs of message confirmations, stored at the target parachain.
+///
+/// Inbound lane data, in the given `lane_state`, is stored under the `inbound_lane` key, but the
+/// returned delivery proof claims to prove the `proof_lane`. Both are the same in valid proofs.
+pub fn make_complex_relayer_confirmation_proofs_with_lanes<
+	BridgedRelayChain,
+	BridgedParachain,
+	ThisChainWithMessages,
+	LaneId,
+>(
+	inbound_lane: LaneId,
+	proof_lane: LaneId,
+	para_header_number: u32,
+	relay_header_number: u32,
+	bridged_para_id: u32,
+	relayer_id_at_this_chain: AccountIdOf<ThisChainWithMessages>,
+	relayers_state: UnrewardedRelayersState,
+	lane_state: LaneState,
+	proof_format: StorageProofFormat,
+) -> (
+	HeaderOf<BridgedRelayChain>,
+	GrandpaJustification<HeaderOf<BridgedRelayChain>>,
+	ParaHead,
+	Vec<(ParaId, ParaHash)>,
+	ParaHeadsProof,
+	FromBridgedChainMessagesDeliveryProof<ParaHash, LaneId>,
+)
+where
+	BridgedRelayChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+	BridgedParachain: bp_runtime::Chain<Hash = ParaHash> + Parachain,
+	ThisChainWithMessages: ChainWithMessages,
+	LaneId: Copy + Encode,
+{
+	// prepare para storage proof containing message delivery proof
+	let (para_state_root, para_storage_proof) =
+		prepare_message_delivery_storage_proof::<BridgedParachain, ThisChainWithMessages, LaneId>(
+			inbound_lane,
+			InboundLaneData {
+				state: lane_state,
+				relayers: vec![
+					UnrewardedRelayer {
+						relayer: relayer_id_at_this_chain.into(),
+						messages: DeliveredMessages::new(1)
+					};
+					relayers_state.unrewarded_relayer_entries as usize
+				]
+				.into(),
+				last_confirmed_nonce: 1,
+			},
+			UnverifiedStorageProofParams::default().with_format(proof_format),
+		);
+
+	let (relay_chain_header, justification, bridged_para_head, parachain_heads, para_heads_proof) =
+		make_complex_bridged_parachain_heads_proof::<BridgedRelayChain, BridgedParachain>(
+			para_state_root,
+			para_header_number,
+			relay_header_number,
+			bridged_para_id,
+			false,
+		);
+
+	let message_delivery_proof = FromBridgedChainMessagesDeliveryProof {
+		bridged_header_hash: bridged_para_head.hash(),
+		storage_proof: para_storage_proof,
+		lane: proof_lane,
+	};
+
+	(
+		relay_chain_header,
+		justification,
+		bridged_para_head,
+		parachain_heads,
+		para_heads_proof,
+		message_delivery_proof,
+	)
+}
+
+/// Prepare storage proofs of message confirmations, stored at the target parachain, where every
+/// relayer has delivered its own range of messages.
+///
+/// Ranges must be increasing and must not overlap, but there may be gaps between them. Returns
+/// the relayers state, matching the proven inbound lane data.
+pub fn make_complex_relayer_confirmation_proofs_with_gaps<
+	BridgedRelayChain,
+	BridgedParachain,
+	ThisChainWithMessages,
+	LaneId,
+>(
+	lane_id: LaneId,
+	para_header_number: u32,
+	relay_header_number: u32,
+	bridged_para_id: u32,
+	delivered_messages: Vec<(AccountIdOf<ThisChainWithMessages>, RangeInclusive<MessageNonce>)>,
+) -> (
+	HeaderOf<BridgedRelayChain>,
+	GrandpaJustification<HeaderOf<BridgedRelayChain>>,
+	ParaHead,
+	Vec<(ParaId, ParaHash)>,
+	ParaHeadsProof,
+	FromBridgedChainMessagesDeliveryProof<ParaHash, LaneId>,
+	UnrewardedRelayersState,
+)
+where
+	BridgedRelayChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+	BridgedParachain: bp_runtime::Chain<Hash = ParaHash> + Parachain,
+	ThisChainWithMessages: ChainWithMessages,
+	LaneId: Copy + Encode,
+{
+	let inbound_lane_data = inbound_lane_data_with_gaps(delivered_messages);
+	let relayers_state = UnrewardedRelayersState::from(&inbound_lane_data);
+
+	// prepare para storage proof containing message delivery proof
+	let (para_state_root, para_storage_proof) =
+		prepare_message_delivery_storage_proof::<BridgedParachain, ThisChainWithMessages, LaneId>(
+			lane_id,
+			inbound_lane_data,
+			UnverifiedStorageProofParams::default(),
+		);
+
+	let (relay_chain_header, justification, bridged_para_head, parachain_heads, para_heads_proof) =
+		make_complex_bridged_parachain_heads_proof::<BridgedRelayChain, BridgedParachain>(
+			para_state_root,
+			para_header_number,
+			relay_header_number,
+			bridged_para_id,
+			false,
+		);
+
+	let message_delivery_proof = FromBridgedChainMessagesDeliveryProof {
+		bridged_header_hash: bridged_para_head.hash(),
+		storage_proof: para_storage_proof,
+		lane: lane_id,
+	};
+
+	(
+		relay_chain_header,
+		justification,
+		bridged_para_head,
+		parachain_heads,
+		para_heads_proof,
+		message_delivery_proof,
+		relayers_state,
+	)
+}
+
+/// Make inbound lane data where every relayer has delivered given range of messages. All messages
+/// before the first range are confirmed.
+fn inbound_lane_data_with_gaps<RelayerId>(
+	delivered_messages: Vec<(RelayerId, RangeInclusive<MessageNonce>)>,
+) -> InboundLaneData<RelayerId> {
+	let last_confirmed_nonce = delivered_messages
+		.first()
+		.map(|(_, messages)| messages.start().saturating_sub(1))
+		.unwrap_or(0);
+	let mut previous_end = last_confirmed_nonce;
+	let relayers = delivered_messages
+		.into_iter()
+		.map(|(relayer, messages)| {
+			assert!(
+				*messages.start() > previous_end && messages.start() <= messages.end(),
+				"delivered message ranges must be increasing and must not overlap",
+			);
+			previous_end = *messages.end();
+
+			UnrewardedRelayer {
+				relayer,
+				messages: DeliveredMessages { begin: *messages.start(), end: *messages.end() },
+			}
+		})
+		.collect();
+
+	InboundLaneData { state: LaneState::Opened, relayers, last_confirmed_nonce }
+}
+
+/// Make bridged parachain header with given state root and relay header that is finalizing it.
+pub fn make_complex_bridged_parachain_heads_proof<BridgedRelayChain, BridgedParachain>(
+	para_state_root: ParaHash,
+	para_header_number: u32,
+	relay_header_number: BlockNumberOf<BridgedRelayChain>,
+	bridged_para_id: u32,
+	is_minimal_call: bool,
+) -> (
+	HeaderOf<BridgedRelayChain>,
+	GrandpaJustification<HeaderOf<BridgedRelayChain>>,
+	ParaHead,
+	Vec<(ParaId, ParaHash)>,
+	ParaHeadsProof,
+)
+where
+	BridgedRelayChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+	BridgedParachain: bp_runtime::Chain<Hash = ParaHash> + Parachain,
+{
+	make_complex_bridged_parachain_heads_proof_with_header_builder::<
+		BridgedRelayChain,
+		BridgedParachain,
+	>(
+		para_state_root,
+		para_header_number,
+		relay_header_number,
+		bridged_para_id,
+		is_minimal_call,
+		make_bridged_para_header::<BridgedParachain>,
+	)
+}
+
+/// Same as [`make_complex_bridged_parachain_heads_proof`], but the bridged parachain header is
+/// built by `build_para_header` from its number and state root, e.g. to add digest items to it.
+pub fn make_complex_bridged_parachain_heads_proof_with_header_builder<
+	BridgedRelayChain,
+	BridgedParachain,
+>(
+	para_state_root: ParaHash,
+	para_header_number: u32,
+	relay_header_number: BlockNumberOf<BridgedRelayChain>,
+	bridged_para_id: u32,
+	is_minimal_call: bool,
+	build_para_header: impl FnOnce(u32, ParaHash) -> HeaderOf<BridgedParachain>,
+) -> (
+	HeaderOf<BridgedRelayChain>,
+	GrandpaJustification<HeaderOf<BridgedRelayChain>>,
+	ParaHead,
+	Vec<(ParaId, ParaHash)>,
+	ParaHeadsProof,
+)
+where
+	BridgedRelayChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+	BridgedParachain: bp_runtime::Chain<Hash = ParaHash> + Parachain,
+{
+	ensure_bridged_para_id::<BridgedParachain>(bridged_para_id);
+
+	let bridged_para_head =
+		ParaHead(build_para_header(para_header_number, para_state_root).encode());
+	let (relay_state_root, para_heads_proof, parachain_heads) =
+		prepare_parachain_heads_proof::<HeaderOf<BridgedParachain>>(vec![(
+			bridged_para_id,
+			bridged_para_head.clone(),
+		)]);
+	assert_eq!(bridged_para_head.hash(), parachain_heads[0].1);
+
+	let (relay_chain_header, justification) =
+		make_complex_bridged_grandpa_header_proof::<BridgedRelayChain>(
+			relay_state_root,
+			relay_header_number,
+			is_minimal_call,
+		);
+
+	(relay_chain_header, justification, bridged_para_head, parachain_heads, para_heads_proof)
+}
+
+/// Make bridged parachain header with given state root and relay header that is NOT finalizing
+/// it, because the relay header state root doesn't match the parachain heads proof.
+///
+/// The GRANDPA justification of the relay header is still valid, so submitting the returned
+/// parachain heads proof only fails at the parachain heads proof verification.
+pub fn make_inconsistent_bridged_parachain_heads_proof<BridgedRelayChain, BridgedParachain>(
+	para_state_root: ParaHash,
+	para_header_number: u32,
+	relay_header_number: BlockNumberOf<BridgedRelayChain>,
+	bridged_para_id: u32,
+	is_minimal_call: bool,
+) -> (
+	HeaderOf<BridgedRelayChain>,
+	GrandpaJustification<HeaderOf<BridgedRelayChain>>,
+	ParaHead,
+	Vec<(ParaId, ParaHash)>,
+	ParaHeadsProof,
+)
+where
+	BridgedRelayChain:
+		bp_runtime::Chain<Hash = RelayBlockHash, BlockNumber = RelayBlockNumber> + ChainWithGrandpa,
+	BridgedParachain: bp_runtime::Chain<Hash = ParaHash> + Parachain,
+{
+	ensure_bridged_para_id::<BridgedParachain>(bridged_para_id);
+
+	let bridged_para_head =
+		make_bridged_para_head::<BridgedParachain>(para_state_root, para_header_number);
+	let (relay_state_root, para_heads_proof, parachain_heads) =
+		prepare_parachain_heads_proof::<HeaderOf<BridgedParachain>>(vec![(
+			bridged_para_id,
+			bridged_para_head.clone(),
+		)]);
+	assert_eq!(bridged_para_head.hash(), parachain_heads[0].1);
+
+	// relay state root, committing to the next head of the same parachain
+	let (mismatched_relay_state_root, _, _) =
+		prepare_parachain_heads_proof::<HeaderOf<BridgedParachain>>(vec![(
+			bridged_para_id,
+			make_bridged_para_head::<BridgedParachain>(para_state_root, para_header_number + 1),
+		)]);
+	assert_ne!(relay_state_root, mismatched_relay_state_root);
+
+	let (relay_chain_header, justification) =
+		make_complex_bridged_grandpa_header_proof::<BridgedRelayChain>(
+			mismatched_relay_state_root,
+			relay_header_number,
+			is_minimal_call,
+		);
+
+	(relay_chain_header, justification, bridged_para_head, parachain_heads, para_heads_proof)
+}
+
+/// Make encoded bridged parachain header with given state root.
+fn make_bridged_para_head<BridgedParachain>(
+	para_state_root: ParaHash,
+	para_header_number: u32,
+) -> ParaHead
+where
+	BridgedParachain: bp_runtime::Chain<Hash = ParaHash> + Parachain,
+{
+	ParaHead(
+		make_bridged_para_header::<BridgedParachain>(para_header_number, para_state_root).encode(),
+	)
+}
+
+/// Make minimal bridged parachain header with given number and state root.
+fn make_bridged_para_header<BridgedParachain>(
+	para_header_number: u32,
+	para_state_root: ParaHash,
+) -> HeaderOf<BridgedParachain>
+where
+	BridgedParachain: bp_runtime::Chain<Hash = ParaHash> + Parachain,
+{
+	bp_test_utils::test_header_with_root::<HeaderOf<BridgedParachain>>(
+		para_header_number.into(),
+		para_state_root,
+	)
+}
+
+/// Panics if `bridged_para_id` is not the id of the `BridgedParachain`.
+///
+/// The bridged parachain heads pallet only accepts heads of the parachain that the messages
+/// pallet is bridged with, so proofs of any other parachain head would be rejected.
+fn ensure_bridged_para_id<BridgedParachain: Parachain>(bridged_para_id: u32) {
+	assert_eq!(
+		bridged_para_id,
+		BridgedParachain::PARACHAIN_ID,
+		"Bridged para id {} does not match the id {} of the bridged parachain",
+		bridged_para_id,
+		BridgedParachain::PARACHAIN_ID,
+	);
+}
+
+/// Panics if the encoded message payload is larger than `max_message_size`.
+fn ensure_message_size(message_payload: &[u8], max_message_size: u32) {
+	assert!(
+		message_payload.len() <= max_message_size as usize,
+		"Encoded message payload of {} bytes exceeds the maximal message size of {} bytes",
+		message_payload.len(),
+		max_message_size,
+	);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_data::{dummy_xcm, HashedLaneId};
+	use bp_runtime::StorageProofChecker;
+	use bp_test_utils::PARAS_PALLET_NAME;
+	use codec::Decode;
+
+	// Only needs to compile: relayer ids are passed to the messages pallet as is, so they may be
+	// of any bridged chain account type, e.g. 20-byte ids of ethereum-style chains.
+	#[allow(dead_code)]
+	fn delivery_calls_accept_20_byte_relayer_ids<Runtime, GPI, PPI, MPI>(
+		relay_chain_header: BridgedHeader<Runtime, GPI>,
+		grandpa_justification: GrandpaJustification<BridgedHeader<Runtime, GPI>>,
+		parachain_heads: Vec<(ParaId, ParaHash)>,
+		para_heads_proof: ParaHeadsProof,
+		message_proof: FromBridgedChainMessagesProof<ParaHash, LaneIdOf<Runtime, MPI>>,
+		relayer_id_at_bridged_chain: sp_core::H160,
+	) where
+		Runtime: pallet_bridge_grandpa::Config<GPI>
+			+ pallet_bridge_parachains::Config<PPI>
+			+ pallet_bridge_messages::Config<MPI, InboundPayload = XcmAsPlainPayload>
+			+ pallet_utility::Config,
+		GPI: 'static,
+		PPI: 'static,
+		MPI: 'static,
+		ParaHash: From<bp_runtime::HashOf<pallet_bridge_grandpa::BridgedChain<Runtime, GPI>>>,
+		bp_runtime::HashOf<pallet_bridge_grandpa::BridgedChain<Runtime, GPI>>: From<ParaHash>,
+		BridgedChainOf<Runtime, MPI>: Chain<Hash = ParaHash, AccountId = sp_core::H160> + Parachain,
+		Runtime::RuntimeCall: From<pallet_bridge_messages::Call<Runtime, MPI>>,
+		<Runtime as pallet_utility::Config>::RuntimeCall:
+			From<pallet_bridge_grandpa::Call<Runtime, GPI>>
+				+ From<pallet_bridge_parachains::Call<Runtime, PPI>>
+				+ From<pallet_bridge_messages::Call<Runtime, MPI>>,
+	{
+		let _ = make_standalone_relayer_delivery_call::<Runtime, MPI>(
+			message_proof.clone(),
+			relayer_id_at_bridged_chain,
+		);
+		let _ = make_complex_relayer_delivery_batch::<Runtime, GPI, PPI, MPI>(
+			relay_chain_header,
+			grandpa_justification,
+			parachain_heads,
+			para_heads_proof,
+			message_proof,
+			relayer_id_at_bridged_chain,
+		);
+	}
+
+	struct TestParachain;
+
+	impl Chain for TestParachain {
+		const ID: bp_runtime::ChainId = *b"tstp";
+
+		type BlockNumber = bp_polkadot_core::BlockNumber;
+		type Hash = ParaHash;
+		type Hasher = bp_polkadot_core::Hasher;
+		type Header = bp_polkadot_core::Header;
+		type AccountId = bp_polkadot_core::AccountId;
+		type Balance = bp_polkadot_core::Balance;
+		type Nonce = bp_polkadot_core::Nonce;
+		type Signature = bp_polkadot_core::Signature;
+
+		const STATE_VERSION: sp_runtime::StateVersion = sp_runtime::StateVersion::V1;
+
+		fn max_extrinsic_size() -> u32 {
+			0
+		}
+		fn max_extrinsic_weight() -> Weight {
+			Weight::zero()
+		}
+	}
+
+	impl Parachain for TestParachain {
+		const PARACHAIN_ID: u32 = 42;
+		const MAX_HEADER_SIZE: u32 = 1_024;
+	}
+
+	impl ChainWithMessages for TestParachain {
+		const WITH_CHAIN_MESSAGES_PALLET_NAME: &'static str = "WithTestParachainBridgeMessages";
+		const MAX_UNREWARDED_RELAYERS_IN_CONFIRMATION_TX: MessageNonce = 16;
+		const MAX_UNCONFIRMED_MESSAGES_IN_CONFIRMATION_TX: MessageNonce = 128;
+	}
+
+	struct TestRelayChain;
+
+	impl Chain for TestRelayChain {
+		const ID: bp_runtime::ChainId = *b"tstr";
+
+		type BlockNumber = RelayBlockNumber;
+		type Hash = RelayBlockHash;
+		type Hasher = bp_polkadot_core::Hasher;
+		type Header = bp_polkadot_core::Header;
+		type AccountId = bp_polkadot_core::AccountId;
+		type Balance = bp_polkadot_core::Balance;
+		type Nonce = bp_polkadot_core::Nonce;
+		type Signature = bp_polkadot_core::Signature;
+
+		const STATE_VERSION: sp_runtime::StateVersion = sp_runtime::StateVersion::V1;
+
+		fn max_extrinsic_size() -> u32 {
+			0
+		}
+		fn max_extrinsic_weight() -> Weight {
+			Weight::zero()
+		}
+	}
+
+	impl ChainWithGrandpa for TestRelayChain {
+		const WITH_CHAIN_GRANDPA_PALLET_NAME: &'static str = "WithTestRelayChainBridgeGrandpa";
+		const MAX_AUTHORITIES_COUNT: u32 = 16;
+		const REASONABLE_HEADERS_IN_JUSTIFICATION_ANCESTRY: u32 = 4;
+		const MAX_MANDATORY_HEADER_SIZE: u32 = 4096;
+		const AVERAGE_HEADER_SIZE: u32 = 4096;
+	}
+
+	#[test]
+	fn parachain_heads_proof_with_custom_para_header_verifies() {
+		let digest_item = sp_runtime::DigestItem::Consensus(*b"test", vec![42]);
+		let (relay_chain_header, justification, para_head, parachain_heads, para_heads_proof) =
+			make_complex_bridged_parachain_heads_proof_with_header_builder::<
+				TestRelayChain,
+				TestParachain,
+			>(ParaHash::repeat_byte(1), 5, 10, 42, true, |number, state_root| {
+				let mut header = make_bridged_para_header::<TestParachain>(number, state_root);
+				header.digest_mut().push(digest_item.clone());
+				header
+			});
+
+		// the relay chain header is finalized by the justification and its state has the custom
+		// parachain head
+		assert_eq!(justification.commit.target_hash, relay_chain_header.hash());
+		assert_eq!(parachain_heads, vec![(ParaId(42), para_head.hash())]);
+		let mut checker = StorageProofChecker::<bp_polkadot_core::Hasher>::new(
+			*relay_chain_header.state_root(),
+			para_heads_proof.storage_proof,
+		)
+		.unwrap();
+		let storage_key =
+			bp_parachains::parachain_head_storage_key_at_source(PARAS_PALLET_NAME, ParaId(42));
+		assert_eq!(
+			checker.read_and_decode_mandatory_value::<ParaHead>(storage_key.0.as_ref()).unwrap(),
+			para_head,
+		);
+
+		let para_header = HeaderOf::<TestParachain>::decode(&mut &para_head.0[..]).unwrap();
+		assert_eq!(*para_header.number(), 5);
+		assert_eq!(*para_header.state_root(), ParaHash::repeat_byte(1));
+		assert_eq!(para_header.digest().logs(), &[digest_item]);
+	}
+
+	#[test]
+	fn delivery_proofs_are_generated_for_all_lane_id_types() {
+		macro_rules! check_delivery_proofs {
+			($lane_id:ty) => {
+				let lane_id = <$lane_id as bp_messages::LaneIdType>::try_new(1, 2).unwrap();
+				let (.., message_proof) = make_complex_relayer_delivery_proofs::<
+					TestRelayChain,
+					TestParachain,
+					TestParachain,
+					$lane_id,
+				>(
+					lane_id,
+					dummy_xcm(),
+					1,
+					[GlobalConsensus(Polkadot)].into(),
+					None,
+					5,
+					10,
+					42,
+					false,
+				);
+				assert_eq!(message_proof.lane, lane_id);
+			};
+		}
+
+		crate::for_each_lane_id_type!(check_delivery_proofs);
+	}
+
+	// Builds the delivery proofs of the dummy message with the given maximal message size.
+	fn make_checked_delivery_proofs_of_dummy_message(max_message_size: u32) {
+		let lane_id = <HashedLaneId as bp_messages::LaneIdType>::try_new(1, 2).unwrap();
+		let _ = make_complex_relayer_delivery_proofs_checked::<
+			TestRelayChain,
+			TestParachain,
+			TestParachain,
+			HashedLaneId,
+		>(
+			lane_id,
+			dummy_xcm(),
+			1,
+			[GlobalConsensus(Polkadot)].into(),
+			None,
+			5,
+			10,
+			42,
+			false,
+			max_message_size,
+		);
+	}
+
+	fn dummy_message_size() -> u32 {
+		prepare_inbound_xcm(dummy_xcm(), [GlobalConsensus(Polkadot)].into()).len() as u32
+	}
+
+	#[test]
+	fn checked_delivery_proofs_accept_message_of_max_size() {
+		make_checked_delivery_proofs_of_dummy_message(dummy_message_size());
+	}
+
+	#[test]
+	#[should_panic(expected = "exceeds the maximal message size")]
+	fn checked_delivery_proofs_reject_message_above_max_size() {
+		make_checked_delivery_proofs_of_dummy_message(dummy_message_size() - 1);
+	}
+
+	#[test]
+	fn ensure_bridged_para_id_accepts_id_of_bridged_parachain() {
+		ensure_bridged_para_id::<TestParachain>(42);
+	}
+
+	#[test]
+	#[should_panic(expected = "does not match the id 42 of the bridged parachain")]
+	fn ensure_bridged_para_id_rejects_mismatched_id() {
+		ensure_bridged_para_id::<TestParachain>(43);
+	}
+
+	#[test]
+	fn inbound_lane_data_with_gaps_works() {
+		let lane_data = inbound_lane_data_with_gaps(vec![(1u64, 3..=5), (2u64, 8..=9)]);
+
+		assert_eq!(lane_data.last_confirmed_nonce, 2);
+		assert_eq!(
+			lane_data.relayers,
+			vec![
+				UnrewardedRelayer { relayer: 1, messages: DeliveredMessages { begin: 3, end: 5 } },
+				UnrewardedRelayer { relayer: 2, messages: DeliveredMessages { begin: 8, end: 9 } },
+			],
+		);
+		// messages 6 and 7 are not delivered, but still counted as unrewarded by the pallet
+		assert_eq!(
+			UnrewardedRelayersState::from(&lane_data),
+			UnrewardedRelayersState {
+				unrewarded_relayer_entries: 2,
+				messages_in_oldest_entry: 3,
+				total_messages: 7,
+				last_delivered_nonce: 9,
+			},
+		);
+	}
 }
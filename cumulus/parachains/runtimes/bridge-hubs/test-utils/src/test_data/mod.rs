@@ -23,23 +23,70 @@ use bp_messages::{
 	target_chain::{DispatchMessage, DispatchMessageData},
 	MessageKey,
 };
-use codec::Encode;
+use codec::{Decode, Encode};
+use core::str::FromStr;
 use frame_support::traits::Get;
 use pallet_bridge_grandpa::BridgedHeader;
+use sp_runtime::traits::TrailingZeroInput;
 use xcm::latest::prelude::*;
 
-use bp_messages::MessageNonce;
+use crate::test_cases::helpers::InboundRelayerId;
+use bp_messages::{DeliveredMessages, InboundLaneData, MessageNonce, UnrewardedRelayer};
 use bp_runtime::BasicOperatingMode;
 use bp_test_utils::authority_list;
-use xcm::GetVersion;
+use sp_std::ops::RangeInclusive;
+use xcm::{GetVersion, IntoVersion, Version as XcmVersion};
 use xcm_builder::{BridgeMessage, HaulBlob, HaulBlobError, HaulBlobExporter};
 use xcm_executor::traits::{validate_export, ExportXcm};
 
+pub use bp_messages::{HashedLaneId, LegacyLaneId};
+pub use pallet_bridge_messages::messages_generation::messages_storage_key;
+
 pub(crate) type XcmAsPlainPayload = sp_std::vec::Vec<u8>;
 
+/// Sizes of a generated message proof, e.g. to check them against the ranges of benchmark
+/// components.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MessageProofSizes {
+	/// Size of the encoded message payload, the `n` of the
+	/// `receive_single_n_bytes_message_proof` benchmark.
+	pub message_payload: u32,
+	/// Total size of all storage proof nodes.
+	pub storage_proof: u32,
+}
+
+impl MessageProofSizes {
+	fn new(message_payload: &[u8], storage_proof: &bp_runtime::RawStorageProof) -> Self {
+		MessageProofSizes {
+			message_payload: message_payload.len() as u32,
+			storage_proof: bp_runtime::raw_storage_proof_size(storage_proof) as u32,
+		}
+	}
+}
+
 pub fn prepare_inbound_xcm(xcm_message: Xcm<()>, destination: InteriorLocation) -> Vec<u8> {
-	let location = xcm::VersionedInteriorLocation::from(destination);
-	let xcm = xcm::VersionedXcm::<()>::from(xcm_message);
+	prepare_inbound_xcm_versioned(xcm_message, destination, xcm::latest::VERSION)
+}
+
+/// Returns XCM message, which may be delivered to this chain, but can't be dispatched, because it
+/// has more instructions than the receiving side is willing to decode.
+pub fn prepare_failing_inbound_xcm() -> Xcm<()> {
+	Xcm(vec![ClearOrigin; xcm::MAX_INSTRUCTIONS_TO_DECODE as usize + 1])
+}
+
+/// Same as [`prepare_inbound_xcm`], but encodes the message and its destination at the given XCM
+/// version, e.g. to check that older versions are still dispatched.
+pub fn prepare_inbound_xcm_versioned(
+	xcm_message: Xcm<()>,
+	destination: InteriorLocation,
+	version: XcmVersion,
+) -> Vec<u8> {
+	let location = xcm::VersionedInteriorLocation::from(destination)
+		.into_version(version)
+		.expect("destination is not convertible to the requested XCM version");
+	let xcm = xcm::VersionedXcm::<()>::from(xcm_message)
+		.into_version(version)
+		.expect("message is not convertible to the requested XCM version");
 
 	// (double encoding, because `.encode()` is called on original Xcm BLOB when it is pushed to the
 	// storage)
@@ -62,11 +109,53 @@ pub fn initialization_data<
 	}
 }
 
+/// Returns the relayer id at the bridged chain of a well-known keyring account, e.g. `"Alice"`.
+///
+/// The id is decoded from the sr25519 public key of the account, so it works for any bridged chain
+/// account id of at most 32 bytes: 32-byte relay chain accounts get the key itself, while shorter
+/// ids, e.g. 20-byte ids of ethereum-style chains, get its leading bytes.
+pub fn relayer_id_from_keyring<Runtime, MPI>(name: &str) -> InboundRelayerId<Runtime, MPI>
+where
+	Runtime: pallet_bridge_messages::Config<MPI>,
+	MPI: 'static,
+{
+	let keyring = sp_keyring::Sr25519Keyring::from_str(name)
+		.unwrap_or_else(|_| panic!("Unknown keyring account: {name}"));
+	Decode::decode(&mut TrailingZeroInput::new(&keyring.to_raw_public()))
+		.expect("decoding from a trailing zero input never fails; qed")
+}
+
 /// Dummy xcm
 pub(crate) fn dummy_xcm() -> Xcm<()> {
 	vec![Trap(42)].into()
 }
 
+/// Returns inbound lane data, expected after `relayer` has delivered `delivered` messages to the
+/// lane with given `before` data, the same way the messages pallet records the delivery.
+pub fn expected_inbound_lane_data_after<RelayerId: Clone + PartialEq>(
+	mut before: InboundLaneData<RelayerId>,
+	relayer: RelayerId,
+	delivered: RangeInclusive<MessageNonce>,
+) -> InboundLaneData<RelayerId> {
+	assert_eq!(
+		*delivered.start(),
+		before.last_delivered_nonce() + 1,
+		"Delivered messages must follow the last delivered message",
+	);
+	if delivered.is_empty() {
+		return before
+	}
+
+	match before.relayers.back_mut() {
+		Some(entry) if entry.relayer == relayer => entry.messages.end = *delivered.end(),
+		_ => before.relayers.push_back(UnrewardedRelayer {
+			relayer,
+			messages: DeliveredMessages { begin: *delivered.start(), end: *delivered.end() },
+		}),
+	}
+	before
+}
+
 pub(crate) fn dispatch_message<LaneId: Encode>(
 	lane_id: LaneId,
 	nonce: MessageNonce,
@@ -78,6 +167,28 @@ pub(crate) fn dispatch_message<LaneId: Encode>(
 	}
 }
 
+/// Invokes `$callback!` with each lane identifier type used by the bridge hubs, e.g. to
+/// instantiate generators that are generic over the lane identifier, like
+/// [`from_parachain::make_complex_relayer_delivery_proofs`], for all of them. Lane identifiers
+/// of every type may be created with `bp_messages::LaneIdType::try_new`.
+///
+/// ```ignore
+/// macro_rules! delivery_proofs {
+/// 	($lane_id:ty) => {
+/// 		let lane_id = <$lane_id as LaneIdType>::try_new(1, 2).unwrap();
+/// 		...
+/// 	};
+/// }
+/// bridge_hub_test_utils::for_each_lane_id_type!(delivery_proofs);
+/// ```
+#[macro_export]
+macro_rules! for_each_lane_id_type {
+	($callback:ident) => {
+		$callback!($crate::test_data::LegacyLaneId);
+		$callback!($crate::test_data::HashedLaneId);
+	};
+}
+
 /// Macro used for simulate_export_message and capturing bytes
 macro_rules! grab_haul_blob (
 	($name:ident, $grabbed_payload:ident) => {
@@ -140,3 +251,93 @@ pub(crate) fn simulate_message_exporter_on_bridged_chain<
 
 	GRABBED_HAUL_BLOB_PAYLOAD.with(|r| r.take().expect("Encoded message should be here"))
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use bp_messages::LaneState;
+	use codec::Decode;
+	use xcm::IdentifyVersion;
+
+	fn decode_inbound_xcm(payload: Vec<u8>) -> BridgeMessage {
+		let blob = Vec::<u8>::decode(&mut &payload[..]).unwrap();
+		BridgeMessage::decode(&mut &blob[..]).unwrap()
+	}
+
+	#[test]
+	fn prepare_inbound_xcm_versioned_works() {
+		for version in [3, 4] {
+			let message = decode_inbound_xcm(prepare_inbound_xcm_versioned(
+				dummy_xcm(),
+				[GlobalConsensus(Polkadot), Parachain(1000)].into(),
+				version,
+			));
+
+			assert_eq!(message.universal_dest.identify_version(), version);
+			assert_eq!(message.message.identify_version(), version);
+			assert_eq!(Xcm::<()>::try_from(message.message), Ok(dummy_xcm()));
+		}
+	}
+
+	#[test]
+	fn prepare_inbound_xcm_uses_latest_version() {
+		let message = decode_inbound_xcm(prepare_inbound_xcm(
+			dummy_xcm(),
+			[GlobalConsensus(Polkadot)].into(),
+		));
+
+		assert_eq!(message.message.identify_version(), xcm::latest::VERSION);
+	}
+
+	#[test]
+	fn prepare_failing_inbound_xcm_is_not_decodable() {
+		let payload =
+			prepare_inbound_xcm(prepare_failing_inbound_xcm(), [GlobalConsensus(Polkadot)].into());
+		let blob = Vec::<u8>::decode(&mut &payload[..]).unwrap();
+		assert!(BridgeMessage::decode(&mut &blob[..]).is_err());
+	}
+
+	#[test]
+	fn expected_inbound_lane_data_after_works_for_fresh_lane() {
+		assert_eq!(
+			expected_inbound_lane_data_after(InboundLaneData::<u64>::opened(), 1, 1..=3),
+			InboundLaneData {
+				relayers: vec![UnrewardedRelayer {
+					relayer: 1,
+					messages: DeliveredMessages { begin: 1, end: 3 },
+				}]
+				.into(),
+				last_confirmed_nonce: 0,
+				state: LaneState::Opened,
+			},
+		);
+	}
+
+	#[test]
+	fn expected_inbound_lane_data_after_appends_to_existing_relayer_entry() {
+		let before = expected_inbound_lane_data_after(InboundLaneData::<u64>::opened(), 1, 1..=3);
+		let before = expected_inbound_lane_data_after(before, 2, 4..=5);
+
+		// the last entry belongs to the same relayer, so it is extended
+		let after = expected_inbound_lane_data_after(before.clone(), 2, 6..=8);
+		assert_eq!(after.last_delivered_nonce(), 8);
+		assert_eq!(
+			after.relayers,
+			vec![
+				UnrewardedRelayer { relayer: 1, messages: DeliveredMessages { begin: 1, end: 3 } },
+				UnrewardedRelayer { relayer: 2, messages: DeliveredMessages { begin: 4, end: 8 } },
+			],
+		);
+
+		// and another relayer gets a new entry
+		let after = expected_inbound_lane_data_after(before, 1, 6..=6);
+		assert_eq!(
+			after.relayers,
+			vec![
+				UnrewardedRelayer { relayer: 1, messages: DeliveredMessages { begin: 1, end: 3 } },
+				UnrewardedRelayer { relayer: 2, messages: DeliveredMessages { begin: 4, end: 5 } },
+				UnrewardedRelayer { relayer: 1, messages: DeliveredMessages { begin: 6, end: 6 } },
+			],
+		);
+	}
+}
@@ -46,6 +46,7 @@ use polkadot_primitives::{CandidateIndex, Hash, ValidatorIndex, ValidatorSignatu
 use rand::SeedableRng;
 
 use sc_keystore::LocalKeystore;
+use sc_telemetry::TelemetryHandle;
 use sp_consensus::SyncOracle;
 
 use futures::{channel::oneshot, prelude::*, StreamExt};
@@ -95,6 +96,7 @@ pub struct ApprovalVotingParallelSubsystem {
 	spawner: Arc<dyn overseer::gen::Spawner + 'static>,
 	clock: Arc<dyn Clock + Send + Sync>,
 	overseer_message_channel_capacity_override: Option<usize>,
+	telemetry: Option<TelemetryHandle>,
 }
 
 impl ApprovalVotingParallelSubsystem {
@@ -141,9 +143,17 @@ impl ApprovalVotingParallelSubsystem {
 			spawner: Arc::new(spawner),
 			clock,
 			overseer_message_channel_capacity_override,
+			telemetry: None,
 		}
 	}
 
+	/// Report the slot and tick of every block imported by the approval voting worker to
+	/// telemetry.
+	pub fn with_telemetry(mut self, telemetry: TelemetryHandle) -> Self {
+		self.telemetry = Some(telemetry);
+		self
+	}
+
 	/// The size of the channel used for the workers.
 	fn workers_channel_size(&self) -> usize {
 		self.overseer_message_channel_capacity_override
@@ -275,6 +285,7 @@ where
 		"approval-voting-parallel-db",
 		"approval-voting-parallel",
 		subsystem.clock.clone(),
+		subsystem.telemetry,
 	)
 	.await?;
 
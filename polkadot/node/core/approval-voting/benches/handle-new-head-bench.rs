@@ -0,0 +1,44 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Benchmarks for importing a new head along with its unknown ancestors.
+//!
+//! Each iteration imports a synthetic chain of N blocks with M candidates each into a fresh
+//! in-memory database, answering Chain API and Runtime API requests from memory. Run with
+//! `cargo bench -p polkadot-node-core-approval-voting --features bench`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use polkadot_node_core_approval_voting::bench::{import_chain, SyntheticChain};
+
+const N_BLOCKS: [u32; 3] = [1, 10, 100];
+const N_CANDIDATES: [u32; 3] = [0, 5, 50];
+
+fn handle_new_head(c: &mut Criterion) {
+	let mut group = c.benchmark_group("handle_new_head");
+	for n_blocks in N_BLOCKS {
+		for n_candidates in N_CANDIDATES {
+			let chain = SyntheticChain::new(n_blocks, n_candidates);
+			let id = format!("{}_blocks_{}_candidates", n_blocks, n_candidates);
+			group.bench_with_input(BenchmarkId::from_parameter(id), &chain, |b, chain| {
+				b.iter(|| assert_eq!(import_chain(chain), n_blocks as usize))
+			});
+		}
+	}
+	group.finish();
+}
+
+criterion_group!(benches, handle_new_head);
+criterion_main!(benches);
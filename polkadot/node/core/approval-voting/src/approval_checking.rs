@@ -24,7 +24,41 @@ use crate::{
 	persisted_entries::{ApprovalEntry, CandidateEntry, TrancheEntry},
 	MAX_RECORDED_NO_SHOW_VALIDATORS_PER_CANDIDATE,
 };
-use polkadot_node_primitives::approval::time::Tick;
+use polkadot_node_primitives::approval::time::{slot_number_to_tick, Tick};
+use polkadot_primitives::SessionInfo;
+use sp_consensus_slots::Slot;
+
+/// Decides when an assigned validator who hasn't approved yet is considered a no-show.
+pub trait NoShowPolicy {
+	/// The tick at which validators assigned at `block_tick` become no-shows.
+	///
+	/// `slot_duration_millis` is the slot duration of the session the block belongs to.
+	fn no_show_deadline(
+		&self,
+		block_tick: Tick,
+		slot_duration_millis: u64,
+		session_info: &SessionInfo,
+	) -> Tick;
+}
+
+/// The default policy, under which validators become no-shows `no_show_slots` slots after
+/// their assignment.
+pub struct SlotsNoShowPolicy;
+
+impl NoShowPolicy for SlotsNoShowPolicy {
+	fn no_show_deadline(
+		&self,
+		block_tick: Tick,
+		slot_duration_millis: u64,
+		session_info: &SessionInfo,
+	) -> Tick {
+		block_tick +
+			slot_number_to_tick(
+				slot_duration_millis,
+				Slot::from(u64::from(session_info.no_show_slots)),
+			)
+	}
+}
 
 /// Result of counting the necessary tranches needed for approving a block.
 #[derive(Debug, PartialEq, Clone)]
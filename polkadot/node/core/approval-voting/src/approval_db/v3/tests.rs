@@ -22,7 +22,10 @@ use crate::{
 		v3::*,
 	},
 	backend::{Backend, OverlayedBackend},
-	ops::{add_block_entry, canonicalize, force_approve, NewCandidateInfo},
+	ops::{
+		add_block_entries, add_block_entry, approved_chain_weight, canonicalize, force_approve,
+		NewCandidateInfo,
+	},
 };
 use polkadot_primitives::{
 	vstaging::{CandidateReceiptV2 as CandidateReceipt, MutateDescriptorV2},
@@ -226,6 +229,59 @@ fn add_block_entry_works() {
 	assert_eq!(candidate_entry_b.block_assignments.keys().collect::<Vec<_>>(), vec![&block_hash_b]);
 }
 
+#[test]
+fn add_block_entry_fails_without_candidate_info() {
+	let (db, store) = make_db();
+
+	let parent_hash = Hash::repeat_byte(1);
+	let block_hash = Hash::repeat_byte(2);
+	let candidate_hash = make_candidate(ParaId::from(1_u32), parent_hash).hash();
+	let block_entry =
+		make_block_entry(block_hash, parent_hash, 10, vec![(CoreIndex(0), candidate_hash)]);
+
+	let mut overlay_db = OverlayedBackend::new(&db);
+	assert!(add_block_entry(&mut overlay_db, block_entry.into(), 10, |_| None).is_err());
+	assert!(overlay_db.into_write_ops().next().is_none());
+
+	assert!(load_block_entry(store.as_ref(), &TEST_CONFIG, &block_hash).unwrap().is_none());
+	assert!(load_candidate_entry(store.as_ref(), &TEST_CONFIG, &candidate_hash)
+		.unwrap()
+		.is_none());
+}
+
+#[test]
+fn expected_approvals_len_matches_validator_count() {
+	let (mut db, store) = make_db();
+
+	let parent_hash = Hash::repeat_byte(1);
+	let block_hash = Hash::repeat_byte(2);
+	let candidate_receipt = make_candidate(ParaId::from(1_u32), parent_hash);
+	let candidate_hash = candidate_receipt.hash();
+	let block_entry =
+		make_block_entry(block_hash, parent_hash, 10, vec![(CoreIndex(0), candidate_hash)]);
+
+	let n_validators = 17;
+	let new_candidate_info = NewCandidateInfo::new(candidate_receipt, GroupIndex(0), None);
+
+	let mut overlay_db = OverlayedBackend::new(&db);
+	let added = add_block_entry(&mut overlay_db, block_entry.into(), n_validators, |_| {
+		Some(new_candidate_info.clone())
+	})
+	.unwrap();
+	let write_ops = overlay_db.into_write_ops();
+	db.write(write_ops).unwrap();
+
+	assert_eq!(added.len(), 1);
+	assert_eq!(added[0].1.expected_approvals_len(), n_validators);
+
+	let stored: crate::persisted_entries::CandidateEntry =
+		load_candidate_entry(store.as_ref(), &TEST_CONFIG, &candidate_hash)
+			.unwrap()
+			.unwrap()
+			.into();
+	assert_eq!(stored.expected_approvals_len(), n_validators);
+}
+
 #[test]
 fn add_block_entry_adds_child() {
 	let (mut db, store) = make_db();
@@ -260,6 +316,103 @@ fn add_block_entry_adds_child() {
 	);
 }
 
+#[test]
+fn add_block_entries_matches_sequential_adds() {
+	let root_hash = Hash::repeat_byte(1);
+	let block_hash_a = Hash::repeat_byte(2);
+	let block_hash_b = Hash::repeat_byte(3);
+	let block_hash_c = Hash::repeat_byte(4);
+
+	let candidate_receipt_a = make_candidate(ParaId::from(1_u32), root_hash);
+	let candidate_receipt_b = make_candidate(ParaId::from(2_u32), block_hash_a);
+	let candidate_hash_a = candidate_receipt_a.hash();
+	let candidate_hash_b = candidate_receipt_b.hash();
+
+	let mut candidate_info = HashMap::new();
+	candidate_info
+		.insert(candidate_hash_a, NewCandidateInfo::new(candidate_receipt_a, GroupIndex(0), None));
+	candidate_info
+		.insert(candidate_hash_b, NewCandidateInfo::new(candidate_receipt_b, GroupIndex(1), None));
+	let candidate_info = |h: &CandidateHash| candidate_info.get(h).map(|x| x.clone());
+
+	let n_validators = 10;
+	let blocks = vec![
+		make_block_entry(block_hash_a, root_hash, 2, vec![(CoreIndex(0), candidate_hash_a)]),
+		make_block_entry(block_hash_b, block_hash_a, 3, vec![(CoreIndex(1), candidate_hash_b)]),
+		// `b`'s candidate is included again in `c`.
+		make_block_entry(block_hash_c, block_hash_b, 4, vec![(CoreIndex(1), candidate_hash_b)]),
+	];
+
+	let setup_db = || {
+		let (mut db, _) = make_db();
+		let mut overlay_db = OverlayedBackend::new(&db);
+		add_block_entry(
+			&mut overlay_db,
+			make_block_entry(root_hash, Default::default(), 1, Vec::new()).into(),
+			n_validators,
+			candidate_info,
+		)
+		.unwrap();
+		let write_ops = overlay_db.into_write_ops();
+		db.write(write_ops).unwrap();
+		db
+	};
+
+	let mut sequential_db = setup_db();
+	let mut overlay_db = OverlayedBackend::new(&sequential_db);
+	let sequential_result = blocks
+		.iter()
+		.map(|entry| {
+			add_block_entry(&mut overlay_db, entry.clone().into(), n_validators, candidate_info)
+				.unwrap()
+		})
+		.collect::<Vec<_>>();
+	let write_ops = overlay_db.into_write_ops();
+	sequential_db.write(write_ops).unwrap();
+
+	let mut batched_db = setup_db();
+	let mut overlay_db = OverlayedBackend::new(&batched_db);
+	let batched_result = add_block_entries(
+		&mut overlay_db,
+		blocks
+			.iter()
+			.map(|entry| (entry.clone().into(), n_validators, candidate_info))
+			.collect(),
+	)
+	.unwrap();
+	let write_ops = overlay_db.into_write_ops();
+	batched_db.write(write_ops).unwrap();
+
+	assert_eq!(batched_result, sequential_result);
+	assert_eq!(batched_result.iter().map(Vec::len).collect::<Vec<_>>(), vec![1, 1, 1]);
+
+	assert_eq!(
+		batched_db.load_stored_blocks().unwrap(),
+		sequential_db.load_stored_blocks().unwrap()
+	);
+	for number in 1..=4 {
+		assert_eq!(
+			batched_db.load_blocks_at_height(&number).unwrap(),
+			sequential_db.load_blocks_at_height(&number).unwrap(),
+		);
+	}
+	for hash in [root_hash, block_hash_a, block_hash_b, block_hash_c] {
+		let entry = batched_db.load_block_entry(&hash).unwrap();
+		assert!(entry.is_some());
+		assert_eq!(entry, sequential_db.load_block_entry(&hash).unwrap());
+	}
+	for candidate_hash in [candidate_hash_a, candidate_hash_b] {
+		let entry = batched_db.load_candidate_entry(&candidate_hash).unwrap();
+		assert!(entry.is_some());
+		assert_eq!(entry, sequential_db.load_candidate_entry(&candidate_hash).unwrap());
+	}
+
+	assert_eq!(
+		batched_db.load_block_entry(&block_hash_b).unwrap().unwrap().children,
+		vec![block_hash_c]
+	);
+}
+
 #[test]
 fn canonicalize_works() {
 	let (mut db, store) = make_db();
@@ -587,6 +740,42 @@ fn force_approve_works() {
 	assert_eq!(approved_hashes, vec![block_hash_b, block_hash_a]);
 }
 
+#[test]
+fn approved_chain_weight_counts_fully_approved_ancestors() {
+	let (mut db, _) = make_db();
+
+	let candidate_hash = CandidateHash(Hash::repeat_byte(42));
+	let single_candidate_vec = vec![(CoreIndex(0), candidate_hash)];
+
+	let block_hash_a = Hash::repeat_byte(1);
+	let block_hash_b = Hash::repeat_byte(2);
+	let block_hash_c = Hash::repeat_byte(3);
+	let block_hash_d = Hash::repeat_byte(4);
+
+	// `a` has no candidates, so is trivially approved. `c` is the only unapproved block.
+	let block_entry_a = make_block_entry(block_hash_a, Default::default(), 1, Vec::new());
+	let mut block_entry_b =
+		make_block_entry(block_hash_b, block_hash_a, 2, single_candidate_vec.clone());
+	block_entry_b.approved_bitfield.set(0, true);
+	let block_entry_c =
+		make_block_entry(block_hash_c, block_hash_b, 3, single_candidate_vec.clone());
+	let mut block_entry_d =
+		make_block_entry(block_hash_d, block_hash_c, 4, single_candidate_vec.clone());
+	block_entry_d.approved_bitfield.set(0, true);
+
+	let mut overlay_db = OverlayedBackend::new(&db);
+	for block_entry in [block_entry_a, block_entry_b, block_entry_c, block_entry_d] {
+		overlay_db.write_block_entry(block_entry.into());
+	}
+	let write_ops = overlay_db.into_write_ops();
+	db.write(write_ops).unwrap();
+
+	assert_eq!(approved_chain_weight(&db, block_hash_d).unwrap(), 3);
+	assert_eq!(approved_chain_weight(&db, block_hash_c).unwrap(), 2);
+	assert_eq!(approved_chain_weight(&db, block_hash_a).unwrap(), 1);
+	assert_eq!(approved_chain_weight(&db, Hash::repeat_byte(5)).unwrap(), 0);
+}
+
 #[test]
 fn load_all_blocks_works() {
 	let (mut db, store) = make_db();
@@ -622,3 +811,29 @@ fn load_all_blocks_works() {
 		vec![block_hash_a, block_hash_b, block_hash_c],
 	)
 }
+
+#[cfg(debug_assertions)]
+#[test]
+fn overlay_detects_conflicting_writes() {
+	let (db, _) = make_db();
+
+	let parent_hash = Hash::repeat_byte(1);
+	let block_hash = Hash::repeat_byte(2);
+
+	let block_entry = make_block_entry(block_hash, parent_hash, 1, Vec::new());
+
+	let mut overlay_db = OverlayedBackend::new(&db);
+	overlay_db.write_block_entry(block_entry.clone().into());
+
+	// Updating the children is a regular read-modify-write and not a conflict.
+	let mut with_child = block_entry.clone();
+	with_child.children.push(Hash::repeat_byte(3));
+	overlay_db.write_block_entry(with_child.into());
+	assert_eq!(overlay_db.write_conflicts(), 0);
+
+	// Changing an import-time field of the same block is.
+	let mut conflicting = block_entry;
+	conflicting.slot = Slot::from(2);
+	overlay_db.write_block_entry(conflicting.into());
+	assert_eq!(overlay_db.write_conflicts(), 1);
+}
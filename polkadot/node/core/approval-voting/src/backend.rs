@@ -29,6 +29,7 @@ use std::collections::HashMap;
 use super::{
 	approval_db::common::StoredBlockRange,
 	persisted_entries::{BlockEntry, CandidateEntry},
+	LOG_TARGET,
 };
 
 #[derive(Debug)]
@@ -118,6 +119,9 @@ pub struct OverlayedBackend<'a, B: 'a> {
 	block_entries: HashMap<Hash, Option<BlockEntry>>,
 	// `None` means 'deleted', missing means query inner.
 	candidate_entries: HashMap<CandidateHash, Option<CandidateEntry>>,
+	// Number of conflicting writes observed within this overlay.
+	#[cfg(debug_assertions)]
+	write_conflicts: usize,
 }
 
 impl<'a, B: 'a + Backend> OverlayedBackend<'a, B> {
@@ -128,6 +132,8 @@ impl<'a, B: 'a + Backend> OverlayedBackend<'a, B> {
 			blocks_at_height: HashMap::new(),
 			block_entries: HashMap::new(),
 			candidate_entries: HashMap::new(),
+			#[cfg(debug_assertions)]
+			write_conflicts: 0,
 		}
 	}
 
@@ -201,6 +207,19 @@ impl<'a, B: 'a + Backend> OverlayedBackend<'a, B> {
 	}
 
 	pub fn write_block_entry(&mut self, entry: BlockEntry) {
+		#[cfg(debug_assertions)]
+		if let Some(Some(prev)) = self.block_entries.get(&entry.block_hash()) {
+			if block_entries_conflict(prev, &entry) {
+				self.write_conflicts += 1;
+				gum::warn!(
+					target: LOG_TARGET,
+					block_hash = ?entry.block_hash(),
+					conflicts = self.write_conflicts,
+					"Conflicting block entry write within a single overlay",
+				);
+			}
+		}
+
 		self.block_entries.insert(entry.block_hash(), Some(entry));
 	}
 
@@ -209,13 +228,34 @@ impl<'a, B: 'a + Backend> OverlayedBackend<'a, B> {
 	}
 
 	pub fn write_candidate_entry(&mut self, entry: CandidateEntry) {
-		self.candidate_entries.insert(entry.candidate_receipt().hash(), Some(entry));
+		let candidate_hash = entry.candidate_receipt().hash();
+
+		#[cfg(debug_assertions)]
+		if let Some(Some(prev)) = self.candidate_entries.get(&candidate_hash) {
+			if candidate_entries_conflict(prev, &entry) {
+				self.write_conflicts += 1;
+				gum::warn!(
+					target: LOG_TARGET,
+					?candidate_hash,
+					conflicts = self.write_conflicts,
+					"Conflicting candidate entry write within a single overlay",
+				);
+			}
+		}
+
+		self.candidate_entries.insert(candidate_hash, Some(entry));
 	}
 
 	pub fn delete_candidate_entry(&mut self, hash: &CandidateHash) {
 		self.candidate_entries.insert(*hash, None);
 	}
 
+	/// The number of conflicting writes observed by this overlay so far.
+	#[cfg(all(test, debug_assertions))]
+	pub fn write_conflicts(&self) -> usize {
+		self.write_conflicts
+	}
+
 	/// Transform this backend into a set of write-ops to be written to the
 	/// inner backend.
 	pub fn into_write_ops(self) -> impl Iterator<Item = BackendWriteOp> {
@@ -247,3 +287,23 @@ impl<'a, B: 'a + Backend> OverlayedBackend<'a, B> {
 			.chain(candidate_entry_ops)
 	}
 }
+
+// Two writes of the same block entry conflict if they disagree on any of the fields
+// which are fixed at import time. The approval bitfield, children and signing state
+// are expected to change between writes.
+#[cfg(debug_assertions)]
+fn block_entries_conflict(prev: &BlockEntry, next: &BlockEntry) -> bool {
+	prev.parent_hash() != next.parent_hash() ||
+		prev.block_number() != next.block_number() ||
+		prev.session() != next.session() ||
+		prev.slot() != next.slot() ||
+		prev.relay_vrf_story() != next.relay_vrf_story() ||
+		prev.candidates() != next.candidates()
+}
+
+// Two writes of the same candidate entry conflict if they disagree on the session the
+// candidate was first seen in. Block assignments and approvals accumulate over time.
+#[cfg(debug_assertions)]
+fn candidate_entries_conflict(prev: &CandidateEntry, next: &CandidateEntry) -> bool {
+	prev.session != next.session
+}
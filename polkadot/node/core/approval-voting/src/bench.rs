@@ -0,0 +1,317 @@
+// Copyright (C) Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Harness for benchmarking the import of new heads, see `benches/handle-new-head-bench.rs`.
+//!
+//! Heads are imported into an in-memory database, with all Chain API and Runtime API requests
+//! answered from a synthetic chain. The blocks of that chain carry BABE digests signed by the
+//! single BABE authority, which is also the first validator of the session and whose keys are in
+//! the keystore of the importing node, so assignments are computed for each block.
+
+use crate::{
+	approval_db::common::{Config as DatabaseConfig, DbBackend},
+	backend::{Backend, OverlayedBackend},
+	import::handle_new_head,
+	Metrics, RealAssignmentCriteria, State,
+};
+use futures::{channel::mpsc, StreamExt};
+use polkadot_node_primitives::{
+	approval::time::SystemClock, BabeAllowedSlots, BabeEpoch, BabeEpochConfiguration,
+	DISPUTE_WINDOW,
+};
+use polkadot_node_subsystem::messages::{
+	AllMessages, ChainApiMessage, RuntimeApiMessage, RuntimeApiRequest,
+};
+use polkadot_node_subsystem_test_helpers::sender_receiver;
+use polkadot_node_subsystem_util::{
+	database::kvdb_impl::DbAdapter,
+	runtime::{Config as RuntimeInfoConfig, RuntimeInfo},
+};
+use polkadot_primitives::{
+	vstaging::{CandidateDescriptorV2, CandidateEvent, CandidateReceiptV2},
+	AssignmentId, CoreIndex, ExecutorParams, GroupIndex, Hash, HeadData, Header, Id as ParaId,
+	IndexedVec, NodeFeatures, SessionIndex, SessionInfo, ValidatorId, ValidatorIndex,
+	ASSIGNMENT_KEY_TYPE_ID, PARACHAIN_KEY_TYPE_ID,
+};
+use sc_keystore::LocalKeystore;
+use sp_application_crypto::ByteArray;
+use sp_consensus_babe::{
+	digests::{CompatibleDigestItem, PreDigest, SecondaryVRFPreDigest},
+	make_vrf_sign_data,
+};
+use sp_consensus_slots::Slot;
+use sp_core::crypto::VrfSecret;
+use sp_keyring::sr25519::Keyring as Sr25519Keyring;
+use sp_keystore::Keystore;
+use sp_runtime::{Digest, DigestItem};
+use std::sync::Arc;
+
+const SESSION: SessionIndex = 1;
+const N_VALIDATORS: u32 = 100;
+const GROUP_SIZE: u32 = 5;
+const RANDOMNESS: [u8; 32] = [0u8; 32];
+// The BABE authority signing all blocks, also the first validator of the session.
+const AUTHOR: Sr25519Keyring = Sr25519Keyring::Alice;
+
+/// A chain of blocks on top of a finalized genesis, with the same number of candidates included
+/// in each block.
+pub struct SyntheticChain {
+	genesis: Header,
+	// Oldest first.
+	blocks: Vec<(Hash, Header, Vec<CandidateEvent>)>,
+	session_info: SessionInfo,
+}
+
+impl SyntheticChain {
+	/// Builds a chain of `n_blocks` blocks, each including a candidate on each of the first
+	/// `n_candidates` cores.
+	pub fn new(n_blocks: u32, n_candidates: u32) -> Self {
+		let genesis = Header {
+			parent_hash: Hash::zero(),
+			number: 0,
+			state_root: Default::default(),
+			extrinsics_root: Default::default(),
+			digest: Default::default(),
+		};
+		let mut blocks = Vec::with_capacity(n_blocks as usize);
+		let mut parent_hash = genesis.hash();
+		for number in 1..=n_blocks {
+			let header = Header {
+				parent_hash,
+				number,
+				state_root: Default::default(),
+				extrinsics_root: Default::default(),
+				digest: babe_digest(Slot::from(number as u64)),
+			};
+			let hash = header.hash();
+			let candidates = (0..n_candidates)
+				.map(|core| candidate_included(parent_hash, number, core))
+				.collect();
+			blocks.push((hash, header, candidates));
+			parent_hash = hash;
+		}
+
+		SyntheticChain { genesis, blocks, session_info: session_info(n_candidates) }
+	}
+
+	/// The hash of the newest block.
+	pub fn head(&self) -> Hash {
+		self.blocks.last().map_or_else(|| self.genesis.hash(), |(hash, _, _)| *hash)
+	}
+
+	fn block(&self, hash: &Hash) -> Option<&(Hash, Header, Vec<CandidateEvent>)> {
+		self.blocks.iter().find(|(h, _, _)| h == hash)
+	}
+
+	// Answers the requests made on import until all senders are dropped.
+	async fn respond(&self, mut messages: mpsc::UnboundedReceiver<AllMessages>) {
+		while let Some(message) = messages.next().await {
+			match message {
+				AllMessages::ChainApi(ChainApiMessage::BlockHeader(hash, tx)) => {
+					let header = if hash == self.genesis.hash() {
+						Some(self.genesis.clone())
+					} else {
+						self.block(&hash).map(|(_, header, _)| header.clone())
+					};
+					let _ = tx.send(Ok(header));
+				},
+				AllMessages::ChainApi(ChainApiMessage::Ancestors {
+					hash,
+					k,
+					response_channel,
+				}) => {
+					let ancestors = self
+						.blocks
+						.iter()
+						.rev()
+						.skip_while(|(h, _, _)| *h != hash)
+						.skip(1)
+						.take(k)
+						.map(|(h, _, _)| *h)
+						.collect();
+					let _ = response_channel.send(Ok(ancestors));
+				},
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(hash, request)) =>
+					match request {
+						RuntimeApiRequest::CandidateEvents(tx) => {
+							let candidates = self
+								.block(&hash)
+								.map_or_else(Vec::new, |(_, _, candidates)| candidates.clone());
+							let _ = tx.send(Ok(candidates));
+						},
+						RuntimeApiRequest::SessionIndexForChild(tx) => {
+							let _ = tx.send(Ok(SESSION));
+						},
+						RuntimeApiRequest::CurrentBabeEpoch(tx) => {
+							let _ = tx.send(Ok(BabeEpoch {
+								epoch_index: SESSION as _,
+								start_slot: Slot::from(0),
+								duration: 200,
+								authorities: vec![(AUTHOR.public().into(), 1)],
+								randomness: RANDOMNESS,
+								config: BabeEpochConfiguration {
+									c: (1, 4),
+									allowed_slots: BabeAllowedSlots::PrimarySlots,
+								},
+							}));
+						},
+						RuntimeApiRequest::SessionInfo(_, tx) => {
+							let _ = tx.send(Ok(Some(self.session_info.clone())));
+						},
+						RuntimeApiRequest::SessionExecutorParams(_, tx) => {
+							let _ = tx.send(Ok(Some(ExecutorParams::default())));
+						},
+						RuntimeApiRequest::NodeFeatures(_, tx) => {
+							let _ = tx.send(Ok(NodeFeatures::EMPTY));
+						},
+						request => panic!("Unexpected runtime API request: {:?}", request),
+					},
+				// Notifications about imported blocks need no answer.
+				AllMessages::ChainSelection(_) | AllMessages::ApprovalDistribution(_) => {},
+				message => panic!("Unexpected message: {:?}", message),
+			}
+		}
+	}
+}
+
+/// Imports the whole `chain` as a single new head into a fresh in-memory database and returns
+/// the number of imported blocks.
+pub fn import_chain(chain: &SyntheticChain) -> usize {
+	let db = DbAdapter::new(kvdb_memorydb::create(1), &[]);
+	let mut db = DbBackend::new(Arc::new(db), DatabaseConfig { col_approval_data: 0 });
+	let mut state = state();
+	let mut session_info_provider = RuntimeInfo::new_with_config(RuntimeInfoConfig {
+		keystore: None,
+		session_cache_lru_size: DISPUTE_WINDOW.get(),
+	});
+	let (sender, messages) = sender_receiver();
+
+	let import = async {
+		// Both senders are dropped once the head is imported, which stops the responder.
+		let mut sender = sender;
+		let mut approval_voting_sender = sender.clone();
+		let mut overlay_db = OverlayedBackend::new(&db);
+		let imported = handle_new_head(
+			&mut sender,
+			&mut approval_voting_sender,
+			&mut state,
+			&mut overlay_db,
+			&mut session_info_provider,
+			chain.head(),
+			&Metrics::default(),
+			&Some(0),
+		)
+		.await
+		.expect("all requests are answered; qed");
+
+		(imported.len(), overlay_db.into_write_ops())
+	};
+	let ((imported, write_ops), ()) =
+		futures::executor::block_on(futures::future::join(import, chain.respond(messages)));
+
+	db.write(write_ops).expect("in-memory database writes don't fail; qed");
+	imported
+}
+
+fn state() -> State {
+	let keystore = LocalKeystore::in_memory();
+	for key_type in [PARACHAIN_KEY_TYPE_ID, ASSIGNMENT_KEY_TYPE_ID] {
+		keystore
+			.sr25519_generate_new(key_type, Some(&AUTHOR.to_seed()))
+			.expect("in-memory keystore doesn't fail; qed");
+	}
+
+	State::with_defaults(
+		Arc::new(keystore),
+		Arc::new(SystemClock),
+		Box::new(RealAssignmentCriteria),
+	)
+}
+
+// A BABE pre-digest for `slot`, with a VRF signed by the author.
+fn babe_digest(slot: Slot) -> Digest {
+	let vrf_signature =
+		AUTHOR.pair().vrf_sign(&make_vrf_sign_data(&RANDOMNESS, slot, SESSION as u64));
+	let mut digest = Digest::default();
+	digest.push(DigestItem::babe_pre_digest(PreDigest::SecondaryVRF(SecondaryVRFPreDigest {
+		authority_index: 0,
+		slot,
+		vrf_signature,
+	})));
+
+	digest
+}
+
+// The candidate included on `core` by block `number`, built on top of `relay_parent`.
+fn candidate_included(relay_parent: Hash, number: u32, core: u32) -> CandidateEvent {
+	let descriptor = CandidateDescriptorV2::new(
+		ParaId::from(core),
+		relay_parent,
+		CoreIndex(core),
+		SESSION,
+		Hash::zero(),
+		Hash::zero(),
+		Hash::zero(),
+		Hash::from_low_u64_be(number as u64),
+		Hash::zero().into(),
+	);
+
+	CandidateEvent::CandidateIncluded(
+		CandidateReceiptV2 { descriptor, commitments_hash: Hash::zero() },
+		HeadData(Vec::new()),
+		CoreIndex(core),
+		GroupIndex(core % (N_VALIDATORS / GROUP_SIZE)),
+	)
+}
+
+fn session_info(n_cores: u32) -> SessionInfo {
+	let key = |index: u32| {
+		let mut raw = [0u8; 32];
+		raw[..4].copy_from_slice(&index.to_le_bytes());
+		raw
+	};
+	let validators: Vec<ValidatorId> = std::iter::once(AUTHOR.public().into())
+		.chain(
+			(1..N_VALIDATORS)
+				.map(|index| ValidatorId::from_slice(&key(index)).expect("keys are 32 bytes; qed")),
+		)
+		.collect();
+	let assignment_keys: Vec<AssignmentId> = std::iter::once(AUTHOR.public().into())
+		.chain(
+			(1..N_VALIDATORS)
+				.map(|index| AssignmentId::from_slice(&key(index)).expect("keys are 32 bytes; qed")),
+		)
+		.collect();
+	let validator_groups = (0..N_VALIDATORS / GROUP_SIZE)
+		.map(|group| (group * GROUP_SIZE..(group + 1) * GROUP_SIZE).map(ValidatorIndex).collect())
+		.collect::<Vec<_>>();
+
+	SessionInfo {
+		validators: validators.into(),
+		discovery_keys: Vec::new(),
+		assignment_keys,
+		validator_groups: IndexedVec::from(validator_groups),
+		n_cores: n_cores.max(1),
+		zeroth_delay_tranche_width: 0,
+		relay_vrf_modulo_samples: 6,
+		n_delay_tranches: 89,
+		no_show_slots: 2,
+		needed_approvals: 30,
+		active_validator_indices: Vec::new(),
+		dispute_period: 6,
+		random_seed: [0u8; 32],
+	}
+}
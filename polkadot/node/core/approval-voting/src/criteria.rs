@@ -212,17 +212,40 @@ impl AssignmentCriteria for RealAssignmentCriteria {
 		assignment: &AssignmentCertV2,
 		backing_groups: Vec<GroupIndex>,
 	) -> Result<DelayTranche, InvalidAssignment> {
-		check_assignment_cert(
-			claimed_core_bitfield,
-			validator_index,
+		verify_assignment(
 			config,
 			relay_vrf_story,
 			assignment,
+			validator_index,
+			claimed_core_bitfield,
 			backing_groups,
 		)
 	}
 }
 
+/// Verify an assignment certificate of the given validator, claiming the given cores, and return
+/// the tranche it is valid for.
+///
+/// This is the check the approval voting subsystem applies to incoming assignments, so it can be
+/// used to re-check archived assignments without running the subsystem.
+pub fn verify_assignment(
+	config: &Config,
+	relay_vrf_story: RelayVRFStory,
+	cert: &AssignmentCertV2,
+	validator_index: ValidatorIndex,
+	claimed_cores: CoreBitfield,
+	backing_groups: Vec<GroupIndex>,
+) -> Result<DelayTranche, InvalidAssignment> {
+	check_assignment_cert(
+		claimed_cores,
+		validator_index,
+		config,
+		relay_vrf_story,
+		cert,
+		backing_groups,
+	)
+}
+
 /// Compute the assignments for a given block. Returns a map containing all assignments to cores in
 /// the block. If more than one assignment targets the given core, only the earliest assignment is
 /// kept.
@@ -979,6 +1002,63 @@ mod tests {
 		check_mutated_assignments(200, 100, 25, |_| Some(true));
 	}
 
+	#[test]
+	fn computed_assignments_verify_with_their_tranche() {
+		let keystore = make_keystore(&[Sr25519Keyring::Alice]);
+		let n_cores = 10;
+		let config = Config {
+			assignment_keys: assignment_keys_plus_random(&[Sr25519Keyring::Alice], 19),
+			validator_groups: basic_groups(20, n_cores),
+			n_cores: n_cores as u32,
+			zeroth_delay_tranche_width: 10,
+			relay_vrf_modulo_samples: 3,
+			n_delay_tranches: 40,
+		};
+		let relay_vrf_story = RelayVRFStory([42u8; 32]);
+		// Alice is in group 0, so leave that one out.
+		let leaving_cores = (1..n_cores)
+			.map(|i| {
+				(
+					CandidateHash(Hash::repeat_byte(i as u8)),
+					CoreIndex(i as u32),
+					GroupIndex(i as u32),
+				)
+			})
+			.collect::<Vec<_>>();
+
+		for enable_v2 in [false, true] {
+			let assignments = compute_assignments(
+				&keystore,
+				relay_vrf_story.clone(),
+				&config,
+				leaving_cores.clone(),
+				enable_v2,
+			);
+			assert!(!assignments.is_empty());
+
+			for (core, assignment) in assignments {
+				let claimed_cores = match assignment.cert().kind.clone() {
+					AssignmentCertKindV2::RelayVRFModuloCompact { core_bitfield } => core_bitfield,
+					_ => core.into(),
+				};
+				let backing_groups =
+					claimed_cores.iter_ones().map(|core| GroupIndex(core as u32)).collect();
+
+				assert_eq!(
+					verify_assignment(
+						&config,
+						relay_vrf_story.clone(),
+						assignment.cert(),
+						assignment.validator_index(),
+						claimed_cores,
+						backing_groups,
+					),
+					Ok(assignment.tranche()),
+				);
+			}
+		}
+	}
+
 	#[test]
 	fn check_rejects_claimed_core_out_of_bounds() {
 		check_mutated_assignments(200, 100, 25, |m| {
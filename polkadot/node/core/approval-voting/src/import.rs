@@ -40,22 +40,31 @@ use polkadot_node_subsystem::{
 		ApprovalDistributionMessage, ChainApiMessage, ChainSelectionMessage, RuntimeApiMessage,
 		RuntimeApiRequest,
 	},
-	overseer, RuntimeApiError, SubsystemError, SubsystemResult,
+	overseer, ChainApiError, RuntimeApiError, SubsystemError, SubsystemResult,
+};
+use polkadot_node_subsystem_util::{
+	determine_new_blocks_with_known_headers, runtime::RuntimeInfo, TimeoutExt,
 };
-use polkadot_node_subsystem_util::{determine_new_blocks, runtime::RuntimeInfo};
 use polkadot_overseer::SubsystemSender;
 use polkadot_primitives::{
 	node_features,
 	vstaging::{CandidateEvent, CandidateReceiptV2 as CandidateReceipt},
-	BlockNumber, CandidateHash, ConsensusLog, CoreIndex, GroupIndex, Hash, Header, SessionIndex,
+	BlockNumber, CandidateHash, ConsensusLog, CoreIndex, GroupIndex, Hash, Header, NodeFeatures,
+	SessionIndex, SessionInfo,
 };
 use sc_keystore::LocalKeystore;
+use sc_telemetry::{telemetry, SUBSTRATE_INFO};
 use sp_consensus_slots::Slot;
 
-use bitvec::order::Lsb0 as BitOrderLsb0;
+use bitvec::{order::Lsb0 as BitOrderLsb0, vec::BitVec};
 use futures::{channel::oneshot, prelude::*};
 
-use std::collections::HashMap;
+use std::{
+	collections::{BTreeMap, HashMap, HashSet},
+	sync::Arc,
+	time::Duration,
+};
+use tracing::Instrument;
 
 use super::approval_db::v3;
 use crate::{
@@ -63,27 +72,225 @@ use crate::{
 	criteria::{AssignmentCriteria, OurAssignment},
 	get_extended_session_info, get_session_info,
 	persisted_entries::CandidateEntry,
+	Metrics,
 };
 
-use polkadot_node_primitives::approval::time::{slot_number_to_tick, Tick};
+use polkadot_node_primitives::approval::time::Tick;
 
 use super::{State, LOG_TARGET};
 
-#[derive(Debug)]
+/// The maximum number of heads kept around for retrying once their session info is available.
+const MAX_DEFERRED_HEADS: usize = 16;
+
+// Origins of the errors raised while importing blocks, telling which phase has failed.
+const DETERMINE_NEW_BLOCKS_ORIGIN: &str = "approval-voting/determine-new-blocks";
+const ADD_BLOCK_ENTRY_ORIGIN: &str = "approval-voting/add-block-entry";
+const FORCE_APPROVE_ORIGIN: &str = "approval-voting/force-approve";
+
+/// How many ancestors of the including block a candidate's relay parent may be found in. This
+/// covers the async backing ancestry plus the time a candidate may spend pending availability.
+const RELAY_PARENT_WINDOW: usize = 16;
+
+/// How included candidates whose relay parent is not the including block or one of its recent
+/// ancestors are treated on import.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RelayParentCheck {
+	/// Relay parents are not checked.
+	#[default]
+	Disabled,
+	/// Such candidates are ignored, with a warning.
+	Drop,
+	/// The whole block fails to import.
+	Reject,
+}
+
+/// What to do when the oldest new block of an imported chain has an unfinalized parent which is
+/// unknown to the approval DB, e.g. because the chain reaches below the look-back window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnMissingAncestor {
+	/// Import the chain anyway, leaving the oldest block without a parent entry.
+	#[default]
+	Proceed,
+	/// Don't import the chain.
+	Skip,
+	/// Also import the missing ancestors, down to the last finalized block.
+	Backfill,
+}
+
+/// How included candidates whose backing group doesn't exist in the session, e.g. because of a
+/// malformed receipt, are treated when deciding whether to insta-approve them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownBackingGroup {
+	/// The backing group is treated as empty.
+	#[default]
+	TreatAsEmpty,
+	/// Such candidates are never insta-approved.
+	IgnoreCandidate,
+}
+
+/// Which blocks may be force-approved by a `ForceApprove` log in the digest of an imported block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ForceApproveTarget {
+	/// Only ancestors of the block, logs targeting the block itself are ignored.
+	#[default]
+	Exclusive,
+	/// The block itself as well, along with all its ancestors.
+	///
+	/// The block is only force-approved once its entry has been written to the approval DB, so
+	/// that the approval isn't overwritten by the fresh entry.
+	Inclusive,
+}
+
+impl ForceApproveTarget {
+	fn accepts(self, target: BlockNumber, block_number: BlockNumber) -> bool {
+		match self {
+			ForceApproveTarget::Exclusive => target < block_number,
+			ForceApproveTarget::Inclusive => target <= block_number,
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
 struct ImportedBlockInfo {
 	included_candidates: Vec<(CandidateHash, CandidateReceipt, CoreIndex, GroupIndex)>,
 	session_index: SessionIndex,
 	assignments: HashMap<CoreIndex, OurAssignment>,
 	n_validators: usize,
+	n_cores: u32,
 	relay_vrf_story: RelayVRFStory,
 	slot: Slot,
 	force_approve: Option<BlockNumber>,
+	// The node features of the session, as active during the import.
+	node_features: NodeFeatures,
+}
+
+impl ImportedBlockInfo {
+	/// The relay VRF story of the block as raw bytes, as stored in its block entry.
+	pub(crate) fn vrf_story_bytes(&self) -> [u8; 32] {
+		self.relay_vrf_story.0
+	}
+
+	/// Whether `feature` was enabled for the session of the block during its import.
+	pub(crate) fn node_feature_enabled(&self, feature: node_features::FeatureIndex) -> bool {
+		node_feature_enabled(&self.node_features, feature)
+	}
+
+	/// The cores with an included candidate, but none of our assignments, in inclusion order.
+	pub(crate) fn unassigned_cores(&self) -> Vec<CoreIndex> {
+		self.included_candidates
+			.iter()
+			.map(|(_, _, core, _)| *core)
+			.filter(|core| !self.assignments.contains_key(core))
+			.collect()
+	}
+}
+
+/// A difference between two [`ImportedBlockInfo`]s, see [`ImportedBlockInfo::diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ImportedBlockInfoDiff {
+	/// The session indices differ.
+	Session(SessionIndex, SessionIndex),
+	/// The candidates included by only one of the blocks, in ascending order.
+	Candidates { only_self: Vec<CandidateHash>, only_other: Vec<CandidateHash> },
+	/// The cores with only one of the blocks' assignments, in ascending order.
+	AssignedCores { only_self: Vec<CoreIndex>, only_other: Vec<CoreIndex> },
+	/// The cores with an assignment of both blocks, but in different tranches, in ascending
+	/// order.
+	AssignmentTranches(Vec<CoreIndex>),
+	/// The numbers of validators differ.
+	Validators(usize, usize),
+	/// The numbers of cores differ.
+	Cores(u32, u32),
+	/// The relay VRF stories differ.
+	RelayVrfStory([u8; 32], [u8; 32]),
+	/// The slots differ.
+	Slot(Slot, Slot),
+	/// The blocks are force-approved differently.
+	ForceApprove(Option<BlockNumber>, Option<BlockNumber>),
+	/// The node features active during the imports differ.
+	NodeFeatures(NodeFeatures, NodeFeatures),
+}
+
+impl ImportedBlockInfo {
+	/// The differences of `other` from this block info, where candidates and assignments are
+	/// compared as sets, regardless of the order they are in.
+	fn diff(&self, other: &Self) -> Vec<ImportedBlockInfoDiff> {
+		use std::collections::BTreeSet;
+
+		fn set_diff<T: Ord + Copy>(a: BTreeSet<T>, b: BTreeSet<T>) -> Option<(Vec<T>, Vec<T>)> {
+			let only_a = a.difference(&b).copied().collect::<Vec<_>>();
+			let only_b = b.difference(&a).copied().collect::<Vec<_>>();
+			(!only_a.is_empty() || !only_b.is_empty()).then_some((only_a, only_b))
+		}
+
+		let mut diffs = Vec::new();
+		if self.session_index != other.session_index {
+			diffs.push(ImportedBlockInfoDiff::Session(self.session_index, other.session_index));
+		}
+		let candidates = |info: &Self| info.included_candidates.iter().map(|c| c.0).collect();
+		if let Some((only_self, only_other)) = set_diff(candidates(self), candidates(other)) {
+			diffs.push(ImportedBlockInfoDiff::Candidates { only_self, only_other });
+		}
+		let cores = |info: &Self| info.assignments.keys().copied().collect();
+		if let Some((only_self, only_other)) = set_diff(cores(self), cores(other)) {
+			diffs.push(ImportedBlockInfoDiff::AssignedCores { only_self, only_other });
+		}
+		let mut moved_cores = self
+			.assignments
+			.iter()
+			.filter(|(core, assignment)| {
+				other
+					.assignments
+					.get(core)
+					.map_or(false, |a| a.tranche() != assignment.tranche())
+			})
+			.map(|(core, _)| *core)
+			.collect::<Vec<_>>();
+		if !moved_cores.is_empty() {
+			moved_cores.sort();
+			diffs.push(ImportedBlockInfoDiff::AssignmentTranches(moved_cores));
+		}
+		if self.n_validators != other.n_validators {
+			diffs.push(ImportedBlockInfoDiff::Validators(self.n_validators, other.n_validators));
+		}
+		if self.n_cores != other.n_cores {
+			diffs.push(ImportedBlockInfoDiff::Cores(self.n_cores, other.n_cores));
+		}
+		if self.relay_vrf_story.0 != other.relay_vrf_story.0 {
+			diffs.push(ImportedBlockInfoDiff::RelayVrfStory(
+				self.relay_vrf_story.0,
+				other.relay_vrf_story.0,
+			));
+		}
+		if self.slot != other.slot {
+			diffs.push(ImportedBlockInfoDiff::Slot(self.slot, other.slot));
+		}
+		if self.force_approve != other.force_approve {
+			diffs
+				.push(ImportedBlockInfoDiff::ForceApprove(self.force_approve, other.force_approve));
+		}
+		if self.node_features != other.node_features {
+			diffs.push(ImportedBlockInfoDiff::NodeFeatures(
+				self.node_features.clone(),
+				other.node_features.clone(),
+			));
+		}
+		diffs
+	}
 }
 
 struct ImportedBlockInfoEnv<'a> {
 	runtime_info: &'a mut RuntimeInfo,
 	assignment_criteria: &'a (dyn AssignmentCriteria + Send + Sync),
 	keystore: &'a LocalKeystore,
+	relay_parent_check: RelayParentCheck,
+	tolerate_candidate_events_errors: bool,
+	core_filter: Option<&'a HashSet<CoreIndex>>,
+	shadow_criteria: Option<&'a (dyn AssignmentCriteria + Send + Sync)>,
+	assignment_observer: Option<&'a (dyn Fn(&CoreIndex, &OurAssignment) + Send + Sync)>,
+	dev_mode: bool,
+	force_approve_target: ForceApproveTarget,
+	metrics: &'a Metrics,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -96,6 +303,9 @@ enum ImportedBlockInfoError {
 	#[error("future cancelled while requesting {0}")]
 	FutureCancelled(&'static str, futures::channel::oneshot::Canceled),
 
+	#[error(transparent)]
+	ChainApiError(ChainApiError),
+
 	#[error(transparent)]
 	ApprovalError(approval_types::v1::ApprovalError),
 
@@ -107,12 +317,140 @@ enum ImportedBlockInfoError {
 
 	#[error("VRF info unavailable")]
 	VrfInfoUnavailable,
+
+	#[error("candidate {0:?} has relay parent {1:?} outside of the import window")]
+	UnexpectedRelayParent(CandidateHash, Hash),
+
+	#[error("no candidate info matches included candidate {0:?}")]
+	MissingCandidateInfo(CandidateHash),
+
+	#[error("more than one candidate included on core {0:?}")]
+	DuplicateCore(CoreIndex),
+
+	#[error("session {0} needs approvals, but has no validators")]
+	EmptyValidatorSet(SessionIndex),
+}
+
+/// A candidate event reported by the runtime, as seen by the import logic.
+///
+/// This is implemented once per candidate receipt version, so that supporting a new
+/// receipt version doesn't require touching the event filtering itself.
+trait ImportCandidateEvent {
+	/// Returns the hash, receipt, core and backing group of the candidate if this event
+	/// reports an inclusion, or `None` otherwise.
+	fn into_included(self) -> Option<(CandidateHash, CandidateReceipt, CoreIndex, GroupIndex)>;
+}
+
+impl ImportCandidateEvent for CandidateEvent {
+	fn into_included(self) -> Option<(CandidateHash, CandidateReceipt, CoreIndex, GroupIndex)> {
+		match self {
+			CandidateEvent::CandidateIncluded(receipt, _, core, group) =>
+				Some((receipt.hash(), receipt, core, group)),
+			_ => None,
+		}
+	}
+}
+
+/// Extracts all included candidates out of the candidate events of a block, in order.
+///
+/// Fails if more than one candidate is included on the same core, as assignments are tracked per
+/// core.
+fn included_candidates<E: ImportCandidateEvent>(
+	events: impl IntoIterator<Item = E>,
+) -> Result<Vec<(CandidateHash, CandidateReceipt, CoreIndex, GroupIndex)>, ImportedBlockInfoError> {
+	let mut cores = HashSet::new();
+	events
+		.into_iter()
+		.filter_map(ImportCandidateEvent::into_included)
+		.map(|included| {
+			if !cores.insert(included.2) {
+				gum::warn!(
+					target: LOG_TARGET,
+					candidate_hash = ?included.0,
+					core = ?included.2,
+					"Candidate included on a core which already has an included candidate",
+				);
+				return Err(ImportedBlockInfoError::DuplicateCore(included.2))
+			}
+
+			Ok(included)
+		})
+		.collect()
+}
+
+/// Checks that the relay parents of the candidates included in `block_hash` are that block or one
+/// of its last [`RELAY_PARENT_WINDOW`] ancestors, handling violations according to `check`.
+async fn check_relay_parents<Sender: SubsystemSender<ChainApiMessage>>(
+	sender: &mut Sender,
+	check: RelayParentCheck,
+	block_hash: Hash,
+	included_candidates: Vec<(CandidateHash, CandidateReceipt, CoreIndex, GroupIndex)>,
+) -> Result<Vec<(CandidateHash, CandidateReceipt, CoreIndex, GroupIndex)>, ImportedBlockInfoError> {
+	if check == RelayParentCheck::Disabled || included_candidates.is_empty() {
+		return Ok(included_candidates)
+	}
+
+	let ancestors = {
+		let (tx, rx) = oneshot::channel();
+		sender
+			.send_message(ChainApiMessage::Ancestors {
+				hash: block_hash,
+				k: RELAY_PARENT_WINDOW,
+				response_channel: tx,
+			})
+			.await;
+
+		match rx.await {
+			Ok(Ok(ancestors)) => ancestors,
+			Ok(Err(error)) => return Err(ImportedBlockInfoError::ChainApiError(error)),
+			Err(error) => return Err(ImportedBlockInfoError::FutureCancelled("Ancestors", error)),
+		}
+	};
+
+	let mut checked = Vec::with_capacity(included_candidates.len());
+	for candidate in included_candidates {
+		let relay_parent = candidate.1.descriptor.relay_parent();
+		if relay_parent == block_hash || ancestors.contains(&relay_parent) {
+			checked.push(candidate);
+			continue
+		}
+
+		if check == RelayParentCheck::Reject {
+			return Err(ImportedBlockInfoError::UnexpectedRelayParent(candidate.0, relay_parent))
+		}
+
+		gum::warn!(
+			target: LOG_TARGET,
+			?block_hash,
+			candidate_hash = ?candidate.0,
+			?relay_parent,
+			"Ignoring included candidate with a relay parent outside of the import window",
+		);
+	}
+
+	Ok(checked)
+}
+
+/// Whether `header` claims a zero parent hash while not being the genesis block, which is only
+/// ever the case for corrupted input.
+fn has_unexpected_zero_parent(header: &Header) -> bool {
+	header.number > 0 && header.parent_hash == Hash::default()
+}
+
+/// Whether `feature` is enabled in `node_features`. Features unknown to the runtime are disabled.
+fn node_feature_enabled(
+	node_features: &NodeFeatures,
+	feature: node_features::FeatureIndex,
+) -> bool {
+	node_features.get(feature as usize).as_deref().copied().unwrap_or(false)
 }
 
 /// Computes information about the imported block. Returns an error if the info couldn't be
 /// extracted.
 #[overseer::contextbounds(ApprovalVoting, prefix = self::overseer)]
-async fn imported_block_info<Sender: SubsystemSender<RuntimeApiMessage>>(
+async fn imported_block_info<
+	Sender: SubsystemSender<RuntimeApiMessage> + SubsystemSender<ChainApiMessage>,
+>(
 	sender: &mut Sender,
 	env: ImportedBlockInfoEnv<'_>,
 	block_hash: Hash,
@@ -122,6 +460,17 @@ async fn imported_block_info<Sender: SubsystemSender<RuntimeApiMessage>>(
 	// Ignore any runtime API errors - that means these blocks are old and finalized.
 	// Only unfinalized blocks factor into the approval voting process.
 
+	// Only the genesis block may have a zero parent hash. Any other block with one is logged, but
+	// still imported, as the check doesn't reject anything.
+	if has_unexpected_zero_parent(block_header) {
+		gum::warn!(
+			target: LOG_TARGET,
+			?block_hash,
+			number = block_header.number,
+			"Importing non-genesis block with a zero parent hash, the input may be corrupted",
+		);
+	}
+
 	// fetch candidates
 	let included_candidates: Vec<_> = {
 		let (c_tx, c_rx) = oneshot::channel();
@@ -134,20 +483,29 @@ async fn imported_block_info<Sender: SubsystemSender<RuntimeApiMessage>>(
 
 		let events: Vec<CandidateEvent> = match c_rx.await {
 			Ok(Ok(events)) => events,
-			Ok(Err(error)) => return Err(ImportedBlockInfoError::RuntimeError(error)),
+			Ok(Err(error)) if env.tolerate_candidate_events_errors => {
+				env.metrics.on_import_runtime_api_error("CandidateEvents");
+				gum::debug!(
+					target: LOG_TARGET,
+					?block_hash,
+					?error,
+					"Failed to fetch candidate events, importing block without candidates",
+				);
+				Vec::new()
+			},
+			Ok(Err(error)) => {
+				env.metrics.on_import_runtime_api_error("CandidateEvents");
+				return Err(ImportedBlockInfoError::RuntimeError(error))
+			},
 			Err(error) =>
 				return Err(ImportedBlockInfoError::FutureCancelled("CandidateEvents", error)),
 		};
 
-		events
-			.into_iter()
-			.filter_map(|e| match e {
-				CandidateEvent::CandidateIncluded(receipt, _, core, group) =>
-					Some((receipt.hash(), receipt, core, group)),
-				_ => None,
-			})
-			.collect()
+		included_candidates(events)?
 	};
+	let included_candidates =
+		check_relay_parents(sender, env.relay_parent_check, block_hash, included_candidates)
+			.await?;
 
 	// fetch session. ignore blocks that are too old, but unless sessions are really
 	// short, that shouldn't happen.
@@ -162,7 +520,10 @@ async fn imported_block_info<Sender: SubsystemSender<RuntimeApiMessage>>(
 
 		let session_index = match s_rx.await {
 			Ok(Ok(s)) => s,
-			Ok(Err(error)) => return Err(ImportedBlockInfoError::RuntimeError(error)),
+			Ok(Err(error)) => {
+				env.metrics.on_import_runtime_api_error("SessionIndexForChild");
+				return Err(ImportedBlockInfoError::RuntimeError(error))
+			},
 			Err(error) =>
 				return Err(ImportedBlockInfoError::FutureCancelled("SessionIndexForChild", error)),
 		};
@@ -183,6 +544,8 @@ async fn imported_block_info<Sender: SubsystemSender<RuntimeApiMessage>>(
 		session_index
 	};
 
+	tracing::Span::current().record("session", session_index);
+
 	let babe_epoch = {
 		let (s_tx, s_rx) = oneshot::channel();
 
@@ -213,56 +576,57 @@ async fn imported_block_info<Sender: SubsystemSender<RuntimeApiMessage>>(
 
 		match s_rx.await {
 			Ok(Ok(s)) => s,
-			Ok(Err(error)) => return Err(ImportedBlockInfoError::RuntimeError(error)),
+			Ok(Err(error)) => {
+				env.metrics.on_import_runtime_api_error("CurrentBabeEpoch");
+				return Err(ImportedBlockInfoError::RuntimeError(error))
+			},
 			Err(error) =>
 				return Err(ImportedBlockInfoError::FutureCancelled("CurrentBabeEpoch", error)),
 		}
 	};
 
-	let extended_session_info =
-		get_extended_session_info(env.runtime_info, sender, block_hash, session_index).await;
-	let enable_v2_assignments = extended_session_info.map_or(false, |extended_session_info| {
-		*extended_session_info
-			.node_features
-			.get(node_features::FeatureIndex::EnableAssignmentsV2 as usize)
-			.as_deref()
-			.unwrap_or(&false)
-	});
+	let node_features =
+		get_extended_session_info(env.runtime_info, sender, block_hash, session_index)
+			.await
+			.map_or(NodeFeatures::EMPTY, |extended_session_info| {
+				extended_session_info.node_features.clone()
+			});
+	let enable_v2_assignments =
+		node_feature_enabled(&node_features, node_features::FeatureIndex::EnableAssignmentsV2);
+	let enable_sassafras_vrf =
+		node_feature_enabled(&node_features, node_features::FeatureIndex::SassafrasRelayVrf);
 
 	let session_info = get_session_info(env.runtime_info, sender, block_hash, session_index)
 		.await
 		.ok_or(ImportedBlockInfoError::SessionInfoUnavailable)?;
 
+	// Without validators, there would be too few of them to gather the needed approvals, so all
+	// candidates would be insta-approved.
+	if session_info.needed_approvals > 0 && session_info.validators.is_empty() {
+		return Err(ImportedBlockInfoError::EmptyValidatorSet(session_index))
+	}
+
 	gum::debug!(target: LOG_TARGET, ?enable_v2_assignments, "V2 assignments");
-	let (assignments, slot, relay_vrf_story) = {
-		let unsafe_vrf = approval_types::v1::babe_unsafe_vrf_info(&block_header);
-
-		match unsafe_vrf {
-			Some(unsafe_vrf) => {
-				let slot = unsafe_vrf.slot();
-
-				match unsafe_vrf.compute_randomness(
-					&babe_epoch.authorities,
-					&babe_epoch.randomness,
-					babe_epoch.epoch_index,
-				) {
-					Ok(relay_vrf) => {
-						let assignments = env.assignment_criteria.compute_assignments(
-							&env.keystore,
-							relay_vrf.clone(),
-							&crate::criteria::Config::from(session_info),
-							included_candidates
-								.iter()
-								.map(|(c_hash, _, core, group)| (*c_hash, *core, *group))
-								.collect(),
-							enable_v2_assignments,
-						);
 
-						(assignments, slot, relay_vrf)
-					},
-					Err(error) => return Err(ImportedBlockInfoError::ApprovalError(error)),
-				}
-			},
+	// Nothing in here awaits, so entering the span directly is fine.
+	let vrf_span = tracing::debug_span!(target: LOG_TARGET, "compute_assignments").entered();
+	let (slot, relay_vrf_story) = match approval_types::v1::babe_unsafe_vrf_info(&block_header) {
+		Some(unsafe_vrf) => {
+			let slot = unsafe_vrf.slot();
+
+			match unsafe_vrf.compute_randomness(
+				&babe_epoch.authorities,
+				&babe_epoch.randomness,
+				babe_epoch.epoch_index,
+			) {
+				Ok(relay_vrf) => (slot, relay_vrf),
+				Err(error) => return Err(ImportedBlockInfoError::ApprovalError(error)),
+			}
+		},
+		None => match sassafras_vrf_info(&block_header, enable_sassafras_vrf)
+			.or_else(|| dev_vrf_info(block_hash, env.dev_mode))
+		{
+			Some(vrf_info) => vrf_info,
 			None => {
 				gum::debug!(
 					target: LOG_TARGET,
@@ -272,14 +636,58 @@ async fn imported_block_info<Sender: SubsystemSender<RuntimeApiMessage>>(
 
 				return Err(ImportedBlockInfoError::VrfInfoUnavailable)
 			},
-		}
+		},
 	};
 
-	gum::trace!(target: LOG_TARGET, n_assignments = assignments.len(), "Produced assignments");
+	let criteria_config = crate::criteria::Config::from(session_info);
+	let leaving_cores: Vec<_> = included_candidates
+		.iter()
+		.filter(|(_, _, core, _)| env.core_filter.map_or(true, |cores| cores.contains(core)))
+		.map(|(c_hash, _, core, group)| (*c_hash, *core, *group))
+		.collect();
+	// The shadow criteria are only evaluated for comparison, their assignments are never used.
+	let shadow_assignments = env.shadow_criteria.map(|shadow_criteria| {
+		shadow_criteria.compute_assignments(
+			&env.keystore,
+			relay_vrf_story.clone(),
+			&criteria_config,
+			leaving_cores.clone(),
+			enable_v2_assignments,
+		)
+	});
+	let assignments = env.assignment_criteria.compute_assignments(
+		&env.keystore,
+		relay_vrf_story.clone(),
+		&criteria_config,
+		leaving_cores,
+		enable_v2_assignments,
+	);
+	if let Some(observer) = env.assignment_observer {
+		for (core, assignment) in &assignments {
+			observer(core, assignment);
+		}
+	}
+	env.metrics.on_assignments_produced(assignments.len(), enable_v2_assignments);
+	env.metrics
+		.on_our_assignment_tranches(assignments.values().map(|a| a.tranche()));
+
+	let cores_per_tranche = assignments.values().fold(BTreeMap::new(), |mut acc, a| {
+		*acc.entry(a.tranche()).or_insert(0usize) += 1;
+		acc
+	});
+	gum::trace!(
+		target: LOG_TARGET,
+		n_assignments = assignments.len(),
+		?cores_per_tranche,
+		"Produced assignments",
+	);
+	drop(vrf_span);
 
 	let force_approve =
 		block_header.digest.convert_first(|l| match ConsensusLog::from_digest_item(l) {
-			Ok(Some(ConsensusLog::ForceApprove(num))) if num < block_header.number => {
+			Ok(Some(ConsensusLog::ForceApprove(num)))
+				if env.force_approve_target.accepts(num, block_header.number) =>
+			{
 				gum::trace!(
 					target: LOG_TARGET,
 					?block_hash,
@@ -304,15 +712,81 @@ async fn imported_block_info<Sender: SubsystemSender<RuntimeApiMessage>>(
 			},
 		});
 
-	Ok(ImportedBlockInfo {
+	let info = ImportedBlockInfo {
 		included_candidates,
 		session_index,
 		assignments,
 		n_validators: session_info.validators.len(),
+		n_cores: session_info.n_cores,
 		relay_vrf_story,
 		slot,
 		force_approve,
-	})
+		node_features,
+	};
+	if let Some(shadow_assignments) = shadow_assignments {
+		let shadow_info = ImportedBlockInfo { assignments: shadow_assignments, ..info.clone() };
+		let diffs = info.diff(&shadow_info);
+		if !diffs.is_empty() {
+			gum::debug!(
+				target: LOG_TARGET,
+				?block_hash,
+				?diffs,
+				"Shadow assignment criteria diverged from the primary ones",
+			);
+			env.metrics.on_shadow_assignments_diverged();
+		}
+	}
+	gum::trace!(
+		target: LOG_TARGET,
+		?block_hash,
+		unassigned_cores = ?info.unassigned_cores(),
+		"Cores with included candidates we are not assigned to",
+	);
+	gum::trace!(
+		target: LOG_TARGET,
+		?block_hash,
+		session = info.session_index,
+		node_features = ?info.node_features,
+		v2_assignments =
+			info.node_feature_enabled(node_features::FeatureIndex::EnableAssignmentsV2),
+		"Node features active during import",
+	);
+
+	Ok(info)
+}
+
+/// Extracts the slot and relay VRF story from a Sassafras slot claim, if the runtime enabled it.
+#[cfg(feature = "sassafras-experimental")]
+fn sassafras_vrf_info(header: &Header, enabled: bool) -> Option<(Slot, RelayVRFStory)> {
+	if !enabled {
+		return None
+	}
+
+	approval_types::v1::sassafras_unsafe_vrf_info(header)
+		.map(|unsafe_vrf| (unsafe_vrf.slot(), unsafe_vrf.compute_randomness()))
+}
+
+#[cfg(not(feature = "sassafras-experimental"))]
+fn sassafras_vrf_info(_header: &Header, _enabled: bool) -> Option<(Slot, RelayVRFStory)> {
+	None
+}
+
+/// Synthesizes the slot and relay VRF story of a block without any VRF, e.g. on manual-seal dev
+/// chains, if dev mode is enabled. The story is derived from the block hash and the slot is the
+/// default one.
+#[cfg(feature = "dev-mode")]
+fn dev_vrf_info(block_hash: Hash, enabled: bool) -> Option<(Slot, RelayVRFStory)> {
+	if !enabled {
+		return None
+	}
+
+	gum::debug!(target: LOG_TARGET, ?block_hash, "Synthesizing VRF info in dev mode");
+	Some((Slot::default(), RelayVRFStory(block_hash.to_fixed_bytes())))
+}
+
+#[cfg(not(feature = "dev-mode"))]
+fn dev_vrf_info(_block_hash: Hash, _enabled: bool) -> Option<(Slot, RelayVRFStory)> {
+	None
 }
 
 /// Information about a block and imported candidates.
@@ -321,6 +795,82 @@ pub struct BlockImportedCandidates {
 	pub block_number: BlockNumber,
 	pub block_tick: Tick,
 	pub imported_candidates: Vec<(CandidateHash, CandidateEntry)>,
+	/// Whether all candidates of the block were insta-approved on import, in which case the
+	/// block was already reported as approved to chain selection.
+	pub fully_insta_approved: bool,
+	/// The session of the block, if it differs from the session of its parent. `None` if the
+	/// block is in the same session as its parent or the parent is unknown to the approval DB.
+	pub new_session: Option<SessionIndex>,
+}
+
+impl BlockImportedCandidates {
+	/// Create information about a block and the candidates imported under it.
+	///
+	/// # Example
+	///
+	/// ```
+	/// # use polkadot_node_core_approval_voting::BlockImportedCandidates;
+	/// # use polkadot_primitives::Hash;
+	/// let imported = BlockImportedCandidates::new(Hash::repeat_byte(1), 10, 240, Vec::new());
+	/// assert_eq!(imported.block_tick, 240);
+	/// ```
+	pub fn new(
+		block_hash: Hash,
+		block_number: BlockNumber,
+		block_tick: Tick,
+		imported_candidates: Vec<(CandidateHash, CandidateEntry)>,
+	) -> Self {
+		BlockImportedCandidates {
+			block_hash,
+			block_number,
+			block_tick,
+			imported_candidates,
+			fully_insta_approved: false,
+			new_session: None,
+		}
+	}
+
+	/// Create information about a block without imported candidates, at tick 0.
+	pub fn empty(block_hash: Hash, block_number: BlockNumber) -> Self {
+		Self::new(block_hash, block_number, 0, Vec::new())
+	}
+}
+
+// Requests the header of a new head from the Chain API, returning `None` if it is unavailable
+// or the Chain API doesn't answer within `timeout`.
+async fn fetch_header<Sender: SubsystemSender<ChainApiMessage>>(
+	sender: &mut Sender,
+	head: Hash,
+	timeout: Duration,
+	metrics: &Metrics,
+) -> SubsystemResult<Option<Header>> {
+	let (h_tx, h_rx) = oneshot::channel();
+	sender.send_message(ChainApiMessage::BlockHeader(head, h_tx)).await;
+	let Some(header) = h_rx.timeout(timeout).await else {
+		gum::warn!(
+			target: LOG_TARGET,
+			?head,
+			?timeout,
+			"Chain API subsystem didn't answer the header request in time",
+		);
+		metrics.on_import_skipped("chain_api_unreachable");
+		return Ok(None)
+	};
+	match header? {
+		Err(e) => {
+			gum::debug!(target: LOG_TARGET, "Chain API subsystem temporarily unreachable {}", e);
+			// May be a better way of handling errors here.
+			metrics.on_import_skipped("chain_api_unreachable");
+			Ok(None)
+		},
+		Ok(None) => {
+			gum::warn!(target: LOG_TARGET, "Missing header for new head {}", head);
+			// May be a better way of handling warnings here.
+			metrics.on_import_skipped("missing_header");
+			Ok(None)
+		},
+		Ok(Some(h)) => Ok(Some(h)),
+	}
 }
 
 /// Handle a new notification of a header. This will
@@ -331,6 +881,9 @@ pub struct BlockImportedCandidates {
 ///   * import the block and candidates to the approval DB
 ///   * and return information about all candidates imported under each block.
 ///
+/// Heads which were deferred by a previous call because session info was not yet available are
+/// retried first.
+///
 /// It is the responsibility of the caller to schedule wakeups for each block.
 pub(crate) async fn handle_new_head<
 	Sender: SubsystemSender<ChainApiMessage>
@@ -341,250 +894,460 @@ pub(crate) async fn handle_new_head<
 >(
 	sender: &mut Sender,
 	approval_voting_sender: &mut AVSender,
-	state: &State,
+	state: &mut State,
 	db: &mut OverlayedBackend<'_, B>,
 	session_info_provider: &mut RuntimeInfo,
 	head: Hash,
+	metrics: &Metrics,
 	finalized_number: &Option<BlockNumber>,
 ) -> SubsystemResult<Vec<BlockImportedCandidates>> {
-	const MAX_HEADS_LOOK_BACK: BlockNumber = MAX_FINALITY_LAG;
+	handle_new_head_with_known_headers(
+		sender,
+		approval_voting_sender,
+		state,
+		db,
+		session_info_provider,
+		head,
+		&HashMap::new(),
+		metrics,
+		finalized_number,
+	)
+	.await
+}
 
-	let header = {
-		let (h_tx, h_rx) = oneshot::channel();
-		sender.send_message(ChainApiMessage::BlockHeader(head, h_tx)).await;
-		match h_rx.await? {
-			Err(e) => {
-				gum::debug!(
-					target: LOG_TARGET,
-					"Chain API subsystem temporarily unreachable {}",
-					e,
-				);
-				// May be a better way of handling errors here.
-				return Ok(Vec::new())
-			},
-			Ok(None) => {
-				gum::warn!(target: LOG_TARGET, "Missing header for new head {}", head);
-				// May be a better way of handling warnings here.
-				return Ok(Vec::new())
-			},
-			Ok(Some(h)) => h,
-		}
+/// Same as [`handle_new_head`], but takes the headers of the head and its ancestors from
+/// `known_headers` where possible, e.g. those cached from a re-org notification, instead of
+/// requesting them from the Chain API.
+pub(crate) async fn handle_new_head_with_known_headers<
+	Sender: SubsystemSender<ChainApiMessage>
+		+ SubsystemSender<RuntimeApiMessage>
+		+ SubsystemSender<ChainSelectionMessage>,
+	AVSender: SubsystemSender<ApprovalDistributionMessage>,
+	B: Backend,
+>(
+	sender: &mut Sender,
+	approval_voting_sender: &mut AVSender,
+	state: &mut State,
+	db: &mut OverlayedBackend<'_, B>,
+	session_info_provider: &mut RuntimeInfo,
+	head: Hash,
+	known_headers: &HashMap<Hash, Header>,
+	metrics: &Metrics,
+	finalized_number: &Option<BlockNumber>,
+) -> SubsystemResult<Vec<BlockImportedCandidates>> {
+	let header = match known_headers.get(&head) {
+		Some(header) => header.clone(),
+		None => match fetch_header(sender, head, state.chain_api_timeout, metrics).await? {
+			Some(header) => header,
+			None => return Ok(Vec::new()),
+		},
 	};
 
-	// If we've just started the node and are far behind,
-	// import at most `MAX_HEADS_LOOK_BACK` blocks.
-	let lower_bound_number = header.number.saturating_sub(MAX_HEADS_LOOK_BACK);
-	let lower_bound_number = finalized_number.unwrap_or(lower_bound_number).max(lower_bound_number);
-
-	let new_blocks = determine_new_blocks(
+	handle_new_head_with_header(
 		sender,
-		|h| db.load_block_entry(h).map(|e| e.is_some()),
+		approval_voting_sender,
+		state,
+		db,
+		session_info_provider,
 		head,
-		&header,
-		lower_bound_number,
+		header,
+		known_headers,
+		metrics,
+		finalized_number,
 	)
-	.map_err(|e| SubsystemError::with_origin("approval-voting", e))
-	.await?;
-
-	if new_blocks.is_empty() {
-		return Ok(Vec::new())
-	}
+	.await
+}
 
-	let mut approval_meta: Vec<BlockApprovalMeta> = Vec::with_capacity(new_blocks.len());
-	let mut imported_candidates = Vec::with_capacity(new_blocks.len());
+/// Same as [`handle_new_head_with_known_headers`], but for a head whose header is already known,
+/// which saves requesting it from the Chain API.
+pub(crate) async fn handle_new_head_with_header<
+	Sender: SubsystemSender<ChainApiMessage>
+		+ SubsystemSender<RuntimeApiMessage>
+		+ SubsystemSender<ChainSelectionMessage>,
+	AVSender: SubsystemSender<ApprovalDistributionMessage>,
+	B: Backend,
+>(
+	sender: &mut Sender,
+	approval_voting_sender: &mut AVSender,
+	state: &mut State,
+	db: &mut OverlayedBackend<'_, B>,
+	session_info_provider: &mut RuntimeInfo,
+	head: Hash,
+	header: Header,
+	known_headers: &HashMap<Hash, Header>,
+	metrics: &Metrics,
+	finalized_number: &Option<BlockNumber>,
+) -> SubsystemResult<Vec<BlockImportedCandidates>> {
+	let mut imported_candidates = Vec::new();
+
+	// Deferred heads are retried before the new head, so that their blocks are written before
+	// any descendants. Those still lacking session info get deferred again.
+	let heads = std::mem::take(&mut state.deferred_heads)
+		.into_iter()
+		.filter(|deferred| *deferred != head)
+		.map(|deferred| (deferred, None))
+		.chain(std::iter::once((head, Some(header))));
+
+	for (head, header) in heads {
+		let span = tracing::debug_span!(
+			target: LOG_TARGET,
+			"handle_new_head",
+			?head,
+			new_blocks_len = tracing::field::Empty,
+		);
 
-	// `determine_new_blocks` gives us a vec in backwards order. we want to move forwards.
-	let imported_blocks_and_info = {
-		let mut imported_blocks_and_info = Vec::with_capacity(new_blocks.len());
-		for (block_hash, block_header) in new_blocks.into_iter().rev() {
-			let env = ImportedBlockInfoEnv {
-				runtime_info: session_info_provider,
-				assignment_criteria: &*state.assignment_criteria,
-				keystore: &state.keystore,
-			};
+		imported_candidates.extend(
+			handle_new_head_inner(
+				sender,
+				approval_voting_sender,
+				state,
+				db,
+				session_info_provider,
+				head,
+				header,
+				known_headers,
+				metrics,
+				finalized_number,
+			)
+			.instrument(span)
+			.await?,
+		);
+	}
 
-			match imported_block_info(sender, env, block_hash, &block_header, finalized_number)
-				.await
-			{
-				Ok(i) => imported_blocks_and_info.push((block_hash, block_header, i)),
-				Err(error) => {
-					// It's possible that we've lost a race with finality.
-					let (tx, rx) = oneshot::channel();
-					sender
-						.send_message(ChainApiMessage::FinalizedBlockHash(block_header.number, tx))
-						.await;
-
-					let lost_to_finality = match rx.await {
-						Ok(Ok(Some(h))) if h != block_hash => true,
-						_ => false,
-					};
+	if let Some(highest) = imported_candidates.iter().max_by_key(|imported| imported.block_number) {
+		state.last_imported = Some((highest.block_hash, highest.block_number));
+	}
 
-					if !lost_to_finality {
-						// Such errors are likely spurious, but this prevents us from getting gaps
-						// in the approval-db.
-						gum::warn!(
-							target: LOG_TARGET,
-							"Skipping chain: unable to gather info about imported block {:?}: {}",
-							(block_hash, block_header.number),
-							error,
-						);
-					}
+	Ok(imported_candidates)
+}
 
-					return Ok(Vec::new())
-				},
-			};
-		}
+/// The number of the highest block below `head_number` that is not imported: the finalized block,
+/// unless it is more than `max_look_back` blocks behind the head.
+///
+/// A finalized block above the head yields a bound above the head, so that nothing is imported.
+fn compute_lower_bound(
+	head_number: BlockNumber,
+	max_look_back: BlockNumber,
+	finalized: Option<BlockNumber>,
+) -> BlockNumber {
+	let look_back_bound = head_number.saturating_sub(max_look_back);
+	finalized.map_or(look_back_bound, |finalized| finalized.max(look_back_bound))
+}
 
-		imported_blocks_and_info
-	};
+async fn handle_new_head_inner<
+	Sender: SubsystemSender<ChainApiMessage>
+		+ SubsystemSender<RuntimeApiMessage>
+		+ SubsystemSender<ChainSelectionMessage>,
+	AVSender: SubsystemSender<ApprovalDistributionMessage>,
+	B: Backend,
+>(
+	sender: &mut Sender,
+	approval_voting_sender: &mut AVSender,
+	state: &mut State,
+	db: &mut OverlayedBackend<'_, B>,
+	session_info_provider: &mut RuntimeInfo,
+	head: Hash,
+	header: Option<Header>,
+	known_headers: &HashMap<Hash, Header>,
+	metrics: &Metrics,
+	finalized_number: &Option<BlockNumber>,
+) -> SubsystemResult<Vec<BlockImportedCandidates>> {
+	const MAX_HEADS_LOOK_BACK: BlockNumber = MAX_FINALITY_LAG;
 
-	gum::trace!(
-		target: LOG_TARGET,
-		imported_blocks = imported_blocks_and_info.len(),
-		"Inserting imported blocks into database"
-	);
+	let header = match header.or_else(|| known_headers.get(&head).cloned()) {
+		Some(header) => header,
+		None => match fetch_header(sender, head, state.chain_api_timeout, metrics).await? {
+			Some(header) => header,
+			None => return Ok(Vec::new()),
+		},
+	};
 
-	for (block_hash, block_header, imported_block_info) in imported_blocks_and_info {
-		let ImportedBlockInfo {
-			included_candidates,
-			session_index,
-			assignments,
-			n_validators,
-			relay_vrf_story,
-			slot,
-			force_approve,
-		} = imported_block_info;
+	// If we've just started the node and are far behind,
+	// import at most `MAX_HEADS_LOOK_BACK` blocks.
+	let lower_bound_number =
+		compute_lower_bound(header.number, MAX_HEADS_LOOK_BACK, *finalized_number);
+
+	// The blocks to import next, in descending order. When importing in windows, these are the
+	// oldest window and the hashes of the remaining new blocks are kept in `pending_hashes`.
+	let (mut new_blocks, mut pending_hashes) = match state.ancestry_window {
+		None => (
+			determine_new_blocks_with_known_headers(
+				sender,
+				|h| db.load_block_entry(h).map(|e| e.is_some()),
+				known_headers,
+				head,
+				&header,
+				lower_bound_number,
+			)
+			.map_err(|e| SubsystemError::with_origin(DETERMINE_NEW_BLOCKS_ORIGIN, e))
+			.await?,
+			Vec::new(),
+		),
+		Some(window) => {
+			let mut hashes =
+				determine_new_block_hashes(sender, db, head, &header, lower_bound_number, window)
+					.await?;
+			let oldest = hashes.split_off(hashes.len().saturating_sub(window));
+			(fetch_ancestry_window(sender, known_headers, oldest).await?, hashes)
+		},
+	};
 
-		let session_info =
-			match get_session_info(session_info_provider, sender, head, session_index).await {
-				Some(session_info) => session_info,
-				None => return Ok(Vec::new()),
-			};
+	if let Some((_, oldest)) = new_blocks.last() {
+		let parent_hash = oldest.parent_hash;
+		let parent_unfinalized = oldest.number.saturating_sub(1) > finalized_number.unwrap_or(0);
+		if parent_unfinalized && db.load_block_entry(&parent_hash)?.is_none() {
+			match state.on_missing_ancestor {
+				OnMissingAncestor::Proceed => {},
+				OnMissingAncestor::Skip => {
+					gum::debug!(
+						target: LOG_TARGET,
+						?head,
+						?parent_hash,
+						"Skipping chain with unknown ancestor",
+					);
+					return Ok(Vec::new())
+				},
+				// Backfilled ancestors are imported along with the oldest window.
+				OnMissingAncestor::Backfill => new_blocks.extend(
+					backfill_ancestry(
+						sender,
+						db,
+						known_headers,
+						parent_hash,
+						finalized_number.unwrap_or(0),
+					)
+					.await?,
+				),
+			}
+		}
+	}
 
-		let block_tick = slot_number_to_tick(state.slot_duration_millis, slot);
+	tracing::Span::current().record("new_blocks_len", new_blocks.len() + pending_hashes.len());
+	if new_blocks.is_empty() {
+		return Ok(Vec::new())
+	}
 
-		let needed_approvals = session_info.needed_approvals;
-		let validator_group_lens: Vec<usize> =
-			session_info.validator_groups.iter().map(|v| v.len()).collect();
-		// insta-approve candidates on low-node testnets:
-		// cf. https://github.com/paritytech/polkadot/issues/2411
-		let num_candidates = included_candidates.len();
-		let approved_bitfield = {
-			if needed_approvals == 0 {
-				gum::debug!(
-					target: LOG_TARGET,
-					block_hash = ?block_hash,
-					"Insta-approving all candidates",
-				);
-				bitvec::bitvec![u8, BitOrderLsb0; 1; num_candidates]
-			} else {
-				let mut result = bitvec::bitvec![u8, BitOrderLsb0; 0; num_candidates];
-				for (i, &(_, _, _, backing_group)) in included_candidates.iter().enumerate() {
-					let backing_group_size =
-						validator_group_lens.get(backing_group.0 as usize).copied().unwrap_or(0);
-					let needed_approvals =
-						usize::try_from(needed_approvals).expect("usize is at least u32; qed");
-					if n_validators.saturating_sub(backing_group_size) < needed_approvals {
-						result.set(i, true);
-					}
-				}
-				if result.any() {
-					gum::debug!(
+	let mut approval_meta: Vec<BlockApprovalMeta> = Vec::with_capacity(new_blocks.len());
+	let mut imported_candidates = Vec::with_capacity(new_blocks.len());
+	// Hashes already seen in this chain, across all windows.
+	let mut seen_hashes = HashSet::with_capacity(new_blocks.len());
+
+	// Blocks are written as soon as their window is imported. Blocks written before an import
+	// failure stay imported and are announced below, while the rest of the chain is left to be
+	// imported along with a later head.
+	'windows: loop {
+		let mut last_window = pending_hashes.is_empty();
+
+		// `determine_new_blocks` gives us a vec in backwards order. we want to move forwards.
+		let imported_blocks_and_info = {
+			let mut imported_blocks_and_info = Vec::with_capacity(new_blocks.len());
+			for (block_hash, block_header) in new_blocks.into_iter().rev() {
+				// A repeated hash would otherwise be written to the DB twice and announced
+				// twice to approval-distribution.
+				if !seen_hashes.insert(block_hash) {
+					gum::warn!(
 						target: LOG_TARGET,
-						block_hash = ?block_hash,
-						"Insta-approving {}/{} candidates as the number of validators is too low",
-						result.count_ones(),
-						result.len(),
+						?block_hash,
+						block_number = block_header.number,
+						"Skipping duplicate block in imported chain",
 					);
+					continue
 				}
-				result
+
+				let env = ImportedBlockInfoEnv {
+					runtime_info: session_info_provider,
+					assignment_criteria: &*state.assignment_criteria,
+					keystore: &state.keystore,
+					relay_parent_check: state.relay_parent_check,
+					tolerate_candidate_events_errors: state.tolerate_candidate_events_errors,
+					core_filter: state.core_filter.as_ref(),
+					shadow_criteria: state.shadow_criteria.as_deref(),
+					assignment_observer: state.assignment_observer.as_deref(),
+					dev_mode: state.dev_mode,
+					force_approve_target: state.force_approve_target,
+					metrics,
+				};
+
+				let span = tracing::debug_span!(
+					target: LOG_TARGET,
+					"imported_block_info",
+					?block_hash,
+					block_number = block_header.number,
+					session = tracing::field::Empty,
+				);
+
+				match imported_block_info(sender, env, block_hash, &block_header, finalized_number)
+					.instrument(span)
+					.await
+				{
+					Ok(i) => imported_blocks_and_info.push((block_hash, block_header, i)),
+					Err(error) => {
+						last_window = true;
+
+						// It's possible that we've lost a race with finality.
+						let (tx, rx) = oneshot::channel();
+						sender
+							.send_message(ChainApiMessage::FinalizedBlockHash(
+								block_header.number,
+								tx,
+							))
+							.await;
+
+						let finalized_at_number = match rx.await {
+							Ok(Ok(h)) => h,
+							_ => None,
+						};
+						let lost_to_finality = is_lost_to_finality(finalized_at_number, block_hash);
+
+						if lost_to_finality {
+							metrics.on_import_skipped("lost_to_finality");
+							imported_blocks_and_info.clear();
+							break
+						}
+
+						if state.defer_unavailable_sessions &&
+							matches!(error, ImportedBlockInfoError::SessionInfoUnavailable)
+						{
+							// Import the ancestors we already have info for and retry the rest
+							// of the chain on the next import.
+							gum::debug!(
+								target: LOG_TARGET,
+								"Deferring chain: session info unavailable for block {:?}",
+								(block_hash, block_header.number),
+							);
+
+							defer_head(&mut state.deferred_heads, head);
+							break
+						}
+
+						// Such errors are likely spurious, but this prevents us from getting
+						// gaps in the approval-db.
+						gum::warn!(
+							target: LOG_TARGET,
+							"Skipping chain: unable to gather info about imported block {:?}: {}",
+							(block_hash, block_header.number),
+							error,
+						);
+
+						metrics.on_import_skipped(
+							if matches!(error, ImportedBlockInfoError::SessionInfoUnavailable) {
+								"session_info_unavailable"
+							} else {
+								"block_info_error"
+							},
+						);
+						imported_blocks_and_info.clear();
+						break
+					},
+				};
 			}
-		};
-		// If all bits are already set, then send an approve message.
-		if approved_bitfield.count_ones() == approved_bitfield.len() {
-			sender.send_message(ChainSelectionMessage::Approved(block_hash)).await;
-		}
 
-		let block_entry = v3::BlockEntry {
-			block_hash,
-			parent_hash: block_header.parent_hash,
-			block_number: block_header.number,
-			session: session_index,
-			slot,
-			relay_vrf_story: relay_vrf_story.0,
-			candidates: included_candidates
-				.iter()
-				.map(|(hash, _, core, _)| (*core, *hash))
-				.collect(),
-			approved_bitfield,
-			children: Vec::new(),
-			candidates_pending_signature: Default::default(),
-			distributed_assignments: Default::default(),
+			imported_blocks_and_info
 		};
 
 		gum::trace!(
 			target: LOG_TARGET,
-			?block_hash,
-			block_number = block_header.number,
-			"Writing BlockEntry",
+			imported_blocks = imported_blocks_and_info.len(),
+			"Inserting imported blocks into database"
 		);
 
-		let candidate_entries =
-			crate::ops::add_block_entry(db, block_entry.into(), n_validators, |candidate_hash| {
-				included_candidates.iter().find(|(hash, _, _, _)| candidate_hash == hash).map(
-					|(_, receipt, core, backing_group)| {
-						super::ops::NewCandidateInfo::new(
-							receipt.clone(),
-							*backing_group,
-							assignments.get(core).map(|a| a.clone().into()),
-						)
-					},
-				)
-			})
-			.map_err(|e| SubsystemError::with_origin("approval-voting", e))?;
-
-		// force-approve needs to load the current block entry as well as all
-		// ancestors. this can only be done after writing the block entry above.
-		if let Some(up_to) = force_approve {
-			gum::debug!(target: LOG_TARGET, ?block_hash, up_to, "Enacting force-approve");
-			let approved_hashes = crate::ops::force_approve(db, block_hash, up_to)
-				.map_err(|e| SubsystemError::with_origin("approval-voting", e))?;
-			gum::debug!(
+		let mut pending_entries: Vec<PendingBlockEntry> =
+			Vec::with_capacity(imported_blocks_and_info.len());
+		let mut new_sessions = Vec::with_capacity(imported_blocks_and_info.len());
+		let mut failed = false;
+		for (block_hash, block_header, imported_block_info) in imported_blocks_and_info {
+			let span = tracing::debug_span!(
 				target: LOG_TARGET,
+				"import_block",
 				?block_hash,
-				up_to,
-				"Force-approving {} blocks",
-				approved_hashes.len()
+				block_number = block_header.number,
+				session = imported_block_info.session_index,
 			);
 
-			// Notify chain-selection of all approved hashes.
-			for hash in approved_hashes {
-				sender.send_message(ChainSelectionMessage::Approved(hash)).await;
+			// Ancestors from earlier windows are already in the overlay, but the ones from this
+			// window are only written once all of it is prepared.
+			let parent_session = match pending_entries.last() {
+				Some(parent) if parent.meta.hash == block_header.parent_hash =>
+					Some(parent.meta.session),
+				_ => db.load_block_entry(&block_header.parent_hash)?.map(|parent| parent.session()),
+			};
+			new_sessions.push(
+				parent_session
+					.filter(|session| *session != imported_block_info.session_index)
+					.map(|_| imported_block_info.session_index),
+			);
+			let block_number = block_header.number;
+
+			let prepared = prepare_block_entry(
+				sender,
+				state,
+				session_info_provider,
+				head,
+				block_hash,
+				block_header,
+				imported_block_info,
+				metrics,
+			)
+			.instrument(span)
+			.await;
+
+			match prepared {
+				Ok(pending) => pending_entries.push(pending),
+				Err(error) => {
+					gum::warn!(
+						target: LOG_TARGET,
+						"Skipping chain: unable to import block {:?}: {}",
+						(block_hash, block_number),
+						error,
+					);
+
+					metrics.on_import_skipped(
+						if matches!(error, ImportedBlockInfoError::SessionInfoUnavailable) {
+							"session_info_unavailable"
+						} else {
+							"block_info_error"
+						},
+					);
+					failed = true;
+					break
+				},
 			}
 		}
 
-		approval_meta.push(BlockApprovalMeta {
-			hash: block_hash,
-			number: block_header.number,
-			parent_hash: block_header.parent_hash,
-			candidates: included_candidates
-				.iter()
-				.map(|(hash, _, core_index, group_index)| (*hash, *core_index, *group_index))
-				.collect(),
-			slot,
-			session: session_index,
-			vrf_story: relay_vrf_story,
-		});
+		// The blocks prepared before a failure are still written and announced.
+		let written = write_block_entries(sender, db, pending_entries)?;
+		for ((meta, candidates), new_session) in written.into_iter().zip(new_sessions) {
+			approval_meta.push(meta);
+			imported_candidates.push(BlockImportedCandidates { new_session, ..candidates });
+		}
 
-		imported_candidates.push(BlockImportedCandidates {
-			block_hash,
-			block_number: block_header.number,
-			block_tick,
-			imported_candidates: candidate_entries
-				.into_iter()
-				.map(|(h, e)| (h, e.into()))
-				.collect(),
-		});
+		if failed || last_window {
+			break
+		}
+
+		// Blocks are only pending when importing in windows.
+		let window = state.ancestry_window.unwrap_or(pending_hashes.len());
+		let next = pending_hashes.split_off(pending_hashes.len().saturating_sub(window));
+		new_blocks = fetch_ancestry_window(sender, known_headers, next).await?;
+		if new_blocks.is_empty() {
+			break
+		}
+	}
+
+	if approval_meta.is_empty() {
+		return Ok(Vec::new())
+	}
+
+	if state.skip_idle_block_announcements &&
+		approval_meta.iter().all(|meta| meta.candidates.is_empty())
+	{
+		gum::trace!(
+			target: LOG_TARGET,
+			head = ?head,
+			chain_length = approval_meta.len(),
+			"Not informing distribution of newly imported chain without candidates",
+		);
+		return Ok(imported_candidates)
 	}
 
 	gum::trace!(
@@ -599,520 +1362,4166 @@ pub(crate) async fn handle_new_head<
 	Ok(imported_candidates)
 }
 
-#[cfg(test)]
-pub(crate) mod tests {
-	use super::*;
-	use crate::{
-		approval_db::common::{load_block_entry, DbBackend},
-		RuntimeInfo, RuntimeInfoConfig, MAX_BLOCKS_WITH_ASSIGNMENT_TIMESTAMPS,
-	};
-	use approval_types::time::Clock;
-	use assert_matches::assert_matches;
-	use polkadot_node_primitives::{
-		approval::v1::{VrfSignature, VrfTranscript},
-		DISPUTE_WINDOW,
-	};
-	use polkadot_node_subsystem::{
-		messages::{AllMessages, ApprovalVotingMessage},
-		SubsystemContext,
-	};
-	use polkadot_node_subsystem_test_helpers::make_subsystem_context;
-	use polkadot_node_subsystem_util::database::Database;
-	use polkadot_primitives::{
-		node_features::FeatureIndex, vstaging::MutateDescriptorV2, ExecutorParams, Id as ParaId,
-		IndexedVec, NodeFeatures, SessionInfo, ValidatorId, ValidatorIndex,
-	};
-	use polkadot_primitives_test_helpers::{dummy_candidate_receipt_v2, dummy_hash};
-	use schnellru::{ByLength, LruMap};
-	pub(crate) use sp_consensus_babe::{
-		digests::{CompatibleDigestItem, PreDigest, SecondaryVRFPreDigest},
-		AllowedSlots, BabeEpochConfiguration, Epoch as BabeEpoch,
-	};
-	use sp_core::{crypto::VrfSecret, testing::TaskExecutor};
-	use sp_keyring::sr25519::Keyring as Sr25519Keyring;
-	pub(crate) use sp_runtime::{Digest, DigestItem};
-	use std::{pin::Pin, sync::Arc};
+/// Determines the hashes of the blocks from `head` back to the highest block known to the
+/// approval DB or above `lower_bound_number`, in descending order, like `determine_new_blocks`
+/// does, but without fetching their headers. Ancestors are requested `window` at a time.
+async fn determine_new_block_hashes<Sender: SubsystemSender<ChainApiMessage>, B: Backend>(
+	sender: &mut Sender,
+	db: &OverlayedBackend<'_, B>,
+	head: Hash,
+	header: &Header,
+	lower_bound_number: BlockNumber,
+	window: usize,
+) -> SubsystemResult<Vec<Hash>> {
+	let min_block_needed = lower_bound_number + 1;
+	if header.number < min_block_needed || db.load_block_entry(&head)?.is_some() {
+		return Ok(Vec::new())
+	}
 
-	use crate::{approval_db::common::Config as DatabaseConfig, criteria, BlockEntry};
+	let mut hashes = vec![head];
+	let mut number = header.number;
+	while number > min_block_needed {
+		let (tx, rx) = oneshot::channel();
+		sender
+			.send_message(ChainApiMessage::Ancestors {
+				hash: hashes[hashes.len() - 1],
+				k: window.min((number - min_block_needed) as usize),
+				response_channel: tx,
+			})
+			.await;
 
-	const DATA_COL: u32 = 0;
+		// Like `determine_new_blocks`, continue with the ancestry we have on errors.
+		let ancestors = match rx.await {
+			Ok(Ok(ancestors)) if !ancestors.is_empty() => ancestors,
+			_ => break,
+		};
 
-	const NUM_COLUMNS: u32 = 1;
+		for hash in ancestors {
+			if db.load_block_entry(&hash)?.is_some() {
+				return Ok(hashes)
+			}
 
-	const TEST_CONFIG: DatabaseConfig = DatabaseConfig { col_approval_data: DATA_COL };
-	#[derive(Default)]
-	struct MockClock;
+			hashes.push(hash);
+			number -= 1;
+		}
+	}
 
-	impl Clock for MockClock {
-		fn tick_now(&self) -> Tick {
-			42 // chosen by fair dice roll
+	Ok(hashes)
+}
+
+/// Fetches the headers of the blocks of an ancestry window, keeping their order, unless they are
+/// in `known_headers`. Returns nothing if any of them can't be fetched.
+async fn fetch_ancestry_window<Sender: SubsystemSender<ChainApiMessage>>(
+	sender: &mut Sender,
+	known_headers: &HashMap<Hash, Header>,
+	hashes: Vec<Hash>,
+) -> SubsystemResult<Vec<(Hash, Header)>> {
+	let mut window = Vec::with_capacity(hashes.len());
+	for hash in hashes {
+		if let Some(header) = known_headers.get(&hash) {
+			window.push((hash, header.clone()));
+			continue
 		}
 
-		fn wait(&self, _tick: Tick) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>> {
-			Box::pin(async move { () })
+		let (tx, rx) = oneshot::channel();
+		sender.send_message(ChainApiMessage::BlockHeader(hash, tx)).await;
+		match rx.await? {
+			Ok(Some(header)) => window.push((hash, header)),
+			Ok(None) | Err(_) => {
+				gum::debug!(target: LOG_TARGET, ?hash, "Unable to fetch header of ancestry window");
+				return Ok(Vec::new())
+			},
 		}
 	}
 
-	fn blank_state() -> State {
-		State {
-			keystore: Arc::new(LocalKeystore::in_memory()),
-			slot_duration_millis: 6_000,
-			clock: Arc::new(MockClock::default()),
-			assignment_criteria: Box::new(MockAssignmentCriteria::default()),
-			per_block_assignments_gathering_times: LruMap::new(ByLength::new(
-				MAX_BLOCKS_WITH_ASSIGNMENT_TIMESTAMPS,
-			)),
-			no_show_stats: Default::default(),
+	Ok(window)
+}
+
+/// Determines the blocks from `hash` back to the last finalized block which are unknown to the
+/// approval DB, in descending order.
+async fn backfill_ancestry<Sender: SubsystemSender<ChainApiMessage>, B: Backend>(
+	sender: &mut Sender,
+	db: &OverlayedBackend<'_, B>,
+	known_headers: &HashMap<Hash, Header>,
+	hash: Hash,
+	finalized_number: BlockNumber,
+) -> SubsystemResult<Vec<(Hash, Header)>> {
+	let header = if let Some(header) = known_headers.get(&hash) {
+		header.clone()
+	} else {
+		let (tx, rx) = oneshot::channel();
+		sender.send_message(ChainApiMessage::BlockHeader(hash, tx)).await;
+		match rx.await? {
+			Ok(Some(header)) => header,
+			Ok(None) | Err(_) => {
+				gum::debug!(target: LOG_TARGET, ?hash, "Unable to backfill missing ancestor");
+				return Ok(Vec::new())
+			},
 		}
-	}
+	};
 
-	fn single_session_state() -> (State, RuntimeInfo) {
-		(
-			blank_state(),
-			RuntimeInfo::new_with_config(RuntimeInfoConfig {
-				keystore: None,
-				session_cache_lru_size: DISPUTE_WINDOW.get(),
-			}),
-		)
+	determine_new_blocks_with_known_headers(
+		sender,
+		|h| db.load_block_entry(h).map(|e| e.is_some()),
+		known_headers,
+		hash,
+		&header,
+		finalized_number,
+	)
+	.map_err(|e| SubsystemError::with_origin(DETERMINE_NEW_BLOCKS_ORIGIN, e))
+	.await
+}
+
+/// Whether `block_hash` lost a race with finality, i.e. another block was finalized at its
+/// height. Unknown finality at that height isn't treated as such.
+fn is_lost_to_finality(finalized_at_number: Option<Hash>, block_hash: Hash) -> bool {
+	finalized_at_number.map_or(false, |finalized| finalized != block_hash)
+}
+
+/// Queues `head` to be retried on the next import, dropping the oldest entry once
+/// [`MAX_DEFERRED_HEADS`] are queued.
+fn defer_head(deferred_heads: &mut Vec<Hash>, head: Hash) {
+	if deferred_heads.contains(&head) {
+		return
 	}
 
-	#[derive(Default)]
-	struct MockAssignmentCriteria {
-		enable_v2: bool,
+	if deferred_heads.len() >= MAX_DEFERRED_HEADS {
+		deferred_heads.remove(0);
 	}
 
-	impl AssignmentCriteria for MockAssignmentCriteria {
-		fn compute_assignments(
-			&self,
-			_keystore: &LocalKeystore,
-			_relay_vrf_story: polkadot_node_primitives::approval::v1::RelayVRFStory,
-			_config: &criteria::Config,
-			_leaving_cores: Vec<(
-				CandidateHash,
-				polkadot_primitives::CoreIndex,
-				polkadot_primitives::GroupIndex,
-			)>,
-			enable_assignments_v2: bool,
-		) -> HashMap<polkadot_primitives::CoreIndex, criteria::OurAssignment> {
-			assert_eq!(enable_assignments_v2, self.enable_v2);
-			HashMap::new()
-		}
+	deferred_heads.push(head);
+}
 
-		fn check_assignment_cert(
-			&self,
-			_claimed_core_bitfield: polkadot_node_primitives::approval::v2::CoreBitfield,
-			_validator_index: polkadot_primitives::ValidatorIndex,
-			_config: &criteria::Config,
-			_relay_vrf_story: polkadot_node_primitives::approval::v1::RelayVRFStory,
-			_assignment: &polkadot_node_primitives::approval::v2::AssignmentCertV2,
-			_backing_groups: Vec<polkadot_primitives::GroupIndex>,
-		) -> Result<polkadot_node_primitives::approval::v1::DelayTranche, criteria::InvalidAssignment>
-		{
-			Ok(0)
-		}
+/// Computes which of the candidates included in `block_hash` are insta-approved, either because
+/// the session needs no approvals at all or because there are too few validators besides the
+/// backing group to gather the needed approvals.
+fn insta_approved_bitfield(
+	state: &State,
+	block_hash: Hash,
+	included_candidates: &[(CandidateHash, CandidateReceipt, CoreIndex, GroupIndex)],
+	n_validators: usize,
+	session_info: &SessionInfo,
+) -> BitVec<u8, BitOrderLsb0> {
+	let needed_approvals = session_info.needed_approvals;
+	if needed_approvals > 0 && state.disable_low_node_insta_approval {
+		return bitvec::bitvec![u8, BitOrderLsb0; 0; included_candidates.len()]
 	}
 
-	// used for generating assignments where the validity of the VRF doesn't matter.
-	pub(crate) fn garbage_vrf_signature() -> VrfSignature {
-		let transcript = VrfTranscript::new(b"test-garbage", &[]);
-		Sr25519Keyring::Alice.pair().vrf_sign(&transcript.into())
+	let validator_group_lens: Vec<usize> =
+		session_info.validator_groups.iter().map(|v| v.len()).collect();
+	let result = compute_insta_approval_bitfield(
+		included_candidates,
+		n_validators,
+		needed_approvals,
+		&validator_group_lens,
+		state.unknown_backing_group,
+	);
+	if needed_approvals == 0 {
+		gum::debug!(
+			target: LOG_TARGET,
+			block_hash = ?block_hash,
+			"Insta-approving all candidates",
+		);
+	} else if result.any() {
+		gum::debug!(
+			target: LOG_TARGET,
+			block_hash = ?block_hash,
+			"Insta-approving {}/{} candidates as the number of validators is too low",
+			result.count_ones(),
+			result.len(),
+		);
 	}
+	result
+}
 
-	fn dummy_session_info(index: SessionIndex) -> SessionInfo {
-		SessionInfo {
-			validators: Default::default(),
-			discovery_keys: Vec::new(),
-			assignment_keys: Vec::new(),
-			validator_groups: Default::default(),
-			n_cores: index as _,
-			zeroth_delay_tranche_width: index as _,
-			relay_vrf_modulo_samples: index as _,
-			n_delay_tranches: index as _,
+/// Computes which of the `included` candidates can't gather `needed_approvals` from the
+/// `n_validators` outside of their backing group, whose sizes are given by `group_lens`.
+/// Candidates backed by a group missing from `group_lens` are treated as per
+/// `unknown_backing_group`.
+///
+/// All candidates are insta-approved if no approvals are needed, which is the case on low-node
+/// testnets, cf. <https://github.com/paritytech/polkadot/issues/2411>.
+fn compute_insta_approval_bitfield(
+	included: &[(CandidateHash, CandidateReceipt, CoreIndex, GroupIndex)],
+	n_validators: usize,
+	needed_approvals: u32,
+	group_lens: &[usize],
+	unknown_backing_group: UnknownBackingGroup,
+) -> BitVec<u8, BitOrderLsb0> {
+	if needed_approvals == 0 {
+		return bitvec::bitvec![u8, BitOrderLsb0; 1; included.len()]
+	}
+
+	let needed_approvals = usize::try_from(needed_approvals).expect("usize is at least u32; qed");
+	let mut result = bitvec::bitvec![u8, BitOrderLsb0; 0; included.len()];
+	for (i, &(candidate_hash, _, _, backing_group)) in included.iter().enumerate() {
+		let backing_group_size = match group_lens.get(backing_group.0 as usize) {
+			Some(len) => *len,
+			None => {
+				gum::warn!(
+					target: LOG_TARGET,
+					?candidate_hash,
+					?backing_group,
+					n_groups = group_lens.len(),
+					policy = ?unknown_backing_group,
+					"Included candidate has an unknown backing group",
+				);
+				match unknown_backing_group {
+					UnknownBackingGroup::TreatAsEmpty => 0,
+					UnknownBackingGroup::IgnoreCandidate => continue,
+				}
+			},
+		};
+		if n_validators.saturating_sub(backing_group_size) < needed_approvals {
+			result.set(i, true);
+		}
+	}
+	result
+}
+
+// A block whose info was gathered and checked, ready to be written to the approval DB.
+struct PendingBlockEntry {
+	entry: v3::BlockEntry,
+	n_validators: usize,
+	candidate_infos: HashMap<CandidateHash, crate::ops::NewCandidateInfo>,
+	force_approve: Option<BlockNumber>,
+	meta: BlockApprovalMeta,
+	block_tick: Tick,
+}
+
+/// Prepares the entry of a single block, whose info was already gathered, for being written to
+/// the approval DB by [`write_block_entries`].
+///
+/// Returns an error if the session info could not be obtained or the info of an included candidate
+/// is missing, in which case the caller should abandon the import.
+async fn prepare_block_entry<Sender: SubsystemSender<RuntimeApiMessage>>(
+	sender: &mut Sender,
+	state: &mut State,
+	session_info_provider: &mut RuntimeInfo,
+	head: Hash,
+	block_hash: Hash,
+	block_header: Header,
+	imported_block_info: ImportedBlockInfo,
+	metrics: &Metrics,
+) -> Result<PendingBlockEntry, ImportedBlockInfoError> {
+	let relay_vrf_story_bytes = imported_block_info.vrf_story_bytes();
+	let ImportedBlockInfo {
+		mut included_candidates,
+		session_index,
+		assignments,
+		n_validators,
+		n_cores,
+		relay_vrf_story,
+		slot,
+		force_approve,
+		node_features: _,
+	} = imported_block_info;
+
+	// The approved bitfield is indexed like the candidates, so they must be sorted before it is
+	// computed.
+	if state.sort_candidates_by_core &&
+		included_candidates.windows(2).any(|pair| pair[0].2 > pair[1].2)
+	{
+		gum::debug!(target: LOG_TARGET, ?block_hash, "Sorting included candidates by core index");
+		included_candidates.sort_by_key(|(_, _, core, _)| *core);
+	}
+
+	let session_info =
+		match get_session_info(session_info_provider, sender, head, session_index).await {
+			Some(session_info) => session_info,
+			None => return Err(ImportedBlockInfoError::SessionInfoUnavailable),
+		};
+
+	let block_tick = state
+		.clock
+		.tick_for_slot(state.session_slot_duration_millis(session_index), slot);
+
+	let approved_bitfield = insta_approved_bitfield(
+		state,
+		block_hash,
+		&included_candidates,
+		n_validators,
+		session_info,
+	);
+	let approved_bitfield = if state.strict_no_insta_approval && approved_bitfield.any() {
+		gum::error!(
+			target: LOG_TARGET,
+			?block_hash,
+			block_number = block_header.number,
+			session = session_index,
+			"Refusing to insta-approve {}/{} candidates, leaving them to approval voting",
+			approved_bitfield.count_ones(),
+			approved_bitfield.len(),
+		);
+		metrics.on_insta_approval_refused();
+		bitvec::bitvec![u8, BitOrderLsb0; 0; approved_bitfield.len()]
+	} else {
+		approved_bitfield
+	};
+
+	let meta_candidates = included_candidates
+		.iter()
+		.map(|(hash, _, core_index, group_index)| (*hash, *core_index, *group_index))
+		.collect();
+	let block_candidates: Vec<_> =
+		included_candidates.iter().map(|(hash, _, core, _)| (*core, *hash)).collect();
+
+	// Blocks may include many candidates, so look them up by hash instead of scanning the list
+	// for each of them. Receipts of candidates included by recently imported blocks are shared
+	// with those blocks.
+	let included_candidates_by_hash: HashMap<_, _> = included_candidates
+		.into_iter()
+		.map(|(hash, receipt, core, backing_group)| {
+			let receipt = match state.receipt_cache.get(&hash) {
+				Some(cached) => cached.clone(),
+				None => {
+					let receipt = Arc::new(receipt);
+					state.receipt_cache.insert(hash, receipt.clone());
+					receipt
+				},
+			};
+			(hash, (receipt, core, backing_group))
+		})
+		.collect();
+
+	// The info of every candidate is gathered before anything is written or announced, as the
+	// block entry must not reference candidates without one.
+	let mut candidate_infos = HashMap::with_capacity(block_candidates.len());
+	for (_, candidate_hash) in &block_candidates {
+		let Some((receipt, core, backing_group)) = included_candidates_by_hash.get(candidate_hash)
+		else {
+			return Err(ImportedBlockInfoError::MissingCandidateInfo(*candidate_hash))
+		};
+		candidate_infos.insert(
+			*candidate_hash,
+			super::ops::NewCandidateInfo::new(
+				receipt.clone(),
+				*backing_group,
+				assignments.get(core).map(|a| a.clone().into()),
+			),
+		);
+	}
+
+	let entry = v3::BlockEntry {
+		block_hash,
+		parent_hash: block_header.parent_hash,
+		block_number: block_header.number,
+		session: session_index,
+		slot,
+		relay_vrf_story: relay_vrf_story_bytes,
+		candidates: block_candidates,
+		approved_bitfield,
+		children: Vec::new(),
+		candidates_pending_signature: Default::default(),
+		distributed_assignments: Default::default(),
+	};
+
+	gum::trace!(
+		target: LOG_TARGET,
+		?block_hash,
+		block_number = block_header.number,
+		n_cores,
+		"Writing BlockEntry",
+	);
+	telemetry!(
+		state.telemetry;
+		SUBSTRATE_INFO;
+		"approval_voting.block_imported";
+		"block_hash" => ?block_hash,
+		"block_number" => block_header.number,
+		"slot" => u64::from(slot),
+		"block_tick" => block_tick,
+	);
+
+	let meta = BlockApprovalMeta {
+		hash: block_hash,
+		number: block_header.number,
+		parent_hash: block_header.parent_hash,
+		candidates: meta_candidates,
+		slot,
+		session: session_index,
+		vrf_story: relay_vrf_story,
+	};
+
+	Ok(PendingBlockEntry { entry, n_validators, candidate_infos, force_approve, meta, block_tick })
+}
+
+/// Writes the prepared entries of a chain of blocks, ordered such that parents come before their
+/// children, to the approval DB in one batch and notifies chain-selection of the blocks which are
+/// approved already.
+async fn write_block_entries<Sender: SubsystemSender<ChainSelectionMessage>, B: Backend>(
+	sender: &mut Sender,
+	db: &mut OverlayedBackend<'_, B>,
+	pending_entries: Vec<PendingBlockEntry>,
+) -> SubsystemResult<Vec<(BlockApprovalMeta, BlockImportedCandidates)>> {
+	let mut entries = Vec::with_capacity(pending_entries.len());
+	let mut written = Vec::with_capacity(pending_entries.len());
+	for pending in pending_entries {
+		let PendingBlockEntry {
+			entry,
+			n_validators,
+			candidate_infos,
+			force_approve,
+			meta,
+			block_tick,
+		} = pending;
+		let fully_insta_approved =
+			entry.approved_bitfield.count_ones() == entry.approved_bitfield.len();
+		entries.push((entry.into(), n_validators, move |candidate_hash: &CandidateHash| {
+			candidate_infos.get(candidate_hash).cloned()
+		}));
+		written.push((meta, block_tick, force_approve, fully_insta_approved));
+	}
+
+	let candidate_entries = crate::ops::add_block_entries(db, entries)
+		.map_err(|e| SubsystemError::with_origin(ADD_BLOCK_ENTRY_ORIGIN, e))?;
+
+	let mut imported = Vec::with_capacity(written.len());
+	for ((meta, block_tick, force_approve, fully_insta_approved), candidate_entries) in
+		written.into_iter().zip(candidate_entries)
+	{
+		let block_hash = meta.hash;
+
+		// If all bits are already set, then send an approve message. It goes on the same
+		// unbounded channel as the force-approvals below, so chain-selection sees the two in
+		// order.
+		if fully_insta_approved {
+			sender.send_unbounded_message(ChainSelectionMessage::Approved(block_hash));
+		}
+
+		// force-approve needs to load the current block entry as well as all
+		// ancestors. this can only be done after writing the block entries above, which would
+		// otherwise overwrite the approval of the current block if it is force-approved as well.
+		if let Some(up_to) = force_approve {
+			gum::debug!(target: LOG_TARGET, ?block_hash, up_to, "Enacting force-approve");
+			let approved_hashes = crate::ops::force_approve(db, block_hash, up_to)
+				.map_err(|e| SubsystemError::with_origin(FORCE_APPROVE_ORIGIN, e))?;
+			gum::debug!(
+				target: LOG_TARGET,
+				?block_hash,
+				up_to,
+				"Force-approving {} blocks",
+				approved_hashes.len()
+			);
+
+			// Notify chain-selection of all approved hashes. These may span many blocks, so they
+			// are sent unbounded, in order, instead of waiting for each of them in the import
+			// loop.
+			for hash in approved_hashes {
+				sender.send_unbounded_message(ChainSelectionMessage::Approved(hash));
+			}
+		}
+
+		let candidates = BlockImportedCandidates {
+			fully_insta_approved,
+			..BlockImportedCandidates::new(
+				block_hash,
+				meta.number,
+				block_tick,
+				candidate_entries.into_iter().map(|(h, e)| (h, e.into())).collect(),
+			)
+		};
+		imported.push((meta, candidates));
+	}
+
+	Ok(imported)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use crate::{
+		approval_db::common::{load_block_entry, DbBackend},
+		RuntimeInfo, RuntimeInfoConfig,
+	};
+	use approval_types::time::Clock;
+	use assert_matches::assert_matches;
+	use polkadot_node_primitives::{
+		approval::{
+			v1::{VrfSignature, VrfTranscript},
+			v2::{AssignmentCertKindV2, AssignmentCertV2},
+		},
+		DISPUTE_WINDOW,
+	};
+	use polkadot_node_subsystem::{
+		messages::{AllMessages, ApprovalVotingMessage},
+		SubsystemContext,
+	};
+	use polkadot_node_subsystem_test_helpers::{
+		make_subsystem_context, TestSubsystemContextHandle,
+	};
+	use polkadot_node_subsystem_util::{
+		database::Database,
+		metrics::{prometheus, Metrics as _},
+	};
+	use polkadot_primitives::{
+		node_features::FeatureIndex, vstaging::MutateDescriptorV2, ExecutorParams, Id as ParaId,
+		IndexedVec, NodeFeatures, SessionInfo, ValidatorId, ValidatorIndex,
+	};
+	use polkadot_primitives_test_helpers::{dummy_candidate_receipt_v2, dummy_hash};
+	use schnellru::{ByLength, LruMap};
+	pub(crate) use sp_consensus_babe::{
+		digests::{CompatibleDigestItem, PreDigest, SecondaryVRFPreDigest},
+		AllowedSlots, BabeEpochConfiguration, Epoch as BabeEpoch,
+	};
+	use sp_core::{crypto::VrfSecret, testing::TaskExecutor};
+	use sp_keyring::sr25519::Keyring as Sr25519Keyring;
+	pub(crate) use sp_runtime::{Digest, DigestItem};
+	use std::{pin::Pin, sync::Arc};
+
+	use crate::{approval_db::common::Config as DatabaseConfig, criteria, BlockEntry};
+
+	const DATA_COL: u32 = 0;
+
+	const NUM_COLUMNS: u32 = 1;
+
+	const TEST_CONFIG: DatabaseConfig = DatabaseConfig { col_approval_data: DATA_COL };
+	#[derive(Default)]
+	struct MockClock;
+
+	impl Clock for MockClock {
+		fn tick_now(&self) -> Tick {
+			42 // chosen by fair dice roll
+		}
+
+		fn wait(&self, _tick: Tick) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>> {
+			Box::pin(async move { () })
+		}
+	}
+
+	fn blank_state() -> State {
+		State::with_defaults(
+			Arc::new(LocalKeystore::in_memory()),
+			Arc::new(MockClock::default()),
+			Box::new(MockAssignmentCriteria::default()),
+		)
+	}
+
+	// An in-memory database holding the block entry of block 4 of session 5 at slot 10, and the
+	// header of block 5 on top of it, claiming the same slot.
+	struct ImportFixture {
+		db: DbBackend,
+		db_writer: Arc<dyn Database>,
+		session: SessionIndex,
+		parent_hash: Hash,
+		header: Header,
+		hash: Hash,
+	}
+
+	fn import_fixture() -> ImportFixture {
+		let db = kvdb_memorydb::create(NUM_COLUMNS);
+		let db = polkadot_node_subsystem_util::database::kvdb_impl::DbAdapter::new(db, &[]);
+		let db_writer: Arc<dyn Database> = Arc::new(db);
+		let mut db = DbBackend::new(db_writer.clone(), TEST_CONFIG);
+
+		let session = 5;
+		let slot = Slot::from(10);
+		let parent_hash = Hash::repeat_byte(0x01);
+
+		let header = Header {
+			digest: {
+				let mut d = Digest::default();
+				let vrf_signature = garbage_vrf_signature();
+				d.push(DigestItem::babe_pre_digest(PreDigest::SecondaryVRF(
+					SecondaryVRFPreDigest { authority_index: 0, slot, vrf_signature },
+				)));
+
+				d
+			},
+			extrinsics_root: Default::default(),
+			number: 5,
+			state_root: Default::default(),
+			parent_hash,
+		};
+		let hash = header.hash();
+
+		let mut overlay_db = OverlayedBackend::new(&db);
+		overlay_db.write_block_entry(
+			v3::BlockEntry {
+				block_hash: parent_hash,
+				parent_hash: Default::default(),
+				block_number: 4,
+				session,
+				slot,
+				relay_vrf_story: Default::default(),
+				candidates: Vec::new(),
+				approved_bitfield: Default::default(),
+				children: Vec::new(),
+				candidates_pending_signature: Default::default(),
+				distributed_assignments: Default::default(),
+			}
+			.into(),
+		);
+
+		let write_ops = overlay_db.into_write_ops();
+		db.write(write_ops).unwrap();
+
+		ImportFixture { db, db_writer, session, parent_hash, header, hash }
+	}
+
+	fn single_session_state() -> (State, RuntimeInfo) {
+		(
+			blank_state(),
+			RuntimeInfo::new_with_config(RuntimeInfoConfig {
+				keystore: None,
+				session_cache_lru_size: DISPUTE_WINDOW.get(),
+			}),
+		)
+	}
+
+	// Prepares and writes the entry of a single block, like a window holding only that block.
+	async fn import_block_entry<
+		Sender: SubsystemSender<RuntimeApiMessage> + SubsystemSender<ChainSelectionMessage>,
+		B: Backend,
+	>(
+		sender: &mut Sender,
+		state: &mut State,
+		db: &mut OverlayedBackend<'_, B>,
+		session_info_provider: &mut RuntimeInfo,
+		head: Hash,
+		block_hash: Hash,
+		block_header: Header,
+		imported_block_info: ImportedBlockInfo,
+		metrics: &Metrics,
+	) -> SubsystemResult<Result<(BlockApprovalMeta, BlockImportedCandidates), ImportedBlockInfoError>>
+	{
+		let pending = match prepare_block_entry(
+			sender,
+			state,
+			session_info_provider,
+			head,
+			block_hash,
+			block_header,
+			imported_block_info,
+			metrics,
+		)
+		.await
+		{
+			Ok(pending) => pending,
+			Err(error) => return Ok(Err(error)),
+		};
+
+		let mut imported = write_block_entries(sender, db, vec![pending]).await?;
+		Ok(Ok(imported.pop().expect("one block entry was written; qed")))
+	}
+
+	#[derive(Default)]
+	struct MockAssignmentCriteria {
+		enable_v2: bool,
+		assignments: HashMap<CoreIndex, OurAssignment>,
+	}
+
+	impl AssignmentCriteria for MockAssignmentCriteria {
+		fn compute_assignments(
+			&self,
+			_keystore: &LocalKeystore,
+			_relay_vrf_story: polkadot_node_primitives::approval::v1::RelayVRFStory,
+			_config: &criteria::Config,
+			_leaving_cores: Vec<(
+				CandidateHash,
+				polkadot_primitives::CoreIndex,
+				polkadot_primitives::GroupIndex,
+			)>,
+			enable_assignments_v2: bool,
+		) -> HashMap<polkadot_primitives::CoreIndex, criteria::OurAssignment> {
+			assert_eq!(enable_assignments_v2, self.enable_v2);
+			self.assignments.clone()
+		}
+
+		fn check_assignment_cert(
+			&self,
+			_claimed_core_bitfield: polkadot_node_primitives::approval::v2::CoreBitfield,
+			_validator_index: polkadot_primitives::ValidatorIndex,
+			_config: &criteria::Config,
+			_relay_vrf_story: polkadot_node_primitives::approval::v1::RelayVRFStory,
+			_assignment: &polkadot_node_primitives::approval::v2::AssignmentCertV2,
+			_backing_groups: Vec<polkadot_primitives::GroupIndex>,
+		) -> Result<polkadot_node_primitives::approval::v1::DelayTranche, criteria::InvalidAssignment>
+		{
+			Ok(0)
+		}
+	}
+
+	// used for generating assignments where the validity of the VRF doesn't matter.
+	pub(crate) fn garbage_vrf_signature() -> VrfSignature {
+		let transcript = VrfTranscript::new(b"test-garbage", &[]);
+		Sr25519Keyring::Alice.pair().vrf_sign(&transcript.into())
+	}
+
+	// used for checking the relay VRF story stored for imported blocks.
+	pub(crate) fn relay_vrf_story_from_seed(seed: u8) -> RelayVRFStory {
+		RelayVRFStory([seed; 32])
+	}
+
+	fn dummy_session_info(index: SessionIndex) -> SessionInfo {
+		SessionInfo {
+			validators: Default::default(),
+			discovery_keys: Vec::new(),
+			assignment_keys: Vec::new(),
+			validator_groups: Default::default(),
+			n_cores: index as _,
+			zeroth_delay_tranche_width: index as _,
+			relay_vrf_modulo_samples: index as _,
+			n_delay_tranches: index as _,
 			no_show_slots: index as _,
-			needed_approvals: index as _,
+			// there are no validators, so there must be no approvals needed either
+			needed_approvals: 0,
 			active_validator_indices: Vec::new(),
 			dispute_period: 6,
 			random_seed: [0u8; 32],
 		}
 	}
 
-	#[test]
-	fn imported_block_info_is_good() {
-		for enable_v2 in [false, true] {
-			let pool = TaskExecutor::new();
-			let (mut ctx, mut handle) =
-				make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
-
-			let session = 5;
-			let session_info = dummy_session_info(session);
+	// Answers to the runtime API requests made by `imported_block_info`.
+	struct ImportRequestResponses {
+		events: Result<Vec<CandidateEvent>, RuntimeApiError>,
+		session: SessionIndex,
+		babe_epoch: BabeEpoch,
+		session_info: SessionInfo,
+		executor_params: ExecutorParams,
+		node_features: NodeFeatures,
+	}
+
+	impl ImportRequestResponses {
+		// Answers for a block including the candidates of `events` in `session`, with the
+		// session info of `dummy_session_info`.
+		fn new(session: SessionIndex, events: Vec<CandidateEvent>) -> Self {
+			ImportRequestResponses {
+				events: Ok(events),
+				session,
+				babe_epoch: BabeEpoch {
+					epoch_index: session as _,
+					start_slot: Slot::from(0),
+					duration: 200,
+					authorities: vec![(Sr25519Keyring::Alice.public().into(), 1)],
+					randomness: [0u8; 32],
+					config: BabeEpochConfiguration {
+						c: (1, 4),
+						allowed_slots: AllowedSlots::PrimarySlots,
+					},
+				},
+				session_info: dummy_session_info(session),
+				executor_params: ExecutorParams::default(),
+				node_features: NodeFeatures::EMPTY,
+			}
+		}
+
+		// Answers for a block including no candidates in `session`.
+		fn without_candidates(session: SessionIndex) -> Self {
+			Self::new(session, Vec::new())
+		}
+	}
+
+	// Drives the runtime API requests made by `imported_block_info` for `header`, in the order
+	// they are made, checking that they are made at the right blocks.
+	async fn respond_to_import_requests(
+		handle: &mut TestSubsystemContextHandle<ApprovalVotingMessage>,
+		header: &Header,
+		responses: ImportRequestResponses,
+	) {
+		respond_to_block_requests(handle, header, &responses).await;
+		respond_to_session_requests(handle, header.hash(), &responses).await;
+	}
+
+	// Drives the requests made for every imported block, up to and including the BABE epoch.
+	async fn respond_to_block_requests(
+		handle: &mut TestSubsystemContextHandle<ApprovalVotingMessage>,
+		header: &Header,
+		responses: &ImportRequestResponses,
+	) {
+		let hash = header.hash();
+
+		assert_matches!(
+			handle.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				h,
+				RuntimeApiRequest::CandidateEvents(c_tx),
+			)) => {
+				assert_eq!(h, hash);
+				let _ = c_tx.send(responses.events.clone());
+			}
+		);
+
+		assert_matches!(
+			handle.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				h,
+				RuntimeApiRequest::SessionIndexForChild(c_tx),
+			)) => {
+				assert_eq!(h, header.parent_hash);
+				let _ = c_tx.send(Ok(responses.session));
+			}
+		);
+
+		assert_matches!(
+			handle.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				h,
+				RuntimeApiRequest::CurrentBabeEpoch(c_tx),
+			)) => {
+				assert_eq!(h, hash);
+				let _ = c_tx.send(Ok(responses.babe_epoch.clone()));
+			}
+		);
+	}
+
+	// Drives the requests made at `block_hash` to fill the session info cache, which are only made
+	// for the first block of a session.
+	async fn respond_to_session_requests(
+		handle: &mut TestSubsystemContextHandle<ApprovalVotingMessage>,
+		block_hash: Hash,
+		responses: &ImportRequestResponses,
+	) {
+		assert_matches!(
+			handle.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				h,
+				RuntimeApiRequest::SessionInfo(idx, si_tx),
+			)) => {
+				assert_eq!(idx, responses.session);
+				assert_eq!(h, block_hash);
+				si_tx.send(Ok(Some(responses.session_info.clone()))).unwrap();
+			}
+		);
+
+		assert_matches!(
+			handle.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				h,
+				RuntimeApiRequest::SessionExecutorParams(idx, si_tx),
+			)) => {
+				assert_eq!(idx, responses.session);
+				assert_eq!(h, block_hash);
+				si_tx.send(Ok(Some(responses.executor_params.clone()))).unwrap();
+			}
+		);
+
+		assert_matches!(
+			handle.recv().await,
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				_,
+				RuntimeApiRequest::NodeFeatures(_, si_tx),
+			)) => {
+				si_tx.send(Ok(responses.node_features.clone())).unwrap();
+			}
+		);
+	}
+
+	// Answers `msg` if it is one of the runtime API requests made by `imported_block_info`, for
+	// tests importing several blocks whose requests are not checked one by one. Any other message
+	// is handed back.
+	fn answer_import_request(
+		msg: AllMessages,
+		responses: &ImportRequestResponses,
+	) -> Option<AllMessages> {
+		match msg {
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				_,
+				RuntimeApiRequest::CandidateEvents(c_tx),
+			)) => {
+				let _ = c_tx.send(responses.events.clone());
+			},
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				_,
+				RuntimeApiRequest::SessionIndexForChild(c_tx),
+			)) => {
+				let _ = c_tx.send(Ok(responses.session));
+			},
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				_,
+				RuntimeApiRequest::CurrentBabeEpoch(c_tx),
+			)) => {
+				let _ = c_tx.send(Ok(responses.babe_epoch.clone()));
+			},
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				_,
+				RuntimeApiRequest::SessionInfo(_, si_tx),
+			)) => {
+				let _ = si_tx.send(Ok(Some(responses.session_info.clone())));
+			},
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				_,
+				RuntimeApiRequest::SessionExecutorParams(_, si_tx),
+			)) => {
+				let _ = si_tx.send(Ok(Some(responses.executor_params.clone())));
+			},
+			AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				_,
+				RuntimeApiRequest::NodeFeatures(_, si_tx),
+			)) => {
+				let _ = si_tx.send(Ok(responses.node_features.clone()));
+			},
+			msg => return Some(msg),
+		}
+
+		None
+	}
+
+	#[test]
+	fn lower_bound_is_finalized_block_within_look_back() {
+		assert_eq!(compute_lower_bound(100, 10, Some(95)), 95);
+		assert_eq!(compute_lower_bound(100, 10, Some(90)), 90);
+	}
+
+	#[test]
+	fn lower_bound_above_head_when_finalized_above_head() {
+		assert_eq!(compute_lower_bound(100, 10, Some(105)), 105);
+	}
+
+	#[test]
+	fn lower_bound_is_look_back_window_when_finalized_below_it() {
+		assert_eq!(compute_lower_bound(100, 10, Some(50)), 90);
+		assert_eq!(compute_lower_bound(5, 10, Some(0)), 0);
+	}
+
+	#[test]
+	fn lower_bound_is_look_back_window_without_finalized() {
+		assert_eq!(compute_lower_bound(100, 10, None), 90);
+		assert_eq!(compute_lower_bound(5, 10, None), 0);
+	}
+
+	#[test]
+	fn imported_block_info_is_good() {
+		for enable_v2 in [false, true] {
+			let pool = TaskExecutor::new();
+			let (mut ctx, mut handle) =
+				make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+			let session = 5;
+
+			let slot = Slot::from(10);
+			let header = Header {
+				digest: {
+					let mut d = Digest::default();
+					let vrf_signature = garbage_vrf_signature();
+					d.push(DigestItem::babe_pre_digest(PreDigest::SecondaryVRF(
+						SecondaryVRFPreDigest { authority_index: 0, slot, vrf_signature },
+					)));
+
+					d
+				},
+				extrinsics_root: Default::default(),
+				number: 5,
+				state_root: Default::default(),
+				parent_hash: Default::default(),
+			};
+
+			let hash = header.hash();
+			let make_candidate = |para_id| {
+				let mut r = dummy_candidate_receipt_v2(dummy_hash());
+				r.descriptor.set_para_id(para_id);
+				r.descriptor.set_relay_parent(hash);
+				r
+			};
+			let candidates = vec![
+				(make_candidate(1.into()), CoreIndex(0), GroupIndex(2)),
+				(make_candidate(2.into()), CoreIndex(1), GroupIndex(3)),
+			];
+
+			let inclusion_events = candidates
+				.iter()
+				.cloned()
+				.map(|(r, c, g)| CandidateEvent::CandidateIncluded(r, Vec::new().into(), c, g))
+				.collect::<Vec<_>>();
+
+			let test_fut = {
+				let included_candidates = candidates
+					.iter()
+					.map(|(r, c, g)| (r.hash(), r.clone(), *c, *g))
+					.collect::<Vec<_>>();
+
+				let mut runtime_info = RuntimeInfo::new_with_config(RuntimeInfoConfig {
+					keystore: None,
+					session_cache_lru_size: DISPUTE_WINDOW.get(),
+				});
+
+				let header = header.clone();
+				Box::pin(async move {
+					// Warned about, but still imported.
+					assert!(has_unexpected_zero_parent(&header));
+
+					let registry = prometheus::Registry::new();
+					let metrics = Metrics::try_register(&registry).unwrap();
+					let env = ImportedBlockInfoEnv {
+						runtime_info: &mut runtime_info,
+						assignment_criteria: &MockAssignmentCriteria {
+							enable_v2,
+							..Default::default()
+						},
+						keystore: &LocalKeystore::in_memory(),
+						relay_parent_check: RelayParentCheck::Disabled,
+						tolerate_candidate_events_errors: false,
+						core_filter: None,
+						shadow_criteria: None,
+						assignment_observer: None,
+						dev_mode: false,
+						force_approve_target: ForceApproveTarget::Exclusive,
+						metrics: &metrics,
+					};
+
+					let info = imported_block_info(ctx.sender(), env, hash, &header, &Some(4))
+						.await
+						.unwrap();
+
+					assert_eq!(info.included_candidates, included_candidates);
+					assert_eq!(info.session_index, session);
+					assert!(info.assignments.is_empty());
+					assert_eq!(info.n_validators, 0);
+					assert_eq!(info.n_cores, session);
+					assert_eq!(info.slot, slot);
+					assert!(info.force_approve.is_none());
+					assert_eq!(
+						info.node_feature_enabled(FeatureIndex::EnableAssignmentsV2),
+						enable_v2,
+					);
+
+					let labels = registry
+						.gather()
+						.into_iter()
+						.find(|family| {
+							family.get_name() == "polkadot_parachain_assignments_computed_total"
+						})
+						.expect("assignments metric is registered")
+						.get_metric()
+						.iter()
+						.map(|m| m.get_label()[0].get_value().to_owned())
+						.collect::<Vec<_>>();
+					assert_eq!(labels, vec![enable_v2.to_string()]);
+				})
+			};
+
+			let aux_fut = Box::pin(async move {
+				let node_features =
+					NodeFeatures::repeat(enable_v2, FeatureIndex::EnableAssignmentsV2 as usize + 1);
+				respond_to_import_requests(
+					&mut handle,
+					&header,
+					ImportRequestResponses {
+						node_features,
+						..ImportRequestResponses::new(session, inclusion_events)
+					},
+				)
+				.await;
+			});
+
+			futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+		}
+	}
+
+	#[test]
+	fn imported_block_info_records_our_assignment_tranches() {
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let session = 5;
+
+		let slot = Slot::from(10);
+		let header = Header {
+			digest: {
+				let mut d = Digest::default();
+				let vrf_signature = garbage_vrf_signature();
+				d.push(DigestItem::babe_pre_digest(PreDigest::SecondaryVRF(
+					SecondaryVRFPreDigest { authority_index: 0, slot, vrf_signature },
+				)));
+
+				d
+			},
+			extrinsics_root: Default::default(),
+			number: 5,
+			state_root: Default::default(),
+			parent_hash: Default::default(),
+		};
+		let hash = header.hash();
+
+		let assignments = [(0, 0), (1, 0), (2, 1), (3, 3)]
+			.into_iter()
+			.map(|(core, tranche)| {
+				let cert = AssignmentCertV2 {
+					kind: AssignmentCertKindV2::RelayVRFDelay { core_index: CoreIndex(core) },
+					vrf: garbage_vrf_signature(),
+				};
+				(CoreIndex(core), OurAssignment::new(cert, tranche, ValidatorIndex(0), false))
+			})
+			.collect();
+
+		let test_fut = {
+			let mut runtime_info = RuntimeInfo::new_with_config(RuntimeInfoConfig {
+				keystore: None,
+				session_cache_lru_size: DISPUTE_WINDOW.get(),
+			});
+
+			let header = header.clone();
+			Box::pin(async move {
+				let registry = prometheus::Registry::new();
+				let metrics = Metrics::try_register(&registry).unwrap();
+				let env = ImportedBlockInfoEnv {
+					runtime_info: &mut runtime_info,
+					assignment_criteria: &MockAssignmentCriteria { enable_v2: false, assignments },
+					keystore: &LocalKeystore::in_memory(),
+					relay_parent_check: RelayParentCheck::Disabled,
+					tolerate_candidate_events_errors: false,
+					core_filter: None,
+					shadow_criteria: None,
+					assignment_observer: None,
+					dev_mode: false,
+					force_approve_target: ForceApproveTarget::Exclusive,
+					metrics: &metrics,
+				};
+
+				let info =
+					imported_block_info(ctx.sender(), env, hash, &header, &Some(4)).await.unwrap();
+				assert_eq!(info.assignments.len(), 4);
+
+				let families = registry.gather();
+				let histogram = families
+					.iter()
+					.find(|family| {
+						family.get_name() == "polkadot_parachain_approval_our_assignment_tranches"
+					})
+					.expect("tranches metric is registered")
+					.get_metric()[0]
+					.get_histogram();
+				assert_eq!(histogram.get_sample_count(), 4);
+
+				// Buckets are cumulative: two assignments at tranche 0, one at 1 and one at 3.
+				let buckets = histogram
+					.get_bucket()
+					.iter()
+					.take(5)
+					.map(|b| (b.get_upper_bound(), b.get_cumulative_count()))
+					.collect::<Vec<_>>();
+				assert_eq!(buckets, vec![(0.0, 2), (1.0, 3), (2.0, 3), (3.0, 4), (4.0, 4)]);
+			})
+		};
+
+		let aux_fut = Box::pin(async move {
+			respond_to_import_requests(
+				&mut handle,
+				&header,
+				ImportRequestResponses::without_candidates(session),
+			)
+			.await;
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
+	#[test]
+	fn core_filter_restricts_computed_assignments() {
+		// Assigns us to every leaving core it is asked about.
+		struct AssignToLeavingCores;
+
+		impl AssignmentCriteria for AssignToLeavingCores {
+			fn compute_assignments(
+				&self,
+				_keystore: &LocalKeystore,
+				_relay_vrf_story: polkadot_node_primitives::approval::v1::RelayVRFStory,
+				_config: &criteria::Config,
+				leaving_cores: Vec<(
+					CandidateHash,
+					polkadot_primitives::CoreIndex,
+					polkadot_primitives::GroupIndex,
+				)>,
+				_enable_assignments_v2: bool,
+			) -> HashMap<polkadot_primitives::CoreIndex, criteria::OurAssignment> {
+				leaving_cores
+					.into_iter()
+					.map(|(_, core, _)| {
+						let cert = AssignmentCertV2 {
+							kind: AssignmentCertKindV2::RelayVRFDelay { core_index: core },
+							vrf: garbage_vrf_signature(),
+						};
+						(core, OurAssignment::new(cert, 0, ValidatorIndex(0), false))
+					})
+					.collect()
+			}
+
+			fn check_assignment_cert(
+				&self,
+				_claimed_core_bitfield: polkadot_node_primitives::approval::v2::CoreBitfield,
+				_validator_index: polkadot_primitives::ValidatorIndex,
+				_config: &criteria::Config,
+				_relay_vrf_story: polkadot_node_primitives::approval::v1::RelayVRFStory,
+				_assignment: &polkadot_node_primitives::approval::v2::AssignmentCertV2,
+				_backing_groups: Vec<polkadot_primitives::GroupIndex>,
+			) -> Result<polkadot_node_primitives::approval::v1::DelayTranche, criteria::InvalidAssignment>
+			{
+				Ok(0)
+			}
+		}
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let session = 5;
+
+		let slot = Slot::from(10);
+		let header = Header {
+			digest: {
+				let mut d = Digest::default();
+				let vrf_signature = garbage_vrf_signature();
+				d.push(DigestItem::babe_pre_digest(PreDigest::SecondaryVRF(
+					SecondaryVRFPreDigest { authority_index: 0, slot, vrf_signature },
+				)));
+
+				d
+			},
+			extrinsics_root: Default::default(),
+			number: 5,
+			state_root: Default::default(),
+			parent_hash: Default::default(),
+		};
+		let hash = header.hash();
+
+		let make_candidate = |para_id| {
+			let mut r = dummy_candidate_receipt_v2(dummy_hash());
+			r.descriptor.set_para_id(para_id);
+			r.descriptor.set_relay_parent(hash);
+			r
+		};
+		let inclusion_events = vec![
+			(make_candidate(1.into()), CoreIndex(0), GroupIndex(2)),
+			(make_candidate(2.into()), CoreIndex(1), GroupIndex(3)),
+		]
+		.into_iter()
+		.map(|(r, c, g)| CandidateEvent::CandidateIncluded(r, Vec::new().into(), c, g))
+		.collect::<Vec<_>>();
+
+		let test_fut = {
+			let mut runtime_info = RuntimeInfo::new_with_config(RuntimeInfoConfig {
+				keystore: None,
+				session_cache_lru_size: DISPUTE_WINDOW.get(),
+			});
+
+			let header = header.clone();
+			Box::pin(async move {
+				let core_filter = [CoreIndex(0)].into_iter().collect();
+				let env = ImportedBlockInfoEnv {
+					runtime_info: &mut runtime_info,
+					assignment_criteria: &AssignToLeavingCores,
+					keystore: &LocalKeystore::in_memory(),
+					relay_parent_check: RelayParentCheck::Disabled,
+					tolerate_candidate_events_errors: false,
+					core_filter: Some(&core_filter),
+					shadow_criteria: None,
+					assignment_observer: None,
+					dev_mode: false,
+					force_approve_target: ForceApproveTarget::Exclusive,
+					metrics: &Metrics::default(),
+				};
+
+				let info =
+					imported_block_info(ctx.sender(), env, hash, &header, &Some(4)).await.unwrap();
+
+				// both candidates are imported, but only the filtered core is assigned
+				assert_eq!(info.included_candidates.len(), 2);
+				assert_eq!(
+					info.assignments.keys().copied().collect::<Vec<_>>(),
+					vec![CoreIndex(0)],
+				);
+			})
+		};
+
+		let aux_fut = Box::pin(async move {
+			respond_to_import_requests(
+				&mut handle,
+				&header,
+				ImportRequestResponses::new(session, inclusion_events),
+			)
+			.await;
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
+	#[test]
+	fn cores_without_assignment_are_reported_unassigned() {
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let session = 5;
+
+		let slot = Slot::from(10);
+		let header = Header {
+			digest: {
+				let mut d = Digest::default();
+				let vrf_signature = garbage_vrf_signature();
+				d.push(DigestItem::babe_pre_digest(PreDigest::SecondaryVRF(
+					SecondaryVRFPreDigest { authority_index: 0, slot, vrf_signature },
+				)));
+
+				d
+			},
+			extrinsics_root: Default::default(),
+			number: 5,
+			state_root: Default::default(),
+			parent_hash: Default::default(),
+		};
+		let hash = header.hash();
+
+		let make_candidate = |para_id| {
+			let mut r = dummy_candidate_receipt_v2(dummy_hash());
+			r.descriptor.set_para_id(para_id);
+			r.descriptor.set_relay_parent(hash);
+			r
+		};
+		let inclusion_events = vec![
+			(make_candidate(1.into()), CoreIndex(0), GroupIndex(2)),
+			(make_candidate(2.into()), CoreIndex(1), GroupIndex(3)),
+		]
+		.into_iter()
+		.map(|(r, c, g)| CandidateEvent::CandidateIncluded(r, Vec::new().into(), c, g))
+		.collect::<Vec<_>>();
+
+		let test_fut = {
+			let mut runtime_info = RuntimeInfo::new_with_config(RuntimeInfoConfig {
+				keystore: None,
+				session_cache_lru_size: DISPUTE_WINDOW.get(),
+			});
+
+			let header = header.clone();
+			Box::pin(async move {
+				// only the first of the two cores is assigned to us
+				let cert = AssignmentCertV2 {
+					kind: AssignmentCertKindV2::RelayVRFDelay { core_index: CoreIndex(0) },
+					vrf: garbage_vrf_signature(),
+				};
+				let assignment = OurAssignment::new(cert, 0, ValidatorIndex(0), false);
+				let assignment_criteria = MockAssignmentCriteria {
+					assignments: [(CoreIndex(0), assignment)].into_iter().collect(),
+					..Default::default()
+				};
+				let env = ImportedBlockInfoEnv {
+					runtime_info: &mut runtime_info,
+					assignment_criteria: &assignment_criteria,
+					keystore: &LocalKeystore::in_memory(),
+					relay_parent_check: RelayParentCheck::Disabled,
+					tolerate_candidate_events_errors: false,
+					core_filter: None,
+					shadow_criteria: None,
+					assignment_observer: None,
+					dev_mode: false,
+					force_approve_target: ForceApproveTarget::Exclusive,
+					metrics: &Metrics::default(),
+				};
+
+				let info =
+					imported_block_info(ctx.sender(), env, hash, &header, &Some(4)).await.unwrap();
+
+				assert_eq!(info.included_candidates.len(), 2);
+				assert_eq!(info.unassigned_cores(), vec![CoreIndex(1)]);
+			})
+		};
+
+		let aux_fut = Box::pin(async move {
+			respond_to_import_requests(
+				&mut handle,
+				&header,
+				ImportRequestResponses::new(session, inclusion_events),
+			)
+			.await;
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
+	#[test]
+	fn diverging_shadow_criteria_are_reported() {
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let session = 5;
+
+		let slot = Slot::from(10);
+		let header = Header {
+			digest: {
+				let mut d = Digest::default();
+				let vrf_signature = garbage_vrf_signature();
+				d.push(DigestItem::babe_pre_digest(PreDigest::SecondaryVRF(
+					SecondaryVRFPreDigest { authority_index: 0, slot, vrf_signature },
+				)));
+
+				d
+			},
+			extrinsics_root: Default::default(),
+			number: 5,
+			state_root: Default::default(),
+			parent_hash: Default::default(),
+		};
+		let hash = header.hash();
+
+		let make_candidate = |para_id| {
+			let mut r = dummy_candidate_receipt_v2(dummy_hash());
+			r.descriptor.set_para_id(para_id);
+			r.descriptor.set_relay_parent(hash);
+			r
+		};
+		let inclusion_events = vec![
+			(make_candidate(1.into()), CoreIndex(0), GroupIndex(2)),
+			(make_candidate(2.into()), CoreIndex(1), GroupIndex(3)),
+		]
+		.into_iter()
+		.map(|(r, c, g)| CandidateEvent::CandidateIncluded(r, Vec::new().into(), c, g))
+		.collect::<Vec<_>>();
+
+		let test_fut = {
+			let mut runtime_info = RuntimeInfo::new_with_config(RuntimeInfoConfig {
+				keystore: None,
+				session_cache_lru_size: DISPUTE_WINDOW.get(),
+			});
+
+			let header = header.clone();
+			Box::pin(async move {
+				// the primary criteria only assign us to the first of the two cores
+				let cert = AssignmentCertV2 {
+					kind: AssignmentCertKindV2::RelayVRFDelay { core_index: CoreIndex(0) },
+					vrf: garbage_vrf_signature(),
+				};
+				let assignment = OurAssignment::new(cert, 0, ValidatorIndex(0), false);
+				let assignment_criteria = MockAssignmentCriteria {
+					assignments: [(CoreIndex(0), assignment)].into_iter().collect(),
+					..Default::default()
+				};
+				// the shadow criteria don't assign us to any core
+				let shadow_criteria = MockAssignmentCriteria::default();
+				let registry = prometheus::Registry::new();
+				let metrics = Metrics::try_register(&registry).unwrap();
+				let env = ImportedBlockInfoEnv {
+					runtime_info: &mut runtime_info,
+					assignment_criteria: &assignment_criteria,
+					keystore: &LocalKeystore::in_memory(),
+					relay_parent_check: RelayParentCheck::Disabled,
+					tolerate_candidate_events_errors: false,
+					core_filter: None,
+					shadow_criteria: Some(&shadow_criteria),
+					assignment_observer: None,
+					dev_mode: false,
+					force_approve_target: ForceApproveTarget::Exclusive,
+					metrics: &metrics,
+				};
+
+				let info =
+					imported_block_info(ctx.sender(), env, hash, &header, &Some(4)).await.unwrap();
+
+				// only the primary assignments are used
+				assert_eq!(
+					info.assignments.keys().copied().collect::<Vec<_>>(),
+					vec![CoreIndex(0)],
+				);
+				let diverged = &metrics.0.as_ref().unwrap().shadow_assignments_diverged_total;
+				assert_eq!(diverged.get(), 1);
+			})
+		};
+
+		let aux_fut = Box::pin(async move {
+			respond_to_import_requests(
+				&mut handle,
+				&header,
+				ImportRequestResponses::new(session, inclusion_events),
+			)
+			.await;
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
+	#[test]
+	fn assignment_observer_is_called_once_per_assignment() {
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let session = 5;
+
+		let slot = Slot::from(10);
+		let header = Header {
+			digest: {
+				let mut d = Digest::default();
+				let vrf_signature = garbage_vrf_signature();
+				d.push(DigestItem::babe_pre_digest(PreDigest::SecondaryVRF(
+					SecondaryVRFPreDigest { authority_index: 0, slot, vrf_signature },
+				)));
+
+				d
+			},
+			extrinsics_root: Default::default(),
+			number: 5,
+			state_root: Default::default(),
+			parent_hash: Default::default(),
+		};
+		let hash = header.hash();
+
+		let make_candidate = |para_id| {
+			let mut r = dummy_candidate_receipt_v2(dummy_hash());
+			r.descriptor.set_para_id(para_id);
+			r.descriptor.set_relay_parent(hash);
+			r
+		};
+		let inclusion_events = vec![
+			(make_candidate(1.into()), CoreIndex(0), GroupIndex(2)),
+			(make_candidate(2.into()), CoreIndex(1), GroupIndex(3)),
+		]
+		.into_iter()
+		.map(|(r, c, g)| CandidateEvent::CandidateIncluded(r, Vec::new().into(), c, g))
+		.collect::<Vec<_>>();
+
+		let test_fut = {
+			let mut runtime_info = RuntimeInfo::new_with_config(RuntimeInfoConfig {
+				keystore: None,
+				session_cache_lru_size: DISPUTE_WINDOW.get(),
+			});
+
+			let header = header.clone();
+			Box::pin(async move {
+				// we are assigned to both cores
+				let assignment = |core_index, tranche| {
+					let cert = AssignmentCertV2 {
+						kind: AssignmentCertKindV2::RelayVRFDelay { core_index },
+						vrf: garbage_vrf_signature(),
+					};
+					(core_index, OurAssignment::new(cert, tranche, ValidatorIndex(0), false))
+				};
+				let assignment_criteria = MockAssignmentCriteria {
+					assignments: [assignment(CoreIndex(0), 0), assignment(CoreIndex(1), 2)]
+						.into_iter()
+						.collect(),
+					..Default::default()
+				};
+				let observed = std::sync::Mutex::new(Vec::new());
+				let observer = |core: &CoreIndex, assignment: &OurAssignment| {
+					observed.lock().unwrap().push((*core, assignment.tranche()));
+				};
+				let env = ImportedBlockInfoEnv {
+					runtime_info: &mut runtime_info,
+					assignment_criteria: &assignment_criteria,
+					keystore: &LocalKeystore::in_memory(),
+					relay_parent_check: RelayParentCheck::Disabled,
+					tolerate_candidate_events_errors: false,
+					core_filter: None,
+					shadow_criteria: None,
+					assignment_observer: Some(&observer),
+					dev_mode: false,
+					force_approve_target: ForceApproveTarget::Exclusive,
+					metrics: &Metrics::default(),
+				};
+
+				let info =
+					imported_block_info(ctx.sender(), env, hash, &header, &Some(4)).await.unwrap();
+
+				// the observer has seen each of the assignments being stored exactly once
+				let mut observed = observed.into_inner().unwrap();
+				observed.sort();
+				assert_eq!(observed, vec![(CoreIndex(0), 0), (CoreIndex(1), 2)]);
+				assert_eq!(info.assignments.len(), 2);
+			})
+		};
+
+		let aux_fut = Box::pin(async move {
+			respond_to_import_requests(
+				&mut handle,
+				&header,
+				ImportRequestResponses::new(session, inclusion_events),
+			)
+			.await;
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
+	#[test]
+	fn included_candidates_matches_v2_events() {
+		let relay_parent = Hash::repeat_byte(0x01);
+		let make_candidate = |para_id| {
+			let mut r = dummy_candidate_receipt_v2(dummy_hash());
+			r.descriptor.set_para_id(para_id);
+			r.descriptor.set_relay_parent(relay_parent);
+			r
+		};
+
+		let included = make_candidate(ParaId::from(1));
+		let backed = make_candidate(ParaId::from(2));
+		let events = vec![
+			CandidateEvent::CandidateBacked(
+				backed.clone(),
+				Vec::new().into(),
+				CoreIndex(1),
+				GroupIndex(1),
+			),
+			CandidateEvent::CandidateIncluded(
+				included.clone(),
+				Vec::new().into(),
+				CoreIndex(0),
+				GroupIndex(0),
+			),
+			CandidateEvent::CandidateTimedOut(backed, Vec::new().into(), CoreIndex(1)),
+		];
+
+		let expected = events
+			.clone()
+			.into_iter()
+			.filter_map(|e| match e {
+				CandidateEvent::CandidateIncluded(receipt, _, core, group) =>
+					Some((receipt.hash(), receipt, core, group)),
+				_ => None,
+			})
+			.collect::<Vec<_>>();
+
+		let candidates = included_candidates(events).unwrap();
+		assert_eq!(candidates, expected);
+		assert_eq!(candidates, vec![(included.hash(), included, CoreIndex(0), GroupIndex(0))]);
+	}
+
+	#[test]
+	fn included_candidates_rejects_duplicate_core() {
+		let make_candidate = |para_id| {
+			let mut r = dummy_candidate_receipt_v2(dummy_hash());
+			r.descriptor.set_para_id(para_id);
+			r
+		};
+
+		let events = vec![
+			CandidateEvent::CandidateIncluded(
+				make_candidate(ParaId::from(1)),
+				Vec::new().into(),
+				CoreIndex(0),
+				GroupIndex(0),
+			),
+			CandidateEvent::CandidateIncluded(
+				make_candidate(ParaId::from(2)),
+				Vec::new().into(),
+				CoreIndex(0),
+				GroupIndex(1),
+			),
+		];
+
+		assert_matches!(
+			included_candidates(events),
+			Err(ImportedBlockInfoError::DuplicateCore(CoreIndex(0)))
+		);
+	}
+
+	#[test]
+	fn imported_block_info_fails_if_no_babe_vrf() {
+		let (_, info) = imported_block_info_without_vrf(0, false);
+		assert_matches!(info, Err(ImportedBlockInfoError::VrfInfoUnavailable));
+	}
+
+	#[cfg(not(feature = "dev-mode"))]
+	#[test]
+	fn dev_mode_has_no_effect_without_feature() {
+		let (_, info) = imported_block_info_without_vrf(0, true);
+		assert_matches!(info, Err(ImportedBlockInfoError::VrfInfoUnavailable));
+	}
+
+	#[cfg(feature = "dev-mode")]
+	#[test]
+	fn imported_block_info_synthesizes_vrf_in_dev_mode() {
+		let (hash, info) = imported_block_info_without_vrf(0, true);
+		let info = info.unwrap();
+
+		assert_eq!(info.slot, Slot::default());
+		assert_eq!(info.relay_vrf_story, RelayVRFStory(hash.to_fixed_bytes()));
+		assert_eq!(info.included_candidates.len(), 2);
+	}
+
+	#[test]
+	fn imported_block_info_fails_if_approvals_needed_without_validators() {
+		let (_, info) = imported_block_info_without_vrf(1, false);
+		assert_matches!(info, Err(ImportedBlockInfoError::EmptyValidatorSet(5)));
+	}
+
+	// Gathers the info of a block including two candidates, whose header has no VRF, in a session
+	// without validators.
+	fn imported_block_info_without_vrf(
+		needed_approvals: u32,
+		dev_mode: bool,
+	) -> (Hash, Result<ImportedBlockInfo, ImportedBlockInfoError>) {
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let session = 5;
+		let session_info = SessionInfo { needed_approvals, ..dummy_session_info(session) };
+
+		let header = Header {
+			digest: Digest::default(),
+			extrinsics_root: Default::default(),
+			number: 5,
+			state_root: Default::default(),
+			parent_hash: Default::default(),
+		};
+
+		let hash = header.hash();
+		let make_candidate = |para_id| {
+			let mut r = dummy_candidate_receipt_v2(dummy_hash());
+			r.descriptor.set_para_id(para_id);
+			r.descriptor.set_relay_parent(hash);
+			r
+		};
+		let candidates = vec![
+			(make_candidate(1.into()), CoreIndex(0), GroupIndex(2)),
+			(make_candidate(2.into()), CoreIndex(1), GroupIndex(3)),
+		];
+
+		let inclusion_events = candidates
+			.iter()
+			.cloned()
+			.map(|(r, c, g)| CandidateEvent::CandidateIncluded(r, Vec::new().into(), c, g))
+			.collect::<Vec<_>>();
+
+		let test_fut = {
+			let mut runtime_info = RuntimeInfo::new_with_config(RuntimeInfoConfig {
+				keystore: None,
+				session_cache_lru_size: DISPUTE_WINDOW.get(),
+			});
+
+			let header = header.clone();
+			Box::pin(async move {
+				let env = ImportedBlockInfoEnv {
+					runtime_info: &mut runtime_info,
+					assignment_criteria: &MockAssignmentCriteria::default(),
+					keystore: &LocalKeystore::in_memory(),
+					relay_parent_check: RelayParentCheck::Disabled,
+					tolerate_candidate_events_errors: false,
+					core_filter: None,
+					shadow_criteria: None,
+					assignment_observer: None,
+					dev_mode,
+					force_approve_target: ForceApproveTarget::Exclusive,
+					metrics: &Metrics::default(),
+				};
+
+				imported_block_info(ctx.sender(), env, hash, &header, &Some(4)).await
+			})
+		};
+
+		let aux_fut = Box::pin(async move {
+			respond_to_import_requests(
+				&mut handle,
+				&header,
+				ImportRequestResponses {
+					session_info,
+					..ImportRequestResponses::new(session, inclusion_events)
+				},
+			)
+			.await;
+		});
+
+		let (info, _) = futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+		(hash, info)
+	}
+
+	#[test]
+	fn candidate_events_error_is_counted() {
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let header = Header {
+			digest: Digest::default(),
+			extrinsics_root: Default::default(),
+			number: 5,
+			state_root: Default::default(),
+			parent_hash: Default::default(),
+		};
+		let hash = header.hash();
+
+		let registry = prometheus::Registry::new();
+		let metrics = Metrics::try_register(&registry).unwrap();
+
+		let test_fut = {
+			let mut runtime_info = RuntimeInfo::new_with_config(RuntimeInfoConfig {
+				keystore: None,
+				session_cache_lru_size: DISPUTE_WINDOW.get(),
+			});
+
+			Box::pin(async move {
+				let env = ImportedBlockInfoEnv {
+					runtime_info: &mut runtime_info,
+					assignment_criteria: &MockAssignmentCriteria::default(),
+					keystore: &LocalKeystore::in_memory(),
+					relay_parent_check: RelayParentCheck::Disabled,
+					tolerate_candidate_events_errors: false,
+					core_filter: None,
+					shadow_criteria: None,
+					assignment_observer: None,
+					dev_mode: false,
+					force_approve_target: ForceApproveTarget::Exclusive,
+					metrics: &metrics,
+				};
+
+				let info = imported_block_info(ctx.sender(), env, hash, &header, &Some(4)).await;
+				assert_matches!(info, Err(ImportedBlockInfoError::RuntimeError(_)));
+
+				let errors = &metrics.0.as_ref().unwrap().import_runtime_api_errors_total;
+				assert_eq!(errors.with_label_values(&["CandidateEvents"]).get(), 1);
+				assert_eq!(errors.with_label_values(&["SessionIndexForChild"]).get(), 0);
+			})
+		};
+
+		let aux_fut = Box::pin(async move {
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					h,
+					RuntimeApiRequest::CandidateEvents(c_tx),
+				)) => {
+					assert_eq!(h, hash);
+					let _ = c_tx.send(Err(RuntimeApiError::NotSupported {
+						runtime_api_name: "candidate_events",
+					}));
+				}
+			);
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
+	#[test]
+	fn imported_block_info_drops_candidates_with_relay_parent_outside_window() {
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let session = 5;
+
+		let slot = Slot::from(10);
+		let parent_hash = Hash::repeat_byte(0x01);
+		let header = Header {
+			digest: {
+				let mut d = Digest::default();
+				let vrf_signature = garbage_vrf_signature();
+				d.push(DigestItem::babe_pre_digest(PreDigest::SecondaryVRF(
+					SecondaryVRFPreDigest { authority_index: 0, slot, vrf_signature },
+				)));
+
+				d
+			},
+			extrinsics_root: Default::default(),
+			number: 5,
+			state_root: Default::default(),
+			parent_hash,
+		};
+
+		let hash = header.hash();
+		let make_candidate = |para_id, relay_parent| {
+			let mut r = dummy_candidate_receipt_v2(dummy_hash());
+			r.descriptor.set_para_id(para_id);
+			r.descriptor.set_relay_parent(relay_parent);
+			r
+		};
+		let in_window = make_candidate(1.into(), parent_hash);
+		let out_of_window = make_candidate(2.into(), Hash::repeat_byte(0xff));
+		let in_window_hash = in_window.hash();
+
+		let inclusion_events = vec![
+			CandidateEvent::CandidateIncluded(
+				in_window,
+				Vec::new().into(),
+				CoreIndex(0),
+				GroupIndex(2),
+			),
+			CandidateEvent::CandidateIncluded(
+				out_of_window,
+				Vec::new().into(),
+				CoreIndex(1),
+				GroupIndex(3),
+			),
+		];
+
+		let test_fut = {
+			let mut runtime_info = RuntimeInfo::new_with_config(RuntimeInfoConfig {
+				keystore: None,
+				session_cache_lru_size: DISPUTE_WINDOW.get(),
+			});
+
+			let header = header.clone();
+			Box::pin(async move {
+				let env = ImportedBlockInfoEnv {
+					runtime_info: &mut runtime_info,
+					assignment_criteria: &MockAssignmentCriteria::default(),
+					keystore: &LocalKeystore::in_memory(),
+					relay_parent_check: RelayParentCheck::Drop,
+					tolerate_candidate_events_errors: false,
+					core_filter: None,
+					shadow_criteria: None,
+					assignment_observer: None,
+					dev_mode: false,
+					force_approve_target: ForceApproveTarget::Exclusive,
+					metrics: &Metrics::default(),
+				};
+
+				let info =
+					imported_block_info(ctx.sender(), env, hash, &header, &Some(4)).await.unwrap();
+
+				assert_eq!(info.included_candidates.len(), 1);
+				assert_eq!(info.included_candidates[0].0, in_window_hash);
+			})
+		};
+
+		let aux_fut = Box::pin(async move {
+			let responses = ImportRequestResponses::new(session, inclusion_events);
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::CandidateEvents(c_tx),
+				)) => {
+					let _ = c_tx.send(responses.events.clone());
+				}
+			);
+
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::ChainApi(ChainApiMessage::Ancestors {
+					hash: h,
+					k,
+					response_channel: tx,
+				}) => {
+					assert_eq!(h, hash);
+					assert_eq!(k, RELAY_PARENT_WINDOW);
+					let _ = tx.send(Ok(vec![parent_hash, Hash::repeat_byte(0x02)]));
+				}
+			);
+
+			// The session index, BABE epoch, session info, executor params and node features.
+			for _ in 0..5 {
+				assert_matches!(answer_import_request(handle.recv().await, &responses), None);
+			}
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
+	#[test]
+	fn imported_block_info_rejects_candidates_with_relay_parent_outside_window() {
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let parent_hash = Hash::repeat_byte(0x01);
+		let header = Header {
+			digest: Digest::default(),
+			extrinsics_root: Default::default(),
+			number: 5,
+			state_root: Default::default(),
+			parent_hash,
+		};
+
+		let hash = header.hash();
+		let out_of_window_relay_parent = Hash::repeat_byte(0xff);
+		let mut candidate = dummy_candidate_receipt_v2(dummy_hash());
+		candidate.descriptor.set_relay_parent(out_of_window_relay_parent);
+		let candidate_hash = candidate.hash();
+
+		let inclusion_events = vec![CandidateEvent::CandidateIncluded(
+			candidate,
+			Vec::new().into(),
+			CoreIndex(0),
+			GroupIndex(2),
+		)];
+
+		let test_fut = {
+			let mut runtime_info = RuntimeInfo::new_with_config(RuntimeInfoConfig {
+				keystore: None,
+				session_cache_lru_size: DISPUTE_WINDOW.get(),
+			});
+
+			let header = header.clone();
+			Box::pin(async move {
+				let env = ImportedBlockInfoEnv {
+					runtime_info: &mut runtime_info,
+					assignment_criteria: &MockAssignmentCriteria::default(),
+					keystore: &LocalKeystore::in_memory(),
+					relay_parent_check: RelayParentCheck::Reject,
+					tolerate_candidate_events_errors: false,
+					core_filter: None,
+					shadow_criteria: None,
+					assignment_observer: None,
+					dev_mode: false,
+					force_approve_target: ForceApproveTarget::Exclusive,
+					metrics: &Metrics::default(),
+				};
+
+				let info = imported_block_info(ctx.sender(), env, hash, &header, &Some(4)).await;
+
+				assert_matches!(
+					info,
+					Err(ImportedBlockInfoError::UnexpectedRelayParent(c, r)) => {
+						assert_eq!(c, candidate_hash);
+						assert_eq!(r, out_of_window_relay_parent);
+					}
+				);
+			})
+		};
+
+		let aux_fut = Box::pin(async move {
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					_,
+					RuntimeApiRequest::CandidateEvents(c_tx),
+				)) => {
+					let _ = c_tx.send(Ok(inclusion_events));
+				}
+			);
+
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::ChainApi(ChainApiMessage::Ancestors { response_channel: tx, .. }) => {
+					let _ = tx.send(Ok(vec![parent_hash, Hash::repeat_byte(0x02)]));
+				}
+			);
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
+	#[cfg(feature = "sassafras-experimental")]
+	#[test]
+	fn imported_block_info_uses_sassafras_slot_claim() {
+		use approval_types::v1::sassafras_primitives::{
+			digests::SlotClaim, vrf::slot_claim_sign_data, SASSAFRAS_ENGINE_ID,
+		};
+		use codec::Encode;
+		use sp_core::{bandersnatch, crypto::Pair as _};
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let session = 5;
+
+		let slot = Slot::from(10);
+		let vrf_signature = bandersnatch::Pair::from_seed(&[1u8; 32])
+			.vrf_sign(&slot_claim_sign_data(&[0u8; 32], slot, 0));
+		let header = Header {
+			digest: {
+				let mut d = Digest::default();
+				d.push(DigestItem::PreRuntime(
+					SASSAFRAS_ENGINE_ID,
+					SlotClaim { authority_idx: 0, slot, vrf_signature, ticket_claim: None }
+						.encode(),
+				));
+
+				d
+			},
+			extrinsics_root: Default::default(),
+			number: 5,
+			state_root: Default::default(),
+			parent_hash: Default::default(),
+		};
+
+		let hash = header.hash();
+
+		let test_fut = {
+			let mut runtime_info = RuntimeInfo::new_with_config(RuntimeInfoConfig {
+				keystore: None,
+				session_cache_lru_size: DISPUTE_WINDOW.get(),
+			});
+
+			let header = header.clone();
+			Box::pin(async move {
+				let env = ImportedBlockInfoEnv {
+					runtime_info: &mut runtime_info,
+					assignment_criteria: &MockAssignmentCriteria::default(),
+					keystore: &LocalKeystore::in_memory(),
+					relay_parent_check: RelayParentCheck::Disabled,
+					tolerate_candidate_events_errors: false,
+					core_filter: None,
+					shadow_criteria: None,
+					assignment_observer: None,
+					dev_mode: false,
+					force_approve_target: ForceApproveTarget::Exclusive,
+					metrics: &Metrics::default(),
+				};
+
+				let info =
+					imported_block_info(ctx.sender(), env, hash, &header, &Some(4)).await.unwrap();
+
+				assert_eq!(info.slot, slot);
+				assert_ne!(info.relay_vrf_story.0, [0u8; 32]);
+			})
+		};
+
+		let aux_fut = Box::pin(async move {
+			let mut node_features = NodeFeatures::EMPTY;
+			node_features.resize(FeatureIndex::SassafrasRelayVrf as usize + 1, false);
+			node_features.set(FeatureIndex::SassafrasRelayVrf as usize, true);
+			respond_to_import_requests(
+				&mut handle,
+				&header,
+				ImportRequestResponses {
+					node_features,
+					..ImportRequestResponses::without_candidates(session)
+				},
+			)
+			.await;
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
+	#[test]
+	fn imported_block_info_fails_if_ancient_session() {
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let session = 5;
+
+		let header = Header {
+			digest: Digest::default(),
+			extrinsics_root: Default::default(),
+			number: 5,
+			state_root: Default::default(),
+			parent_hash: Default::default(),
+		};
+
+		let hash = header.hash();
+		let make_candidate = |para_id| {
+			let mut r = dummy_candidate_receipt_v2(dummy_hash());
+			r.descriptor.set_para_id(para_id);
+			r.descriptor.set_relay_parent(hash);
+			r
+		};
+		let candidates = vec![
+			(make_candidate(1.into()), CoreIndex(0), GroupIndex(2)),
+			(make_candidate(2.into()), CoreIndex(1), GroupIndex(3)),
+		];
+
+		let inclusion_events = candidates
+			.iter()
+			.cloned()
+			.map(|(r, c, g)| CandidateEvent::CandidateIncluded(r, Vec::new().into(), c, g))
+			.collect::<Vec<_>>();
+
+		let test_fut = {
+			let mut runtime_info = RuntimeInfo::new_with_config(RuntimeInfoConfig {
+				keystore: None,
+				session_cache_lru_size: DISPUTE_WINDOW.get(),
+			});
+
+			let header = header.clone();
+			Box::pin(async move {
+				let env = ImportedBlockInfoEnv {
+					runtime_info: &mut runtime_info,
+					assignment_criteria: &MockAssignmentCriteria::default(),
+					keystore: &LocalKeystore::in_memory(),
+					relay_parent_check: RelayParentCheck::Disabled,
+					tolerate_candidate_events_errors: false,
+					core_filter: None,
+					shadow_criteria: None,
+					assignment_observer: None,
+					dev_mode: false,
+					force_approve_target: ForceApproveTarget::Exclusive,
+					metrics: &Metrics::default(),
+				};
+
+				let info = imported_block_info(ctx.sender(), env, hash, &header, &Some(6)).await;
+
+				assert_matches!(info, Err(ImportedBlockInfoError::BlockAlreadyFinalized));
+			})
+		};
+
+		let aux_fut = Box::pin(async move {
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					h,
+					RuntimeApiRequest::CandidateEvents(c_tx),
+				)) => {
+					assert_eq!(h, hash);
+					let _ = c_tx.send(Ok(inclusion_events));
+				}
+			);
+
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					h,
+					RuntimeApiRequest::SessionIndexForChild(c_tx),
+				)) => {
+					assert_eq!(h, header.parent_hash);
+					let _ = c_tx.send(Ok(session));
+				}
+			);
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
+	#[test]
+	fn imported_block_info_extracts_force_approve() {
+		assert_eq!(extract_force_approve(3, ForceApproveTarget::Exclusive), Some(3));
+		assert_eq!(extract_force_approve(3, ForceApproveTarget::Inclusive), Some(3));
+	}
+
+	#[test]
+	fn force_approve_of_imported_block_depends_on_target() {
+		// the imported block is #5
+		assert_eq!(extract_force_approve(5, ForceApproveTarget::Exclusive), None);
+		assert_eq!(extract_force_approve(5, ForceApproveTarget::Inclusive), Some(5));
+		assert_eq!(extract_force_approve(6, ForceApproveTarget::Inclusive), None);
+	}
+
+	// Gets the info of block #5, whose digest force-approves blocks up to `up_to`.
+	fn extract_force_approve(
+		up_to: BlockNumber,
+		force_approve_target: ForceApproveTarget,
+	) -> Option<BlockNumber> {
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let session = 5;
+
+		let slot = Slot::from(10);
+
+		let header = Header {
+			digest: {
+				let mut d = Digest::default();
+				let vrf_signature = garbage_vrf_signature();
+				d.push(DigestItem::babe_pre_digest(PreDigest::SecondaryVRF(
+					SecondaryVRFPreDigest { authority_index: 0, slot, vrf_signature },
+				)));
+
+				d.push(ConsensusLog::ForceApprove(up_to).into());
+
+				d
+			},
+			extrinsics_root: Default::default(),
+			number: 5,
+			state_root: Default::default(),
+			parent_hash: Default::default(),
+		};
+
+		let hash = header.hash();
+		let make_candidate = |para_id| {
+			let mut r = dummy_candidate_receipt_v2(dummy_hash());
+			r.descriptor.set_para_id(para_id);
+			r.descriptor.set_relay_parent(hash);
+			r
+		};
+		let candidates = vec![
+			(make_candidate(1.into()), CoreIndex(0), GroupIndex(2)),
+			(make_candidate(2.into()), CoreIndex(1), GroupIndex(3)),
+		];
+
+		let inclusion_events = candidates
+			.iter()
+			.cloned()
+			.map(|(r, c, g)| CandidateEvent::CandidateIncluded(r, Vec::new().into(), c, g))
+			.collect::<Vec<_>>();
+
+		let test_fut = {
+			let included_candidates = candidates
+				.iter()
+				.map(|(r, c, g)| (r.hash(), r.clone(), *c, *g))
+				.collect::<Vec<_>>();
+
+			let mut runtime_info = RuntimeInfo::new_with_config(RuntimeInfoConfig {
+				keystore: None,
+				session_cache_lru_size: DISPUTE_WINDOW.get(),
+			});
+
+			let header = header.clone();
+			Box::pin(async move {
+				let env = ImportedBlockInfoEnv {
+					runtime_info: &mut runtime_info,
+					assignment_criteria: &MockAssignmentCriteria::default(),
+					keystore: &LocalKeystore::in_memory(),
+					relay_parent_check: RelayParentCheck::Disabled,
+					tolerate_candidate_events_errors: false,
+					core_filter: None,
+					shadow_criteria: None,
+					assignment_observer: None,
+					dev_mode: false,
+					force_approve_target,
+					metrics: &Metrics::default(),
+				};
+
+				let info =
+					imported_block_info(ctx.sender(), env, hash, &header, &Some(4)).await.unwrap();
+
+				assert_eq!(info.included_candidates, included_candidates);
+				assert_eq!(info.session_index, session);
+				assert!(info.assignments.is_empty());
+				assert_eq!(info.n_validators, 0);
+				assert_eq!(info.n_cores, session);
+				assert_eq!(info.slot, slot);
+				info.force_approve
+			})
+		};
+
+		let aux_fut = Box::pin(async move {
+			respond_to_import_requests(
+				&mut handle,
+				&header,
+				ImportRequestResponses::new(session, inclusion_events),
+			)
+			.await;
+		});
+
+		let (force_approve, _) =
+			futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+		force_approve
+	}
+
+	#[test]
+	fn insta_approval_works() {
+		check_insta_approval(false, false);
+	}
+
+	#[test]
+	fn low_node_insta_approval_can_be_disabled() {
+		check_insta_approval(true, false);
+	}
+
+	#[test]
+	fn insta_approval_is_refused_in_strict_mode() {
+		check_insta_approval_with_strictness(false, true, false);
+	}
+
+	#[test]
+	fn unknown_backing_group_policy_is_applied() {
+		// Too few validators to gather the needed approvals, whatever the backing group size.
+		let session_info = SessionInfo {
+			validator_groups: IndexedVec::<GroupIndex, Vec<ValidatorIndex>>::from(vec![
+				vec![ValidatorIndex(0), ValidatorIndex(1)],
+			]),
+			needed_approvals: 7,
+			..dummy_session_info(1)
+		};
+		let candidates = [GroupIndex(0), GroupIndex(5)]
+			.into_iter()
+			.enumerate()
+			.map(|(i, group)| {
+				let receipt = dummy_candidate_receipt_v2(Hash::repeat_byte(i as u8));
+				(receipt.hash(), receipt, CoreIndex(i as u32), group)
+			})
+			.collect::<Vec<_>>();
+
+		let mut state = blank_state();
+		for (policy, expected) in [
+			(UnknownBackingGroup::TreatAsEmpty, bitvec::bitvec![u8, BitOrderLsb0; 1, 1]),
+			(UnknownBackingGroup::IgnoreCandidate, bitvec::bitvec![u8, BitOrderLsb0; 1, 0]),
+		] {
+			state.unknown_backing_group = policy;
+			assert_eq!(
+				insta_approved_bitfield(&state, Hash::zero(), &candidates, 6, &session_info),
+				expected,
+			);
+		}
+	}
+
+	#[test]
+	fn insta_approval_bitfield_edge_cases() {
+		let candidates = |groups: &[u32]| {
+			groups
+				.iter()
+				.enumerate()
+				.map(|(i, group)| {
+					let receipt = dummy_candidate_receipt_v2(Hash::repeat_byte(i as u8));
+					(receipt.hash(), receipt, CoreIndex(i as u32), GroupIndex(*group))
+				})
+				.collect::<Vec<_>>()
+		};
+		let treat_as_empty = UnknownBackingGroup::TreatAsEmpty;
+
+		// no approvals needed: all candidates are insta-approved, even with unknown groups
+		assert_eq!(
+			compute_insta_approval_bitfield(&candidates(&[0, 7]), 10, 0, &[3], treat_as_empty),
+			bitvec::bitvec![u8, BitOrderLsb0; 1, 1],
+		);
+		assert!(compute_insta_approval_bitfield(&[], 10, 0, &[3], treat_as_empty).is_empty());
+
+		// enough validators outside of the backing group
+		assert_eq!(
+			compute_insta_approval_bitfield(&candidates(&[0]), 10, 7, &[3], treat_as_empty),
+			bitvec::bitvec![u8, BitOrderLsb0; 0],
+		);
+
+		// a group larger than the validator count leaves no validators to approve
+		assert_eq!(
+			compute_insta_approval_bitfield(&candidates(&[0, 1]), 4, 1, &[6, 1], treat_as_empty),
+			bitvec::bitvec![u8, BitOrderLsb0; 1, 0],
+		);
+
+		// an out-of-range group index is treated as per the policy
+		assert_eq!(
+			compute_insta_approval_bitfield(&candidates(&[1]), 10, 7, &[3], treat_as_empty),
+			bitvec::bitvec![u8, BitOrderLsb0; 0],
+		);
+		assert_eq!(
+			compute_insta_approval_bitfield(&candidates(&[1]), 6, 7, &[3], treat_as_empty),
+			bitvec::bitvec![u8, BitOrderLsb0; 1],
+		);
+		assert_eq!(
+			compute_insta_approval_bitfield(
+				&candidates(&[1]),
+				6,
+				7,
+				&[3],
+				UnknownBackingGroup::IgnoreCandidate,
+			),
+			bitvec::bitvec![u8, BitOrderLsb0; 0],
+		);
+	}
+
+	#[test]
+	fn imported_block_info_diff_reports_changed_fields() {
+		let assignment = |core_index| {
+			let cert = AssignmentCertV2 {
+				kind: AssignmentCertKindV2::RelayVRFDelay { core_index },
+				vrf: garbage_vrf_signature(),
+			};
+			(core_index, OurAssignment::new(cert, 0, ValidatorIndex(0), false))
+		};
+		let make_info = |cores: [CoreIndex; 2], slot: u64| ImportedBlockInfo {
+			included_candidates: Vec::new(),
+			session_index: 5,
+			assignments: cores.into_iter().map(assignment).collect(),
+			n_validators: 0,
+			n_cores: 0,
+			relay_vrf_story: RelayVRFStory([0x2a; 32]),
+			slot: Slot::from(slot),
+			force_approve: None,
+			node_features: NodeFeatures::EMPTY,
+		};
+
+		// the order of assignments doesn't matter
+		let info = make_info([CoreIndex(0), CoreIndex(1)], 10);
+		assert_eq!(info.diff(&make_info([CoreIndex(1), CoreIndex(0)], 10)), Vec::new());
+
+		assert_eq!(
+			info.diff(&make_info([CoreIndex(0), CoreIndex(1)], 11)),
+			vec![ImportedBlockInfoDiff::Slot(Slot::from(10), Slot::from(11))],
+		);
+
+		let mut node_features = NodeFeatures::EMPTY;
+		node_features.resize(FeatureIndex::EnableAssignmentsV2 as usize + 1, true);
+		let other = ImportedBlockInfo {
+			n_validators: 10,
+			relay_vrf_story: RelayVRFStory([0x2b; 32]),
+			node_features: node_features.clone(),
+			..make_info([CoreIndex(0), CoreIndex(1)], 10)
+		};
+		assert_eq!(
+			info.diff(&other),
+			vec![
+				ImportedBlockInfoDiff::Validators(0, 10),
+				ImportedBlockInfoDiff::RelayVrfStory([0x2a; 32], [0x2b; 32]),
+				ImportedBlockInfoDiff::NodeFeatures(NodeFeatures::EMPTY, node_features),
+			],
+		);
+	}
+
+	#[test]
+	fn relay_vrf_story_from_seed_is_deterministic() {
+		assert_eq!(relay_vrf_story_from_seed(1).0, relay_vrf_story_from_seed(1).0);
+		assert_ne!(relay_vrf_story_from_seed(1).0, relay_vrf_story_from_seed(2).0);
+	}
+
+	#[test]
+	fn vrf_story_bytes_match_stored_block_entry() {
+		let db = kvdb_memorydb::create(NUM_COLUMNS);
+		let db = polkadot_node_subsystem_util::database::kvdb_impl::DbAdapter::new(db, &[]);
+		let db_writer: Arc<dyn Database> = Arc::new(db);
+		let mut db = DbBackend::new(db_writer.clone(), TEST_CONFIG);
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let session = 5;
+		let header = Header {
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+			number: 5,
+			state_root: Default::default(),
+			parent_hash: Hash::repeat_byte(0x01),
+		};
+		let hash = header.hash();
+
+		let info = ImportedBlockInfo {
+			included_candidates: Vec::new(),
+			session_index: session,
+			assignments: HashMap::new(),
+			n_validators: 0,
+			n_cores: 0,
+			relay_vrf_story: relay_vrf_story_from_seed(0x2a),
+			slot: Slot::from(10),
+			force_approve: None,
+			node_features: NodeFeatures::EMPTY,
+		};
+		let vrf_story_bytes = info.vrf_story_bytes();
+		assert_eq!(vrf_story_bytes, relay_vrf_story_from_seed(0x2a).0);
+
+		let (mut state, mut session_info_provider) = single_session_state();
+		let test_fut = Box::pin(async move {
+			let mut overlay_db = OverlayedBackend::new(&db);
+			let (_, imported) = import_block_entry(
+				ctx.sender(),
+				&mut state,
+				&mut overlay_db,
+				&mut session_info_provider,
+				hash,
+				hash,
+				header,
+				info,
+				&Metrics::default(),
+			)
+			.await
+			.unwrap()
+			.unwrap();
+			assert!(imported.fully_insta_approved);
+
+			let write_ops = overlay_db.into_write_ops();
+			db.write(write_ops).unwrap();
+
+			let entry = load_block_entry(db_writer.as_ref(), &TEST_CONFIG, &hash).unwrap().unwrap();
+			assert_eq!(entry.relay_vrf_story, vrf_story_bytes);
+		});
+
+		let aux_fut = Box::pin(async move {
+			respond_to_session_requests(
+				&mut handle,
+				hash,
+				&ImportRequestResponses::without_candidates(session),
+			)
+			.await;
+
+			// the block has no candidates, so it is approved right away
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::ChainSelection(ChainSelectionMessage::Approved(h)) => {
+					assert_eq!(h, hash);
+				}
+			);
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
+	#[test]
+	fn add_block_entry_errors_carry_their_origin() {
+		// A backend whose storage is unavailable.
+		struct FailingBackend;
+
+		impl Backend for FailingBackend {
+			fn load_block_entry(
+				&self,
+				_: &Hash,
+			) -> SubsystemResult<Option<crate::persisted_entries::BlockEntry>> {
+				Err(SubsystemError::Context("storage unavailable".into()))
+			}
+			fn load_candidate_entry(
+				&self,
+				_: &CandidateHash,
+			) -> SubsystemResult<Option<crate::persisted_entries::CandidateEntry>> {
+				Err(SubsystemError::Context("storage unavailable".into()))
+			}
+			fn load_blocks_at_height(&self, _: &BlockNumber) -> SubsystemResult<Vec<Hash>> {
+				Err(SubsystemError::Context("storage unavailable".into()))
+			}
+			fn load_all_blocks(&self) -> SubsystemResult<Vec<Hash>> {
+				Err(SubsystemError::Context("storage unavailable".into()))
+			}
+			fn load_stored_blocks(
+				&self,
+			) -> SubsystemResult<Option<crate::approval_db::common::StoredBlockRange>> {
+				Err(SubsystemError::Context("storage unavailable".into()))
+			}
+			fn write<I>(&mut self, _: I) -> SubsystemResult<()>
+			where
+				I: IntoIterator<Item = crate::backend::BackendWriteOp>,
+			{
+				Ok(())
+			}
+		}
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let session = 5;
+		let header = Header {
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+			number: 5,
+			state_root: Default::default(),
+			parent_hash: Hash::repeat_byte(0x01),
+		};
+		let hash = header.hash();
+
+		let info = ImportedBlockInfo {
+			included_candidates: Vec::new(),
+			session_index: session,
+			assignments: HashMap::new(),
+			n_validators: 0,
+			n_cores: 0,
+			relay_vrf_story: RelayVRFStory([0x2a; 32]),
+			slot: Slot::from(10),
+			force_approve: None,
+			node_features: NodeFeatures::EMPTY,
+		};
+
+		let (mut state, mut session_info_provider) = single_session_state();
+		let test_fut = Box::pin(async move {
+			let backend = FailingBackend;
+			let mut overlay_db = OverlayedBackend::new(&backend);
+			let result = import_block_entry(
+				ctx.sender(),
+				&mut state,
+				&mut overlay_db,
+				&mut session_info_provider,
+				hash,
+				hash,
+				header,
+				info,
+				&Metrics::default(),
+			)
+			.await;
+
+			assert_matches!(
+				result,
+				Err(SubsystemError::FromOrigin { origin, .. }) => {
+					assert_eq!(origin, ADD_BLOCK_ENTRY_ORIGIN);
+				}
+			);
+		});
+
+		let aux_fut = Box::pin(async move {
+			respond_to_session_requests(
+				&mut handle,
+				hash,
+				&ImportRequestResponses::without_candidates(session),
+			)
+			.await;
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
+	#[test]
+	fn many_candidates_are_linked_to_their_receipts() {
+		const CANDIDATES: u8 = 50;
+
+		let db = kvdb_memorydb::create(NUM_COLUMNS);
+		let db = polkadot_node_subsystem_util::database::kvdb_impl::DbAdapter::new(db, &[]);
+		let db_writer: Arc<dyn Database> = Arc::new(db);
+		let db = DbBackend::new(db_writer.clone(), TEST_CONFIG);
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let session = 5;
+		let header = Header {
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+			number: 5,
+			state_root: Default::default(),
+			parent_hash: Hash::repeat_byte(0x01),
+		};
+		let hash = header.hash();
+
+		let included_candidates: Vec<_> = (0..CANDIDATES)
+			.map(|i| {
+				let receipt = dummy_candidate_receipt_v2(Hash::repeat_byte(i));
+				// cores and groups are listed in a different order than the candidates
+				let core = CoreIndex((CANDIDATES - 1 - i) as u32);
+				let group = GroupIndex((i as u32 * 7) % CANDIDATES as u32);
+				(receipt.hash(), receipt, core, group)
+			})
+			.collect();
+
+		let info = ImportedBlockInfo {
+			included_candidates: included_candidates.clone(),
+			session_index: session,
+			assignments: HashMap::new(),
+			n_validators: 0,
+			n_cores: CANDIDATES as u32,
+			relay_vrf_story: RelayVRFStory([0x2a; 32]),
+			slot: Slot::from(10),
+			force_approve: None,
+			node_features: NodeFeatures::EMPTY,
+		};
+
+		let (mut state, mut session_info_provider) = single_session_state();
+		let test_fut = Box::pin(async move {
+			let mut overlay_db = OverlayedBackend::new(&db);
+			let (_, imported) = import_block_entry(
+				ctx.sender(),
+				&mut state,
+				&mut overlay_db,
+				&mut session_info_provider,
+				hash,
+				hash,
+				header,
+				info,
+				&Metrics::default(),
+			)
+			.await
+			.unwrap()
+			.unwrap();
+
+			assert_eq!(imported.imported_candidates.len(), CANDIDATES as usize);
+			for (candidate_hash, entry) in imported.imported_candidates {
+				// the same candidate as found by scanning the included candidates
+				let (_, receipt, _, group) = included_candidates
+					.iter()
+					.find(|(hash, _, _, _)| *hash == candidate_hash)
+					.unwrap();
+				assert_eq!(entry.candidate_receipt(), receipt);
+				assert_eq!(entry.approval_entry(&hash).unwrap().backing_group(), *group);
+			}
+		});
+
+		let aux_fut = Box::pin(async move {
+			respond_to_session_requests(
+				&mut handle,
+				hash,
+				&ImportRequestResponses::without_candidates(session),
+			)
+			.await;
+
+			// the session needs no approvals, so all candidates are insta-approved
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::ChainSelection(ChainSelectionMessage::Approved(h)) => {
+					assert_eq!(h, hash);
+				}
+			);
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
+	#[test]
+	fn imported_block_is_reported_to_telemetry() {
+		let db = kvdb_memorydb::create(NUM_COLUMNS);
+		let db = polkadot_node_subsystem_util::database::kvdb_impl::DbAdapter::new(db, &[]);
+		let db_writer: Arc<dyn Database> = Arc::new(db);
+		let db = DbBackend::new(db_writer.clone(), TEST_CONFIG);
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let session = 5;
+		let header = Header {
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+			number: 5,
+			state_root: Default::default(),
+			parent_hash: Hash::repeat_byte(0x01),
+		};
+		let hash = header.hash();
+
+		let info = ImportedBlockInfo {
+			included_candidates: Vec::new(),
+			session_index: session,
+			assignments: HashMap::new(),
+			n_validators: 0,
+			n_cores: 0,
+			relay_vrf_story: RelayVRFStory([0x2a; 32]),
+			slot: Slot::from(10),
+			force_approve: None,
+			node_features: NodeFeatures::EMPTY,
+		};
+
+		let (telemetry, mut reported) = sc_telemetry::TelemetryHandle::new_detached(16);
+		let (mut state, mut session_info_provider) = single_session_state();
+		state.telemetry = Some(telemetry);
+		let test_fut = Box::pin(async move {
+			let mut overlay_db = OverlayedBackend::new(&db);
+			import_block_entry(
+				ctx.sender(),
+				&mut state,
+				&mut overlay_db,
+				&mut session_info_provider,
+				hash,
+				hash,
+				header,
+				info,
+				&Metrics::default(),
+			)
+			.await
+			.unwrap()
+			.unwrap();
+
+			let payload = reported.next().await.unwrap();
+			assert_eq!(payload["msg"], "approval_voting.block_imported");
+			assert_eq!(payload["block_hash"], format!("{:?}", hash));
+			assert_eq!(payload["block_number"], 5);
+			assert_eq!(payload["slot"], 10);
+		});
+
+		let aux_fut = Box::pin(async move {
+			respond_to_session_requests(
+				&mut handle,
+				hash,
+				&ImportRequestResponses::without_candidates(session),
+			)
+			.await;
+
+			// a block without candidates has nothing to approve
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::ChainSelection(ChainSelectionMessage::Approved(h)) => {
+					assert_eq!(h, hash);
+				}
+			);
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
+	// Panics unless the candidates of the block entry stored for `hash` are in core index order,
+	// returning that order.
+	fn assert_stored_candidates_in_core_order(db: &dyn Database, hash: &Hash) -> Vec<CoreIndex> {
+		let entry = load_block_entry(db, &TEST_CONFIG, hash).unwrap().unwrap();
+		let cores: Vec<_> = entry.candidates.iter().map(|(core, _)| *core).collect();
+		assert!(
+			cores.windows(2).all(|pair| pair[0] < pair[1]),
+			"Candidates are not stored in core index order: {:?}",
+			cores,
+		);
+		cores
+	}
+
+	fn import_candidates_out_of_core_order(sort_candidates_by_core: bool) -> Vec<CoreIndex> {
+		let db = kvdb_memorydb::create(NUM_COLUMNS);
+		let db = polkadot_node_subsystem_util::database::kvdb_impl::DbAdapter::new(db, &[]);
+		let db_writer: Arc<dyn Database> = Arc::new(db);
+		let mut db = DbBackend::new(db_writer.clone(), TEST_CONFIG);
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let session = 5;
+		let header = Header {
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+			number: 5,
+			state_root: Default::default(),
+			parent_hash: Hash::repeat_byte(0x01),
+		};
+		let hash = header.hash();
+
+		let included_candidates: Vec<_> = [2, 0, 1]
+			.into_iter()
+			.map(|core| {
+				let receipt = dummy_candidate_receipt_v2(Hash::repeat_byte(core as u8));
+				(receipt.hash(), receipt, CoreIndex(core), GroupIndex(core))
+			})
+			.collect();
+
+		let info = ImportedBlockInfo {
+			included_candidates: included_candidates.clone(),
+			session_index: session,
+			assignments: HashMap::new(),
+			n_validators: 0,
+			n_cores: 3,
+			relay_vrf_story: relay_vrf_story_from_seed(0x2a),
+			slot: Slot::from(10),
+			force_approve: None,
+			node_features: NodeFeatures::EMPTY,
+		};
+
+		let (mut state, mut session_info_provider) = single_session_state();
+		state.sort_candidates_by_core = sort_candidates_by_core;
+		let test_fut = Box::pin(async move {
+			let mut overlay_db = OverlayedBackend::new(&db);
+			import_block_entry(
+				ctx.sender(),
+				&mut state,
+				&mut overlay_db,
+				&mut session_info_provider,
+				hash,
+				hash,
+				header,
+				info,
+				&Metrics::default(),
+			)
+			.await
+			.unwrap()
+			.unwrap();
+
+			let write_ops = overlay_db.into_write_ops();
+			db.write(write_ops).unwrap();
+		});
+
+		let aux_fut = Box::pin(async move {
+			respond_to_session_requests(
+				&mut handle,
+				hash,
+				&ImportRequestResponses::without_candidates(session),
+			)
+			.await;
+
+			// the session needs no approvals, so all candidates are insta-approved
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::ChainSelection(ChainSelectionMessage::Approved(h)) => {
+					assert_eq!(h, hash);
+				}
+			);
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+
+		let entry = load_block_entry(db_writer.as_ref(), &TEST_CONFIG, &hash).unwrap().unwrap();
+		// the candidates are stored along with their cores, whatever the order
+		for (core, candidate_hash) in &entry.candidates {
+			assert!(included_candidates
+				.iter()
+				.any(|(hash, _, c, _)| hash == candidate_hash && c == core));
+		}
+		if sort_candidates_by_core {
+			assert_stored_candidates_in_core_order(db_writer.as_ref(), &hash)
+		} else {
+			entry.candidates.iter().map(|(core, _)| *core).collect()
+		}
+	}
+
+	#[test]
+	fn candidates_are_stored_in_candidate_events_order_by_default() {
+		assert_eq!(
+			import_candidates_out_of_core_order(false),
+			vec![CoreIndex(2), CoreIndex(0), CoreIndex(1)],
+		);
+	}
+
+	#[test]
+	fn candidates_can_be_stored_in_core_order() {
+		assert_eq!(
+			import_candidates_out_of_core_order(true),
+			vec![CoreIndex(0), CoreIndex(1), CoreIndex(2)],
+		);
+	}
+
+	// Imports two forks including the same candidate, returning their hashes and the resulting
+	// candidate entry.
+	fn import_forks_with_shared_candidate(
+		receipt_cache_size: u32,
+	) -> (Vec<Hash>, CandidateReceipt, crate::persisted_entries::CandidateEntry) {
+		let db = kvdb_memorydb::create(NUM_COLUMNS);
+		let db = polkadot_node_subsystem_util::database::kvdb_impl::DbAdapter::new(db, &[]);
+		let db_writer: Arc<dyn Database> = Arc::new(db);
+		let db = DbBackend::new(db_writer.clone(), TEST_CONFIG);
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let session = 5;
+		let headers: Vec<_> = (1..=2)
+			.map(|i| Header {
+				digest: Default::default(),
+				extrinsics_root: Hash::repeat_byte(i),
+				number: 5,
+				state_root: Default::default(),
+				parent_hash: Hash::repeat_byte(0x01),
+			})
+			.collect();
+		let hashes: Vec<_> = headers.iter().map(|header| header.hash()).collect();
+
+		let receipt = dummy_candidate_receipt_v2(Hash::repeat_byte(0x2a));
+		let candidate_hash = receipt.hash();
+
+		let (mut state, mut session_info_provider) = single_session_state();
+		state.receipt_cache = LruMap::new(ByLength::new(receipt_cache_size));
+
+		let test_fut = {
+			let receipt = receipt.clone();
+			Box::pin(async move {
+				let mut overlay_db = OverlayedBackend::new(&db);
+				for header in headers {
+					let hash = header.hash();
+					let info = ImportedBlockInfo {
+						included_candidates: vec![(
+							candidate_hash,
+							receipt.clone(),
+							CoreIndex(0),
+							GroupIndex(0),
+						)],
+						session_index: session,
+						assignments: HashMap::new(),
+						n_validators: 0,
+						n_cores: 1,
+						relay_vrf_story: RelayVRFStory([0x2a; 32]),
+						slot: Slot::from(10),
+						force_approve: None,
+						node_features: NodeFeatures::EMPTY,
+					};
+
+					import_block_entry(
+						ctx.sender(),
+						&mut state,
+						&mut overlay_db,
+						&mut session_info_provider,
+						hash,
+						hash,
+						header,
+						info,
+						&Metrics::default(),
+					)
+					.await
+					.unwrap()
+					.unwrap();
+				}
+
+				overlay_db.load_candidate_entry(&candidate_hash).unwrap().unwrap()
+			})
+		};
+
+		let aux_fut = {
+			let hashes = hashes.clone();
+			Box::pin(async move {
+				respond_to_session_requests(
+					&mut handle,
+					hashes[0],
+					&ImportRequestResponses::without_candidates(session),
+				)
+				.await;
+
+				// the session info is cached for the second fork
+				for hash in hashes {
+					assert_matches!(
+						handle.recv().await,
+						AllMessages::ChainSelection(ChainSelectionMessage::Approved(h)) => {
+							assert_eq!(h, hash);
+						}
+					);
+				}
+			})
+		};
+
+		let (entry, _) = futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+		(hashes, receipt, entry)
+	}
+
+	#[test]
+	fn receipt_cache_does_not_affect_imported_candidates() {
+		let (hashes, receipt, with_cache) =
+			import_forks_with_shared_candidate(crate::DEFAULT_RECEIPT_CACHE_SIZE);
+		let (_, _, without_cache) = import_forks_with_shared_candidate(0);
+
+		assert_eq!(with_cache, without_cache);
+		assert_eq!(with_cache.candidate_receipt(), &receipt);
+		for hash in hashes {
+			assert_eq!(with_cache.approval_entry(&hash).unwrap().backing_group(), GroupIndex(0));
+		}
+	}
+
+	#[test]
+	fn force_approve_notifies_chain_selection_of_all_ancestors() {
+		const ANCESTORS: u32 = 50;
+
+		let db = kvdb_memorydb::create(NUM_COLUMNS);
+		let db = polkadot_node_subsystem_util::database::kvdb_impl::DbAdapter::new(db, &[]);
+		let db_writer: Arc<dyn Database> = Arc::new(db);
+		let mut db = DbBackend::new(db_writer.clone(), TEST_CONFIG);
+		let mut overlay_db = OverlayedBackend::new(&db);
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let session = 5;
+		let slot = Slot::from(10);
+
+		// blocks 1..=ANCESTORS, each with a single unapproved candidate.
+		let mut parent_hash = Hash::zero();
+		let mut ancestors = Vec::new();
+		for number in 1..=ANCESTORS {
+			let block_hash = Hash::from_low_u64_be(number as u64);
+			overlay_db.write_block_entry(
+				v3::BlockEntry {
+					block_hash,
+					parent_hash,
+					block_number: number,
+					session,
+					slot,
+					relay_vrf_story: Default::default(),
+					candidates: vec![(CoreIndex(0), CandidateHash(block_hash))],
+					approved_bitfield: bitvec::bitvec![u8, BitOrderLsb0; 0; 1],
+					children: Vec::new(),
+					candidates_pending_signature: Default::default(),
+					distributed_assignments: Default::default(),
+				}
+				.into(),
+			);
+			ancestors.push(block_hash);
+			parent_hash = block_hash;
+		}
+
+		let write_ops = overlay_db.into_write_ops();
+		db.write(write_ops).unwrap();
+
+		let header = Header {
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+			number: ANCESTORS + 1,
+			state_root: Default::default(),
+			parent_hash,
+		};
+		let hash = header.hash();
+
+		let info = ImportedBlockInfo {
+			included_candidates: Vec::new(),
+			session_index: session,
+			assignments: HashMap::new(),
+			n_validators: 0,
+			n_cores: 0,
+			relay_vrf_story: RelayVRFStory([0u8; 32]),
+			slot,
+			force_approve: Some(ANCESTORS),
+			node_features: NodeFeatures::EMPTY,
+		};
+
+		let (mut state, mut session_info_provider) = single_session_state();
+		let test_fut = Box::pin(async move {
+			let mut overlay_db = OverlayedBackend::new(&db);
+			import_block_entry(
+				ctx.sender(),
+				&mut state,
+				&mut overlay_db,
+				&mut session_info_provider,
+				hash,
+				hash,
+				header,
+				info,
+				&Metrics::default(),
+			)
+			.await
+			.unwrap()
+			.unwrap();
+
+			let write_ops = overlay_db.into_write_ops();
+			db.write(write_ops).unwrap();
+		});
+
+		let aux_fut = Box::pin(async move {
+			respond_to_session_requests(
+				&mut handle,
+				hash,
+				&ImportRequestResponses::without_candidates(session),
+			)
+			.await;
+
+			// the block has no candidates, so it is approved right away
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::ChainSelection(ChainSelectionMessage::Approved(h)) => {
+					assert_eq!(h, hash);
+				}
+			);
+
+			// followed by all force-approved ancestors, from the most recent one
+			for ancestor in ancestors.into_iter().rev() {
+				assert_matches!(
+					handle.recv().await,
+					AllMessages::ChainSelection(ChainSelectionMessage::Approved(h)) => {
+						assert_eq!(h, ancestor);
+					}
+				);
+			}
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
+	#[test]
+	fn force_approving_imported_block_approves_its_candidates() {
+		let db = kvdb_memorydb::create(NUM_COLUMNS);
+		let db = polkadot_node_subsystem_util::database::kvdb_impl::DbAdapter::new(db, &[]);
+		let db_writer: Arc<dyn Database> = Arc::new(db);
+		let mut db = DbBackend::new(db_writer.clone(), TEST_CONFIG);
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let session = 5;
+		let header = Header {
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+			number: 5,
+			state_root: Default::default(),
+			parent_hash: Hash::repeat_byte(0x01),
+		};
+		let hash = header.hash();
+
+		let receipt = dummy_candidate_receipt_v2(hash);
+		let info = ImportedBlockInfo {
+			included_candidates: vec![(receipt.hash(), receipt, CoreIndex(0), GroupIndex(0))],
+			session_index: session,
+			assignments: HashMap::new(),
+			n_validators: 0,
+			n_cores: 1,
+			relay_vrf_story: relay_vrf_story_from_seed(0x2a),
+			slot: Slot::from(10),
+			force_approve: Some(header.number),
+			node_features: NodeFeatures::EMPTY,
+		};
+
+		// the candidate is not insta-approved, so it is only approved by force
+		let (mut state, mut session_info_provider) = single_session_state();
+		state.disable_low_node_insta_approval = true;
+		state.force_approve_target = ForceApproveTarget::Inclusive;
+		let test_fut = Box::pin(async move {
+			let mut overlay_db = OverlayedBackend::new(&db);
+			let (_, imported) = import_block_entry(
+				ctx.sender(),
+				&mut state,
+				&mut overlay_db,
+				&mut session_info_provider,
+				hash,
+				hash,
+				header,
+				info,
+				&Metrics::default(),
+			)
+			.await
+			.unwrap()
+			.unwrap();
+			assert!(!imported.fully_insta_approved);
+
+			let write_ops = overlay_db.into_write_ops();
+			db.write(write_ops).unwrap();
+		});
+
+		let aux_fut = Box::pin(async move {
+			let session_info = SessionInfo { needed_approvals: 2, ..dummy_session_info(session) };
+			respond_to_session_requests(
+				&mut handle,
+				hash,
+				&ImportRequestResponses {
+					session_info,
+					..ImportRequestResponses::without_candidates(session)
+				},
+			)
+			.await;
+
+			// the block is approved once, by force
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::ChainSelection(ChainSelectionMessage::Approved(h)) => {
+					assert_eq!(h, hash);
+				}
+			);
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+
+		// the approval is not overwritten by the block entry written on import
+		let entry = load_block_entry(db_writer.as_ref(), &TEST_CONFIG, &hash).unwrap().unwrap();
+		assert_eq!(entry.approved_bitfield.len(), 1);
+		assert!(entry.approved_bitfield.all());
+	}
+
+	#[test]
+	fn node_features_unknown_to_the_runtime_are_disabled() {
+		let v2 = FeatureIndex::EnableAssignmentsV2;
+		let enabled = NodeFeatures::repeat(true, v2 as usize + 1);
+
+		assert!(node_feature_enabled(&enabled, v2));
+		assert!(!node_feature_enabled(&NodeFeatures::repeat(false, v2 as usize + 1), v2));
+		assert!(!node_feature_enabled(&NodeFeatures::EMPTY, v2));
+		assert!(!node_feature_enabled(&enabled, FeatureIndex::FirstUnassigned));
+	}
+
+	#[test]
+	fn zero_parent_is_unexpected_above_genesis_only() {
+		let header = |number, parent_hash| Header {
+			parent_hash,
+			number,
+			state_root: Default::default(),
+			extrinsics_root: Default::default(),
+			digest: Default::default(),
+		};
+
+		assert!(!has_unexpected_zero_parent(&header(0, Hash::default())));
+		assert!(has_unexpected_zero_parent(&header(5, Hash::default())));
+		assert!(!has_unexpected_zero_parent(&header(5, Hash::repeat_byte(0x01))));
+	}
+
+	#[test]
+	fn block_finalized_at_its_height_is_not_lost_to_finality() {
+		let block_hash = Hash::repeat_byte(0x01);
+		assert!(!is_lost_to_finality(Some(block_hash), block_hash));
+	}
+
+	#[test]
+	fn block_with_other_block_finalized_at_its_height_is_lost_to_finality() {
+		assert!(is_lost_to_finality(Some(Hash::repeat_byte(0x02)), Hash::repeat_byte(0x01)));
+	}
+
+	#[test]
+	fn block_without_finalized_block_at_its_height_is_not_lost_to_finality() {
+		assert!(!is_lost_to_finality(None, Hash::repeat_byte(0x01)));
+	}
+
+	#[test]
+	fn missing_header_is_counted_as_skipped_import() {
+		let db = kvdb_memorydb::create(NUM_COLUMNS);
+		let db = polkadot_node_subsystem_util::database::kvdb_impl::DbAdapter::new(db, &[]);
+		let db_writer: Arc<dyn Database> = Arc::new(db);
+		let db = DbBackend::new(db_writer.clone(), TEST_CONFIG);
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let hash = Hash::repeat_byte(0x02);
+		let (mut state, mut session_info_provider) = single_session_state();
+
+		let test_fut = Box::pin(async move {
+			let registry = prometheus::Registry::new();
+			let metrics = Metrics::try_register(&registry).unwrap();
+			let mut overlay_db = OverlayedBackend::new(&db);
+
+			let mut approval_voting_sender = ctx.sender().clone();
+			let result = handle_new_head(
+				ctx.sender(),
+				&mut approval_voting_sender,
+				&mut state,
+				&mut overlay_db,
+				&mut session_info_provider,
+				hash,
+				&metrics,
+				&Some(1),
+			)
+			.await
+			.unwrap();
+			assert!(result.is_empty());
+
+			let skipped = &metrics.0.as_ref().unwrap().import_skipped_total;
+			assert_eq!(skipped.with_label_values(&["missing_header"]).get(), 1);
+			assert_eq!(skipped.with_label_values(&["chain_api_unreachable"]).get(), 0);
+		});
+
+		let aux_fut = Box::pin(async move {
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::ChainApi(ChainApiMessage::BlockHeader(h, tx)) => {
+					assert_eq!(h, hash);
+					let _ = tx.send(Ok(None));
+				}
+			);
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
+	#[test]
+	fn unanswered_header_request_times_out() {
+		let db = kvdb_memorydb::create(NUM_COLUMNS);
+		let db = polkadot_node_subsystem_util::database::kvdb_impl::DbAdapter::new(db, &[]);
+		let db_writer: Arc<dyn Database> = Arc::new(db);
+		let db = DbBackend::new(db_writer.clone(), TEST_CONFIG);
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let hash = Hash::repeat_byte(0x02);
+		let (mut state, mut session_info_provider) = single_session_state();
+		state.chain_api_timeout = Duration::from_millis(50);
+
+		// The Chain API never answers, but keeps the request open until the import is done.
+		let (done_tx, done_rx) = oneshot::channel::<()>();
+
+		let test_fut = Box::pin(async move {
+			let registry = prometheus::Registry::new();
+			let metrics = Metrics::try_register(&registry).unwrap();
+			let mut overlay_db = OverlayedBackend::new(&db);
+
+			let mut approval_voting_sender = ctx.sender().clone();
+			let started = std::time::Instant::now();
+			let result = handle_new_head(
+				ctx.sender(),
+				&mut approval_voting_sender,
+				&mut state,
+				&mut overlay_db,
+				&mut session_info_provider,
+				hash,
+				&metrics,
+				&Some(1),
+			)
+			.await
+			.unwrap();
+			assert!(result.is_empty());
+			assert!(started.elapsed() < Duration::from_secs(5));
+
+			let skipped = &metrics.0.as_ref().unwrap().import_skipped_total;
+			assert_eq!(skipped.with_label_values(&["chain_api_unreachable"]).get(), 1);
+			assert_eq!(skipped.with_label_values(&["missing_header"]).get(), 0);
+
+			let _ = done_tx.send(());
+		});
+
+		let aux_fut = Box::pin(async move {
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::ChainApi(ChainApiMessage::BlockHeader(h, tx)) => {
+					assert_eq!(h, hash);
+					let _ = done_rx.await;
+					drop(tx);
+				}
+			);
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
+	#[test]
+	fn handle_new_head_with_header_matches_fetched_header() {
+		// The block hash depends on the VRF signature in the header, which differs between runs.
+		let without_hashes = |result: Vec<(Hash, BlockNumber, Tick, Vec<_>)>| {
+			result
+				.into_iter()
+				.map(|(hash, number, tick, candidates)| {
+					let candidates = candidates
+						.into_iter()
+						.map(|(_, entry): (CandidateHash, CandidateEntry)| {
+							let approval_entry = entry.approval_entry(&hash).unwrap();
+							(
+								entry.candidate_receipt().descriptor.para_id(),
+								approval_entry.backing_group(),
+								approval_entry.is_approved(),
+								entry.approvals().to_bitvec(),
+							)
+						})
+						.collect::<Vec<_>>();
+					(number, tick, candidates)
+				})
+				.collect::<Vec<_>>()
+		};
+
+		assert_eq!(
+			without_hashes(check_insta_approval(false, true)),
+			without_hashes(check_insta_approval(false, false)),
+		);
+	}
+
+	fn check_insta_approval(
+		disable_low_node_insta_approval: bool,
+		header_known: bool,
+	) -> Vec<(Hash, BlockNumber, Tick, Vec<(CandidateHash, CandidateEntry)>)> {
+		check_insta_approval_with_strictness(disable_low_node_insta_approval, false, header_known)
+	}
 
-			let slot = Slot::from(10);
-			let header = Header {
-				digest: {
-					let mut d = Digest::default();
-					let vrf_signature = garbage_vrf_signature();
-					d.push(DigestItem::babe_pre_digest(PreDigest::SecondaryVRF(
-						SecondaryVRFPreDigest { authority_index: 0, slot, vrf_signature },
-					)));
+	fn check_insta_approval_with_strictness(
+		disable_low_node_insta_approval: bool,
+		strict_no_insta_approval: bool,
+		header_known: bool,
+	) -> Vec<(Hash, BlockNumber, Tick, Vec<(CandidateHash, CandidateEntry)>)> {
+		let ImportFixture { mut db, db_writer, session, header, hash, .. } = import_fixture();
 
-					d
-				},
-				extrinsics_root: Default::default(),
-				number: 5,
-				state_root: Default::default(),
-				parent_hash: Default::default(),
-			};
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
 
-			let hash = header.hash();
-			let make_candidate = |para_id| {
-				let mut r = dummy_candidate_receipt_v2(dummy_hash());
-				r.descriptor.set_para_id(para_id);
-				r.descriptor.set_relay_parent(hash);
-				r
+		let irrelevant = 666;
+		let session_info =
+			SessionInfo {
+				validators: IndexedVec::<ValidatorIndex, ValidatorId>::from(
+					vec![Sr25519Keyring::Alice.public().into(); 6],
+				),
+				discovery_keys: Vec::new(),
+				assignment_keys: Vec::new(),
+				validator_groups: IndexedVec::<GroupIndex, Vec<ValidatorIndex>>::from(vec![
+					vec![ValidatorIndex(0); 5],
+					vec![ValidatorIndex(0); 2],
+				]),
+				n_cores: 6,
+				needed_approvals: 2,
+				zeroth_delay_tranche_width: irrelevant,
+				relay_vrf_modulo_samples: irrelevant,
+				n_delay_tranches: irrelevant,
+				no_show_slots: irrelevant,
+				active_validator_indices: Vec::new(),
+				dispute_period: 6,
+				random_seed: [0u8; 32],
 			};
-			let candidates = vec![
-				(make_candidate(1.into()), CoreIndex(0), GroupIndex(2)),
-				(make_candidate(2.into()), CoreIndex(1), GroupIndex(3)),
-			];
-
-			let inclusion_events = candidates
-				.iter()
-				.cloned()
-				.map(|(r, c, g)| CandidateEvent::CandidateIncluded(r, Vec::new().into(), c, g))
-				.collect::<Vec<_>>();
 
-			let test_fut = {
-				let included_candidates = candidates
-					.iter()
-					.map(|(r, c, g)| (r.hash(), r.clone(), *c, *g))
-					.collect::<Vec<_>>();
+		let make_candidate = |para_id| {
+			let mut r = dummy_candidate_receipt_v2(dummy_hash());
+			r.descriptor.set_para_id(para_id);
+			r.descriptor.set_relay_parent(hash);
+			r
+		};
+		let candidates = vec![
+			(make_candidate(ParaId::from(1)), CoreIndex(0), GroupIndex(0)),
+			(make_candidate(ParaId::from(2)), CoreIndex(1), GroupIndex(1)),
+		];
+		let inclusion_events = candidates
+			.iter()
+			.cloned()
+			.map(|(r, c, g)| CandidateEvent::CandidateIncluded(r, Vec::new().into(), c, g))
+			.collect::<Vec<_>>();
 
-				let mut runtime_info = RuntimeInfo::new_with_config(RuntimeInfoConfig {
-					keystore: None,
-					session_cache_lru_size: DISPUTE_WINDOW.get(),
-				});
+		let (mut state, mut session_info_provider) = single_session_state();
+		state.disable_low_node_insta_approval = disable_low_node_insta_approval;
+		state.strict_no_insta_approval = strict_no_insta_approval;
+		let registry = prometheus::Registry::new();
+		let metrics = Metrics::try_register(&registry).unwrap();
 
-				let header = header.clone();
-				Box::pin(async move {
-					let env = ImportedBlockInfoEnv {
-						runtime_info: &mut runtime_info,
-						assignment_criteria: &MockAssignmentCriteria { enable_v2 },
-						keystore: &LocalKeystore::in_memory(),
-					};
+		let test_fut = {
+			let test_header = header.clone();
+			Box::pin(async move {
+				let mut overlay_db = OverlayedBackend::new(&db);
 
-					let info = imported_block_info(ctx.sender(), env, hash, &header, &Some(4))
-						.await
-						.unwrap();
+				let mut approval_voting_sender = ctx.sender().clone();
+				let result = if header_known {
+					handle_new_head_with_header(
+						ctx.sender(),
+						&mut approval_voting_sender,
+						&mut state,
+						&mut overlay_db,
+						&mut session_info_provider,
+						hash,
+						test_header,
+						&HashMap::new(),
+						&metrics,
+						&Some(1),
+					)
+					.await
+				} else {
+					handle_new_head(
+						ctx.sender(),
+						&mut approval_voting_sender,
+						&mut state,
+						&mut overlay_db,
+						&mut session_info_provider,
+						hash,
+						&metrics,
+						&Some(1),
+					)
+					.await
+				}
+				.unwrap();
 
-					assert_eq!(info.included_candidates, included_candidates);
-					assert_eq!(info.session_index, session);
-					assert!(info.assignments.is_empty());
-					assert_eq!(info.n_validators, 0);
-					assert_eq!(info.slot, slot);
-					assert!(info.force_approve.is_none());
-				})
-			};
+				let write_ops = overlay_db.into_write_ops();
+				db.write(write_ops).unwrap();
 
-			let aux_fut = Box::pin(async move {
-				assert_matches!(
-					handle.recv().await,
-					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-						h,
-						RuntimeApiRequest::CandidateEvents(c_tx),
-					)) => {
-						assert_eq!(h, hash);
-						let _ = c_tx.send(Ok(inclusion_events));
-					}
+				assert_eq!(result.len(), 1);
+				assert_eq!(state.last_imported_head(), Some((hash, 5)));
+				// slot 10 with 6s slots starts at tick 120.
+				assert_eq!(result[0].block_tick, 120);
+				let candidates = &result[0].imported_candidates;
+				assert_eq!(candidates.len(), 2);
+				assert_eq!(candidates[0].1.expected_approvals_len(), 6);
+				assert_eq!(candidates[1].1.expected_approvals_len(), 6);
+				// the first candidate should be insta-approved unless the heuristic is disabled or
+				// insta-approvals are refused, the second should not
+				let entry: BlockEntry = load_block_entry(db_writer.as_ref(), &TEST_CONFIG, &hash)
+					.unwrap()
+					.unwrap()
+					.into();
+				assert_eq!(
+					entry.is_candidate_approved(&candidates[0].0),
+					!disable_low_node_insta_approval && !strict_no_insta_approval,
 				);
-
-				assert_matches!(
-					handle.recv().await,
-					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-						h,
-						RuntimeApiRequest::SessionIndexForChild(c_tx),
-					)) => {
-						assert_eq!(h, header.parent_hash);
-						let _ = c_tx.send(Ok(session));
-					}
+				let refused = &metrics.0.as_ref().unwrap().insta_approvals_refused_total;
+				assert_eq!(
+					refused.get(),
+					(!disable_low_node_insta_approval && strict_no_insta_approval) as u64,
 				);
+				assert!(!entry.is_candidate_approved(&candidates[1].0));
+				// we haven't approved anything ourselves, so no approvals are pending signature
+				assert_eq!(entry.num_candidates_pending_signature(), 0);
+				assert!(!entry.candidate_is_pending_signature(candidates[1].0));
+				// so the block as a whole is never insta-approved
+				assert!(!result[0].fully_insta_approved);
+
+				result
+					.into_iter()
+					.map(|b| (b.block_hash, b.block_number, b.block_tick, b.imported_candidates))
+					.collect::<Vec<_>>()
+			})
+		};
 
+		let aux_fut = Box::pin(async move {
+			if !header_known {
 				assert_matches!(
 					handle.recv().await,
-					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+					AllMessages::ChainApi(ChainApiMessage::BlockHeader(
 						h,
-						RuntimeApiRequest::CurrentBabeEpoch(c_tx),
+						tx,
 					)) => {
 						assert_eq!(h, hash);
-						let _ = c_tx.send(Ok(BabeEpoch {
-							epoch_index: session as _,
-							start_slot: Slot::from(0),
-							duration: 200,
-							authorities: vec![(Sr25519Keyring::Alice.public().into(), 1)],
-							randomness: [0u8; 32],
-							config: BabeEpochConfiguration {
-								c: (1, 4),
-								allowed_slots: AllowedSlots::PrimarySlots,
-							},
-						}));
+						let _ = tx.send(Ok(Some(header.clone())));
 					}
 				);
+			}
 
-				assert_matches!(
-					handle.recv().await,
-					AllMessages::RuntimeApi(
-						RuntimeApiMessage::Request(
-							req_block_hash,
-							RuntimeApiRequest::SessionInfo(idx, si_tx),
-						)
-					) => {
-						assert_eq!(session, idx);
-						assert_eq!(req_block_hash, hash);
-						si_tx.send(Ok(Some(session_info.clone()))).unwrap();
-					}
-				);
+			// determine_new_blocks exits early as the parent_hash is in the DB
 
-				assert_matches!(
-					handle.recv().await,
-					AllMessages::RuntimeApi(
-						RuntimeApiMessage::Request(
-							req_block_hash,
-							RuntimeApiRequest::SessionExecutorParams(idx, si_tx),
-						)
-					) => {
-						assert_eq!(session, idx);
-						assert_eq!(req_block_hash, hash);
-						si_tx.send(Ok(Some(ExecutorParams::default()))).unwrap();
-					}
-				);
+			respond_to_import_requests(
+				&mut handle,
+				&header,
+				ImportRequestResponses {
+					session_info,
+					..ImportRequestResponses::new(session, inclusion_events)
+				},
+			)
+			.await;
 
-				assert_matches!(
-					handle.recv().await,
-					AllMessages::RuntimeApi(
-						RuntimeApiMessage::Request(_, RuntimeApiRequest::NodeFeatures(_, si_tx), )
-					) => {
-						si_tx.send(Ok(NodeFeatures::repeat(enable_v2, FeatureIndex::EnableAssignmentsV2 as usize + 1))).unwrap();
-					}
-				);
-			});
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::ApprovalDistribution(ApprovalDistributionMessage::NewBlocks(
+					approval_meta
+				)) => {
+					assert_eq!(approval_meta.len(), 1);
+				}
+			);
+		});
 
-			futures::executor::block_on(futures::future::join(test_fut, aux_fut));
-		}
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut)).0
 	}
 
 	#[test]
-	fn imported_block_info_fails_if_no_babe_vrf() {
+	fn candidate_events_error_imports_block_without_candidates_when_tolerated() {
+		let ImportFixture { mut db, db_writer, session, header, hash, .. } = import_fixture();
+
 		let pool = TaskExecutor::new();
 		let (mut ctx, mut handle) =
 			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
 
-		let session = 5;
-		let session_info = dummy_session_info(session);
+		let (mut state, mut session_info_provider) = single_session_state();
+		state.tolerate_candidate_events_errors = true;
 
-		let header = Header {
-			digest: Digest::default(),
-			extrinsics_root: Default::default(),
-			number: 5,
-			state_root: Default::default(),
-			parent_hash: Default::default(),
-		};
+		let test_fut = {
+			Box::pin(async move {
+				let mut overlay_db = OverlayedBackend::new(&db);
 
-		let hash = header.hash();
-		let make_candidate = |para_id| {
-			let mut r = dummy_candidate_receipt_v2(dummy_hash());
-			r.descriptor.set_para_id(para_id);
-			r.descriptor.set_relay_parent(hash);
-			r
+				let mut approval_voting_sender = ctx.sender().clone();
+				let result = handle_new_head(
+					ctx.sender(),
+					&mut approval_voting_sender,
+					&mut state,
+					&mut overlay_db,
+					&mut session_info_provider,
+					hash,
+					&Metrics::default(),
+					&Some(1),
+				)
+				.await
+				.unwrap();
+
+				let write_ops = overlay_db.into_write_ops();
+				db.write(write_ops).unwrap();
+
+				assert_eq!(result.len(), 1);
+				assert!(result[0].imported_candidates.is_empty());
+
+				let entry: BlockEntry = load_block_entry(db_writer.as_ref(), &TEST_CONFIG, &hash)
+					.unwrap()
+					.unwrap()
+					.into();
+				assert!(entry.candidates().is_empty());
+			})
 		};
-		let candidates = vec![
-			(make_candidate(1.into()), CoreIndex(0), GroupIndex(2)),
-			(make_candidate(2.into()), CoreIndex(1), GroupIndex(3)),
-		];
 
-		let inclusion_events = candidates
-			.iter()
-			.cloned()
-			.map(|(r, c, g)| CandidateEvent::CandidateIncluded(r, Vec::new().into(), c, g))
-			.collect::<Vec<_>>();
+		let aux_fut = Box::pin(async move {
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::ChainApi(ChainApiMessage::BlockHeader(
+					h,
+					tx,
+				)) => {
+					assert_eq!(h, hash);
+					let _ = tx.send(Ok(Some(header.clone())));
+				}
+			);
+
+			respond_to_import_requests(
+				&mut handle,
+				&header,
+				ImportRequestResponses {
+					events: Err(RuntimeApiError::NotSupported {
+						runtime_api_name: "candidate_events",
+					}),
+					..ImportRequestResponses::without_candidates(session)
+				},
+			)
+			.await;
+
+			// No candidates, so the block is approved right away.
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::ChainSelection(ChainSelectionMessage::Approved(h)) => {
+					assert_eq!(h, hash);
+				}
+			);
+
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::ApprovalDistribution(ApprovalDistributionMessage::NewBlocks(
+					approval_meta
+				)) => {
+					assert_eq!(approval_meta.len(), 1);
+				}
+			);
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
+	#[test]
+	fn block_without_candidates_is_imported() {
+		let ImportFixture { mut db, db_writer, session, header, hash, .. } = import_fixture();
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
 
-		let test_fut = {
-			let mut runtime_info = RuntimeInfo::new_with_config(RuntimeInfoConfig {
-				keystore: None,
-				session_cache_lru_size: DISPUTE_WINDOW.get(),
-			});
+		let (mut state, mut session_info_provider) = single_session_state();
 
+		let test_fut = {
 			let header = header.clone();
 			Box::pin(async move {
-				let env = ImportedBlockInfoEnv {
-					runtime_info: &mut runtime_info,
-					assignment_criteria: &MockAssignmentCriteria::default(),
-					keystore: &LocalKeystore::in_memory(),
-				};
+				let mut overlay_db = OverlayedBackend::new(&db);
 
-				let info = imported_block_info(ctx.sender(), env, hash, &header, &Some(4)).await;
+				let mut approval_voting_sender = ctx.sender().clone();
+				let result = handle_new_head_with_header(
+					ctx.sender(),
+					&mut approval_voting_sender,
+					&mut state,
+					&mut overlay_db,
+					&mut session_info_provider,
+					hash,
+					header,
+					&HashMap::new(),
+					&Metrics::default(),
+					&Some(1),
+				)
+				.await
+				.unwrap();
+
+				let write_ops = overlay_db.into_write_ops();
+				db.write(write_ops).unwrap();
+
+				assert_eq!(result.len(), 1);
+				assert!(result[0].imported_candidates.is_empty());
+				assert!(result[0].fully_insta_approved);
 
-				assert_matches!(info, Err(ImportedBlockInfoError::VrfInfoUnavailable));
+				let entry =
+					load_block_entry(db_writer.as_ref(), &TEST_CONFIG, &hash).unwrap().unwrap();
+				assert!(entry.candidates.is_empty());
+				assert!(entry.approved_bitfield.is_empty());
 			})
 		};
 
 		let aux_fut = Box::pin(async move {
+			respond_to_import_requests(
+				&mut handle,
+				&header,
+				ImportRequestResponses::without_candidates(session),
+			)
+			.await;
+
+			// there's nothing to approve, so chain selection learns the block is approved right
+			// away, or it would never be
 			assert_matches!(
 				handle.recv().await,
-				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-					h,
-					RuntimeApiRequest::CandidateEvents(c_tx),
-				)) => {
+				AllMessages::ChainSelection(ChainSelectionMessage::Approved(h)) => {
 					assert_eq!(h, hash);
-					let _ = c_tx.send(Ok(inclusion_events));
 				}
 			);
 
 			assert_matches!(
 				handle.recv().await,
-				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-					h,
-					RuntimeApiRequest::SessionIndexForChild(c_tx),
+				AllMessages::ApprovalDistribution(ApprovalDistributionMessage::NewBlocks(
+					approval_meta
 				)) => {
-					assert_eq!(h, header.parent_hash);
-					let _ = c_tx.send(Ok(session));
+					assert_eq!(approval_meta.len(), 1);
+					assert_eq!(approval_meta[0].hash, hash);
+					assert!(approval_meta[0].candidates.is_empty());
 				}
 			);
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
+	#[test]
+	fn block_without_candidates_is_not_announced_when_skipping_idle_blocks() {
+		let ImportFixture { mut db, db_writer, session, header, hash, .. } = import_fixture();
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let (mut state, mut session_info_provider) = single_session_state();
+		state.skip_idle_block_announcements = true;
+
+		let test_fut = {
+			Box::pin(async move {
+				let mut overlay_db = OverlayedBackend::new(&db);
+
+				let mut approval_voting_sender = ctx.sender().clone();
+				let result = handle_new_head(
+					ctx.sender(),
+					&mut approval_voting_sender,
+					&mut state,
+					&mut overlay_db,
+					&mut session_info_provider,
+					hash,
+					&Metrics::default(),
+					&Some(1),
+				)
+				.await
+				.unwrap();
+
+				let write_ops = overlay_db.into_write_ops();
+				db.write(write_ops).unwrap();
+
+				assert_eq!(result.len(), 1);
+				assert!(result[0].imported_candidates.is_empty());
+
+				let entry: BlockEntry = load_block_entry(db_writer.as_ref(), &TEST_CONFIG, &hash)
+					.unwrap()
+					.unwrap()
+					.into();
+				assert!(entry.candidates().is_empty());
+			})
+		};
 
+		let aux_fut = Box::pin(async move {
 			assert_matches!(
 				handle.recv().await,
-				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+				AllMessages::ChainApi(ChainApiMessage::BlockHeader(
 					h,
-					RuntimeApiRequest::CurrentBabeEpoch(c_tx),
+					tx,
 				)) => {
 					assert_eq!(h, hash);
-					let _ = c_tx.send(Ok(BabeEpoch {
-						epoch_index: session as _,
-						start_slot: Slot::from(0),
-						duration: 200,
-						authorities: vec![(Sr25519Keyring::Alice.public().into(), 1)],
-						randomness: [0u8; 32],
-						config: BabeEpochConfiguration {
-							c: (1, 4),
-							allowed_slots: AllowedSlots::PrimarySlots,
-						},
-					}));
+					let _ = tx.send(Ok(Some(header.clone())));
 				}
 			);
 
+			respond_to_import_requests(
+				&mut handle,
+				&header,
+				ImportRequestResponses::without_candidates(session),
+			)
+			.await;
+
+			// No candidates, so the block is approved right away.
 			assert_matches!(
 				handle.recv().await,
-				AllMessages::RuntimeApi(
-					RuntimeApiMessage::Request(
-						req_block_hash,
-						RuntimeApiRequest::SessionInfo(idx, si_tx),
-					)
-				) => {
-					assert_eq!(session, idx);
-					assert_eq!(req_block_hash, hash);
-					si_tx.send(Ok(Some(session_info.clone()))).unwrap();
+				AllMessages::ChainSelection(ChainSelectionMessage::Approved(h)) => {
+					assert_eq!(h, hash);
 				}
 			);
 
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::RuntimeApi(
-					RuntimeApiMessage::Request(
-						req_block_hash,
-						RuntimeApiRequest::SessionExecutorParams(idx, si_tx),
-					)
-				) => {
-					assert_eq!(session, idx);
-					assert_eq!(req_block_hash, hash);
-					si_tx.send(Ok(Some(ExecutorParams::default()))).unwrap();
+			// The block has nothing to approve, so approval distribution isn't informed of it
+			// before the subsystem is gone.
+			assert_matches!(handle.try_recv().await, None);
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
+	#[test]
+	fn deferred_head_is_imported_once_session_info_is_available() {
+		let ImportFixture { mut db, db_writer, session, parent_hash, header, hash, .. } =
+			import_fixture();
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let parent_header = Header {
+			digest: Digest::default(),
+			extrinsics_root: Default::default(),
+			number: 4,
+			state_root: Default::default(),
+			parent_hash: Default::default(),
+		};
+
+		let (mut state, mut session_info_provider) = single_session_state();
+		state.defer_unavailable_sessions = true;
+
+		let test_fut = {
+			Box::pin(async move {
+				let mut approval_voting_sender = ctx.sender().clone();
+
+				// Session info is not available yet, so the head gets deferred.
+				let mut overlay_db = OverlayedBackend::new(&db);
+				let result = handle_new_head(
+					ctx.sender(),
+					&mut approval_voting_sender,
+					&mut state,
+					&mut overlay_db,
+					&mut session_info_provider,
+					hash,
+					&Metrics::default(),
+					&Some(1),
+				)
+				.await
+				.unwrap();
+
+				assert!(result.is_empty());
+				assert_eq!(state.deferred_heads, vec![hash]);
+				let write_ops = overlay_db.into_write_ops();
+				db.write(write_ops).unwrap();
+				assert!(load_block_entry(db_writer.as_ref(), &TEST_CONFIG, &hash)
+					.unwrap()
+					.is_none());
+
+				// The next activated head is already known, but the deferred one is retried.
+				let mut overlay_db = OverlayedBackend::new(&db);
+				let result = handle_new_head(
+					ctx.sender(),
+					&mut approval_voting_sender,
+					&mut state,
+					&mut overlay_db,
+					&mut session_info_provider,
+					parent_hash,
+					&Metrics::default(),
+					&Some(1),
+				)
+				.await
+				.unwrap();
+
+				assert_eq!(result.len(), 1);
+				assert_eq!(result[0].block_hash, hash);
+				assert!(state.deferred_heads.is_empty());
+				let write_ops = overlay_db.into_write_ops();
+				db.write(write_ops).unwrap();
+				assert!(load_block_entry(db_writer.as_ref(), &TEST_CONFIG, &hash)
+					.unwrap()
+					.is_some());
+			})
+		};
+
+		let aux_fut = Box::pin(async move {
+			let responses = ImportRequestResponses::without_candidates(session);
+			for session_info_available in [false, true] {
+				// The header of the activated head is fetched before the deferred one is retried.
+				if session_info_available {
+					assert_matches!(
+						handle.recv().await,
+						AllMessages::ChainApi(ChainApiMessage::BlockHeader(h, tx)) => {
+							assert_eq!(h, parent_hash);
+							let _ = tx.send(Ok(Some(parent_header.clone())));
+						}
+					);
 				}
-			);
 
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::RuntimeApi(
-					RuntimeApiMessage::Request(_, RuntimeApiRequest::NodeFeatures(_, si_tx), )
-				) => {
-					si_tx.send(Ok(NodeFeatures::EMPTY)).unwrap();
+				assert_matches!(
+					handle.recv().await,
+					AllMessages::ChainApi(ChainApiMessage::BlockHeader(h, tx)) => {
+						assert_eq!(h, hash);
+						let _ = tx.send(Ok(Some(header.clone())));
+					}
+				);
+
+				respond_to_block_requests(&mut handle, &header, &responses).await;
+
+				if !session_info_available {
+					// Requested once for the extended session info and once for the session info.
+					for _ in 0..2 {
+						assert_matches!(
+							handle.recv().await,
+							AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+								_,
+								RuntimeApiRequest::SessionInfo(_, si_tx),
+							)) => {
+								si_tx.send(Ok(None)).unwrap();
+							}
+						);
+					}
+
+					assert_matches!(
+						handle.recv().await,
+						AllMessages::ChainApi(ChainApiMessage::FinalizedBlockHash(number, tx)) => {
+							assert_eq!(number, 5);
+							let _ = tx.send(Ok(None));
+						}
+					);
+
+					continue
 				}
-			);
+
+				respond_to_session_requests(&mut handle, hash, &responses).await;
+
+				// No candidates, so the block is approved right away.
+				assert_matches!(
+					handle.recv().await,
+					AllMessages::ChainSelection(ChainSelectionMessage::Approved(h)) => {
+						assert_eq!(h, hash);
+					}
+				);
+
+				assert_matches!(
+					handle.recv().await,
+					AllMessages::ApprovalDistribution(ApprovalDistributionMessage::NewBlocks(
+						approval_meta
+					)) => {
+						assert_eq!(approval_meta.len(), 1);
+					}
+				);
+			}
 		});
 
 		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
 	}
 
 	#[test]
-	fn imported_block_info_fails_if_ancient_session() {
+	fn missing_ancestor_proceed_imports_truncated_chain() {
+		check_missing_ancestor_policy(OnMissingAncestor::Proceed);
+	}
+
+	#[test]
+	fn missing_ancestor_skip_imports_nothing() {
+		check_missing_ancestor_policy(OnMissingAncestor::Skip);
+	}
+
+	#[test]
+	fn missing_ancestor_backfill_imports_ancestors() {
+		check_missing_ancestor_policy(OnMissingAncestor::Backfill);
+	}
+
+	// Imports block 5 with block 3 finalized, while the header of block 4 can't be fetched at
+	// first, which truncates the ancestry to the head alone.
+	fn check_missing_ancestor_policy(policy: OnMissingAncestor) {
+		let db = kvdb_memorydb::create(NUM_COLUMNS);
+		let db = polkadot_node_subsystem_util::database::kvdb_impl::DbAdapter::new(db, &[]);
+		let db_writer: Arc<dyn Database> = Arc::new(db);
+		let db = DbBackend::new(db_writer.clone(), TEST_CONFIG);
+
 		let pool = TaskExecutor::new();
 		let (mut ctx, mut handle) =
 			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
 
 		let session = 5;
+		let slot = Slot::from(10);
 
-		let header = Header {
-			digest: Digest::default(),
+		let make_header = |number, parent_hash| Header {
+			digest: {
+				let mut d = Digest::default();
+				let vrf_signature = garbage_vrf_signature();
+				d.push(DigestItem::babe_pre_digest(PreDigest::SecondaryVRF(
+					SecondaryVRFPreDigest { authority_index: 0, slot, vrf_signature },
+				)));
+
+				d
+			},
 			extrinsics_root: Default::default(),
-			number: 5,
+			number,
 			state_root: Default::default(),
-			parent_hash: Default::default(),
+			parent_hash,
 		};
-
+		let parent_header = make_header(4, Hash::repeat_byte(0x03));
+		let parent_hash = parent_header.hash();
+		let header = make_header(5, parent_hash);
 		let hash = header.hash();
-		let make_candidate = |para_id| {
-			let mut r = dummy_candidate_receipt_v2(dummy_hash());
-			r.descriptor.set_para_id(para_id);
-			r.descriptor.set_relay_parent(hash);
-			r
-		};
-		let candidates = vec![
-			(make_candidate(1.into()), CoreIndex(0), GroupIndex(2)),
-			(make_candidate(2.into()), CoreIndex(1), GroupIndex(3)),
-		];
 
-		let inclusion_events = candidates
-			.iter()
-			.cloned()
-			.map(|(r, c, g)| CandidateEvent::CandidateIncluded(r, Vec::new().into(), c, g))
-			.collect::<Vec<_>>();
+		let (mut state, mut session_info_provider) = single_session_state();
+		state.on_missing_ancestor = policy;
 
-		let test_fut = {
-			let mut runtime_info = RuntimeInfo::new_with_config(RuntimeInfoConfig {
-				keystore: None,
-				session_cache_lru_size: DISPUTE_WINDOW.get(),
-			});
+		let expected_blocks = match policy {
+			OnMissingAncestor::Proceed => vec![hash],
+			OnMissingAncestor::Skip => Vec::new(),
+			OnMissingAncestor::Backfill => vec![parent_hash, hash],
+		};
 
-			let header = header.clone();
+		let test_fut = {
+			let expected_blocks = expected_blocks.clone();
 			Box::pin(async move {
-				let env = ImportedBlockInfoEnv {
-					runtime_info: &mut runtime_info,
-					assignment_criteria: &MockAssignmentCriteria::default(),
-					keystore: &LocalKeystore::in_memory(),
-				};
-
-				let info = imported_block_info(ctx.sender(), env, hash, &header, &Some(6)).await;
+				let mut overlay_db = OverlayedBackend::new(&db);
+				let mut approval_voting_sender = ctx.sender().clone();
+				let result = handle_new_head(
+					ctx.sender(),
+					&mut approval_voting_sender,
+					&mut state,
+					&mut overlay_db,
+					&mut session_info_provider,
+					hash,
+					&Metrics::default(),
+					&Some(3),
+				)
+				.await
+				.unwrap();
 
-				assert_matches!(info, Err(ImportedBlockInfoError::BlockAlreadyFinalized));
+				assert_eq!(
+					result.iter().map(|b| b.block_hash).collect::<Vec<_>>(),
+					expected_blocks
+				);
 			})
 		};
 
 		let aux_fut = Box::pin(async move {
 			assert_matches!(
 				handle.recv().await,
-				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-					h,
-					RuntimeApiRequest::CandidateEvents(c_tx),
-				)) => {
+				AllMessages::ChainApi(ChainApiMessage::BlockHeader(h, tx)) => {
 					assert_eq!(h, hash);
-					let _ = c_tx.send(Ok(inclusion_events));
+					let _ = tx.send(Ok(Some(header.clone())));
 				}
 			);
 
 			assert_matches!(
 				handle.recv().await,
-				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-					h,
-					RuntimeApiRequest::SessionIndexForChild(c_tx),
+				AllMessages::ChainApi(ChainApiMessage::BlockHeader(h, tx)) => {
+					assert_eq!(h, parent_hash);
+					let _ = tx.send(Ok(None));
+				}
+			);
+
+			if policy == OnMissingAncestor::Backfill {
+				assert_matches!(
+					handle.recv().await,
+					AllMessages::ChainApi(ChainApiMessage::BlockHeader(h, tx)) => {
+						assert_eq!(h, parent_hash);
+						let _ = tx.send(Ok(Some(parent_header.clone())));
+					}
+				);
+			}
+
+			let responses = ImportRequestResponses::without_candidates(session);
+			for (i, block_hash) in expected_blocks.iter().enumerate() {
+				let block_header = if *block_hash == hash { &header } else { &parent_header };
+				respond_to_block_requests(&mut handle, block_header, &responses).await;
+
+				// Session info is cached after the first block.
+				if i == 0 {
+					respond_to_session_requests(&mut handle, *block_hash, &responses).await;
+				}
+			}
+
+			if expected_blocks.is_empty() {
+				return
+			}
+
+			// No candidates, so the blocks are approved right away.
+			for block_hash in &expected_blocks {
+				assert_matches!(
+					handle.recv().await,
+					AllMessages::ChainSelection(ChainSelectionMessage::Approved(h)) => {
+						assert_eq!(h, *block_hash);
+					}
+				);
+			}
+
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::ApprovalDistribution(ApprovalDistributionMessage::NewBlocks(
+					approval_meta
 				)) => {
-					assert_eq!(h, header.parent_hash);
-					let _ = c_tx.send(Ok(session));
+					assert_eq!(approval_meta.len(), expected_blocks.len());
 				}
 			);
 		});
@@ -1121,209 +5530,404 @@ pub(crate) mod tests {
 	}
 
 	#[test]
-	fn imported_block_info_extracts_force_approve() {
+	fn ancestry_windows_import_like_whole_batch() {
+		let (whole_meta, whole_imported, whole_entries) = import_gap_of_six_blocks(None, None);
+		let (windowed_meta, windowed_imported, windowed_entries) =
+			import_gap_of_six_blocks(Some(2), None);
+
+		assert_eq!(whole_meta.len(), 6);
+		assert_eq!(whole_imported, whole_meta.iter().map(|meta| meta.0).collect::<Vec<_>>());
+		assert_eq!(windowed_meta, whole_meta);
+		assert_eq!(windowed_imported, whole_imported);
+		assert_eq!(windowed_entries, whole_entries);
+	}
+
+	#[test]
+	fn failing_ancestry_window_keeps_earlier_windows_imported() {
+		let (whole_meta, _, (_, _, whole_entries)) = import_gap_of_six_blocks(None, None);
+		// Windows of blocks 2-3, 4-5 and 6-7, the last of which fails to import.
+		let (meta, imported, (range, at_height, entries)) =
+			import_gap_of_six_blocks(Some(2), Some(6));
+
+		// The blocks of the earlier windows are written and announced, the others are not.
+		assert_eq!(meta, whole_meta[..4]);
+		assert_eq!(imported, whole_meta[..4].iter().map(|meta| meta.0).collect::<Vec<_>>());
+		assert_eq!(entries.len(), 4);
+		assert_eq!(entries[..3], whole_entries[..3]);
+		assert!(entries[3].children.is_empty());
+		assert_eq!(range, Some(crate::approval_db::common::StoredBlockRange(2, 6)));
+		assert!(at_height[4..].iter().all(|hashes| hashes.is_empty()));
+	}
+
+	// Imports blocks 2 to 7 on top of the finalized block 1, answering all requests made along
+	// the way, except for the candidate events of `failing_block`. Returns the blocks of the
+	// `NewBlocks` message, the hashes of the imported blocks and the resulting DB state.
+	fn import_gap_of_six_blocks(
+		ancestry_window: Option<usize>,
+		failing_block: Option<BlockNumber>,
+	) -> (
+		Vec<(Hash, BlockNumber, Hash, Slot, SessionIndex)>,
+		Vec<Hash>,
+		(Option<crate::approval_db::common::StoredBlockRange>, Vec<Vec<Hash>>, Vec<BlockEntry>),
+	) {
+		let db = kvdb_memorydb::create(NUM_COLUMNS);
+		let db = polkadot_node_subsystem_util::database::kvdb_impl::DbAdapter::new(db, &[]);
+		let db_writer: Arc<dyn Database> = Arc::new(db);
+		let db = DbBackend::new(db_writer.clone(), TEST_CONFIG);
+
 		let pool = TaskExecutor::new();
 		let (mut ctx, mut handle) =
 			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
 
 		let session = 5;
-		let session_info = dummy_session_info(session);
+		let responses = ImportRequestResponses::without_candidates(session);
 
-		let slot = Slot::from(10);
+		let mut hashes = vec![Hash::repeat_byte(0x01)];
+		let mut headers = Vec::new();
+		for number in 2..=7 {
+			let header = Header {
+				digest: {
+					let mut d = Digest::default();
+					let vrf_signature = garbage_vrf_signature();
+					d.push(DigestItem::babe_pre_digest(PreDigest::SecondaryVRF(
+						SecondaryVRFPreDigest {
+							authority_index: 0,
+							slot: Slot::from(number as u64),
+							vrf_signature,
+						},
+					)));
 
-		let header = Header {
-			digest: {
-				let mut d = Digest::default();
-				let vrf_signature = garbage_vrf_signature();
-				d.push(DigestItem::babe_pre_digest(PreDigest::SecondaryVRF(
-					SecondaryVRFPreDigest { authority_index: 0, slot, vrf_signature },
-				)));
+					d
+				},
+				extrinsics_root: Default::default(),
+				number,
+				state_root: Default::default(),
+				parent_hash: hashes[hashes.len() - 1],
+			};
+			hashes.push(header.hash());
+			headers.push(header);
+		}
+		let head = hashes[hashes.len() - 1];
+		let failing_hash = failing_block.map(|number| hashes[number as usize - 1]);
 
-				d.push(ConsensusLog::ForceApprove(3).into());
+		let (mut state, mut session_info_provider) = single_session_state();
+		state.ancestry_window = ancestry_window;
 
-				d
-			},
-			extrinsics_root: Default::default(),
-			number: 5,
-			state_root: Default::default(),
-			parent_hash: Default::default(),
-		};
+		let test_fut = {
+			let hashes = hashes.clone();
+			Box::pin(async move {
+				let mut overlay_db = OverlayedBackend::new(&db);
+				let mut approval_voting_sender = ctx.sender().clone();
+				let result = handle_new_head(
+					ctx.sender(),
+					&mut approval_voting_sender,
+					&mut state,
+					&mut overlay_db,
+					&mut session_info_provider,
+					head,
+					&Metrics::default(),
+					&Some(1),
+				)
+				.await
+				.unwrap();
 
-		let hash = header.hash();
-		let make_candidate = |para_id| {
-			let mut r = dummy_candidate_receipt_v2(dummy_hash());
-			r.descriptor.set_para_id(para_id);
-			r.descriptor.set_relay_parent(hash);
-			r
+				(
+					result.iter().map(|b| b.block_hash).collect::<Vec<_>>(),
+					(
+						overlay_db.load_stored_blocks().unwrap(),
+						(2..=7)
+							.map(|number| overlay_db.load_blocks_at_height(&number).unwrap())
+							.collect::<Vec<_>>(),
+						hashes[1..]
+							.iter()
+							.filter_map(|hash| overlay_db.load_block_entry(hash).unwrap())
+							.collect::<Vec<_>>(),
+					),
+				)
+			})
 		};
-		let candidates = vec![
-			(make_candidate(1.into()), CoreIndex(0), GroupIndex(2)),
-			(make_candidate(2.into()), CoreIndex(1), GroupIndex(3)),
-		];
 
-		let inclusion_events = candidates
-			.iter()
-			.cloned()
-			.map(|(r, c, g)| CandidateEvent::CandidateIncluded(r, Vec::new().into(), c, g))
-			.collect::<Vec<_>>();
+		let aux_fut = Box::pin(async move {
+			loop {
+				match handle.recv().await {
+					AllMessages::ChainApi(ChainApiMessage::BlockHeader(h, tx)) => {
+						let header = headers.iter().find(|header| header.hash() == h).cloned();
+						let _ = tx.send(Ok(header));
+					},
+					AllMessages::ChainApi(ChainApiMessage::Ancestors {
+						hash,
+						k,
+						response_channel,
+					}) => {
+						assert!(ancestry_window.map_or(true, |window| k <= window));
+						let index = hashes.iter().position(|h| *h == hash).unwrap();
+						let ancestors = hashes[..index].iter().rev().take(k).cloned().collect();
+						let _ = response_channel.send(Ok(ancestors));
+					},
+					AllMessages::RuntimeApi(RuntimeApiMessage::Request(
+						h,
+						RuntimeApiRequest::CandidateEvents(c_tx),
+					)) if Some(h) == failing_hash => {
+						let _ = c_tx.send(Err(RuntimeApiError::NotSupported {
+							runtime_api_name: "candidate_events",
+						}));
+					},
+					// The failing block is not finalized.
+					AllMessages::ChainApi(ChainApiMessage::FinalizedBlockHash(_, tx)) => {
+						let _ = tx.send(Ok(None));
+					},
+					// No candidates, so the blocks are approved right away.
+					AllMessages::ChainSelection(ChainSelectionMessage::Approved(_)) => {},
+					AllMessages::ApprovalDistribution(ApprovalDistributionMessage::NewBlocks(
+						approval_meta,
+					)) =>
+						return approval_meta
+							.into_iter()
+							.map(|meta| {
+								(meta.hash, meta.number, meta.parent_hash, meta.slot, meta.session)
+							})
+							.collect::<Vec<_>>(),
+					msg =>
+						if let Some(msg) = answer_import_request(msg, &responses) {
+							panic!("Unexpected message: {:?}", msg)
+						},
+				}
+			}
+		});
 
-		let test_fut = {
-			let included_candidates = candidates
-				.iter()
-				.map(|(r, c, g)| (r.hash(), r.clone(), *c, *g))
-				.collect::<Vec<_>>();
+		let ((imported, entries), meta) =
+			futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+		(meta, imported, entries)
+	}
 
-			let mut runtime_info = RuntimeInfo::new_with_config(RuntimeInfoConfig {
-				keystore: None,
-				session_cache_lru_size: DISPUTE_WINDOW.get(),
-			});
+	#[test]
+	fn duplicate_blocks_in_imported_chain_are_imported_once() {
+		let db = kvdb_memorydb::create(NUM_COLUMNS);
+		let db = polkadot_node_subsystem_util::database::kvdb_impl::DbAdapter::new(db, &[]);
+		let db_writer: Arc<dyn Database> = Arc::new(db);
+		let db = DbBackend::new(db_writer.clone(), TEST_CONFIG);
 
-			let header = header.clone();
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let session = 5;
+		let responses = ImportRequestResponses::without_candidates(session);
+
+		let mut hashes = vec![Hash::repeat_byte(0x01)];
+		let mut headers = Vec::new();
+		for number in 2..=4 {
+			let header = Header {
+				digest: {
+					let mut d = Digest::default();
+					let vrf_signature = garbage_vrf_signature();
+					d.push(DigestItem::babe_pre_digest(PreDigest::SecondaryVRF(
+						SecondaryVRFPreDigest {
+							authority_index: 0,
+							slot: Slot::from(number as u64),
+							vrf_signature,
+						},
+					)));
+
+					d
+				},
+				extrinsics_root: Default::default(),
+				number,
+				state_root: Default::default(),
+				parent_hash: hashes[hashes.len() - 1],
+			};
+			hashes.push(header.hash());
+			headers.push(header);
+		}
+		let head = hashes[hashes.len() - 1];
+
+		let (mut state, mut session_info_provider) = single_session_state();
+
+		let test_fut = {
+			let hashes = hashes.clone();
 			Box::pin(async move {
-				let env = ImportedBlockInfoEnv {
-					runtime_info: &mut runtime_info,
-					assignment_criteria: &MockAssignmentCriteria::default(),
-					keystore: &LocalKeystore::in_memory(),
-				};
+				let mut overlay_db = OverlayedBackend::new(&db);
+				let mut approval_voting_sender = ctx.sender().clone();
+				let result = handle_new_head(
+					ctx.sender(),
+					&mut approval_voting_sender,
+					&mut state,
+					&mut overlay_db,
+					&mut session_info_provider,
+					head,
+					&Metrics::default(),
+					&Some(1),
+				)
+				.await
+				.unwrap();
 
-				let info =
-					imported_block_info(ctx.sender(), env, hash, &header, &Some(4)).await.unwrap();
+				assert_eq!(result.iter().map(|b| b.block_hash).collect::<Vec<_>>(), hashes[1..]);
+				assert_eq!(overlay_db.load_blocks_at_height(&3).unwrap(), vec![hashes[2]]);
 
-				assert_eq!(info.included_candidates, included_candidates);
-				assert_eq!(info.session_index, session);
-				assert!(info.assignments.is_empty());
-				assert_eq!(info.n_validators, 0);
-				assert_eq!(info.slot, slot);
-				assert_eq!(info.force_approve, Some(3));
+				let parent = overlay_db.load_block_entry(&hashes[1]).unwrap().unwrap();
+				assert_eq!(parent.children, vec![hashes[2]]);
 			})
 		};
 
 		let aux_fut = Box::pin(async move {
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-					h,
-					RuntimeApiRequest::CandidateEvents(c_tx),
-				)) => {
-					assert_eq!(h, hash);
-					let _ = c_tx.send(Ok(inclusion_events));
+			loop {
+				match handle.recv().await {
+					AllMessages::ChainApi(ChainApiMessage::BlockHeader(h, tx)) => {
+						let header = headers.iter().find(|header| header.hash() == h).cloned();
+						let _ = tx.send(Ok(header));
+					},
+					// Repeat the parent of the head, so the new blocks contain it twice.
+					AllMessages::ChainApi(ChainApiMessage::Ancestors {
+						hash,
+						k,
+						response_channel,
+					}) => {
+						let index = hashes.iter().position(|h| *h == hash).unwrap();
+						let _ = response_channel.send(Ok(vec![hashes[index - 1]; k]));
+					},
+					// No candidates, so the blocks are approved right away.
+					AllMessages::ChainSelection(ChainSelectionMessage::Approved(_)) => {},
+					AllMessages::ApprovalDistribution(ApprovalDistributionMessage::NewBlocks(
+						approval_meta,
+					)) => {
+						assert_eq!(
+							approval_meta.iter().map(|meta| meta.hash).collect::<Vec<_>>(),
+							hashes[1..],
+						);
+						return
+					},
+					msg =>
+						if let Some(msg) = answer_import_request(msg, &responses) {
+							panic!("Unexpected message: {:?}", msg)
+						},
 				}
-			);
+			}
+		});
 
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-					h,
-					RuntimeApiRequest::SessionIndexForChild(c_tx),
-				)) => {
-					assert_eq!(h, header.parent_hash);
-					let _ = c_tx.send(Ok(session));
-				}
-			);
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
 
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-					h,
-					RuntimeApiRequest::CurrentBabeEpoch(c_tx),
-				)) => {
-					assert_eq!(h, hash);
-					let _ = c_tx.send(Ok(BabeEpoch {
-						epoch_index: session as _,
-						start_slot: Slot::from(0),
-						duration: 200,
-						authorities: vec![(Sr25519Keyring::Alice.public().into(), 1)],
-						randomness: [0u8; 32],
-						config: BabeEpochConfiguration {
-							c: (1, 4),
-							allowed_slots: AllowedSlots::PrimarySlots,
+	#[test]
+	fn known_headers_are_not_requested() {
+		let db = kvdb_memorydb::create(NUM_COLUMNS);
+		let db = polkadot_node_subsystem_util::database::kvdb_impl::DbAdapter::new(db, &[]);
+		let db_writer: Arc<dyn Database> = Arc::new(db);
+		let db = DbBackend::new(db_writer.clone(), TEST_CONFIG);
+
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
+
+		let session = 5;
+		let responses = ImportRequestResponses::without_candidates(session);
+
+		let mut hashes = vec![Hash::repeat_byte(0x01)];
+		let mut headers = Vec::new();
+		for number in 2..=7 {
+			let header = Header {
+				digest: {
+					let mut d = Digest::default();
+					let vrf_signature = garbage_vrf_signature();
+					d.push(DigestItem::babe_pre_digest(PreDigest::SecondaryVRF(
+						SecondaryVRFPreDigest {
+							authority_index: 0,
+							slot: Slot::from(number as u64),
+							vrf_signature,
 						},
-					}));
-				}
-			);
+					)));
+
+					d
+				},
+				extrinsics_root: Default::default(),
+				number,
+				state_root: Default::default(),
+				parent_hash: hashes[hashes.len() - 1],
+			};
+			hashes.push(header.hash());
+			headers.push(header);
+		}
+		let head = hashes[hashes.len() - 1];
+
+		// The head and its three closest ancestors are cached, e.g. from a re-org notification.
+		let known_headers = headers[2..]
+			.iter()
+			.map(|header| (header.hash(), header.clone()))
+			.collect::<HashMap<_, _>>();
+
+		let (mut state, mut session_info_provider) = single_session_state();
+
+		let test_fut = {
+			let hashes = hashes.clone();
+			Box::pin(async move {
+				let mut overlay_db = OverlayedBackend::new(&db);
+				let mut approval_voting_sender = ctx.sender().clone();
+				let result = handle_new_head_with_known_headers(
+					ctx.sender(),
+					&mut approval_voting_sender,
+					&mut state,
+					&mut overlay_db,
+					&mut session_info_provider,
+					head,
+					&known_headers,
+					&Metrics::default(),
+					&Some(1),
+				)
+				.await
+				.unwrap();
 
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::RuntimeApi(
-					RuntimeApiMessage::Request(
-						req_block_hash,
-						RuntimeApiRequest::SessionInfo(idx, si_tx),
-					)
-				) => {
-					assert_eq!(session, idx);
-					assert_eq!(req_block_hash, hash);
-					si_tx.send(Ok(Some(session_info.clone()))).unwrap();
-				}
-			);
+				assert_eq!(result.iter().map(|b| b.block_hash).collect::<Vec<_>>(), hashes[1..]);
+			})
+		};
 
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::RuntimeApi(
-					RuntimeApiMessage::Request(
-						req_block_hash,
-						RuntimeApiRequest::SessionExecutorParams(idx, si_tx),
-					)
-				) => {
-					assert_eq!(session, idx);
-					assert_eq!(req_block_hash, hash);
-					si_tx.send(Ok(Some(ExecutorParams::default()))).unwrap();
+		let aux_fut = Box::pin(async move {
+			let mut requested_headers = Vec::new();
+			loop {
+				match handle.recv().await {
+					AllMessages::ChainApi(ChainApiMessage::BlockHeader(h, tx)) => {
+						requested_headers.push(h);
+						let header = headers.iter().find(|header| header.hash() == h).cloned();
+						let _ = tx.send(Ok(header));
+					},
+					AllMessages::ChainApi(ChainApiMessage::Ancestors {
+						hash,
+						k,
+						response_channel,
+					}) => {
+						let index = hashes.iter().position(|h| *h == hash).unwrap();
+						let ancestors = hashes[..index].iter().rev().take(k).cloned().collect();
+						let _ = response_channel.send(Ok(ancestors));
+					},
+					// No candidates, so the blocks are approved right away.
+					AllMessages::ChainSelection(ChainSelectionMessage::Approved(_)) => {},
+					AllMessages::ApprovalDistribution(ApprovalDistributionMessage::NewBlocks(
+						_,
+					)) => break,
+					msg =>
+						if let Some(msg) = answer_import_request(msg, &responses) {
+							panic!("Unexpected message: {:?}", msg)
+						},
 				}
-			);
+			}
 
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::RuntimeApi(
-					RuntimeApiMessage::Request(_, RuntimeApiRequest::NodeFeatures(_, si_tx), )
-				) => {
-					si_tx.send(Ok(NodeFeatures::EMPTY)).unwrap();
-				}
-			);
+			// Only the headers of blocks 2 and 3 are requested.
+			assert_eq!(requested_headers, vec![hashes[2], hashes[1]]);
 		});
 
 		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
 	}
 
 	#[test]
-	fn insta_approval_works() {
+	fn block_ticks_use_session_slot_durations() {
 		let db = kvdb_memorydb::create(NUM_COLUMNS);
 		let db = polkadot_node_subsystem_util::database::kvdb_impl::DbAdapter::new(db, &[]);
 		let db_writer: Arc<dyn Database> = Arc::new(db);
 		let mut db = DbBackend::new(db_writer.clone(), TEST_CONFIG);
-		let mut overlay_db = OverlayedBackend::new(&db);
 
 		let pool = TaskExecutor::new();
 		let (mut ctx, mut handle) =
 			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
 
-		let session = 5;
-		let irrelevant = 666;
-		let session_info =
-			SessionInfo {
-				validators: IndexedVec::<ValidatorIndex, ValidatorId>::from(
-					vec![Sr25519Keyring::Alice.public().into(); 6],
-				),
-				discovery_keys: Vec::new(),
-				assignment_keys: Vec::new(),
-				validator_groups: IndexedVec::<GroupIndex, Vec<ValidatorIndex>>::from(vec![
-					vec![ValidatorIndex(0); 5],
-					vec![ValidatorIndex(0); 2],
-				]),
-				n_cores: 6,
-				needed_approvals: 2,
-				zeroth_delay_tranche_width: irrelevant,
-				relay_vrf_modulo_samples: irrelevant,
-				n_delay_tranches: irrelevant,
-				no_show_slots: irrelevant,
-				active_validator_indices: Vec::new(),
-				dispute_period: 6,
-				random_seed: [0u8; 32],
-			};
-
-		let slot = Slot::from(10);
-
-		let parent_hash = Hash::repeat_byte(0x01);
-
-		let header = Header {
+		let make_header = |number, parent_hash, slot| Header {
 			digest: {
 				let mut d = Digest::default();
 				let vrf_signature = garbage_vrf_signature();
@@ -1334,62 +5938,35 @@ pub(crate) mod tests {
 				d
 			},
 			extrinsics_root: Default::default(),
-			number: 5,
+			number,
 			state_root: Default::default(),
 			parent_hash,
 		};
-
-		let hash = header.hash();
-		let make_candidate = |para_id| {
-			let mut r = dummy_candidate_receipt_v2(dummy_hash());
-			r.descriptor.set_para_id(para_id);
-			r.descriptor.set_relay_parent(hash);
-			r
-		};
-		let candidates = vec![
-			(make_candidate(ParaId::from(1)), CoreIndex(0), GroupIndex(0)),
-			(make_candidate(ParaId::from(2)), CoreIndex(1), GroupIndex(1)),
-		];
-		let inclusion_events = candidates
-			.iter()
-			.cloned()
-			.map(|(r, c, g)| CandidateEvent::CandidateIncluded(r, Vec::new().into(), c, g))
-			.collect::<Vec<_>>();
-
-		let (state, mut session_info_provider) = single_session_state();
-		overlay_db.write_block_entry(
-			v3::BlockEntry {
-				block_hash: parent_hash,
-				parent_hash: Default::default(),
-				block_number: 4,
-				session,
-				slot,
-				relay_vrf_story: Default::default(),
-				candidates: Vec::new(),
-				approved_bitfield: Default::default(),
-				children: Vec::new(),
-				candidates_pending_signature: Default::default(),
-				distributed_assignments: Default::default(),
-			}
-			.into(),
-		);
-
-		let write_ops = overlay_db.into_write_ops();
-		db.write(write_ops).unwrap();
-
-		let test_fut = {
-			Box::pin(async move {
+		// The first block is in session 5, which uses the default 6s slots, while the second one
+		// is in session 6, which uses 12s slots.
+		let header_a = make_header(5, Hash::repeat_byte(0x04), Slot::from(10));
+		let hash_a = header_a.hash();
+		let header_b = make_header(6, hash_a, Slot::from(11));
+		let hash_b = header_b.hash();
+		let blocks = vec![(hash_a, header_a, 5), (hash_b, header_b, 6)];
+
+		let (mut state, mut session_info_provider) = single_session_state();
+		state.session_slot_durations.insert(6, 12_000);
+
+		let test_fut = Box::pin(async move {
+			let mut block_ticks = Vec::new();
+			for head in [hash_a, hash_b] {
 				let mut overlay_db = OverlayedBackend::new(&db);
-
 				let mut approval_voting_sender = ctx.sender().clone();
 				let result = handle_new_head(
 					ctx.sender(),
 					&mut approval_voting_sender,
-					&state,
+					&mut state,
 					&mut overlay_db,
 					&mut session_info_provider,
-					hash,
-					&Some(1),
+					head,
+					&Metrics::default(),
+					&Some(4),
 				)
 				.await
 				.unwrap();
@@ -1398,123 +5975,148 @@ pub(crate) mod tests {
 				db.write(write_ops).unwrap();
 
 				assert_eq!(result.len(), 1);
-				let candidates = &result[0].imported_candidates;
-				assert_eq!(candidates.len(), 2);
-				assert_eq!(candidates[0].1.approvals().len(), 6);
-				assert_eq!(candidates[1].1.approvals().len(), 6);
-				// the first candidate should be insta-approved
-				// the second should not
-				let entry: BlockEntry = load_block_entry(db_writer.as_ref(), &TEST_CONFIG, &hash)
-					.unwrap()
-					.unwrap()
-					.into();
-				assert!(entry.is_candidate_approved(&candidates[0].0));
-				assert!(!entry.is_candidate_approved(&candidates[1].0));
-			})
-		};
+				block_ticks.push(result[0].block_tick);
+			}
+
+			// 10 * 6s and 11 * 12s, in half-second ticks.
+			assert_eq!(block_ticks, vec![120, 264]);
+		});
 
 		let aux_fut = Box::pin(async move {
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::ChainApi(ChainApiMessage::BlockHeader(
-					h,
-					tx,
-				)) => {
-					assert_eq!(h, hash);
-					let _ = tx.send(Ok(Some(header.clone())));
-				}
-			);
+			for (hash, header, session) in blocks {
+				assert_matches!(
+					handle.recv().await,
+					AllMessages::ChainApi(ChainApiMessage::BlockHeader(h, tx)) => {
+						assert_eq!(h, hash);
+						let _ = tx.send(Ok(Some(header.clone())));
+					}
+				);
 
-			// determine_new_blocks exits early as the parent_hash is in the DB
+				respond_to_import_requests(
+					&mut handle,
+					&header,
+					ImportRequestResponses::without_candidates(session),
+				)
+				.await;
 
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-					h,
-					RuntimeApiRequest::CandidateEvents(c_tx),
-				)) => {
-					assert_eq!(h, hash.clone());
-					let _ = c_tx.send(Ok(inclusion_events));
-				}
-			);
+				assert_matches!(
+					handle.recv().await,
+					AllMessages::ChainSelection(ChainSelectionMessage::Approved(h)) => {
+						assert_eq!(h, hash);
+					}
+				);
 
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-					h,
-					RuntimeApiRequest::SessionIndexForChild(c_tx),
-				)) => {
-					assert_eq!(h, parent_hash.clone());
-					let _ = c_tx.send(Ok(session));
-				}
-			);
+				assert_matches!(
+					handle.recv().await,
+					AllMessages::ApprovalDistribution(ApprovalDistributionMessage::NewBlocks(
+						approval_meta
+					)) => {
+						assert_eq!(approval_meta.len(), 1);
+					}
+				);
+			}
+		});
 
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::RuntimeApi(RuntimeApiMessage::Request(
-					h,
-					RuntimeApiRequest::CurrentBabeEpoch(c_tx),
-				)) => {
-					assert_eq!(h, hash);
-					let _ = c_tx.send(Ok(BabeEpoch {
-						epoch_index: session as _,
-						start_slot: Slot::from(0),
-						duration: 200,
-						authorities: vec![(Sr25519Keyring::Alice.public().into(), 1)],
-						randomness: [0u8; 32],
-						config: BabeEpochConfiguration {
-							c: (1, 4),
-							allowed_slots: AllowedSlots::PrimarySlots,
-						},
-					}));
-				}
-			);
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
 
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::RuntimeApi(
-					RuntimeApiMessage::Request(
-						req_block_hash,
-						RuntimeApiRequest::SessionInfo(idx, si_tx),
-					)
-				) => {
-					assert_eq!(session, idx);
-					assert_eq!(req_block_hash, hash);
-					si_tx.send(Ok(Some(session_info.clone()))).unwrap();
-				}
-			);
+	#[test]
+	fn session_boundary_block_is_reported() {
+		let db = kvdb_memorydb::create(NUM_COLUMNS);
+		let db = polkadot_node_subsystem_util::database::kvdb_impl::DbAdapter::new(db, &[]);
+		let db_writer: Arc<dyn Database> = Arc::new(db);
+		let mut db = DbBackend::new(db_writer.clone(), TEST_CONFIG);
 
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::RuntimeApi(
-					RuntimeApiMessage::Request(
-						req_block_hash,
-						RuntimeApiRequest::SessionExecutorParams(idx, si_tx),
-					)
-				) => {
-					assert_eq!(session, idx);
-					assert_eq!(req_block_hash, hash);
-					si_tx.send(Ok(Some(ExecutorParams::default()))).unwrap();
-				}
-			);
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) =
+			make_subsystem_context::<ApprovalVotingMessage, _>(pool.clone());
 
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::RuntimeApi(
-					RuntimeApiMessage::Request(_, RuntimeApiRequest::NodeFeatures(_, si_tx), )
-				) => {
-					si_tx.send(Ok(NodeFeatures::EMPTY)).unwrap();
-				}
-			);
+		let make_header = |number, parent_hash, slot| Header {
+			digest: {
+				let mut d = Digest::default();
+				let vrf_signature = garbage_vrf_signature();
+				d.push(DigestItem::babe_pre_digest(PreDigest::SecondaryVRF(
+					SecondaryVRFPreDigest { authority_index: 0, slot, vrf_signature },
+				)));
 
-			assert_matches!(
-				handle.recv().await,
-				AllMessages::ApprovalDistribution(ApprovalDistributionMessage::NewBlocks(
-					approval_meta
-				)) => {
-					assert_eq!(approval_meta.len(), 1);
-				}
-			);
+				d
+			},
+			extrinsics_root: Default::default(),
+			number,
+			state_root: Default::default(),
+			parent_hash,
+		};
+		// The first block is in session 5 and its parent is unknown, while the second one starts
+		// session 6.
+		let header_a = make_header(5, Hash::repeat_byte(0x04), Slot::from(10));
+		let hash_a = header_a.hash();
+		let header_b = make_header(6, hash_a, Slot::from(11));
+		let hash_b = header_b.hash();
+		let blocks = vec![(hash_a, header_a, 5), (hash_b, header_b, 6)];
+
+		let (mut state, mut session_info_provider) = single_session_state();
+
+		let test_fut = Box::pin(async move {
+			let mut new_sessions = Vec::new();
+			for head in [hash_a, hash_b] {
+				let mut overlay_db = OverlayedBackend::new(&db);
+				let mut approval_voting_sender = ctx.sender().clone();
+				let result = handle_new_head(
+					ctx.sender(),
+					&mut approval_voting_sender,
+					&mut state,
+					&mut overlay_db,
+					&mut session_info_provider,
+					head,
+					&Metrics::default(),
+					&Some(4),
+				)
+				.await
+				.unwrap();
+
+				let write_ops = overlay_db.into_write_ops();
+				db.write(write_ops).unwrap();
+
+				assert_eq!(result.len(), 1);
+				new_sessions.push((result[0].block_hash, result[0].new_session));
+			}
+
+			assert_eq!(new_sessions, vec![(hash_a, None), (hash_b, Some(6))]);
+		});
+
+		let aux_fut = Box::pin(async move {
+			for (hash, header, session) in blocks {
+				assert_matches!(
+					handle.recv().await,
+					AllMessages::ChainApi(ChainApiMessage::BlockHeader(h, tx)) => {
+						assert_eq!(h, hash);
+						let _ = tx.send(Ok(Some(header.clone())));
+					}
+				);
+
+				respond_to_import_requests(
+					&mut handle,
+					&header,
+					ImportRequestResponses::without_candidates(session),
+				)
+				.await;
+
+				assert_matches!(
+					handle.recv().await,
+					AllMessages::ChainSelection(ChainSelectionMessage::Approved(h)) => {
+						assert_eq!(h, hash);
+					}
+				);
+
+				assert_matches!(
+					handle.recv().await,
+					AllMessages::ApprovalDistribution(ApprovalDistributionMessage::NewBlocks(
+						approval_meta
+					)) => {
+						assert_eq!(approval_meta.len(), 1);
+					}
+				);
+			}
 		});
 
 		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
@@ -59,9 +59,9 @@ use polkadot_primitives::{
 	ValidatorSignature,
 };
 use sc_keystore::LocalKeystore;
+use sc_telemetry::TelemetryHandle;
 use sp_application_crypto::Pair;
 use sp_consensus::SyncOracle;
-use sp_consensus_slots::Slot;
 use std::time::Instant;
 
 // The max number of blocks we keep track of assignments gathering times. Normally,
@@ -89,16 +89,23 @@ use std::{
 use schnellru::{ByLength, LruMap};
 
 use approval_checking::RequiredTranches;
+pub use approval_checking::{NoShowPolicy, SlotsNoShowPolicy};
 use bitvec::{order::Lsb0, vec::BitVec};
 pub use criteria::{AssignmentCriteria, Config as AssignmentConfig, RealAssignmentCriteria};
+pub use import::{
+	BlockImportedCandidates, ForceApproveTarget, OnMissingAncestor, RelayParentCheck,
+	UnknownBackingGroup,
+};
 use persisted_entries::{ApprovalEntry, BlockEntry, CandidateEntry};
 use polkadot_node_primitives::approval::time::{
-	slot_number_to_tick, Clock, ClockExt, DelayedApprovalTimer, SystemClock, Tick,
+	Clock, ClockExt, DelayedApprovalTimer, SystemClock, Tick,
 };
 
 mod approval_checking;
 pub mod approval_db;
 mod backend;
+#[cfg(feature = "bench")]
+pub mod bench;
 pub mod criteria;
 mod import;
 mod ops;
@@ -108,7 +115,7 @@ use crate::{
 	approval_checking::{Check, TranchesToApproveResult},
 	approval_db::common::{Config as DatabaseConfig, DbBackend},
 	backend::{Backend, OverlayedBackend},
-	criteria::InvalidAssignmentReason,
+	criteria::{InvalidAssignmentReason, OurAssignment},
 	persisted_entries::OurApproval,
 };
 
@@ -121,6 +128,10 @@ const APPROVAL_CHECKING_TIMEOUT: Duration = Duration::from_secs(120);
 /// Value rather arbitrarily: Should not be hit in practice, it exists to more easily diagnose dead
 /// lock issues for example.
 const WAIT_FOR_SIGS_TIMEOUT: Duration = Duration::from_millis(500);
+/// How long are we willing to wait for the Chain API to answer requests made on block import?
+pub(crate) const DEFAULT_CHAIN_API_TIMEOUT: Duration = Duration::from_secs(5);
+/// How many candidate receipts are shared between blocks including the same candidate on import.
+pub(crate) const DEFAULT_RECEIPT_CACHE_SIZE: u32 = 16;
 const APPROVAL_CACHE_SIZE: u32 = 1024;
 
 /// The maximum number of times we retry to approve a block if is still needed.
@@ -183,12 +194,65 @@ pub struct ApprovalVotingSubsystem {
 	max_approval_retries: u32,
 	/// The backoff before we retry the approval.
 	retry_backoff: Duration,
+	/// Whether blocks whose session info is not yet available should be retried on the next
+	/// import instead of abandoning the chain.
+	defer_unavailable_sessions: bool,
+	/// How included candidates with an unexpected relay parent are treated on import.
+	relay_parent_check: RelayParentCheck,
+	/// Whether candidates are never insta-approved because there are too few validators to
+	/// gather the needed approvals.
+	disable_low_node_insta_approval: bool,
+	/// Whether insta-approvals are refused and reported as errors, see
+	/// [`Self::with_strict_no_insta_approval`].
+	strict_no_insta_approval: bool,
+	/// How candidates whose backing group doesn't exist in the session are treated when deciding
+	/// whether to insta-approve them.
+	unknown_backing_group: UnknownBackingGroup,
+	/// How imported chains with an unknown, unfinalized ancestor are treated.
+	on_missing_ancestor: OnMissingAncestor,
+	/// If set, new blocks are fetched and imported in windows of this many blocks.
+	ancestry_window: Option<usize>,
+	/// Whether VRF info is synthesized for blocks without any, see [`Self::with_dev_mode`].
+	dev_mode: bool,
+	/// Whether imported chains without any candidates are kept from approval distribution.
+	skip_idle_block_announcements: bool,
+	/// Whether blocks whose candidate events can't be fetched are imported without candidates.
+	tolerate_candidate_events_errors: bool,
+	/// Whether candidates are written to block entries in core index order, see
+	/// [`Self::with_candidates_sorted_by_core`].
+	sort_candidates_by_core: bool,
+	/// Which blocks may be force-approved by the digest of an imported block.
+	force_approve_target: ForceApproveTarget,
+	/// How long to wait for the Chain API to answer requests made on block import.
+	chain_api_timeout: Duration,
+	/// Receives an event for every imported block.
+	telemetry: Option<TelemetryHandle>,
+	/// If set, assignments are only computed for candidates included on these cores.
+	core_filter: Option<HashSet<CoreIndex>>,
+	/// How many candidate receipts are kept for sharing between blocks including the same
+	/// candidate on import.
+	receipt_cache_size: u32,
+	/// Evaluated alongside the assignment criteria on import, to compare their assignments.
+	shadow_criteria: Option<Box<dyn AssignmentCriteria + Send + Sync>>,
+	/// Called with each of our assignments computed on import, before it is stored.
+	assignment_observer: Option<Arc<dyn Fn(&CoreIndex, &OurAssignment) + Send + Sync>>,
+	/// How many sessions of session info are cached. Never less than the dispute window.
+	session_cache_size: u32,
+	/// Notified once all blocks up to an activated head have been imported.
+	caught_up_signal: Option<oneshot::Sender<()>>,
+	/// Slot durations of sessions which don't use `slot_duration_millis`, e.g. because the slot
+	/// duration was changed by a hard fork.
+	session_slot_durations: HashMap<SessionIndex, u64>,
+	/// Decides when assigned validators who haven't approved yet become no-shows.
+	no_show_policy: Box<dyn NoShowPolicy + Send + Sync>,
 }
 
 #[derive(Clone)]
 struct MetricsInner {
 	imported_candidates_total: prometheus::Counter<prometheus::U64>,
 	assignments_produced: prometheus::Histogram,
+	assignments_computed_total: prometheus::CounterVec<prometheus::U64>,
+	our_assignment_tranches: prometheus::Histogram,
 	approvals_produced_total: prometheus::CounterVec<prometheus::U64>,
 	no_shows_total: prometheus::Counter<prometheus::U64>,
 	// The difference from `no_shows_total` is that this counts all observed no-shows at any
@@ -205,6 +269,10 @@ struct MetricsInner {
 	time_recover_and_approve: prometheus::Histogram,
 	candidate_signatures_requests_total: prometheus::Counter<prometheus::U64>,
 	unapproved_candidates_in_unfinalized_chain: prometheus::Gauge<prometheus::U64>,
+	import_skipped_total: prometheus::CounterVec<prometheus::U64>,
+	import_runtime_api_errors_total: prometheus::CounterVec<prometheus::U64>,
+	shadow_assignments_diverged_total: prometheus::Counter<prometheus::U64>,
+	insta_approvals_refused_total: prometheus::Counter<prometheus::U64>,
 	// The time it takes in each stage to gather enough assignments.
 	// We defined a `stage` as being the entire process of gathering enough assignments to
 	// be able to approve a candidate:
@@ -232,6 +300,23 @@ impl Metrics {
 		}
 	}
 
+	fn on_assignments_produced(&self, count: usize, v2_enabled: bool) {
+		if let Some(metrics) = &self.0 {
+			metrics
+				.assignments_computed_total
+				.with_label_values(&[if v2_enabled { "true" } else { "false" }])
+				.inc_by(count as u64);
+		}
+	}
+
+	fn on_our_assignment_tranches(&self, tranches: impl IntoIterator<Item = DelayTranche>) {
+		if let Some(metrics) = &self.0 {
+			for tranche in tranches {
+				metrics.our_assignment_tranches.observe(tranche as f64);
+			}
+		}
+	}
+
 	fn on_approval_coalesce(&self, num_coalesced: u32) {
 		if let Some(metrics) = &self.0 {
 			// Count how many candidates we covered with this coalesced approvals,
@@ -334,6 +419,30 @@ impl Metrics {
 		}
 	}
 
+	fn on_import_skipped(&self, reason: &'static str) {
+		if let Some(metrics) = &self.0 {
+			metrics.import_skipped_total.with_label_values(&[reason]).inc();
+		}
+	}
+
+	fn on_import_runtime_api_error(&self, request: &'static str) {
+		if let Some(metrics) = &self.0 {
+			metrics.import_runtime_api_errors_total.with_label_values(&[request]).inc();
+		}
+	}
+
+	fn on_shadow_assignments_diverged(&self) {
+		if let Some(metrics) = &self.0 {
+			metrics.shadow_assignments_diverged_total.inc();
+		}
+	}
+
+	fn on_insta_approval_refused(&self) {
+		if let Some(metrics) = &self.0 {
+			metrics.insta_approvals_refused_total.inc();
+		}
+	}
+
 	pub fn observe_assignment_gathering_time(&self, stage: usize, elapsed_as_millis: usize) {
 		if let Some(metrics) = &self.0 {
 			let stage_string = stage.to_string();
@@ -370,6 +479,25 @@ impl metrics::Metrics for Metrics {
 				)?,
 				registry,
 			)?,
+			assignments_computed_total: prometheus::register(
+				prometheus::CounterVec::new(
+					prometheus::Opts::new(
+						"polkadot_parachain_assignments_computed_total",
+						"Number of our own assignments computed for imported blocks",
+					),
+					&["v2_enabled"]
+				)?,
+				registry,
+			)?,
+			our_assignment_tranches: prometheus::register(
+				prometheus::Histogram::with_opts(
+					prometheus::HistogramOpts::new(
+						"polkadot_parachain_approval_our_assignment_tranches",
+						"Tranches of our own assignments computed for imported blocks",
+					).buckets(vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 10.0, 15.0, 25.0, 40.0, 70.0]),
+				)?,
+				registry,
+			)?,
 			approvals_produced_total: prometheus::register(
 				prometheus::CounterVec::new(
 					prometheus::Opts::new(
@@ -476,6 +604,40 @@ impl metrics::Metrics for Metrics {
 				)?,
 				registry,
 			)?,
+			import_skipped_total: prometheus::register(
+				prometheus::CounterVec::new(
+					prometheus::Opts::new(
+						"polkadot_parachain_approval_import_skipped_total",
+						"Number of new heads whose chain was not imported by the approval voting subsystem",
+					),
+					&["reason"]
+				)?,
+				registry,
+			)?,
+			import_runtime_api_errors_total: prometheus::register(
+				prometheus::CounterVec::new(
+					prometheus::Opts::new(
+						"polkadot_parachain_approval_import_runtime_api_errors_total",
+						"Number of runtime API requests that failed while importing blocks",
+					),
+					&["request"]
+				)?,
+				registry,
+			)?,
+			shadow_assignments_diverged_total: prometheus::register(
+				prometheus::Counter::new(
+					"polkadot_parachain_approval_shadow_assignments_diverged_total",
+					"Number of imported blocks for which the shadow assignment criteria computed different assignments",
+				)?,
+				registry,
+			)?,
+			insta_approvals_refused_total: prometheus::register(
+				prometheus::Counter::new(
+					"polkadot_parachain_approval_insta_approvals_refused_total",
+					"Number of imported blocks with insta-approved candidates while insta-approvals are refused",
+				)?,
+				registry,
+			)?,
 			assignments_gathering_time_by_stage: prometheus::register(
 				prometheus::HistogramVec::new(
 					prometheus::HistogramOpts::new(
@@ -539,7 +701,215 @@ impl ApprovalVotingSubsystem {
 			spawner,
 			max_approval_retries,
 			retry_backoff,
+			defer_unavailable_sessions: false,
+			relay_parent_check: RelayParentCheck::Disabled,
+			disable_low_node_insta_approval: false,
+			strict_no_insta_approval: false,
+			unknown_backing_group: UnknownBackingGroup::TreatAsEmpty,
+			on_missing_ancestor: OnMissingAncestor::Proceed,
+			ancestry_window: None,
+			dev_mode: false,
+			skip_idle_block_announcements: false,
+			tolerate_candidate_events_errors: false,
+			sort_candidates_by_core: false,
+			force_approve_target: ForceApproveTarget::Exclusive,
+			chain_api_timeout: DEFAULT_CHAIN_API_TIMEOUT,
+			telemetry: None,
+			core_filter: None,
+			receipt_cache_size: DEFAULT_RECEIPT_CACHE_SIZE,
+			shadow_criteria: None,
+			assignment_observer: None,
+			session_cache_size: DISPUTE_WINDOW.get(),
+			caught_up_signal: None,
+			session_slot_durations: HashMap::new(),
+			no_show_policy: Box::new(SlotsNoShowPolicy),
+		}
+	}
+
+	/// Retry importing blocks whose session info is not yet available when the next head is
+	/// activated, instead of abandoning the chain.
+	pub fn with_deferred_session_imports(mut self, enabled: bool) -> Self {
+		self.defer_unavailable_sessions = enabled;
+		self
+	}
+
+	/// Check that candidates included in imported blocks have a recent ancestor as relay parent.
+	pub fn with_relay_parent_check(mut self, check: RelayParentCheck) -> Self {
+		self.relay_parent_check = check;
+		self
+	}
+
+	/// Require approval checking for every candidate, even when there are too few validators
+	/// besides the backing group to gather the needed approvals. Sessions which need no approvals
+	/// at all are still insta-approved.
+	pub fn with_low_node_insta_approval_disabled(mut self, disabled: bool) -> Self {
+		self.disable_low_node_insta_approval = disabled;
+		self
+	}
+
+	/// Treat any insta-approval as an error: it is logged and counted, and the candidates are
+	/// left to be approved through regular approval voting instead. Meant for networks where
+	/// insta-approvals are never expected, so that a misconfiguration doesn't go unnoticed.
+	pub fn with_strict_no_insta_approval(mut self, strict: bool) -> Self {
+		self.strict_no_insta_approval = strict;
+		self
+	}
+
+	/// Choose how included candidates whose backing group doesn't exist in the session are
+	/// treated when deciding whether to insta-approve them.
+	pub fn with_unknown_backing_group_policy(mut self, policy: UnknownBackingGroup) -> Self {
+		self.unknown_backing_group = policy;
+		self
+	}
+
+	/// Choose how imported chains whose oldest new block has an unknown, unfinalized parent are
+	/// treated.
+	pub fn with_missing_ancestor_policy(mut self, policy: OnMissingAncestor) -> Self {
+		self.on_missing_ancestor = policy;
+		self
+	}
+
+	/// Fetch and import the new blocks of a head in windows of at most `window` blocks, oldest
+	/// first, instead of fetching all their headers before importing any of them. This bounds the
+	/// memory used when far behind the chain head, at the cost of some more Chain API requests.
+	///
+	/// Each window is written once imported, so if a block of a later window can't be imported,
+	/// the earlier windows stay imported and are announced to approval distribution. The rest of
+	/// the chain is imported along with a later head.
+	pub fn with_ancestry_window(mut self, window: usize) -> Self {
+		self.ancestry_window = Some(window.max(1));
+		self
+	}
+
+	/// Import blocks without BABE VRF info, e.g. on manual-seal dev chains, with a relay VRF story
+	/// derived from the block hash and the default slot, instead of dropping them.
+	///
+	/// Only takes effect if the crate is built with the `dev-mode` feature, which must never be
+	/// enabled for production nodes: the synthesized VRF story is known to block authors.
+	pub fn with_dev_mode(mut self, enabled: bool) -> Self {
+		self.dev_mode = enabled;
+		self
+	}
+
+	/// Don't inform approval distribution of newly imported blocks if none of them includes any
+	/// candidates, which saves needless traffic on chains without parachain activity. Blocks are
+	/// still announced along with any imported block that has candidates to approve.
+	pub fn with_skip_idle_block_announcements(mut self, enabled: bool) -> Self {
+		self.skip_idle_block_announcements = enabled;
+		self
+	}
+
+	/// Import blocks whose candidate events can't be fetched from the runtime, e.g. because their
+	/// state was pruned, with no candidates instead of abandoning them. This keeps them tracked
+	/// for chain selection, but may hide real runtime failures, so it is off by default.
+	pub fn with_candidate_events_errors_tolerated(mut self, tolerated: bool) -> Self {
+		self.tolerate_candidate_events_errors = tolerated;
+		self
+	}
+
+	/// Debug option writing the candidates of imported blocks to their block entries in core
+	/// index order, instead of the order of the runtime's candidate events. Meant for surfacing
+	/// code which implicitly relies on either order.
+	pub fn with_candidates_sorted_by_core(mut self, sorted: bool) -> Self {
+		self.sort_candidates_by_core = sorted;
+		self
+	}
+
+	/// Choose whether a `ForceApprove` log in the digest of an imported block may force-approve
+	/// the block itself, instead of only its ancestors.
+	pub fn with_force_approve_target(mut self, target: ForceApproveTarget) -> Self {
+		self.force_approve_target = target;
+		self
+	}
+
+	/// Skip importing a head if the Chain API doesn't answer the header request for it within
+	/// `timeout`, instead of stalling the block import.
+	pub fn with_chain_api_timeout(mut self, timeout: Duration) -> Self {
+		self.chain_api_timeout = timeout;
+		self
+	}
+
+	/// Report the slot and tick of every imported block to telemetry.
+	pub fn with_telemetry(mut self, telemetry: TelemetryHandle) -> Self {
+		self.telemetry = Some(telemetry);
+		self
+	}
+
+	/// Only compute our assignments for candidates included on the given cores, e.g. on nodes
+	/// which only care about specific parachains. Candidates on other cores are never checked by
+	/// this node.
+	pub fn with_core_filter(mut self, cores: HashSet<CoreIndex>) -> Self {
+		self.core_filter = Some(cores);
+		self
+	}
+
+	/// Keep up to `size` candidate receipts, so that blocks including the same candidate, e.g.
+	/// forks imported together, share its receipt instead of each cloning it. `0` disables the
+	/// cache.
+	pub fn with_receipt_cache_size(mut self, size: u32) -> Self {
+		self.receipt_cache_size = size;
+		self
+	}
+
+	/// Also compute assignments with `criteria` when importing blocks, and report when they differ
+	/// from the ones of the assignment criteria in use. The shadow assignments are never stored
+	/// or acted upon, which allows evaluating changes to the criteria on live nodes.
+	pub fn with_shadow_criteria(
+		mut self,
+		criteria: Box<dyn AssignmentCriteria + Send + Sync>,
+	) -> Self {
+		self.shadow_criteria = Some(criteria);
+		self
+	}
+
+	/// Call `observer` with each of our assignments computed when importing blocks, before the
+	/// block entry holding it is stored. Allows external telemetry to inspect the assignments.
+	pub fn with_assignment_observer(
+		mut self,
+		observer: Arc<dyn Fn(&CoreIndex, &OurAssignment) + Send + Sync>,
+	) -> Self {
+		self.assignment_observer = Some(observer);
+		self
+	}
+
+	/// Cache the session info of up to `size` sessions. Sizes below the dispute window are raised
+	/// to it, as session info of any session in the window may be needed at any time.
+	pub fn with_session_cache_size(mut self, size: u32) -> Self {
+		if size < DISPUTE_WINDOW.get() {
+			gum::warn!(
+				target: LOG_TARGET,
+				size,
+				dispute_window = DISPUTE_WINDOW.get(),
+				"Session cache size is smaller than the dispute window, using the dispute window",
+			);
 		}
+
+		self.session_cache_size = size.max(DISPUTE_WINDOW.get());
+		self
+	}
+
+	/// Get notified once, the first time all blocks up to an activated head have been imported,
+	/// e.g. when the start-up catch-up is done.
+	pub fn with_caught_up_signal(mut self, signal: oneshot::Sender<()>) -> Self {
+		self.caught_up_signal = Some(signal);
+		self
+	}
+
+	/// Use the given slot duration for blocks of `session`, instead of the one from the config.
+	pub fn with_session_slot_duration(
+		mut self,
+		session: SessionIndex,
+		slot_duration_millis: u64,
+	) -> Self {
+		self.session_slot_durations.insert(session, slot_duration_millis);
+		self
+	}
+
+	/// Decide with `policy` when assigned validators who haven't approved yet become no-shows,
+	/// instead of after the `no_show_slots` of their session.
+	pub fn with_no_show_policy(mut self, policy: Box<dyn NoShowPolicy + Send + Sync>) -> Self {
+		self.no_show_policy = policy;
+		self
 	}
 
 	/// Revert to the block corresponding to the specified `hash`.
@@ -555,6 +925,16 @@ impl ApprovalVotingSubsystem {
 		let ops = overlay.into_write_ops();
 		backend.write(ops)
 	}
+
+	/// The number of fully-approved blocks in the chain ending at `head`, as far back as the
+	/// approval DB goes. Intended for debugging chain selection.
+	pub fn approved_chain_weight(&self, head: Hash) -> Result<u32, SubsystemError> {
+		let config =
+			approval_db::common::Config { col_approval_data: self.db_config.col_approval_data };
+		let backend = approval_db::common::DbBackend::new(self.db.clone(), config);
+
+		ops::approved_chain_weight(&backend, head)
+	}
 }
 
 // Checks and logs approval vote db state. It is perfectly normal to start with an
@@ -890,6 +1270,8 @@ where
 struct State {
 	keystore: Arc<LocalKeystore>,
 	slot_duration_millis: u64,
+	// Slot durations of sessions which don't use `slot_duration_millis`.
+	session_slot_durations: HashMap<SessionIndex, u64>,
 	clock: Arc<dyn Clock + Send + Sync>,
 	assignment_criteria: Box<dyn AssignmentCriteria + Send + Sync>,
 	// Per block, candidate records about how long we take until we gather enough
@@ -898,6 +1280,55 @@ struct State {
 	per_block_assignments_gathering_times:
 		LruMap<BlockNumber, HashMap<(Hash, CandidateHash), AssignmentGatheringRecord>>,
 	no_show_stats: NoShowStats,
+	// Whether heads whose chain could not be fully imported because of missing session info
+	// should be retried on the next import.
+	defer_unavailable_sessions: bool,
+	// Heads waiting for session info to become available, oldest first.
+	deferred_heads: Vec<Hash>,
+	// How included candidates with an unexpected relay parent are treated on import.
+	relay_parent_check: RelayParentCheck,
+	// Whether candidates with too few potential checkers are left for approval checking instead
+	// of being insta-approved.
+	disable_low_node_insta_approval: bool,
+	// Whether insta-approvals are refused, leaving the candidates to regular approval voting.
+	strict_no_insta_approval: bool,
+	// How candidates with a backing group unknown to the session are treated on insta-approval.
+	unknown_backing_group: UnknownBackingGroup,
+	// How imported chains with an unknown, unfinalized ancestor are treated.
+	on_missing_ancestor: OnMissingAncestor,
+	// If set, new blocks are fetched and imported in windows of this many blocks.
+	ancestry_window: Option<usize>,
+	// Whether VRF info is synthesized for blocks without any. Needs the `dev-mode` feature.
+	dev_mode: bool,
+	// Whether imported chains without any candidates are kept from approval distribution.
+	skip_idle_block_announcements: bool,
+	// Whether blocks whose candidate events can't be fetched are imported without candidates.
+	tolerate_candidate_events_errors: bool,
+	// Whether candidates are written to block entries in core index order instead of the order
+	// of the candidate events.
+	sort_candidates_by_core: bool,
+	// Which blocks may be force-approved by the digest of an imported block.
+	force_approve_target: ForceApproveTarget,
+	// How long to wait for the Chain API to answer requests made on block import.
+	chain_api_timeout: Duration,
+	// Receives an event for every imported block.
+	telemetry: Option<TelemetryHandle>,
+	// If set, assignments are only computed for candidates included on these cores.
+	core_filter: Option<HashSet<CoreIndex>>,
+	// Evaluated alongside `assignment_criteria` on import, only to compare their assignments.
+	shadow_criteria: Option<Box<dyn AssignmentCriteria + Send + Sync>>,
+	// Called with each of our assignments computed on import, before it is stored.
+	assignment_observer: Option<Arc<dyn Fn(&CoreIndex, &OurAssignment) + Send + Sync>>,
+	// Recently imported candidate receipts, shared between blocks including the same candidate.
+	receipt_cache: LruMap<CandidateHash, Arc<CandidateReceipt>>,
+	// Whether all blocks up to an activated head have been imported at least once.
+	caught_up: bool,
+	// Notified when `caught_up` is first set.
+	caught_up_signal: Option<oneshot::Sender<()>>,
+	// The highest-numbered block imported by the last handled head, if any.
+	last_imported: Option<(Hash, BlockNumber)>,
+	// Decides when assigned validators become no-shows.
+	no_show_policy: Box<dyn NoShowPolicy + Send + Sync>,
 }
 
 // Regularly dump the no-show stats at this block number frequency.
@@ -963,6 +1394,14 @@ impl Default for AssignmentGatheringRecord {
 
 #[overseer::contextbounds(ApprovalVoting, prefix = self::overseer)]
 impl State {
+	// The slot duration of blocks in the given session.
+	fn session_slot_duration_millis(&self, session: SessionIndex) -> u64 {
+		self.session_slot_durations
+			.get(&session)
+			.copied()
+			.unwrap_or(self.slot_duration_millis)
+	}
+
 	// Compute the required tranches for approval for this block and candidate combo.
 	// Fails if there is no approval entry for the block under the candidate or no candidate entry
 	// under the block, or if the session is out of bounds.
@@ -989,12 +1428,11 @@ impl State {
 		};
 		let block_hash = block_entry.block_hash();
 
-		let tranche_now = self.clock.tranche_now(self.slot_duration_millis, block_entry.slot());
-		let block_tick = slot_number_to_tick(self.slot_duration_millis, block_entry.slot());
-		let no_show_duration = slot_number_to_tick(
-			self.slot_duration_millis,
-			Slot::from(u64::from(session_info.no_show_slots)),
-		);
+		let slot_duration_millis = self.session_slot_duration_millis(block_entry.session());
+		let tranche_now = self.clock.tranche_now(slot_duration_millis, block_entry.slot());
+		let block_tick = self.clock.tick_for_slot(slot_duration_millis, block_entry.slot());
+		let no_show_duration =
+			self.no_show_duration(block_entry.session(), block_tick, session_info);
 
 		if let Some(approval_entry) = candidate_entry.approval_entry(&block_hash) {
 			let TranchesToApproveResult {
@@ -1069,6 +1507,20 @@ impl State {
 		}
 	}
 
+	// The number of ticks after `block_tick` at which validators assigned to a block of the given
+	// session become no-shows.
+	fn no_show_duration(
+		&self,
+		session: SessionIndex,
+		block_tick: Tick,
+		session_info: &SessionInfo,
+	) -> Tick {
+		let slot_duration_millis = self.session_slot_duration_millis(session);
+		self.no_show_policy
+			.no_show_deadline(block_tick, slot_duration_millis, session_info)
+			.saturating_sub(block_tick)
+	}
+
 	fn mark_begining_of_gathering_assignments(
 		&mut self,
 		block_number: BlockNumber,
@@ -1111,6 +1563,31 @@ impl State {
 		}
 	}
 
+	// Called after an activated head has been handled. Once nothing is left to import behind the
+	// head, notes that we are caught up, which is only reported the first time.
+	fn note_head_handled(&mut self, head: Hash, head_imported: bool) {
+		if self.caught_up || !head_imported || !self.deferred_heads.is_empty() {
+			return
+		}
+
+		self.caught_up = true;
+		gum::info!(
+			target: LOG_TARGET,
+			?head,
+			last_imported = ?self.last_imported_head(),
+			"Approval voting caught up with the chain head",
+		);
+
+		if let Some(signal) = self.caught_up_signal.take() {
+			let _ = signal.send(());
+		}
+	}
+
+	// The highest-numbered block imported by the last handled head, if any.
+	pub(crate) fn last_imported_head(&self) -> Option<(Hash, BlockNumber)> {
+		self.last_imported
+	}
+
 	fn cleanup_assignments_gathering_timestamp(&mut self, remove_lower_than: BlockNumber) {
 		while let Some((block_number, _)) = self.per_block_assignments_gathering_times.peek_oldest()
 		{
@@ -1180,6 +1657,108 @@ impl State {
 	}
 }
 
+// The in-memory bookkeeping of `State`, minus the keystore and the pluggable components, so
+// that tests can set it up once and reuse it.
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq)]
+struct StateSnapshot {
+	// Most recently used first.
+	per_block_assignments_gathering_times:
+		Vec<(BlockNumber, HashMap<(Hash, CandidateHash), AssignmentGatheringRecord>)>,
+	no_show_stats: NoShowStats,
+	deferred_heads: Vec<Hash>,
+	last_imported: Option<(Hash, BlockNumber)>,
+	// Most recently used first.
+	receipt_cache: Vec<(CandidateHash, Arc<CandidateReceipt>)>,
+	caught_up: bool,
+}
+
+#[cfg(any(test, feature = "bench"))]
+impl State {
+	// A state with the default options of the subsystem, for importing blocks outside of a running
+	// subsystem in tests and benchmarks.
+	fn with_defaults(
+		keystore: Arc<LocalKeystore>,
+		clock: Arc<dyn Clock + Send + Sync>,
+		assignment_criteria: Box<dyn AssignmentCriteria + Send + Sync>,
+	) -> Self {
+		State {
+			keystore,
+			slot_duration_millis: 6_000,
+			session_slot_durations: HashMap::new(),
+			clock,
+			assignment_criteria,
+			per_block_assignments_gathering_times: LruMap::new(ByLength::new(
+				MAX_BLOCKS_WITH_ASSIGNMENT_TIMESTAMPS,
+			)),
+			no_show_stats: NoShowStats::default(),
+			defer_unavailable_sessions: false,
+			deferred_heads: Vec::new(),
+			relay_parent_check: RelayParentCheck::Disabled,
+			disable_low_node_insta_approval: false,
+			strict_no_insta_approval: false,
+			unknown_backing_group: UnknownBackingGroup::TreatAsEmpty,
+			on_missing_ancestor: OnMissingAncestor::Proceed,
+			ancestry_window: None,
+			dev_mode: false,
+			skip_idle_block_announcements: false,
+			tolerate_candidate_events_errors: false,
+			sort_candidates_by_core: false,
+			force_approve_target: ForceApproveTarget::Exclusive,
+			chain_api_timeout: DEFAULT_CHAIN_API_TIMEOUT,
+			telemetry: None,
+			core_filter: None,
+			shadow_criteria: None,
+			assignment_observer: None,
+			receipt_cache: LruMap::new(ByLength::new(DEFAULT_RECEIPT_CACHE_SIZE)),
+			caught_up: false,
+			caught_up_signal: None,
+			last_imported: None,
+			no_show_policy: Box::new(SlotsNoShowPolicy),
+		}
+	}
+}
+
+#[cfg(test)]
+impl State {
+	fn snapshot(&self) -> StateSnapshot {
+		StateSnapshot {
+			per_block_assignments_gathering_times: self
+				.per_block_assignments_gathering_times
+				.iter()
+				.map(|(block_number, records)| (*block_number, records.clone()))
+				.collect(),
+			no_show_stats: self.no_show_stats.clone(),
+			deferred_heads: self.deferred_heads.clone(),
+			last_imported: self.last_imported,
+			receipt_cache: self
+				.receipt_cache
+				.iter()
+				.map(|(hash, receipt)| (*hash, receipt.clone()))
+				.collect(),
+			caught_up: self.caught_up,
+		}
+	}
+
+	fn restore(&mut self, snapshot: StateSnapshot) {
+		self.per_block_assignments_gathering_times.clear();
+		// Insert the oldest first, to end up with the same usage order.
+		for (block_number, records) in
+			snapshot.per_block_assignments_gathering_times.into_iter().rev()
+		{
+			self.per_block_assignments_gathering_times.insert(block_number, records);
+		}
+		self.no_show_stats = snapshot.no_show_stats;
+		self.deferred_heads = snapshot.deferred_heads;
+		self.last_imported = snapshot.last_imported;
+		self.receipt_cache.clear();
+		for (hash, receipt) in snapshot.receipt_cache.into_iter().rev() {
+			self.receipt_cache.insert(hash, receipt);
+		}
+		self.caught_up = snapshot.caught_up;
+	}
+}
+
 #[derive(Debug, Clone)]
 enum Action {
 	ScheduleWakeup {
@@ -1251,12 +1830,36 @@ where
 	let mut state = State {
 		keystore: subsystem.keystore,
 		slot_duration_millis: subsystem.slot_duration_millis,
+		session_slot_durations: subsystem.session_slot_durations,
 		clock: subsystem.clock,
 		assignment_criteria,
 		per_block_assignments_gathering_times: LruMap::new(ByLength::new(
 			MAX_BLOCKS_WITH_ASSIGNMENT_TIMESTAMPS,
 		)),
 		no_show_stats: NoShowStats::default(),
+		defer_unavailable_sessions: subsystem.defer_unavailable_sessions,
+		deferred_heads: Vec::new(),
+		relay_parent_check: subsystem.relay_parent_check,
+		disable_low_node_insta_approval: subsystem.disable_low_node_insta_approval,
+		strict_no_insta_approval: subsystem.strict_no_insta_approval,
+		unknown_backing_group: subsystem.unknown_backing_group,
+		on_missing_ancestor: subsystem.on_missing_ancestor,
+		ancestry_window: subsystem.ancestry_window,
+		dev_mode: subsystem.dev_mode,
+		skip_idle_block_announcements: subsystem.skip_idle_block_announcements,
+		tolerate_candidate_events_errors: subsystem.tolerate_candidate_events_errors,
+		sort_candidates_by_core: subsystem.sort_candidates_by_core,
+		force_approve_target: subsystem.force_approve_target,
+		chain_api_timeout: subsystem.chain_api_timeout,
+		telemetry: subsystem.telemetry,
+		core_filter: subsystem.core_filter,
+		shadow_criteria: subsystem.shadow_criteria,
+		assignment_observer: subsystem.assignment_observer,
+		receipt_cache: LruMap::new(ByLength::new(subsystem.receipt_cache_size)),
+		caught_up: false,
+		caught_up_signal: subsystem.caught_up_signal,
+		last_imported: None,
+		no_show_policy: subsystem.no_show_policy,
 	};
 
 	let mut last_finalized_height: Option<BlockNumber> = {
@@ -1276,7 +1879,7 @@ where
 	// `None` on start-up. Gets initialized/updated on leaf update
 	let mut session_info_provider = RuntimeInfo::new_with_config(RuntimeInfoConfig {
 		keystore: None,
-		session_cache_lru_size: DISPUTE_WINDOW.get(),
+		session_cache_lru_size: subsystem.session_cache_size,
 	});
 
 	let mut wakeups = Wakeups::default();
@@ -1480,8 +2083,9 @@ pub async fn start_approval_worker<
 	task_name: &'static str,
 	group_name: &'static str,
 	clock: Arc<dyn Clock + Send + Sync>,
+	telemetry: Option<TelemetryHandle>,
 ) -> SubsystemResult<()> {
-	let approval_voting = ApprovalVotingSubsystem::with_config_and_clock(
+	let mut approval_voting = ApprovalVotingSubsystem::with_config_and_clock(
 		config,
 		db.clone(),
 		keystore,
@@ -1492,6 +2096,9 @@ pub async fn start_approval_worker<
 		MAX_APPROVAL_RETRIES,
 		APPROVAL_CHECKING_TIMEOUT / 2,
 	);
+	if let Some(telemetry) = telemetry {
+		approval_voting = approval_voting.with_telemetry(telemetry);
+	}
 	let backend = DbBackend::new(db.clone(), approval_voting.db_config);
 	let spawner = approval_voting.spawner.clone();
 	spawner.spawn_blocking(
@@ -2032,6 +2639,7 @@ async fn handle_from_overseer<
 					db,
 					session_info_provider,
 					head,
+					metrics,
 					last_finalized_height,
 				)
 				.await
@@ -2080,6 +2688,9 @@ async fn handle_from_overseer<
 								}
 							}
 						}
+
+						let head_imported = db.load_block_entry(&head)?.is_some();
+						state.note_head_handled(head, head_imported);
 					},
 				}
 			}
@@ -3295,12 +3906,10 @@ async fn process_wakeup<Sender: SubsystemSender<RuntimeApiMessage>>(
 			None => return Ok(Vec::new()),
 		};
 
-	let block_tick = slot_number_to_tick(state.slot_duration_millis, block_entry.slot());
-	let no_show_duration = slot_number_to_tick(
-		state.slot_duration_millis,
-		Slot::from(u64::from(session_info.no_show_slots)),
-	);
-	let tranche_now = state.clock.tranche_now(state.slot_duration_millis, block_entry.slot());
+	let slot_duration_millis = state.session_slot_duration_millis(block_entry.session());
+	let block_tick = state.clock.tick_for_slot(slot_duration_millis, block_entry.slot());
+	let no_show_duration = state.no_show_duration(block_entry.session(), block_tick, session_info);
+	let tranche_now = state.clock.tranche_now(slot_duration_millis, block_entry.slot());
 
 	gum::trace!(
 		target: LOG_TARGET,
@@ -4019,7 +4628,10 @@ fn compute_delayed_approval_sending_tick(
 	session_info: &SessionInfo,
 	metrics: &Metrics,
 ) -> Tick {
-	let current_block_tick = slot_number_to_tick(state.slot_duration_millis, block_entry.slot());
+	let current_block_tick = state.clock.tick_for_slot(
+		state.session_slot_duration_millis(block_entry.session()),
+		block_entry.slot(),
+	);
 	let assignment_tranche = candidate_entry
 		.approval_entry(&block_entry.block_hash())
 		.and_then(|approval_entry| approval_entry.our_assignment())
@@ -4028,10 +4640,8 @@ fn compute_delayed_approval_sending_tick(
 
 	let assignment_triggered_tick = current_block_tick + assignment_tranche as Tick;
 
-	let no_show_duration_ticks = slot_number_to_tick(
-		state.slot_duration_millis,
-		Slot::from(u64::from(session_info.no_show_slots)),
-	);
+	let no_show_duration_ticks =
+		state.no_show_duration(block_entry.session(), current_block_tick, session_info);
 	let tick_now = state.clock.tick_now();
 
 	let sign_no_later_than = min(
@@ -24,7 +24,10 @@ use polkadot_primitives::{
 	vstaging::CandidateReceiptV2 as CandidateReceipt, BlockNumber, CandidateHash, GroupIndex, Hash,
 };
 
-use std::collections::{hash_map::Entry, BTreeMap, HashMap};
+use std::{
+	collections::{hash_map::Entry, BTreeMap, HashMap},
+	sync::Arc,
+};
 
 use super::{
 	approval_db::{common::StoredBlockRange, v2::OurAssignment},
@@ -37,7 +40,8 @@ use super::{
 /// candidate and approval entries.
 #[derive(Clone)]
 pub struct NewCandidateInfo {
-	candidate: CandidateReceipt,
+	// Shared with other blocks including the candidate, only cloned if the candidate is new.
+	candidate: Arc<CandidateReceipt>,
 	backing_group: GroupIndex,
 	our_assignment: Option<OurAssignment>,
 }
@@ -45,11 +49,11 @@ pub struct NewCandidateInfo {
 impl NewCandidateInfo {
 	/// Convenience constructor
 	pub fn new(
-		candidate: CandidateReceipt,
+		candidate: impl Into<Arc<CandidateReceipt>>,
 		backing_group: GroupIndex,
 		our_assignment: Option<OurAssignment>,
 	) -> Self {
-		Self { candidate, backing_group, our_assignment }
+		Self { candidate: candidate.into(), backing_group, our_assignment }
 	}
 }
 
@@ -190,19 +194,80 @@ pub fn canonicalize(
 /// candidate entries and add this as a child of any block entry corresponding to the
 /// parent hash.
 ///
-/// Has no effect if there is already an entry for the block or `candidate_info` returns
-/// `None` for any of the candidates referenced by the block entry. In these cases,
-/// no information about new candidates will be referred to by this function.
+/// Has no effect if there is already an entry for the block, in which case no information about
+/// new candidates will be referred to by this function. Fails without writing anything if
+/// `candidate_info` returns `None` for any of the candidates referenced by the block entry.
+#[cfg(test)]
 pub fn add_block_entry(
 	store: &mut OverlayedBackend<'_, impl Backend>,
 	entry: BlockEntry,
 	n_validators: usize,
 	candidate_info: impl Fn(&CandidateHash) -> Option<NewCandidateInfo>,
+) -> SubsystemResult<Vec<(CandidateHash, CandidateEntry)>> {
+	Ok(add_block_entries(store, vec![(entry, n_validators, candidate_info)])?
+		.pop()
+		.unwrap_or_default())
+}
+
+/// Record a batch of new block entries, ordered such that parents come before their children.
+///
+/// This is equivalent to calling [`add_block_entry`] for each of the entries in order, but
+/// blocks added earlier in the batch are kept at hand for linking their children, instead of
+/// being reloaded from the store. Returns the candidate entries of each block, in order.
+pub fn add_block_entries<F: Fn(&CandidateHash) -> Option<NewCandidateInfo>>(
+	store: &mut OverlayedBackend<'_, impl Backend>,
+	entries: Vec<(BlockEntry, usize, F)>,
+) -> SubsystemResult<Vec<Vec<(CandidateHash, CandidateEntry)>>> {
+	let mut added = HashMap::with_capacity(entries.len());
+	let mut candidate_entries = Vec::with_capacity(entries.len());
+
+	let mut result = Ok(());
+	for (entry, n_validators, candidate_info) in entries {
+		match add_block_entry_inner(store, &mut added, entry, n_validators, candidate_info) {
+			Ok(entries) => candidate_entries.push(entries),
+			Err(e) => {
+				result = Err(e);
+				break
+			},
+		}
+	}
+
+	// Blocks added before any error must be written, just as if they were added one by one.
+	for (_, entry) in added {
+		store.write_block_entry(entry);
+	}
+
+	result.map(|_| candidate_entries)
+}
+
+// Adds a block entry, linking it to its parent in `added` if present and putting it there
+// instead of writing it to the store.
+fn add_block_entry_inner(
+	store: &mut OverlayedBackend<'_, impl Backend>,
+	added: &mut HashMap<Hash, BlockEntry>,
+	entry: BlockEntry,
+	n_validators: usize,
+	candidate_info: impl Fn(&CandidateHash) -> Option<NewCandidateInfo>,
 ) -> SubsystemResult<Vec<(CandidateHash, CandidateEntry)>> {
 	let session = entry.session();
 	let parent_hash = entry.parent_hash();
 	let number = entry.block_number();
 
+	// Look up all candidate infos before writing anything, so that a missing one doesn't leave a
+	// partially written block behind.
+	let mut candidate_infos = Vec::with_capacity(entry.candidates().len());
+	for (_, candidate_hash) in entry.candidates() {
+		match candidate_info(candidate_hash) {
+			None =>
+				return Err(SubsystemError::Context(format!(
+					"find info of candidate {:?} included by block {:?}",
+					candidate_hash,
+					entry.block_hash(),
+				))),
+			Some(info) => candidate_infos.push((*candidate_hash, info)),
+		}
+	}
+
 	// Update the stored block range.
 	{
 		let new_range = match store.load_stored_blocks()? {
@@ -230,17 +295,13 @@ pub fn add_block_entry(
 
 	// read and write all updated entries.
 	{
-		for (_, candidate_hash) in entry.candidates() {
-			let NewCandidateInfo { candidate, backing_group, our_assignment } =
-				match candidate_info(candidate_hash) {
-					None => return Ok(Vec::new()),
-					Some(info) => info,
-				};
-
+		for (candidate_hash, NewCandidateInfo { candidate, backing_group, our_assignment }) in
+			candidate_infos
+		{
 			let mut candidate_entry =
 				store.load_candidate_entry(&candidate_hash)?.unwrap_or_else(move || {
 					CandidateEntry {
-						candidate,
+						candidate: Arc::unwrap_or_clone(candidate),
 						session,
 						block_assignments: BTreeMap::new(),
 						approvals: bitvec::bitvec![u8, BitOrderLsb0; 0; n_validators],
@@ -261,18 +322,23 @@ pub fn add_block_entry(
 
 			store.write_candidate_entry(candidate_entry.clone());
 
-			candidate_entries.push((*candidate_hash, candidate_entry));
+			candidate_entries.push((candidate_hash, candidate_entry));
 		}
 	};
 
 	// Update the child index for the parent.
-	store.load_block_entry(&parent_hash)?.map(|mut e| {
-		e.children.push(entry.block_hash());
-		store.write_block_entry(e);
-	});
+	match added.get_mut(&parent_hash) {
+		Some(parent) => parent.children.push(entry.block_hash()),
+		None => {
+			store.load_block_entry(&parent_hash)?.map(|mut e| {
+				e.children.push(entry.block_hash());
+				store.write_block_entry(e);
+			});
+		},
+	}
 
 	// Put the new block entry in.
-	store.write_block_entry(entry);
+	added.insert(entry.block_hash(), entry);
 
 	Ok(candidate_entries)
 }
@@ -338,6 +404,23 @@ pub fn force_approve(
 	Ok(approved_hashes)
 }
 
+/// Count the fully-approved blocks in the chain ending at `head`, walking back through all
+/// ancestors still present in the store.
+pub fn approved_chain_weight(store: &impl Backend, head: Hash) -> SubsystemResult<u32> {
+	let mut weight = 0;
+	let mut cur_hash = head;
+
+	while let Some(entry) = store.load_block_entry(&cur_hash)? {
+		if entry.is_fully_approved() {
+			weight += 1;
+		}
+
+		cur_hash = entry.parent_hash();
+	}
+
+	Ok(weight)
+}
+
 /// Revert to the block corresponding to the specified `hash`.
 /// The operation is not allowed for blocks older than the last finalized one.
 pub fn revert_to(
@@ -368,6 +368,12 @@ impl CandidateEntry {
 		prev
 	}
 
+	/// The length of the bit-vec of approvals, which is the number of validators in the session
+	/// of the candidate: the bit-vec is sized to it when the candidate is first imported.
+	pub fn expected_approvals_len(&self) -> usize {
+		self.approvals.len()
+	}
+
 	/// Query whether a given validator has approved the candidate.
 	pub fn has_approved(&self, validator: ValidatorIndex) -> bool {
 		self.approvals.get(validator.0 as usize).map(|b| *b).unwrap_or(false)
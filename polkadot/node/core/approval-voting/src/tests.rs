@@ -24,6 +24,7 @@ use overseer::prometheus::{
 };
 use polkadot_node_primitives::{
 	approval::{
+		time::slot_number_to_tick,
 		v1::{
 			AssignmentCert, AssignmentCertKind, DelayTranche, VrfPreOutput, VrfProof, VrfSignature,
 			RELAY_VRF_MODULO_CONTEXT,
@@ -51,6 +52,7 @@ use std::{cmp::max, time::Duration};
 
 use assert_matches::assert_matches;
 use parking_lot::Mutex;
+use sp_consensus_slots::Slot;
 use sp_keyring::sr25519::Keyring as Sr25519Keyring;
 use sp_keystore::Keystore;
 use std::{
@@ -472,6 +474,8 @@ struct HarnessConfigBuilder {
 	backend: Option<TestStore>,
 	assignment_criteria: Option<Box<dyn AssignmentCriteria + Send + Sync + 'static>>,
 	major_syncing: bool,
+	caught_up_signal: Option<oneshot::Sender<()>>,
+	session_cache_size: Option<u32>,
 }
 
 impl HarnessConfigBuilder {
@@ -493,6 +497,16 @@ impl HarnessConfigBuilder {
 		self
 	}
 
+	pub fn caught_up_signal(&mut self, signal: oneshot::Sender<()>) -> &mut Self {
+		self.caught_up_signal = Some(signal);
+		self
+	}
+
+	pub fn session_cache_size(&mut self, size: u32) -> &mut Self {
+		self.session_cache_size = Some(size);
+		self
+	}
+
 	pub fn build(&mut self) -> HarnessConfig {
 		let (sync_oracle, sync_oracle_handle) =
 			self.sync_oracle.take().unwrap_or_else(|| make_sync_oracle(self.major_syncing));
@@ -508,6 +522,8 @@ impl HarnessConfigBuilder {
 			clock: self.clock.take().unwrap_or_else(|| MockClock::new(0)),
 			backend: self.backend.take().unwrap_or_else(|| TestStore::default()),
 			assignment_criteria,
+			caught_up_signal: self.caught_up_signal.take(),
+			session_cache_size: self.session_cache_size,
 		}
 	}
 }
@@ -518,6 +534,8 @@ struct HarnessConfig {
 	clock: MockClock,
 	backend: TestStore,
 	assignment_criteria: Box<dyn AssignmentCriteria + Send + Sync + 'static>,
+	caught_up_signal: Option<oneshot::Sender<()>>,
+	session_cache_size: Option<u32>,
 }
 
 impl HarnessConfig {
@@ -544,8 +562,15 @@ fn test_harness<T: Future<Output = VirtualOverseer>>(
 ) {
 	sp_tracing::init_for_tests();
 
-	let HarnessConfig { sync_oracle, sync_oracle_handle, clock, backend, assignment_criteria } =
-		config;
+	let HarnessConfig {
+		sync_oracle,
+		sync_oracle_handle,
+		clock,
+		backend,
+		assignment_criteria,
+		caught_up_signal,
+		session_cache_size,
+	} = config;
 
 	let pool = sp_core::testing::TaskExecutor::new();
 	let (mut context, virtual_overseer) =
@@ -561,27 +586,28 @@ fn test_harness<T: Future<Output = VirtualOverseer>>(
 	let db = kvdb_memorydb::create(test_constants::NUM_COLUMNS);
 	let db = polkadot_node_subsystem_util::database::kvdb_impl::DbAdapter::new(db, &[]);
 	let sender = context.sender().clone();
-	let subsystem = run(
-		context,
-		sender.clone(),
-		sender.clone(),
-		ApprovalVotingSubsystem::with_config_and_clock(
-			Config {
-				col_approval_data: test_constants::TEST_CONFIG.col_approval_data,
-				slot_duration_millis: SLOT_DURATION_MILLIS,
-			},
-			Arc::new(db),
-			Arc::new(keystore),
-			sync_oracle,
-			Metrics::default(),
-			clock.clone(),
-			Arc::new(SpawnGlue(pool)),
-			NUM_APPROVAL_RETRIES,
-			RETRY_BACKOFF,
-		),
-		assignment_criteria,
-		backend,
+	let mut approval_voting = ApprovalVotingSubsystem::with_config_and_clock(
+		Config {
+			col_approval_data: test_constants::TEST_CONFIG.col_approval_data,
+			slot_duration_millis: SLOT_DURATION_MILLIS,
+		},
+		Arc::new(db),
+		Arc::new(keystore),
+		sync_oracle,
+		Metrics::default(),
+		clock.clone(),
+		Arc::new(SpawnGlue(pool)),
+		NUM_APPROVAL_RETRIES,
+		RETRY_BACKOFF,
 	);
+	if let Some(signal) = caught_up_signal {
+		approval_voting = approval_voting.with_caught_up_signal(signal);
+	}
+	if let Some(size) = session_cache_size {
+		approval_voting = approval_voting.with_session_cache_size(size);
+	}
+	let subsystem =
+		run(context, sender.clone(), sender.clone(), approval_voting, assignment_criteria, backend);
 
 	let test_fut = test(TestHarness { virtual_overseer, clock, sync_oracle_handle });
 
@@ -1275,6 +1301,122 @@ fn subsystem_rejects_approval_if_no_candidate_entry() {
 	});
 }
 
+#[test]
+fn caught_up_signal_fires_once_chain_is_imported() {
+	let (caught_up_tx, mut caught_up_rx) = oneshot::channel();
+	let config = HarnessConfigBuilder::default().caught_up_signal(caught_up_tx).build();
+	test_harness(config, |test_harness| async move {
+		let TestHarness { mut virtual_overseer, sync_oracle_handle: _sync_oracle_handle, .. } =
+			test_harness;
+		assert_matches!(
+			overseer_recv(&mut virtual_overseer).await,
+			AllMessages::ChainApi(ChainApiMessage::FinalizedBlockNumber(rx)) => {
+				rx.send(Ok(0)).unwrap();
+			}
+		);
+
+		let block_hash = Hash::repeat_byte(0x01);
+		let mut builder = ChainBuilder::new();
+		builder.add_block(
+			block_hash,
+			ChainBuilder::GENESIS_HASH,
+			1,
+			BlockConfig {
+				slot: Slot::from(1),
+				candidates: None,
+				session_info: None,
+				end_syncing: false,
+			},
+		);
+		builder.add_block(
+			Hash::repeat_byte(0x02),
+			block_hash,
+			2,
+			BlockConfig {
+				slot: Slot::from(2),
+				candidates: None,
+				session_info: None,
+				end_syncing: false,
+			},
+		);
+
+		// The finalized genesis block is never imported, so we can't be caught up before
+		// importing block 1. Afterwards, the signal must have fired exactly once, even though
+		// block 2 was handled as well.
+		builder.build(&mut virtual_overseer).await;
+		assert_eq!(caught_up_rx.try_recv(), Ok(Some(())));
+		assert!(caught_up_rx.try_recv().is_err());
+
+		virtual_overseer
+	});
+}
+
+#[test]
+fn session_info_stays_cached_with_custom_cache_size() {
+	let sessions = DISPUTE_WINDOW.get() + 2;
+
+	let config = HarnessConfigBuilder::default().session_cache_size(sessions).build();
+	test_harness(config, |test_harness| async move {
+		let TestHarness { mut virtual_overseer, sync_oracle_handle: _sync_oracle_handle, .. } =
+			test_harness;
+		assert_matches!(
+			overseer_recv(&mut virtual_overseer).await,
+			AllMessages::ChainApi(ChainApiMessage::FinalizedBlockNumber(rx)) => {
+				rx.send(Ok(0)).unwrap();
+			}
+		);
+
+		// Each block is in the session of its number.
+		let mut builder = ChainBuilder::new();
+		let mut parent_hash = ChainBuilder::GENESIS_HASH;
+		for number in 1..=sessions {
+			let hash = Hash::repeat_byte(number as u8);
+			builder.add_block(
+				hash,
+				parent_hash,
+				number,
+				BlockConfig {
+					slot: Slot::from(number as u64),
+					candidates: None,
+					session_info: None,
+					end_syncing: false,
+				},
+			);
+			parent_hash = hash;
+		}
+		builder.build(&mut virtual_overseer).await;
+
+		// Session 1 is older than the dispute window, but still within the cache, so importing a
+		// fork in it doesn't request its session info again.
+		let config = BlockConfig {
+			slot: Slot::from(1),
+			candidates: None,
+			session_info: None,
+			end_syncing: false,
+		};
+		let fork_header = ChainBuilder::make_header(ChainBuilder::GENESIS_HASH, config.slot, 1);
+		let ancestry = vec![
+			(
+				ChainBuilder::GENESIS_HASH,
+				ChainBuilder::make_header(ChainBuilder::GENESIS_PARENT_HASH, Slot::from(0), 0),
+			),
+			(Hash::repeat_byte(0xaa), fork_header),
+		];
+		import_block(
+			&mut virtual_overseer,
+			&ancestry,
+			1,
+			&config,
+			false,
+			true,
+			Arc::new(AtomicBool::new(false)),
+		)
+		.await;
+
+		virtual_overseer
+	});
+}
+
 #[test]
 fn subsystem_rejects_approval_if_no_block_entry() {
 	test_harness(HarnessConfig::default(), |test_harness| async move {
@@ -5629,16 +5771,11 @@ fn subsystem_launches_missed_assignments_on_restart() {
 // Test we correctly update the timer when we mark the beginning of gathering assignments.
 #[test]
 fn test_gathering_assignments_statements() {
-	let mut state = State {
-		keystore: Arc::new(LocalKeystore::in_memory()),
-		slot_duration_millis: 6_000,
-		clock: Arc::new(MockClock::default()),
-		assignment_criteria: Box::new(MockAssignmentCriteria::check_only(|_| Ok(0))),
-		per_block_assignments_gathering_times: LruMap::new(ByLength::new(
-			MAX_BLOCKS_WITH_ASSIGNMENT_TIMESTAMPS,
-		)),
-		no_show_stats: NoShowStats::default(),
-	};
+	let mut state = State::with_defaults(
+		Arc::new(LocalKeystore::in_memory()),
+		Arc::new(MockClock::default()),
+		Box::new(MockAssignmentCriteria::check_only(|_| Ok(0))),
+	);
 
 	for i in 0..200i32 {
 		state.mark_begining_of_gathering_assignments(
@@ -5719,24 +5856,139 @@ fn test_gathering_assignments_statements() {
 	assert_eq!(state.per_block_assignments_gathering_times.len(), 0);
 }
 
+// A policy giving validators twice as long as the default one before they become no-shows.
+struct DoubleNoShowPolicy(SlotsNoShowPolicy);
+
+impl NoShowPolicy for DoubleNoShowPolicy {
+	fn no_show_deadline(
+		&self,
+		block_tick: Tick,
+		slot_duration_millis: u64,
+		session_info: &SessionInfo,
+	) -> Tick {
+		let deadline = self.0.no_show_deadline(block_tick, slot_duration_millis, session_info);
+		deadline + (deadline - block_tick)
+	}
+}
+
+fn no_show_policy_test_state() -> State {
+	State {
+		slot_duration_millis: SLOT_DURATION_MILLIS,
+		..State::with_defaults(
+			Arc::new(LocalKeystore::in_memory()),
+			Arc::new(MockClock::default()),
+			Box::new(MockAssignmentCriteria::check_only(|_| Ok(0))),
+		)
+	}
+}
+
+// Test the no-show duration is derived from the configured no-show policy.
+#[test]
+fn test_custom_no_show_policy() {
+	let mut state = no_show_policy_test_state();
+
+	let session_info = session_info(&[Sr25519Keyring::Alice, Sr25519Keyring::Bob]);
+	let block_tick = slot_to_tick(10);
+
+	let default_duration = state.no_show_duration(1, block_tick, &session_info);
+	assert_eq!(default_duration, slot_to_tick(session_info.no_show_slots as u64));
+
+	state.no_show_policy = Box::new(DoubleNoShowPolicy(SlotsNoShowPolicy));
+	assert_eq!(state.no_show_duration(1, block_tick, &session_info), 2 * default_duration);
+}
+
+// Test the no-show duration follows the slot duration of the block's session.
+#[test]
+fn test_no_show_duration_uses_session_slot_duration() {
+	let mut state = no_show_policy_test_state();
+	state.session_slot_durations.insert(1, SLOT_DURATION_MILLIS);
+	state.session_slot_durations.insert(2, 2 * SLOT_DURATION_MILLIS);
+
+	let session_info = session_info(&[Sr25519Keyring::Alice, Sr25519Keyring::Bob]);
+	let no_show_slots = Slot::from(session_info.no_show_slots as u64);
+	let block_tick = slot_to_tick(10);
+
+	assert_eq!(
+		state.no_show_duration(1, block_tick, &session_info),
+		slot_number_to_tick(SLOT_DURATION_MILLIS, no_show_slots),
+	);
+	assert_eq!(
+		state.no_show_duration(2, block_tick, &session_info),
+		slot_number_to_tick(2 * SLOT_DURATION_MILLIS, no_show_slots),
+	);
+	assert_eq!(
+		state.no_show_duration(2, block_tick, &session_info),
+		2 * state.no_show_duration(1, block_tick, &session_info),
+	);
+}
+
+// Test a snapshot of a populated state restores into a fresh state that behaves the same.
+#[test]
+fn test_state_snapshot_restore() {
+	let make_state = || {
+		State::with_defaults(
+			Arc::new(LocalKeystore::in_memory()),
+			Arc::new(MockClock::default()),
+			Box::new(MockAssignmentCriteria::check_only(|_| Ok(0))),
+		)
+	};
+
+	let mut state = make_state();
+	for i in 0..10u32 {
+		let block_hash = Hash::repeat_byte(i as u8);
+		let candidate_hash = CandidateHash(Hash::repeat_byte(i as u8 + 100));
+		state.mark_begining_of_gathering_assignments(i, block_hash, candidate_hash);
+		state.record_no_shows(1, i % 3, &vec![ValidatorIndex(i % 4)]);
+	}
+	state.deferred_heads.push(Hash::repeat_byte(0xaa));
+	for i in 0..3u8 {
+		let receipt = dummy_candidate_receipt_v2(Hash::repeat_byte(i));
+		state.receipt_cache.insert(receipt.hash(), Arc::new(receipt));
+	}
+	state.caught_up = true;
+
+	let snapshot = state.snapshot();
+	assert_eq!(snapshot.per_block_assignments_gathering_times.len(), 10);
+	assert_eq!(snapshot.receipt_cache.len(), 3);
+
+	let mut restored = make_state();
+	restored.restore(snapshot.clone());
+	assert_eq!(restored.snapshot(), snapshot);
+
+	// Both states evolve identically from here on.
+	for state in [&mut state, &mut restored] {
+		state.cleanup_assignments_gathering_timestamp(4);
+		state.mark_gathered_enough_assignments(
+			5,
+			Hash::repeat_byte(5),
+			CandidateHash(Hash::repeat_byte(105)),
+		);
+	}
+	assert_eq!(restored.snapshot(), state.snapshot());
+	assert_eq!(restored.per_block_assignments_gathering_times.len(), 6);
+	assert_eq!(
+		restored.per_block_assignments_gathering_times.peek_oldest().map(|(n, _)| *n),
+		Some(4)
+	);
+}
+
 // Test we note the time we took to transition RequiredTranche  from Pending to Exact and
 // that we increase the stage when we transition from Exact to Pending.
 #[test]
 fn test_observe_assignment_gathering_status() {
-	let mut state = State {
-		keystore: Arc::new(LocalKeystore::in_memory()),
-		slot_duration_millis: 6_000,
-		clock: Arc::new(MockClock::default()),
-		assignment_criteria: Box::new(MockAssignmentCriteria::check_only(|_| Ok(0))),
-		per_block_assignments_gathering_times: LruMap::new(ByLength::new(
-			MAX_BLOCKS_WITH_ASSIGNMENT_TIMESTAMPS,
-		)),
-		no_show_stats: NoShowStats::default(),
-	};
+	let mut state = State::with_defaults(
+		Arc::new(LocalKeystore::in_memory()),
+		Arc::new(MockClock::default()),
+		Box::new(MockAssignmentCriteria::check_only(|_| Ok(0))),
+	);
 
 	let metrics_inner = MetricsInner {
 		imported_candidates_total: IntCounter::new("dummy", "dummy").unwrap(),
 		assignments_produced: Histogram::with_opts(HistogramOpts::new("dummy", "dummy")).unwrap(),
+		assignments_computed_total: IntCounterVec::new(Opts::new("dummy", "dummy"), &["dummy"])
+			.unwrap(),
+		our_assignment_tranches: Histogram::with_opts(HistogramOpts::new("dummy", "dummy"))
+			.unwrap(),
 		approvals_produced_total: IntCounterVec::new(Opts::new("dummy", "dummy"), &["dummy"])
 			.unwrap(),
 		no_shows_total: IntCounter::new("dummy", "dummy").unwrap(),
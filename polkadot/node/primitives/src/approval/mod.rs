@@ -28,6 +28,8 @@ pub mod v1 {
 	pub use sp_consensus_babe::{
 		Randomness, Slot, VrfPreOutput, VrfProof, VrfSignature, VrfTranscript,
 	};
+	#[cfg(feature = "sassafras-experimental")]
+	pub use sp_consensus_sassafras as sassafras_primitives;
 
 	use codec::{Decode, Encode};
 	use polkadot_primitives::{
@@ -216,6 +218,45 @@ pub mod v1 {
 
 		None
 	}
+
+	/// An unsafe VRF pre-output taken from a Sassafras slot claim.
+	#[cfg(feature = "sassafras-experimental")]
+	pub struct SassafrasUnsafeVRFPreOutput {
+		vrf_pre_output: sassafras_primitives::vrf::VrfPreOutput,
+		slot: Slot,
+	}
+
+	#[cfg(feature = "sassafras-experimental")]
+	impl SassafrasUnsafeVRFPreOutput {
+		/// Get the slot.
+		pub fn slot(&self) -> Slot {
+			self.slot
+		}
+
+		/// Compute the randomness associated with this VRF output.
+		///
+		/// The slot claim pre-output already commits to the epoch randomness, so unlike BABE
+		/// no epoch information is required.
+		pub fn compute_randomness(self) -> RelayVRFStory {
+			let output = self.vrf_pre_output.make_bytes();
+			RelayVRFStory(sp_core::blake2_256(&(RELAY_VRF_STORY_CONTEXT, output).encode()))
+		}
+	}
+
+	/// Extract the slot number and relay VRF from a header carrying a Sassafras slot claim.
+	///
+	/// This fails if there is no Sassafras `PreRuntime` digest.
+	#[cfg(feature = "sassafras-experimental")]
+	pub fn sassafras_unsafe_vrf_info(header: &Header) -> Option<SassafrasUnsafeVRFPreOutput> {
+		use sassafras_primitives::digests::SlotClaim;
+
+		header.digest.logs.iter().find_map(|digest| {
+			SlotClaim::try_from(digest).ok().map(|claim| SassafrasUnsafeVRFPreOutput {
+				vrf_pre_output: claim.vrf_signature.pre_output,
+				slot: claim.slot,
+			})
+		})
+	}
 }
 
 /// A list of primitives introduced by v2.
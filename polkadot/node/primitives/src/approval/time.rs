@@ -27,6 +27,7 @@ use crate::approval::v1::DelayTranche;
 use sp_consensus_slots::Slot;
 use std::{
 	collections::HashSet,
+	ops::RangeInclusive,
 	pin::Pin,
 	task::Poll,
 	time::{Duration, SystemTime},
@@ -50,6 +51,11 @@ pub trait Clock {
 
 	/// Yields a future which concludes when the given tick is reached.
 	fn wait(&self, tick: Tick) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+	/// Yields the tick at which the given slot starts.
+	fn tick_for_slot(&self, slot_duration_millis: u64, slot: Slot) -> Tick {
+		slot_number_to_tick(slot_duration_millis, slot)
+	}
 }
 
 /// Extension methods for clocks.
@@ -104,6 +110,14 @@ pub fn slot_number_to_tick(slot_duration_millis: u64, slot: Slot) -> Tick {
 	u64::from(slot) * ticks_per_slot
 }
 
+/// Converts each of the given slots to its tick, e.g. to precompute a slot to tick table.
+/// See [`slot_number_to_tick`].
+pub fn ticks_for_slots(slot_duration_millis: u64, slots: RangeInclusive<Slot>) -> Vec<Tick> {
+	(u64::from(*slots.start())..=u64::from(*slots.end()))
+		.map(|slot| slot_number_to_tick(slot_duration_millis, slot.into()))
+		.collect()
+}
+
 /// Converts a tick to the slot number.
 pub fn tick_to_slot_number(slot_duration_millis: u64, tick: Tick) -> Slot {
 	let ticks_per_slot = slot_duration_millis / TICK_DURATION_MILLIS;
@@ -180,7 +194,23 @@ mod tests {
 
 	use crate::approval::time::{Clock, SystemClock};
 
-	use super::DelayedApprovalTimer;
+	use super::{ticks_for_slots, DelayedApprovalTimer, Slot, TICK_DURATION_MILLIS};
+
+	#[test]
+	fn tick_for_slot_uses_slot_duration() {
+		assert_eq!(SystemClock.tick_for_slot(6_000, Slot::from(10)), 120);
+	}
+
+	#[test]
+	fn ticks_for_slots_are_a_slot_apart() {
+		let ticks = ticks_for_slots(6_000, Slot::from(10)..=Slot::from(20));
+
+		assert_eq!(ticks.len(), 11);
+		assert_eq!(ticks[0], 120);
+		for pair in ticks.windows(2) {
+			assert_eq!(pair[1] - pair[0], 6_000 / TICK_DURATION_MILLIS);
+		}
+	}
 
 	#[test]
 	fn test_select_empty_timer() {
@@ -445,6 +445,7 @@ where
 				chain_selection_config,
 				fetch_chunks_threshold,
 				enable_approval_voting_parallel,
+				telemetry: telemetry.as_ref().map(|x| x.handle()),
 			})
 		};
 
@@ -43,6 +43,7 @@ use sc_authority_discovery::Service as AuthorityDiscoveryService;
 use sc_client_api::AuxStore;
 use sc_keystore::LocalKeystore;
 use sc_network::{NetworkStateInfo, NotificationService};
+use sc_telemetry::TelemetryHandle;
 use std::{collections::HashMap, sync::Arc};
 
 pub use polkadot_approval_distribution::ApprovalDistribution as ApprovalDistributionSubsystem;
@@ -142,6 +143,8 @@ pub struct ExtendedOverseerGenArgs {
 	/// Enable approval-voting-parallel subsystem and disable the standalone approval-voting and
 	/// approval-distribution subsystems.
 	pub enable_approval_voting_parallel: bool,
+	/// Telemetry handle, used by approval voting to report imported blocks.
+	pub telemetry: Option<TelemetryHandle>,
 }
 
 /// Obtain a prepared validator `Overseer`, that is initialized with all default values.
@@ -181,6 +184,7 @@ pub fn validator_overseer_builder<Spawner, RuntimeClient>(
 		chain_selection_config,
 		fetch_chunks_threshold,
 		enable_approval_voting_parallel,
+		telemetry,
 	}: ExtendedOverseerGenArgs,
 ) -> Result<
 	InitializedOverseerBuilder<
@@ -234,6 +238,18 @@ where
 	let approval_voting_parallel_metrics: ApprovalVotingParallelMetrics =
 		Metrics::register(registry)?;
 
+	let mut approval_voting = ApprovalVotingSubsystem::with_config(
+		approval_voting_config.clone(),
+		parachains_db.clone(),
+		keystore.clone(),
+		Box::new(sync_service.clone()),
+		approval_voting_parallel_metrics.approval_voting_metrics(),
+		Arc::new(spawner.clone()),
+	);
+	if let Some(telemetry) = telemetry {
+		approval_voting = approval_voting.with_telemetry(telemetry);
+	}
+
 	let builder = Overseer::builder()
 		.network_bridge_tx(NetworkBridgeTxSubsystem::new(
 			network_service.clone(),
@@ -323,14 +339,7 @@ where
 			approval_voting_config.slot_duration_millis,
 			Arc::new(RealAssignmentCriteria {}),
 		))
-		.approval_voting(ApprovalVotingSubsystem::with_config(
-			approval_voting_config.clone(),
-			parachains_db.clone(),
-			keystore.clone(),
-			Box::new(sync_service.clone()),
-			approval_voting_parallel_metrics.approval_voting_metrics(),
-			Arc::new(spawner.clone()),
-		))
+		.approval_voting(approval_voting)
 		.approval_voting_parallel(DummySubsystem)
 		.gossip_support(GossipSupportSubsystem::new(
 			keystore.clone(),
@@ -403,6 +412,7 @@ pub fn validator_with_parallel_overseer_builder<Spawner, RuntimeClient>(
 		chain_selection_config,
 		fetch_chunks_threshold,
 		enable_approval_voting_parallel,
+		telemetry,
 	}: ExtendedOverseerGenArgs,
 ) -> Result<
 	InitializedOverseerBuilder<
@@ -455,6 +465,20 @@ where
 	let network_bridge_metrics: NetworkBridgeMetrics = Metrics::register(registry)?;
 	let approval_voting_parallel_metrics: ApprovalVotingParallelMetrics =
 		Metrics::register(registry)?;
+
+	let mut approval_voting_parallel = ApprovalVotingParallelSubsystem::with_config(
+		approval_voting_config,
+		parachains_db.clone(),
+		keystore.clone(),
+		Box::new(sync_service.clone()),
+		approval_voting_parallel_metrics,
+		spawner.clone(),
+		overseer_message_channel_capacity_override,
+	);
+	if let Some(telemetry) = telemetry {
+		approval_voting_parallel = approval_voting_parallel.with_telemetry(telemetry);
+	}
+
 	let builder = Overseer::builder()
 		.network_bridge_tx(NetworkBridgeTxSubsystem::new(
 			network_service.clone(),
@@ -541,15 +565,7 @@ where
 		))
 		.approval_distribution(DummySubsystem)
 		.approval_voting(DummySubsystem)
-		.approval_voting_parallel(ApprovalVotingParallelSubsystem::with_config(
-			approval_voting_config,
-			parachains_db.clone(),
-			keystore.clone(),
-			Box::new(sync_service.clone()),
-			approval_voting_parallel_metrics,
-			spawner.clone(),
-			overseer_message_channel_capacity_override,
-		))
+		.approval_voting_parallel(approval_voting_parallel)
 		.gossip_support(GossipSupportSubsystem::new(
 			keystore.clone(),
 			authority_discovery_service.clone(),
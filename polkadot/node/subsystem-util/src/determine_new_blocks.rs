@@ -19,6 +19,7 @@
 use futures::{channel::oneshot, prelude::*};
 use polkadot_node_subsystem::{messages::ChainApiMessage, SubsystemSender};
 use polkadot_primitives::{BlockNumber, Hash, Header};
+use std::collections::HashMap;
 
 /// Given a new chain-head hash, this determines the hashes of all new blocks we should track
 /// metadata for, given this head.
@@ -38,6 +39,30 @@ pub async fn determine_new_blocks<E, Sender>(
 	header: &Header,
 	lower_bound_number: BlockNumber,
 ) -> Result<Vec<(Hash, Header)>, E>
+where
+	Sender: SubsystemSender<ChainApiMessage>,
+{
+	determine_new_blocks_with_known_headers(
+		sender,
+		is_known,
+		&HashMap::new(),
+		head,
+		header,
+		lower_bound_number,
+	)
+	.await
+}
+
+/// Same as [`determine_new_blocks`], but takes the headers of ancestors from `known_headers`
+/// where possible, instead of requesting them from the Chain API.
+pub async fn determine_new_blocks_with_known_headers<E, Sender>(
+	sender: &mut Sender,
+	is_known: impl Fn(&Hash) -> Result<bool, E>,
+	known_headers: &HashMap<Hash, Header>,
+	head: Hash,
+	header: &Header,
+	lower_bound_number: BlockNumber,
+) -> Result<Vec<(Hash, Header)>, E>
 where
 	Sender: SubsystemSender<ChainApiMessage>,
 {
@@ -105,26 +130,29 @@ where
 		};
 
 		let batch_headers = {
-			let (batch_senders, batch_receivers) = (0..batch_hashes.len())
-				.map(|_| oneshot::channel())
-				.unzip::<_, _, Vec<_>, Vec<_>>();
-
-			for (hash, batched_sender) in batch_hashes.iter().cloned().zip(batch_senders) {
-				sender
-					.send_message(ChainApiMessage::BlockHeader(hash, batched_sender).into())
-					.await;
-			}
-
 			let mut requests = futures::stream::FuturesOrdered::new();
-			batch_receivers
-				.into_iter()
-				.map(|rx| async move {
-					match rx.await {
-						Err(_) | Ok(Err(_)) => None,
-						Ok(Ok(h)) => h,
+			for hash in batch_hashes.iter().cloned() {
+				// Headers we already know are not requested.
+				let known_header = known_headers.get(&hash).cloned();
+				let rx = if known_header.is_none() {
+					let (tx, rx) = oneshot::channel();
+					sender.send_message(ChainApiMessage::BlockHeader(hash, tx).into()).await;
+					Some(rx)
+				} else {
+					None
+				};
+
+				requests.push_back(async move {
+					match (known_header, rx) {
+						(Some(h), _) => Some(h),
+						(None, Some(rx)) => match rx.await {
+							Err(_) | Ok(Err(_)) => None,
+							Ok(Ok(h)) => h,
+						},
+						(None, None) => None,
 					}
-				})
-				.for_each(|x| requests.push_back(x));
+				});
+			}
 
 			let batch_headers: Vec<_> =
 				requests.flat_map(|x: Option<Header>| stream::iter(x)).collect().await;
@@ -166,7 +194,7 @@ mod tests {
 	use polkadot_node_subsystem_test_helpers::make_subsystem_context;
 	use polkadot_overseer::{AllMessages, SubsystemContext};
 	use sp_core::testing::TaskExecutor;
-	use std::collections::{HashMap, HashSet};
+	use std::collections::HashSet;
 
 	#[derive(Default)]
 	struct TestKnownBlocks {
@@ -332,6 +360,69 @@ mod tests {
 		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
 	}
 
+	#[test]
+	fn determine_new_blocks_does_not_request_known_headers() {
+		let pool = TaskExecutor::new();
+		let (mut ctx, mut handle) = make_subsystem_context::<(), _>(pool.clone());
+
+		let known = TestKnownBlocks::default();
+
+		let chain = TestChain::new(10, 9);
+
+		let head = chain.header_by_number(18).unwrap().clone();
+		let head_hash = head.hash();
+		let lower_bound_number = 12;
+
+		let known_headers = (14..=17)
+			.map(|n| chain.header_by_number(n).map(|h| (h.hash(), h.clone())).unwrap())
+			.collect::<HashMap<_, _>>();
+		let expected_ancestry = (13..=18)
+			.map(|n| chain.header_by_number(n).map(|h| (h.hash(), h.clone())).unwrap())
+			.rev()
+			.collect::<Vec<_>>();
+
+		let test_fut = Box::pin(async move {
+			let ancestry = determine_new_blocks_with_known_headers(
+				ctx.sender(),
+				|h| known.is_known(h),
+				&known_headers,
+				head_hash,
+				&head,
+				lower_bound_number,
+			)
+			.await
+			.unwrap();
+
+			assert_eq!(ancestry, expected_ancestry);
+		});
+
+		let aux_fut = Box::pin(async move {
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::ChainApi(ChainApiMessage::Ancestors {
+					hash: h,
+					k,
+					response_channel: tx,
+				}) => {
+					assert_eq!(h, head_hash);
+					assert_eq!(k, 4);
+					let _ = tx.send(Ok(chain.ancestry(&h, k as _)));
+				}
+			);
+
+			// Only the header of block 13 is unknown.
+			assert_matches!(
+				handle.recv().await,
+				AllMessages::ChainApi(ChainApiMessage::BlockHeader(h, tx)) => {
+					assert_eq!(h, chain.hash_by_number(13).unwrap());
+					let _ = tx.send(Ok(chain.header_by_hash(&h).map(|h| h.clone())));
+				}
+			);
+		});
+
+		futures::executor::block_on(futures::future::join(test_fut, aux_fut));
+	}
+
 	#[test]
 	fn determine_new_blocks_back_to_known() {
 		let pool = TaskExecutor::new();
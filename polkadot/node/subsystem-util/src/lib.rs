@@ -62,7 +62,7 @@ use std::{
 };
 use thiserror::Error;
 
-pub use determine_new_blocks::determine_new_blocks;
+pub use determine_new_blocks::{determine_new_blocks, determine_new_blocks_with_known_headers};
 pub use metered;
 pub use polkadot_node_network_protocol::MIN_GOSSIP_PEERS;
 
@@ -2100,10 +2100,14 @@ pub mod node_features {
 		/// See [RFC-103](https://github.com/polkadot-fellows/RFCs/pull/103) for details.
 		/// Only enable if at least 2/3 of nodes support the feature.
 		CandidateReceiptV2 = 3,
+		/// Tells if the relay VRF story may be taken from a Sassafras slot claim for headers
+		/// without a BABE pre-digest. Experimental: only honoured by nodes built with
+		/// Sassafras support.
+		SassafrasRelayVrf = 4,
 		/// First unassigned feature bit.
 		/// Every time a new feature flag is assigned it should take this value.
 		/// and this should be incremented.
-		FirstUnassigned = 4,
+		FirstUnassigned = 5,
 	}
 }
 
@@ -456,6 +456,28 @@ impl TelemetryHandle {
 	pub fn on_connect_stream(&self) -> ConnectionNotifierReceiver {
 		self.connection_notifier.on_connect_stream()
 	}
+
+	/// Make a new handle that isn't connected to any [`TelemetryWorker`], along with a stream of
+	/// the payloads reported through it.
+	///
+	/// This is meant to observe the telemetry reported by a component in tests.
+	#[cfg(any(test, feature = "test-helpers"))]
+	pub fn new_detached(
+		buffer_size: usize,
+	) -> (Self, impl Stream<Item = serde_json::Map<String, serde_json::Value>>) {
+		let (message_sender, message_receiver) = mpsc::channel(buffer_size);
+		let (register_sender, _) = tracing_unbounded("mpsc_telemetry_register", 10_000);
+		let handle = TelemetryHandle {
+			message_sender: Arc::new(Mutex::new(message_sender)),
+			id: 0,
+			connection_notifier: TelemetryConnectionNotifier {
+				register_sender,
+				addresses: Vec::new(),
+			},
+		};
+
+		(handle, message_receiver.map(|(_, _, payload)| payload))
+	}
 }
 
 /// Used to create a stream of events with only one event: when a telemetry connection
@@ -21,7 +21,7 @@
 
 extern crate alloc;
 
-use codec::{Decode, DecodeWithMemTracking, Encode};
+use codec::{Decode, DecodeLimit, DecodeWithMemTracking, Encode};
 
 pub use sp_application_crypto;
 use sp_application_crypto::sr25519;
@@ -30,13 +30,96 @@ use alloc::vec::Vec;
 pub use sp_core::{hash::H256, RuntimeDebug};
 use sp_runtime::traits::{BlakeTwo256, ExtrinsicLike, Verify};
 
+#[cfg(feature = "std")]
+pub mod test_accounts;
+
+/// The maximal nesting depth of [`Extrinsic::Batch`]es accepted by
+/// [`Extrinsic::check_batch_depth`].
+pub const MAX_BATCH_DEPTH: u32 = 8;
+
+/// The maximal number of leading payload bytes printed by the [`Extrinsic`] `Debug` impl.
+const DEBUG_BYTES_LIMIT: usize = 8;
+
 /// Extrinsic for test-runtime.
-#[derive(
-	Clone, PartialEq, Eq, Encode, Decode, DecodeWithMemTracking, RuntimeDebug, scale_info::TypeInfo,
-)]
+#[derive(Clone, PartialEq, Eq, Encode, Decode, DecodeWithMemTracking, scale_info::TypeInfo)]
 pub enum Extrinsic {
 	IncludeData(Vec<u8>),
 	StorageChange(Vec<u8>, Option<Vec<u8>>),
+	/// Extrinsics that are applied atomically, in order.
+	Batch(Vec<Extrinsic>),
+	/// Bare extrinsic carrying an arbitrary note.
+	Remark(Vec<u8>),
+}
+
+/// Prints the leading bytes of a payload as hex, followed by its length if it is truncated.
+struct BytesSummary<'a>(&'a [u8]);
+
+impl core::fmt::Debug for BytesSummary<'_> {
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+		f.write_str("0x")?;
+		for byte in self.0.iter().take(DEBUG_BYTES_LIMIT) {
+			write!(f, "{:02x}", byte)?;
+		}
+		if self.0.len() > DEBUG_BYTES_LIMIT {
+			write!(f, "..({} bytes)", self.0.len())?;
+		}
+		Ok(())
+	}
+}
+
+impl core::fmt::Debug for Extrinsic {
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+		match self {
+			Extrinsic::IncludeData(data) =>
+				f.debug_tuple("IncludeData").field(&BytesSummary(data)).finish(),
+			Extrinsic::StorageChange(key, value) => f
+				.debug_tuple("StorageChange")
+				.field(&BytesSummary(key))
+				.field(&value.as_deref().map(BytesSummary))
+				.finish(),
+			Extrinsic::Batch(extrinsics) => f.debug_tuple("Batch").field(extrinsics).finish(),
+			Extrinsic::Remark(remark) =>
+				f.debug_tuple("Remark").field(&BytesSummary(remark)).finish(),
+		}
+	}
+}
+
+impl Extrinsic {
+	/// Decodes an extrinsic, rejecting batches nested deeper than [`MAX_BATCH_DEPTH`].
+	///
+	/// The depth is limited while decoding, so overly nested input is rejected before it is
+	/// recursed into.
+	pub fn decode_checked(input: &mut &[u8]) -> Result<Self, codec::Error> {
+		Self::decode_with_depth_limit(MAX_BATCH_DEPTH, input)
+	}
+
+	/// Checks that batches within this extrinsic are nested at most [`MAX_BATCH_DEPTH`] deep.
+	///
+	/// Meant for extrinsics built in memory, decoded ones are checked by [`Self::decode_checked`].
+	pub fn check_batch_depth(&self) -> Result<(), codec::Error> {
+		let mut pending = alloc::vec![(self, 0)];
+		while let Some((extrinsic, depth)) = pending.pop() {
+			if let Extrinsic::Batch(extrinsics) = extrinsic {
+				if depth >= MAX_BATCH_DEPTH {
+					return Err("Batch nested too deep".into())
+				}
+				pending.extend(extrinsics.iter().map(|extrinsic| (extrinsic, depth + 1)));
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Returns the bytes carried by this extrinsic: the included data, the changed storage key or
+	/// the remark. Batches carry no bytes of their own.
+	pub fn payload(&self) -> Option<&[u8]> {
+		match self {
+			Extrinsic::IncludeData(data) => Some(data),
+			Extrinsic::StorageChange(key, _) => Some(key),
+			Extrinsic::Batch(_) => None,
+			Extrinsic::Remark(remark) => Some(remark),
+		}
+	}
 }
 
 #[cfg(feature = "serde")]
@@ -49,21 +132,25 @@ impl serde::Serialize for Extrinsic {
 	}
 }
 
+#[cfg(feature = "serde")]
+impl<'a> serde::Deserialize<'a> for Extrinsic {
+	fn deserialize<D>(de: D) -> Result<Self, D::Error>
+	where
+		D: serde::Deserializer<'a>,
+	{
+		let bytes = <Vec<u8> as serde::Deserialize>::deserialize(de)?;
+		Self::decode_checked(&mut &bytes[..])
+			.map_err(|e| serde::de::Error::custom(alloc::format!("Decode error: {}", e)))
+	}
+}
+
 impl ExtrinsicLike for Extrinsic {
 	fn is_signed(&self) -> Option<bool> {
-		if let Extrinsic::IncludeData(_) = *self {
-			Some(false)
-		} else {
-			Some(true)
-		}
+		Some(!self.is_bare())
 	}
 
 	fn is_bare(&self) -> bool {
-		if let Extrinsic::IncludeData(_) = *self {
-			true
-		} else {
-			false
-		}
+		matches!(self, Extrinsic::IncludeData(_) | Extrinsic::Remark(_))
 	}
 }
 
@@ -85,3 +172,147 @@ pub type Digest = sp_runtime::generic::Digest;
 pub type Block = sp_runtime::generic::Block<Header, Extrinsic>;
 /// A test block's header.
 pub type Header = sp_runtime::generic::Header<BlockNumber, BlakeTwo256>;
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use quickcheck::{Arbitrary, Gen, QuickCheck};
+
+	fn nested_batch(depth: u32) -> Extrinsic {
+		(0..depth).fold(Extrinsic::IncludeData(vec![42]), |inner, _| Extrinsic::Batch(vec![inner]))
+	}
+
+	impl Arbitrary for Extrinsic {
+		fn arbitrary(g: &mut Gen) -> Self {
+			arbitrary_extrinsic(g, 0)
+		}
+	}
+
+	// Generates any of the variants, with batches nested at most `MAX_BATCH_DEPTH` deep. Variants
+	// added to `Extrinsic` must be generated here as well to be covered by the round-trip test.
+	fn arbitrary_extrinsic(g: &mut Gen, depth: u32) -> Extrinsic {
+		let variants: &[u8] = if depth < MAX_BATCH_DEPTH { &[0, 1, 2, 3] } else { &[0, 1, 3] };
+		match g.choose(variants).unwrap() {
+			0 => Extrinsic::IncludeData(arbitrary_payload(g)),
+			1 => {
+				let key = arbitrary_payload(g);
+				let value = bool::arbitrary(g).then(|| arbitrary_payload(g));
+				Extrinsic::StorageChange(key, value)
+			},
+			2 => Extrinsic::Batch(
+				(0..usize::arbitrary(g) % 4).map(|_| arbitrary_extrinsic(g, depth + 1)).collect(),
+			),
+			_ => Extrinsic::Remark(arbitrary_payload(g)),
+		}
+	}
+
+	// Payloads of each of the compact length encodings used for vectors under 1 GiB.
+	fn arbitrary_payload(g: &mut Gen) -> Vec<u8> {
+		let len = match g.choose(&[0, 1, 2]).unwrap() {
+			0 => usize::arbitrary(g) % (1 << 6),
+			1 => (1 << 6) + usize::arbitrary(g) % ((1 << 14) - (1 << 6)),
+			_ => (1 << 14) + usize::arbitrary(g) % (1 << 16),
+		};
+		(0..len).map(|_| u8::arbitrary(g)).collect()
+	}
+
+	#[test]
+	fn arbitrary_extrinsics_encode_decode_roundtrip() {
+		fn property(extrinsic: Extrinsic) -> bool {
+			let encoded = extrinsic.encode();
+			Extrinsic::decode(&mut &encoded[..]).ok().as_ref() == Some(&extrinsic) &&
+				Extrinsic::decode_checked(&mut &encoded[..]).ok().as_ref() == Some(&extrinsic)
+		}
+
+		QuickCheck::new().tests(50).quickcheck(property as fn(Extrinsic) -> bool);
+	}
+
+	#[test]
+	fn batch_encode_decode_roundtrip() {
+		let batch = Extrinsic::Batch(vec![
+			Extrinsic::IncludeData(vec![1, 2, 3]),
+			Extrinsic::StorageChange(vec![4], Some(vec![5])),
+			Extrinsic::Batch(vec![Extrinsic::StorageChange(vec![6], None)]),
+		]);
+
+		let encoded = batch.encode();
+		assert_eq!(Extrinsic::decode(&mut &encoded[..]).unwrap(), batch);
+		assert_eq!(Extrinsic::decode_checked(&mut &encoded[..]).unwrap(), batch);
+		assert_eq!(batch.is_signed(), Some(true));
+		assert!(!batch.is_bare());
+	}
+
+	#[test]
+	fn batch_depth_is_limited() {
+		let allowed = nested_batch(MAX_BATCH_DEPTH);
+		assert!(allowed.check_batch_depth().is_ok());
+		assert_eq!(Extrinsic::decode_checked(&mut &allowed.encode()[..]).unwrap(), allowed);
+
+		let too_deep = nested_batch(MAX_BATCH_DEPTH + 1);
+		assert!(too_deep.check_batch_depth().is_err());
+		assert!(Extrinsic::decode_checked(&mut &too_deep.encode()[..]).is_err());
+	}
+
+	#[test]
+	fn decode_checked_rejects_deep_nesting_without_recursing() {
+		// Nesting deep enough to overflow the stack if it was decoded recursively: every level is
+		// the `Batch` variant index followed by the compact encoded length of one.
+		let levels = 1_000_000;
+		let mut encoded = [2u8, 4].repeat(levels);
+		encoded.extend(Extrinsic::IncludeData(vec![42]).encode());
+
+		assert!(Extrinsic::decode_checked(&mut &encoded[..]).is_err());
+	}
+
+	#[test]
+	fn remark_is_bare() {
+		let remark = Extrinsic::Remark(b"hello".to_vec());
+		assert_eq!(Extrinsic::decode(&mut &remark.encode()[..]).unwrap(), remark);
+		assert_eq!(remark.is_signed(), Some(false));
+		assert!(remark.is_bare());
+	}
+
+	#[test]
+	fn debug_output_truncates_payloads() {
+		let data = (0..100).collect::<Vec<u8>>();
+		assert_eq!(
+			format!("{:?}", Extrinsic::IncludeData(data.clone())),
+			"IncludeData(0x0001020304050607..(100 bytes))",
+		);
+		let batch = Extrinsic::Batch(vec![Extrinsic::StorageChange(vec![1, 2], Some(data))]);
+		assert_eq!(
+			format!("{:?}", batch),
+			"Batch([StorageChange(0x0102, Some(0x0001020304050607..(100 bytes)))])",
+		);
+		assert_eq!(format!("{:?}", Extrinsic::Remark(vec![0xab])), "Remark(0xab)");
+	}
+
+	#[test]
+	fn payload_returns_carried_bytes() {
+		assert_eq!(Extrinsic::IncludeData(vec![1, 2]).payload(), Some(&[1, 2][..]));
+		assert_eq!(Extrinsic::StorageChange(vec![3], Some(vec![4])).payload(), Some(&[3][..]));
+		assert_eq!(Extrinsic::StorageChange(vec![5], None).payload(), Some(&[5][..]));
+		assert_eq!(Extrinsic::Batch(vec![Extrinsic::IncludeData(vec![6])]).payload(), None);
+		assert_eq!(Extrinsic::Remark(vec![7]).payload(), Some(&[7][..]));
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn serde_json_roundtrip() {
+		let extrinsics = vec![
+			Extrinsic::IncludeData(vec![1, 2, 3]),
+			Extrinsic::StorageChange(vec![4], Some(vec![5])),
+			Extrinsic::StorageChange(vec![6], None),
+			Extrinsic::Batch(vec![Extrinsic::IncludeData(vec![7]), Extrinsic::Remark(vec![8])]),
+			Extrinsic::Remark(b"hello".to_vec()),
+		];
+
+		for extrinsic in extrinsics {
+			let json = serde_json::to_string(&extrinsic).unwrap();
+			assert_eq!(serde_json::from_str::<Extrinsic>(&json).unwrap(), extrinsic);
+		}
+
+		let too_deep = serde_json::to_string(&nested_batch(MAX_BATCH_DEPTH + 1)).unwrap();
+		assert!(serde_json::from_str::<Extrinsic>(&too_deep).is_err());
+	}
+}
@@ -0,0 +1,73 @@
+// This file is part of Substrate.
+
+// Copyright (C) Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Deterministic sr25519 accounts of the well-known test users.
+//!
+//! The keys are derived the same way as those of `sp_keyring::Sr25519Keyring`, so e.g.
+//! `account_id("alice")` is the account of `Sr25519Keyring::Alice`.
+
+use crate::{AccountId, AccountSignature};
+use sp_core::{sr25519, Pair};
+
+/// Names of the well-known test users, with the secret URIs their keys are derived from.
+const ACCOUNTS: [(&str, &str); 8] = [
+	("alice", "//Alice"),
+	("bob", "//Bob"),
+	("charlie", "//Charlie"),
+	("dave", "//Dave"),
+	("eve", "//Eve"),
+	("ferdie", "//Ferdie"),
+	("one", "//One"),
+	("two", "//Two"),
+];
+
+/// Returns the key pair of the well-known test user `who`, e.g. `"alice"`.
+///
+/// Panics if `who` is not a well-known test user.
+pub fn pair(who: &str) -> sr25519::Pair {
+	let (_, uri) = ACCOUNTS
+		.iter()
+		.find(|(name, _)| name.eq_ignore_ascii_case(who))
+		.unwrap_or_else(|| panic!("{who} is not a well-known test account"));
+	sr25519::Pair::from_string(uri, None).expect("static values are known good; qed")
+}
+
+/// Returns the account id of the well-known test user `who`.
+pub fn account_id(who: &str) -> AccountId {
+	pair(who).public()
+}
+
+/// Signs `payload` with the key of the well-known test user `who`.
+pub fn sign_with(who: &str, payload: &[u8]) -> AccountSignature {
+	pair(who).sign(payload)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_runtime::traits::Verify;
+
+	#[test]
+	fn signature_is_verified_against_account_id() {
+		let payload = b"test payload";
+		let signature = sign_with("alice", payload);
+
+		assert!(signature.verify(&payload[..], &account_id("alice")));
+		assert!(!signature.verify(&payload[..], &account_id("bob")));
+		assert_eq!(account_id("Alice"), account_id("alice"));
+	}
+}